@@ -0,0 +1 @@
+ALTER TABLE places ADD COLUMN IF NOT EXISTS categories TEXT[];
@@ -0,0 +1,3 @@
+-- Foursquare details: hours, price, phone, social links, time zone and when
+-- they were fetched
+ALTER TABLE places ADD COLUMN IF NOT EXISTS details JSONB;
@@ -0,0 +1,17 @@
+CREATE TABLE IF NOT EXISTS jobs (
+    id BIGSERIAL PRIMARY KEY,
+    kind TEXT NOT NULL,
+    place_id BIGINT NOT NULL REFERENCES places (id) ON DELETE CASCADE,
+    -- pending, running or dead
+    status TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    run_at TIMESTAMPTZ NOT NULL,
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL
+);
+
+-- At most one live job of each kind per place
+CREATE UNIQUE INDEX IF NOT EXISTS jobs_kind_place_id_idx ON jobs (kind, place_id)
+    WHERE status <> 'dead';
+CREATE INDEX IF NOT EXISTS jobs_status_run_at_idx ON jobs (status, run_at);
@@ -0,0 +1,17 @@
+-- Photos were stored as `{prefix}original{suffix}` URLs. Keep the prefix and
+-- suffix instead so any size can be asked for.
+ALTER TABLE places ADD COLUMN photo_sources JSONB;
+
+UPDATE places
+SET photo_sources = (
+    SELECT COALESCE(jsonb_agg(jsonb_build_object(
+        'prefix', split_part(url, '/original/', 1) || '/',
+        'suffix', '/' || split_part(url, '/original/', 2)
+    )), '[]'::JSONB)
+    FROM unnest(places.photos) AS url
+    WHERE strpos(url, '/original/') > 0
+)
+WHERE photos IS NOT NULL;
+
+ALTER TABLE places DROP COLUMN photos;
+ALTER TABLE places RENAME COLUMN photo_sources TO photos;
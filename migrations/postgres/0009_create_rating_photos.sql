@@ -0,0 +1,16 @@
+-- Photos users attach to their ratings. The images themselves are in the
+-- blob store under key and thumbnail_key.
+CREATE TABLE IF NOT EXISTS rating_photos (
+    id BIGSERIAL PRIMARY KEY,
+    rating_id BIGINT NOT NULL REFERENCES ratings (id) ON DELETE CASCADE,
+    place_id BIGINT NOT NULL REFERENCES places (id) ON DELETE CASCADE,
+    phone_number BIGINT NOT NULL REFERENCES users (phone_number) ON DELETE CASCADE,
+    key TEXT NOT NULL,
+    thumbnail_key TEXT NOT NULL,
+    width INTEGER NOT NULL,
+    height INTEGER NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS rating_photos_rating_id_idx ON rating_photos (rating_id);
+CREATE INDEX IF NOT EXISTS rating_photos_place_id_idx ON rating_photos (place_id);
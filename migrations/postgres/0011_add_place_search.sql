@@ -0,0 +1,8 @@
+-- Trigram indexes over the text place searches match, so ILIKE on part of a
+-- name, street or town doesn't scan every place
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX IF NOT EXISTS places_name_trgm_idx ON places USING GIN (name gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS places_address_trgm_idx ON places USING GIN (address gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS places_street_trgm_idx ON places USING GIN (street gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS places_place_trgm_idx ON places USING GIN (place gin_trgm_ops);
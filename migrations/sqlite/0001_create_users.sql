@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS users (
+    phone_number INTEGER PRIMARY KEY NOT NULL,
+    first_name TEXT NOT NULL,
+    last_name TEXT NOT NULL,
+    is_verified BOOLEAN NOT NULL DEFAULT FALSE
+);
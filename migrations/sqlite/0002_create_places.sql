@@ -0,0 +1,17 @@
+CREATE TABLE IF NOT EXISTS places (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    address TEXT NOT NULL UNIQUE,
+    full_address TEXT,
+    country TEXT,
+    region TEXT,
+    postcode TEXT,
+    place TEXT,
+    street TEXT,
+    photos TEXT,
+    website TEXT,
+    foursquare_id TEXT
+);
+
+CREATE INDEX IF NOT EXISTS places_name_idx ON places (name);
+CREATE INDEX IF NOT EXISTS places_postcode_idx ON places (postcode);
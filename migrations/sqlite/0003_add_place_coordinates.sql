@@ -0,0 +1,2 @@
+ALTER TABLE places ADD COLUMN latitude REAL;
+ALTER TABLE places ADD COLUMN longitude REAL;
@@ -0,0 +1 @@
+ALTER TABLE places ADD COLUMN categories TEXT;
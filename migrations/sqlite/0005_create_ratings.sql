@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS ratings (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    place_id INTEGER NOT NULL REFERENCES places (id) ON DELETE CASCADE,
+    phone_number INTEGER NOT NULL REFERENCES users (phone_number) ON DELETE CASCADE,
+    score INTEGER NOT NULL CHECK (score BETWEEN 1 AND 10),
+    review TEXT,
+    -- RFC 3339 timestamps in UTC
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    UNIQUE (place_id, phone_number)
+);
+
+CREATE INDEX IF NOT EXISTS ratings_phone_number_idx ON ratings (phone_number);
@@ -0,0 +1,18 @@
+CREATE TABLE IF NOT EXISTS jobs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    kind TEXT NOT NULL,
+    place_id INTEGER NOT NULL REFERENCES places (id) ON DELETE CASCADE,
+    -- pending, running or dead
+    status TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    -- RFC 3339 timestamps in UTC with microseconds, so they sort as text
+    run_at TEXT NOT NULL,
+    last_error TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+-- At most one live job of each kind per place
+CREATE UNIQUE INDEX IF NOT EXISTS jobs_kind_place_id_idx ON jobs (kind, place_id)
+    WHERE status <> 'dead';
+CREATE INDEX IF NOT EXISTS jobs_status_run_at_idx ON jobs (status, run_at);
@@ -0,0 +1,12 @@
+-- Photos were stored as `{prefix}original{suffix}` URLs. Keep the prefix and
+-- suffix instead so any size can be asked for.
+UPDATE places
+SET photos = (
+    SELECT json_group_array(json_object(
+        'prefix', substr(value, 1, instr(value, '/original/')),
+        'suffix', substr(value, instr(value, '/original/') + 9)
+    ))
+    FROM json_each(places.photos)
+    WHERE instr(value, '/original/') > 0
+)
+WHERE photos IS NOT NULL;
@@ -0,0 +1,30 @@
+-- Trigram index over the text place searches match, so matching part of a
+-- name, street or town doesn't scan every place
+CREATE VIRTUAL TABLE IF NOT EXISTS places_search USING fts5(
+    name,
+    address,
+    street,
+    place,
+    content = 'places',
+    content_rowid = 'id',
+    tokenize = 'trigram'
+);
+
+INSERT INTO places_search (places_search) VALUES ('rebuild');
+
+CREATE TRIGGER IF NOT EXISTS places_search_insert AFTER INSERT ON places BEGIN
+    INSERT INTO places_search (rowid, name, address, street, place)
+    VALUES (new.id, new.name, new.address, new.street, new.place);
+END;
+
+CREATE TRIGGER IF NOT EXISTS places_search_delete AFTER DELETE ON places BEGIN
+    INSERT INTO places_search (places_search, rowid, name, address, street, place)
+    VALUES ('delete', old.id, old.name, old.address, old.street, old.place);
+END;
+
+CREATE TRIGGER IF NOT EXISTS places_search_update AFTER UPDATE ON places BEGIN
+    INSERT INTO places_search (places_search, rowid, name, address, street, place)
+    VALUES ('delete', old.id, old.name, old.address, old.street, old.place);
+    INSERT INTO places_search (rowid, name, address, street, place)
+    VALUES (new.id, new.name, new.address, new.street, new.place);
+END;
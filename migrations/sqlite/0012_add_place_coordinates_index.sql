@@ -0,0 +1,3 @@
+-- Index over the coordinates nearby and bounded searches filter on, so
+-- finding the places in a box doesn't scan every place
+CREATE INDEX IF NOT EXISTS places_coordinates_idx ON places (latitude, longitude);
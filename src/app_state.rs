@@ -1,7 +1,13 @@
 use crate::{
+    blob::DynBlobStore,
+    geo::tile::TileCache,
+    jobs::JobQueue,
     oauth::OAuth,
-    places::search::DynPlacesSearch,
-    repository::{places::DynPlacesRepo, user::DynUserRepo},
+    places::{
+        activity::PlaceActivity, photo::PhotoProxy, search::DynPlacesSearch,
+        session::SearchSessions,
+    },
+    repository::{places::DynPlacesRepo, ratings::DynRatingsRepo, user::DynUserRepo},
     sms::DynSMSVerify,
 };
 
@@ -9,7 +15,14 @@ use crate::{
 pub struct AppState {
     pub user_repo: DynUserRepo,
     pub places_repo: DynPlacesRepo,
+    pub ratings_repo: DynRatingsRepo,
     pub sms_verify: DynSMSVerify,
     pub places_search: DynPlacesSearch,
+    pub search_sessions: SearchSessions,
+    pub tile_cache: TileCache,
+    pub jobs: JobQueue,
+    pub place_activity: PlaceActivity,
+    pub photo_proxy: PhotoProxy,
+    pub blob_store: DynBlobStore,
     pub oauth: OAuth,
 }
@@ -0,0 +1,99 @@
+use std::{io::ErrorKind, path::PathBuf};
+
+use axum::async_trait;
+
+use super::{content_type_of, validate_key, Blob, BlobStore};
+
+/// Keeps blobs as files under a directory. Media types aren't stored, so they
+/// come from the key's extension.
+#[derive(Clone)]
+pub struct LocalBlobStore {
+    root: PathBuf,
+}
+
+impl LocalBlobStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        LocalBlobStore { root: root.into() }
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf, String> {
+        validate_key(key)?;
+        Ok(self.root.join(key))
+    }
+}
+
+#[async_trait]
+impl BlobStore for LocalBlobStore {
+    async fn put(&self, key: &str, blob: Blob) -> Result<(), String> {
+        let path = self.path(key)?;
+        // Written under a temporary name first so a half written blob is
+        // never read.
+        let partial = path.with_extension(format!("{}.partial", uuid::Uuid::new_v4()));
+        let written = async {
+            if let Some(dir) = path.parent() {
+                tokio::fs::create_dir_all(dir).await?;
+            }
+            tokio::fs::write(&partial, &blob.bytes).await?;
+            tokio::fs::rename(&partial, &path).await
+        };
+        match written.await {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                eprintln!("error writing blob {}: {}", key, e);
+                let _ = tokio::fs::remove_file(&partial).await;
+                Err("Blob not stored".to_string())
+            }
+        }
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Blob>, String> {
+        match tokio::fs::read(self.path(key)?).await {
+            Ok(bytes) => Ok(Some(Blob {
+                bytes,
+                content_type: content_type_of(key).to_string(),
+            })),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => {
+                eprintln!("error reading blob {}: {}", key, e);
+                Err("Could not read blob".to_string())
+            }
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), String> {
+        match tokio::fs::remove_file(self.path(key)?).await {
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            Err(e) => {
+                eprintln!("error deleting blob {}: {}", key, e);
+                Err("Blob not deleted".to_string())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_put_get_and_delete() {
+        let root = std::env::temp_dir().join(format!("blobs-{}", uuid::Uuid::new_v4()));
+        let store = LocalBlobStore::new(&root);
+        let blob = Blob {
+            bytes: b"jpeg".to_vec(),
+            content_type: "image/jpeg".to_string(),
+        };
+
+        assert_eq!(store.get("ratings/1/a.jpg").await.unwrap(), None);
+        store.put("ratings/1/a.jpg", blob.clone()).await.unwrap();
+        assert_eq!(store.get("ratings/1/a.jpg").await.unwrap(), Some(blob));
+
+        store.delete("ratings/1/a.jpg").await.unwrap();
+        store.delete("ratings/1/a.jpg").await.unwrap();
+        assert_eq!(store.get("ratings/1/a.jpg").await.unwrap(), None);
+        assert!(store.get("../a.jpg").await.is_err());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,76 @@
+pub mod local;
+pub mod s3;
+
+use std::sync::Arc;
+
+use axum::async_trait;
+
+/// A stored file and its media type.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Blob {
+    pub bytes: Vec<u8>,
+    pub content_type: String,
+}
+
+pub type DynBlobStore = Arc<dyn BlobStore>;
+
+/// Somewhere to keep uploaded files. Keys are `/` separated paths such as
+/// `ratings/4/1f2e.jpg`.
+#[async_trait]
+pub trait BlobStore: Send + Sync + 'static {
+    /// Stores the blob, replacing any blob already at `key`.
+    async fn put(&self, key: &str, blob: Blob) -> Result<(), String>;
+    async fn get(&self, key: &str) -> Result<Option<Blob>, String>;
+    /// Deleting a missing blob is not an error.
+    async fn delete(&self, key: &str) -> Result<(), String>;
+}
+
+/// Keys may only hold path segments of letters, digits, `-`, `_` and `.`,
+/// so they can't escape a directory or need escaping in a URL.
+pub fn validate_key(key: &str) -> Result<(), String> {
+    let valid = !key.is_empty()
+        && key.split('/').all(|segment| {
+            !segment.is_empty()
+                && segment != "."
+                && segment != ".."
+                && segment
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        });
+    if !valid {
+        return Err(format!("Blob key {} not valid", key));
+    }
+    Ok(())
+}
+
+/// Guessed from the file extension, for stores that don't keep media types.
+pub fn content_type_of(key: &str) -> &'static str {
+    let extension = key.rsplit_once('.').map(|(_, e)| e.to_lowercase());
+    match extension.as_deref() {
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("png") => "image/png",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_key() {
+        assert!(validate_key("ratings/4/1f2e-3d.jpg").is_ok());
+        for key in [
+            "",
+            "/ratings",
+            "ratings//1.jpg",
+            "../secrets",
+            "a/./b",
+            "a b.jpg",
+        ] {
+            assert!(validate_key(key).is_err(), "{}", key);
+        }
+        assert_eq!(content_type_of("ratings/4/1.JPG"), "image/jpeg");
+        assert_eq!(content_type_of("ratings/4/1"), "application/octet-stream");
+    }
+}
@@ -0,0 +1,86 @@
+use std::{collections::BTreeMap, f64::consts::PI};
+
+use super::Coordinates;
+
+/// Web Mercator can't show the poles, so latitudes are clamped to this.
+pub const MAX_MERCATOR_LATITUDE: f64 = 85.051_128_78;
+pub const TILE_SIZE_PIXELS: f64 = 256.0;
+pub const MAX_ZOOM: u8 = 22;
+
+/// Points within a square of this many screen pixels are clustered together.
+pub const CLUSTER_CELL_PIXELS: f64 = 64.0;
+
+/// From this zoom on, every point is shown on its own.
+pub const MAX_CLUSTER_ZOOM: u8 = 17;
+
+/// Where `coordinates` fall on the Web Mercator map of the world at `zoom`,
+/// in pixels from its top left corner.
+pub fn world_pixel(coordinates: &Coordinates, zoom: u8) -> (f64, f64) {
+    let size = TILE_SIZE_PIXELS * 2f64.powi(i32::from(zoom));
+    let latitude = coordinates
+        .latitude
+        .clamp(-MAX_MERCATOR_LATITUDE, MAX_MERCATOR_LATITUDE)
+        .to_radians();
+    let x = (coordinates.longitude + 180.0) / 360.0 * size;
+    let y = (1.0 - (latitude.tan() + 1.0 / latitude.cos()).ln() / PI) / 2.0 * size;
+    (x, y)
+}
+
+/// Groups items whose coordinates land in the same `CLUSTER_CELL_PIXELS`
+/// square at `zoom`. Groups come out in a stable order, west to east and
+/// north to south; above `MAX_CLUSTER_ZOOM` every item is its own group.
+pub fn grid_clusters<T>(items: Vec<(Coordinates, T)>, zoom: u8) -> Vec<Vec<(Coordinates, T)>> {
+    if zoom >= MAX_CLUSTER_ZOOM {
+        return items.into_iter().map(|item| vec![item]).collect();
+    }
+
+    let mut cells: BTreeMap<(i64, i64), Vec<(Coordinates, T)>> = BTreeMap::new();
+    for (coordinates, item) in items {
+        let (x, y) = world_pixel(&coordinates, zoom);
+        let cell = (
+            (x / CLUSTER_CELL_PIXELS).floor() as i64,
+            (y / CLUSTER_CELL_PIXELS).floor() as i64,
+        );
+        cells.entry(cell).or_default().push((coordinates, item));
+    }
+    cells.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(latitude: f64, longitude: f64) -> Coordinates {
+        Coordinates {
+            latitude,
+            longitude,
+        }
+    }
+
+    #[test]
+    fn test_world_pixel() {
+        assert_eq!(world_pixel(&at(0.0, 0.0), 0), (128.0, 128.0));
+        assert_eq!(world_pixel(&at(0.0, -180.0), 1), (0.0, 256.0));
+
+        let (_, top) = world_pixel(&at(90.0, 0.0), 0);
+        assert!(top.abs() < 1e-6, "{}", top);
+    }
+
+    #[test]
+    fn test_grid_clusters_by_zoom() {
+        let items = vec![
+            (at(40.7756, -111.8940), "arlo"),
+            (at(40.7718, -111.9120), "red iguana"),
+            (at(40.2338, -111.6585), "provo"),
+        ];
+
+        let zoomed_out = grid_clusters(items.clone(), 8);
+        assert_eq!(zoomed_out.len(), 2);
+        assert_eq!(zoomed_out.iter().map(|c| c.len()).max(), Some(2));
+
+        let zoomed_in = grid_clusters(items.clone(), 14);
+        assert_eq!(zoomed_in.len(), 3);
+
+        assert_eq!(grid_clusters(items, MAX_CLUSTER_ZOOM).len(), 3);
+    }
+}
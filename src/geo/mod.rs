@@ -1,8 +1,302 @@
+pub mod cluster;
+pub mod tile;
+
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize)]
+/// Mean length of one degree of latitude, which is close enough to constant
+/// for the search radii we deal with.
+pub const METERS_PER_DEGREE_LATITUDE: f64 = 111_320.0;
+
+/// Mean radius of the earth used for great-circle distances.
+pub const EARTH_RADIUS_METERS: f64 = 6_371_008.8;
+
+const GEOHASH_ALPHABET: &[u8; 32] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct Coordinates {
     pub latitude: f64,
     pub longitude: f64,
 }
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BoundingBox {
+    pub min_longitude: f64,
+    pub min_latitude: f64,
+    pub max_longitude: f64,
+    pub max_latitude: f64,
+}
+
+impl Coordinates {
+    /// Great-circle distance to `other` using the haversine formula.
+    pub fn distance_meters(&self, other: &Coordinates) -> f64 {
+        let latitude_delta = (other.latitude - self.latitude).to_radians();
+        let longitude_delta = (other.longitude - self.longitude).to_radians();
+        let a = (latitude_delta / 2.0).sin().powi(2)
+            + self.latitude.to_radians().cos()
+                * other.latitude.to_radians().cos()
+                * (longitude_delta / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_METERS * a.sqrt().min(1.0).asin()
+    }
+
+    /// Initial compass bearing towards `other`, in degrees clockwise from
+    /// north between 0 and 360.
+    pub fn bearing_degrees(&self, other: &Coordinates) -> f64 {
+        let latitude = self.latitude.to_radians();
+        let other_latitude = other.latitude.to_radians();
+        let longitude_delta = (other.longitude - self.longitude).to_radians();
+        let y = longitude_delta.sin() * other_latitude.cos();
+        let x = latitude.cos() * other_latitude.sin()
+            - latitude.sin() * other_latitude.cos() * longitude_delta.cos();
+        (y.atan2(x).to_degrees() + 360.0) % 360.0
+    }
+
+    /// The geohash of the cell containing these coordinates, `precision`
+    /// characters long. Nearby coordinates share a cell, and so a prefix.
+    pub fn geohash(&self, precision: usize) -> String {
+        let mut latitude_range = (-90.0, 90.0);
+        let mut longitude_range = (-180.0, 180.0);
+        let mut hash = String::with_capacity(precision);
+        let mut index = 0;
+        let mut bits = 0;
+        let mut longitude_bit = true;
+
+        while hash.len() < precision {
+            let (range, value) = if longitude_bit {
+                (&mut longitude_range, self.longitude)
+            } else {
+                (&mut latitude_range, self.latitude)
+            };
+            let middle = (range.0 + range.1) / 2.0;
+            index <<= 1;
+            if value >= middle {
+                index |= 1;
+                range.0 = middle;
+            } else {
+                range.1 = middle;
+            }
+            longitude_bit = !longitude_bit;
+
+            bits += 1;
+            if bits == 5 {
+                hash.push(GEOHASH_ALPHABET[index] as char);
+                index = 0;
+                bits = 0;
+            }
+        }
+        hash
+    }
+
+    /// The box reaching `radius_meters` north, south, east and west of these
+    /// coordinates. Degrees of longitude shrink towards the poles, so the
+    /// east-west span widens with the latitude.
+    pub fn bounding_box(&self, radius_meters: f64) -> BoundingBox {
+        let latitude_delta = radius_meters / METERS_PER_DEGREE_LATITUDE;
+        let meters_per_degree_longitude =
+            METERS_PER_DEGREE_LATITUDE * self.latitude.to_radians().cos();
+        let longitude_delta = if meters_per_degree_longitude > 1.0 {
+            (radius_meters / meters_per_degree_longitude).min(180.0)
+        } else {
+            180.0
+        };
+
+        BoundingBox {
+            min_longitude: (self.longitude - longitude_delta).max(-180.0),
+            min_latitude: (self.latitude - latitude_delta).max(-90.0),
+            max_longitude: (self.longitude + longitude_delta).min(180.0),
+            max_latitude: (self.latitude + latitude_delta).min(90.0),
+        }
+    }
+}
+
+impl BoundingBox {
+    pub fn contains(&self, coordinates: &Coordinates) -> bool {
+        coordinates.latitude >= self.min_latitude
+            && coordinates.latitude <= self.max_latitude
+            && coordinates.longitude >= self.min_longitude
+            && coordinates.longitude <= self.max_longitude
+    }
+
+    /// Splits the box into `cells` by `cells` boxes of equal size, which
+    /// share their edges.
+    pub fn grid(&self, cells: usize) -> Vec<BoundingBox> {
+        let latitude_step = (self.max_latitude - self.min_latitude) / cells as f64;
+        let longitude_step = (self.max_longitude - self.min_longitude) / cells as f64;
+        let mut grid = Vec::with_capacity(cells * cells);
+        for row in 0..cells {
+            for column in 0..cells {
+                grid.push(BoundingBox {
+                    min_longitude: self.min_longitude + longitude_step * column as f64,
+                    min_latitude: self.min_latitude + latitude_step * row as f64,
+                    max_longitude: if column + 1 == cells {
+                        self.max_longitude
+                    } else {
+                        self.min_longitude + longitude_step * (column + 1) as f64
+                    },
+                    max_latitude: if row + 1 == cells {
+                        self.max_latitude
+                    } else {
+                        self.min_latitude + latitude_step * (row + 1) as f64
+                    },
+                });
+            }
+        }
+        grid
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-4, "{} is not close to {}", a, b);
+    }
+
+    #[test]
+    fn test_bounding_box_at_equator() {
+        let bbox = Coordinates {
+            latitude: 0.0,
+            longitude: 0.0,
+        }
+        .bounding_box(METERS_PER_DEGREE_LATITUDE);
+
+        assert_close(bbox.min_latitude, -1.0);
+        assert_close(bbox.max_latitude, 1.0);
+        assert_close(bbox.min_longitude, -1.0);
+        assert_close(bbox.max_longitude, 1.0);
+    }
+
+    #[test]
+    fn test_bounding_box_widens_with_latitude() {
+        let bbox = Coordinates {
+            latitude: 60.0,
+            longitude: -111.891,
+        }
+        .bounding_box(METERS_PER_DEGREE_LATITUDE);
+
+        assert_close(bbox.max_latitude - bbox.min_latitude, 2.0);
+        assert_close(bbox.max_longitude - bbox.min_longitude, 4.0);
+    }
+
+    #[test]
+    fn test_grid_covers_bounding_box() {
+        let bbox = BoundingBox {
+            min_longitude: -112.0,
+            min_latitude: 40.0,
+            max_longitude: -111.0,
+            max_latitude: 41.0,
+        };
+        let grid = bbox.grid(4);
+
+        assert_eq!(grid.len(), 16);
+        assert_close(grid[0].max_longitude, -111.75);
+        assert_close(grid[0].max_latitude, 40.25);
+        assert_eq!(grid[15].max_longitude, bbox.max_longitude);
+        assert_eq!(grid[15].max_latitude, bbox.max_latitude);
+        for corner in [(40.0, -112.0), (40.5, -111.5), (41.0, -111.0)] {
+            let coordinates = Coordinates {
+                latitude: corner.0,
+                longitude: corner.1,
+            };
+            assert!(grid.iter().any(|cell| cell.contains(&coordinates)));
+        }
+    }
+
+    #[test]
+    fn test_distance_meters() {
+        let salt_lake_city = Coordinates {
+            latitude: 40.7608,
+            longitude: -111.891,
+        };
+        let provo = Coordinates {
+            latitude: 40.2338,
+            longitude: -111.6585,
+        };
+
+        assert_eq!(salt_lake_city.distance_meters(&salt_lake_city), 0.0);
+        let distance = salt_lake_city.distance_meters(&provo);
+        assert!((60_000.0..63_000.0).contains(&distance), "{}", distance);
+        assert_close(distance, provo.distance_meters(&salt_lake_city));
+    }
+
+    #[test]
+    fn test_bearing_degrees() {
+        let origin = Coordinates {
+            latitude: 0.0,
+            longitude: 0.0,
+        };
+        let towards = |latitude, longitude| {
+            origin.bearing_degrees(&Coordinates {
+                latitude,
+                longitude,
+            })
+        };
+
+        assert_close(towards(1.0, 0.0), 0.0);
+        assert_close(towards(0.0, 1.0), 90.0);
+        assert_close(towards(-1.0, 0.0), 180.0);
+        assert_close(towards(0.0, -1.0), 270.0);
+
+        let salt_lake_city = Coordinates {
+            latitude: 40.7608,
+            longitude: -111.891,
+        };
+        let provo = Coordinates {
+            latitude: 40.2338,
+            longitude: -111.6585,
+        };
+        let bearing = salt_lake_city.bearing_degrees(&provo);
+        assert!((160.0..165.0).contains(&bearing), "{}", bearing);
+    }
+
+    #[test]
+    fn test_bounding_box_contains() {
+        let center = Coordinates {
+            latitude: 40.7608,
+            longitude: -111.891,
+        };
+        let bbox = center.bounding_box(1_000.0);
+
+        assert!(bbox.contains(&center));
+        assert!(!bbox.contains(&Coordinates {
+            latitude: 40.7808,
+            longitude: -111.891,
+        }));
+    }
+
+    #[test]
+    fn test_geohash() {
+        let coordinates = Coordinates {
+            latitude: 57.64911,
+            longitude: 10.40744,
+        };
+        assert_eq!(coordinates.geohash(11), "u4pruydqqvj");
+        assert_eq!(coordinates.geohash(5), "u4pru");
+
+        let salt_lake_city = Coordinates {
+            latitude: 40.7608,
+            longitude: -111.891,
+        };
+        let nearby = Coordinates {
+            latitude: 40.7609,
+            longitude: -111.8911,
+        };
+        assert_eq!(salt_lake_city.geohash(6), nearby.geohash(6));
+        assert_ne!(salt_lake_city.geohash(6), coordinates.geohash(6));
+    }
+
+    #[test]
+    fn test_bounding_box_is_clamped() {
+        let bbox = Coordinates {
+            latitude: 89.99,
+            longitude: 179.9,
+        }
+        .bounding_box(50_000.0);
+
+        assert_eq!(bbox.max_latitude, 90.0);
+        assert_eq!(bbox.max_longitude, 180.0);
+        assert!(bbox.min_longitude >= -180.0);
+    }
+}
@@ -0,0 +1,479 @@
+use std::{
+    collections::HashMap,
+    f64::consts::PI,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use super::{
+    cluster::{world_pixel, MAX_ZOOM, TILE_SIZE_PIXELS},
+    BoundingBox, Coordinates,
+};
+
+pub const MVT_CONTENT_TYPE: &str = "application/vnd.mapbox-vector-tile";
+
+/// The resolution of tile coordinates, the Mapbox default.
+pub const TILE_EXTENT: u32 = 4096;
+
+pub const DEFAULT_TILE_CACHE_TTL: Duration = Duration::from_secs(5 * 60);
+pub const DEFAULT_TILE_CACHE_MAX_ENTRIES: usize = 10_000;
+
+/// A tile of the Web Mercator map, numbered like Mapbox and OpenStreetMap
+/// tiles: `x` west to east and `y` north to south.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TileId {
+    pub z: u8,
+    pub x: u32,
+    pub y: u32,
+}
+
+impl TileId {
+    pub fn new(z: u8, x: u32, y: u32) -> Result<Self, String> {
+        if z > MAX_ZOOM {
+            return Err(format!("Zoom must be between 0 and {}", MAX_ZOOM));
+        }
+        let tiles = 1u64 << z;
+        if u64::from(x) >= tiles || u64::from(y) >= tiles {
+            return Err("Tile not valid".to_string());
+        }
+        Ok(TileId { z, x, y })
+    }
+
+    /// The tile at `z` that `coordinates` fall in.
+    pub fn containing(coordinates: &Coordinates, z: u8) -> Self {
+        let (x, y) = world_pixel(coordinates, z);
+        let last = ((1u64 << z) - 1) as f64;
+        TileId {
+            z,
+            x: (x / TILE_SIZE_PIXELS).floor().clamp(0.0, last) as u32,
+            y: (y / TILE_SIZE_PIXELS).floor().clamp(0.0, last) as u32,
+        }
+    }
+
+    pub fn bounding_box(&self) -> BoundingBox {
+        let tiles = 2f64.powi(i32::from(self.z));
+        let longitude = |x: f64| x / tiles * 360.0 - 180.0;
+        let latitude = |y: f64| (PI * (1.0 - 2.0 * y / tiles)).sinh().atan().to_degrees();
+        BoundingBox {
+            min_longitude: longitude(f64::from(self.x)),
+            min_latitude: latitude(f64::from(self.y) + 1.0),
+            max_longitude: longitude(f64::from(self.x) + 1.0),
+            max_latitude: latitude(f64::from(self.y)),
+        }
+    }
+
+    /// Where `coordinates` fall inside this tile, in `TILE_EXTENT` units from
+    /// its top left corner.
+    fn position(&self, coordinates: &Coordinates) -> (i32, i32) {
+        let (x, y) = world_pixel(coordinates, self.z);
+        let scale = f64::from(TILE_EXTENT) / TILE_SIZE_PIXELS;
+        (
+            ((x - f64::from(self.x) * TILE_SIZE_PIXELS) * scale).round() as i32,
+            ((y - f64::from(self.y) * TILE_SIZE_PIXELS) * scale).round() as i32,
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TileValue {
+    String(String),
+    Double(f64),
+    Uint(u64),
+}
+
+/// A point feature of a vector tile.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TileFeature {
+    pub id: u64,
+    pub coordinates: Coordinates,
+    pub properties: Vec<(String, TileValue)>,
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn write_key(buf: &mut Vec<u8>, field: u32, wire_type: u8) {
+    write_varint(buf, u64::from(field << 3 | u32::from(wire_type)));
+}
+
+fn write_uint(buf: &mut Vec<u8>, field: u32, value: u64) {
+    write_key(buf, field, 0);
+    write_varint(buf, value);
+}
+
+fn write_bytes(buf: &mut Vec<u8>, field: u32, bytes: &[u8]) {
+    write_key(buf, field, 2);
+    write_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+fn write_packed(buf: &mut Vec<u8>, field: u32, values: &[u32]) {
+    let mut packed = Vec::new();
+    for value in values {
+        write_varint(&mut packed, u64::from(*value));
+    }
+    write_bytes(buf, field, &packed);
+}
+
+fn zigzag(value: i32) -> u32 {
+    ((value << 1) ^ (value >> 31)) as u32
+}
+
+fn encode_value(value: &TileValue) -> Vec<u8> {
+    let mut buf = Vec::new();
+    match value {
+        TileValue::String(s) => write_bytes(&mut buf, 1, s.as_bytes()),
+        TileValue::Double(d) => {
+            write_key(&mut buf, 3, 1);
+            buf.extend_from_slice(&d.to_le_bytes());
+        }
+        TileValue::Uint(u) => write_uint(&mut buf, 5, *u),
+    }
+    buf
+}
+
+/// Encodes `features` as a single layer Mapbox Vector Tile (version 2).
+/// Keys and values shared between features are only written once.
+pub fn encode_tile(tile: &TileId, layer_name: &str, features: &[TileFeature]) -> Vec<u8> {
+    let mut keys: Vec<&str> = Vec::new();
+    let mut values: Vec<Vec<u8>> = Vec::new();
+    let mut key_indexes: HashMap<&str, u32> = HashMap::new();
+    let mut value_indexes: HashMap<Vec<u8>, u32> = HashMap::new();
+
+    let mut layer = Vec::new();
+    write_uint(&mut layer, 15, 2);
+    write_bytes(&mut layer, 1, layer_name.as_bytes());
+    for feature in features {
+        let mut tags = Vec::with_capacity(feature.properties.len() * 2);
+        for (key, value) in &feature.properties {
+            let key_index = *key_indexes.entry(key).or_insert_with(|| {
+                keys.push(key);
+                keys.len() as u32 - 1
+            });
+            let value_index =
+                *value_indexes
+                    .entry(encode_value(value))
+                    .or_insert_with_key(|encoded| {
+                        values.push(encoded.clone());
+                        values.len() as u32 - 1
+                    });
+            tags.extend([key_index, value_index]);
+        }
+
+        let (x, y) = tile.position(&feature.coordinates);
+        let mut encoded = Vec::new();
+        write_uint(&mut encoded, 1, feature.id);
+        write_packed(&mut encoded, 2, &tags);
+        // A point: one MoveTo command followed by its position.
+        write_uint(&mut encoded, 3, 1);
+        write_packed(&mut encoded, 4, &[1 | 1 << 3, zigzag(x), zigzag(y)]);
+        write_bytes(&mut layer, 2, &encoded);
+    }
+    for key in keys {
+        write_bytes(&mut layer, 3, key.as_bytes());
+    }
+    for value in values {
+        write_bytes(&mut layer, 4, &value);
+    }
+    write_uint(&mut layer, 5, u64::from(TILE_EXTENT));
+
+    let mut tile = Vec::new();
+    write_bytes(&mut tile, 3, &layer);
+    tile
+}
+
+struct CachedTile {
+    bytes: Vec<u8>,
+    inserted_at: Instant,
+}
+
+/// Encoded tiles, kept for `ttl` or until something inside them changes.
+/// Changes made through the API invalidate the tiles they fall in; the
+/// `ttl` bounds how long edits made by search providers take to show up.
+#[derive(Clone)]
+pub struct TileCache {
+    tiles: Arc<Mutex<HashMap<TileId, CachedTile>>>,
+    ttl: Duration,
+    max_entries: usize,
+}
+
+impl Default for TileCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_TILE_CACHE_TTL, DEFAULT_TILE_CACHE_MAX_ENTRIES)
+    }
+}
+
+impl TileCache {
+    pub fn new(ttl: Duration, max_entries: usize) -> Self {
+        TileCache {
+            tiles: Arc::new(Mutex::new(HashMap::new())),
+            ttl,
+            max_entries,
+        }
+    }
+
+    pub fn get(&self, tile: &TileId) -> Option<Vec<u8>> {
+        let mut tiles = self.tiles.lock().unwrap();
+        let cached = tiles.get(tile)?;
+        if cached.inserted_at.elapsed() >= self.ttl {
+            tiles.remove(tile);
+            return None;
+        }
+        Some(cached.bytes.clone())
+    }
+
+    /// Stores a tile, making room by dropping expired tiles and then the
+    /// oldest one.
+    pub fn insert(&self, tile: TileId, bytes: Vec<u8>) {
+        if self.max_entries == 0 {
+            return;
+        }
+        let mut tiles = self.tiles.lock().unwrap();
+        if !tiles.contains_key(&tile) && tiles.len() >= self.max_entries {
+            let ttl = self.ttl;
+            tiles.retain(|_, t| t.inserted_at.elapsed() < ttl);
+        }
+        if !tiles.contains_key(&tile) && tiles.len() >= self.max_entries {
+            let oldest = tiles
+                .iter()
+                .min_by_key(|(_, t)| t.inserted_at)
+                .map(|(id, _)| *id);
+            if let Some(id) = oldest {
+                tiles.remove(&id);
+            }
+        }
+        tiles.insert(
+            tile,
+            CachedTile {
+                bytes,
+                inserted_at: Instant::now(),
+            },
+        );
+    }
+
+    /// Drops the tile containing `coordinates` at every zoom.
+    pub fn invalidate(&self, coordinates: &Coordinates) {
+        let mut tiles = self.tiles.lock().unwrap();
+        for z in 0..=MAX_ZOOM {
+            tiles.remove(&TileId::containing(coordinates, z));
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tiles.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::geo::cluster::MAX_MERCATOR_LATITUDE;
+
+    use super::*;
+
+    enum Field<'a> {
+        Varint(u64),
+        Fixed64([u8; 8]),
+        Bytes(&'a [u8]),
+    }
+
+    fn read_varint(bytes: &[u8], at: &mut usize) -> u64 {
+        let mut value = 0;
+        let mut shift = 0;
+        loop {
+            let byte = bytes[*at];
+            *at += 1;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte < 0x80 {
+                return value;
+            }
+            shift += 7;
+        }
+    }
+
+    fn fields(bytes: &[u8]) -> Vec<(u64, Field<'_>)> {
+        let mut at = 0;
+        let mut fields = Vec::new();
+        while at < bytes.len() {
+            let key = read_varint(bytes, &mut at);
+            let field = match key & 7 {
+                0 => Field::Varint(read_varint(bytes, &mut at)),
+                1 => {
+                    at += 8;
+                    Field::Fixed64(bytes[at - 8..at].try_into().unwrap())
+                }
+                2 => {
+                    let len = read_varint(bytes, &mut at) as usize;
+                    at += len;
+                    Field::Bytes(&bytes[at - len..at])
+                }
+                wire_type => panic!("unexpected wire type {}", wire_type),
+            };
+            fields.push((key >> 3, field));
+        }
+        fields
+    }
+
+    fn bytes_of(fields: &[(u64, Field<'_>)], number: u64) -> Vec<Vec<u8>> {
+        fields
+            .iter()
+            .filter_map(|(n, f)| match f {
+                Field::Bytes(b) if *n == number => Some(b.to_vec()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn varint_of(fields: &[(u64, Field<'_>)], number: u64) -> Option<u64> {
+        fields.iter().find_map(|(n, f)| match f {
+            Field::Varint(v) if *n == number => Some(*v),
+            _ => None,
+        })
+    }
+
+    fn packed(bytes: &[u8]) -> Vec<u64> {
+        let mut at = 0;
+        let mut values = Vec::new();
+        while at < bytes.len() {
+            values.push(read_varint(bytes, &mut at));
+        }
+        values
+    }
+
+    #[test]
+    fn test_tile_bounding_box() {
+        let world = TileId::new(0, 0, 0).unwrap().bounding_box();
+        assert_eq!(world.min_longitude, -180.0);
+        assert_eq!(world.max_longitude, 180.0);
+        assert!((world.max_latitude - MAX_MERCATOR_LATITUDE).abs() < 1e-6);
+
+        let north_west = TileId::new(1, 0, 0).unwrap().bounding_box();
+        assert_eq!(north_west.max_longitude, 0.0);
+        assert!(north_west.min_latitude.abs() < 1e-9);
+
+        let salt_lake_city = Coordinates {
+            latitude: 40.7608,
+            longitude: -111.891,
+        };
+        let tile = TileId::containing(&salt_lake_city, 14);
+        assert_eq!((tile.x, tile.y), (3099, 6157));
+        assert!(tile.bounding_box().contains(&salt_lake_city));
+    }
+
+    #[test]
+    fn test_tile_id_rejects_tiles_outside_the_map() {
+        assert!(TileId::new(2, 3, 3).is_ok());
+        assert!(TileId::new(2, 4, 0).is_err());
+        assert!(TileId::new(2, 0, 4).is_err());
+        assert!(TileId::new(MAX_ZOOM + 1, 0, 0).is_err());
+    }
+
+    #[test]
+    fn test_encode_tile() {
+        let tile = TileId::new(1, 0, 0).unwrap();
+        let features = [
+            TileFeature {
+                id: 7,
+                coordinates: Coordinates {
+                    latitude: 66.513_260_443,
+                    longitude: -90.0,
+                },
+                properties: vec![
+                    ("name".to_string(), TileValue::String("Arlo".to_string())),
+                    ("score".to_string(), TileValue::Double(7.5)),
+                ],
+            },
+            TileFeature {
+                id: 8,
+                coordinates: Coordinates {
+                    latitude: 0.0,
+                    longitude: -180.0,
+                },
+                properties: vec![("score".to_string(), TileValue::Double(7.5))],
+            },
+        ];
+
+        let encoded = encode_tile(&tile, "places", &features);
+
+        let layers = bytes_of(&fields(&encoded), 3);
+        assert_eq!(layers.len(), 1);
+        let layer = fields(&layers[0]);
+        assert_eq!(varint_of(&layer, 15), Some(2));
+        assert_eq!(bytes_of(&layer, 1), vec![b"places".to_vec()]);
+        assert_eq!(varint_of(&layer, 5), Some(u64::from(TILE_EXTENT)));
+        assert_eq!(
+            bytes_of(&layer, 3),
+            vec![b"name".to_vec(), b"score".to_vec()]
+        );
+
+        let values = bytes_of(&layer, 4);
+        assert_eq!(values.len(), 2);
+        match &fields(&values[1])[0] {
+            (3, Field::Fixed64(d)) => assert_eq!(f64::from_le_bytes(*d), 7.5),
+            _ => panic!("expected a double"),
+        }
+
+        let encoded_features = bytes_of(&layer, 2);
+        let first = fields(&encoded_features[0]);
+        assert_eq!(varint_of(&first, 1), Some(7));
+        assert_eq!(varint_of(&first, 3), Some(1));
+        assert_eq!(packed(&bytes_of(&first, 2)[0]), vec![0, 0, 1, 1]);
+        assert_eq!(
+            packed(&bytes_of(&first, 4)[0]),
+            vec![9, u64::from(zigzag(2048)), u64::from(zigzag(2048))]
+        );
+
+        let second = fields(&encoded_features[1]);
+        assert_eq!(packed(&bytes_of(&second, 2)[0]), vec![1, 1]);
+        assert_eq!(
+            packed(&bytes_of(&second, 4)[0]),
+            vec![9, 0, u64::from(zigzag(4096))]
+        );
+    }
+
+    #[test]
+    fn test_tile_cache_invalidates_tiles_containing_a_point() {
+        let arlo = Coordinates {
+            latitude: 40.7756,
+            longitude: -111.894,
+        };
+        let provo = Coordinates {
+            latitude: 40.2338,
+            longitude: -111.6585,
+        };
+        let cache = TileCache::default();
+        for z in [4, 14] {
+            cache.insert(TileId::containing(&arlo, z), vec![z]);
+        }
+        let provo_tile = TileId::containing(&provo, 14);
+        cache.insert(provo_tile, vec![1]);
+
+        cache.invalidate(&arlo);
+
+        assert!(cache.get(&TileId::containing(&arlo, 14)).is_none());
+        assert!(cache.get(&TileId::containing(&arlo, 4)).is_none());
+        assert_eq!(cache.get(&provo_tile), Some(vec![1]));
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_tile_cache_expires_and_evicts() {
+        let expiring = TileCache::new(Duration::ZERO, 10);
+        expiring.insert(TileId::new(0, 0, 0).unwrap(), vec![1]);
+        assert!(expiring.get(&TileId::new(0, 0, 0).unwrap()).is_none());
+
+        let small = TileCache::new(DEFAULT_TILE_CACHE_TTL, 1);
+        small.insert(TileId::new(1, 0, 0).unwrap(), vec![1]);
+        small.insert(TileId::new(1, 1, 0).unwrap(), vec![2]);
+        assert_eq!(small.len(), 1);
+        assert_eq!(small.get(&TileId::new(1, 1, 0).unwrap()), Some(vec![2]));
+    }
+}
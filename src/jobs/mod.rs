@@ -0,0 +1,557 @@
+pub mod refresh;
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
+use futures::{future, stream::FuturesUnordered, StreamExt};
+use tokio::sync::Notify;
+
+use crate::{
+    places::{
+        search::{read_place, update_place, DynPlacesSearch},
+        PlaceDetails, DETAILS_MAX_AGE_DAYS,
+    },
+    repository::{
+        jobs::{DynJobsRepo, Job, JobKind},
+        places::DynPlacesRepo,
+    },
+    shutdown::Shutdown,
+};
+
+pub const DEFAULT_JOB_CONCURRENCY: usize = 4;
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 5;
+pub const DEFAULT_RETRY_BACKOFF: Duration = Duration::from_secs(2);
+pub const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(60 * 60);
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+pub const DEFAULT_JOB_LEASE: Duration = Duration::from_secs(15 * 60);
+pub const DEFAULT_REFRESH_INTERVAL: Duration = Duration::from_secs(60 * 60);
+pub const DEFAULT_REFRESH_MAX_AGE: Duration =
+    Duration::from_secs(DETAILS_MAX_AGE_DAYS as u64 * 24 * 60 * 60);
+pub const DEFAULT_REFRESH_BATCH_SIZE: usize = 100;
+
+#[derive(Clone, Copy, Debug)]
+pub struct JobOptions {
+    /// How many jobs run at once.
+    pub concurrency: usize,
+    /// Attempts before a job is moved to the dead letters.
+    pub max_attempts: u32,
+    /// The wait before the first retry, doubling with every failed attempt.
+    pub backoff: Duration,
+    /// How often to look for jobs that have become due.
+    pub poll_interval: Duration,
+    /// How long a claimed job may run before it is taken for abandoned by a
+    /// worker that stopped, and queued again. Other instances may still be
+    /// running the jobs they claimed, so it should be well over the time any
+    /// job takes.
+    pub lease: Duration,
+    /// How often to look for places with stale details.
+    pub refresh_interval: Duration,
+    /// How old details get before they are fetched again.
+    pub refresh_max_age: Duration,
+    /// The most places queued for a refresh each time.
+    pub refresh_batch_size: usize,
+}
+
+impl Default for JobOptions {
+    fn default() -> Self {
+        JobOptions {
+            concurrency: DEFAULT_JOB_CONCURRENCY,
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            backoff: DEFAULT_RETRY_BACKOFF,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            lease: DEFAULT_JOB_LEASE,
+            refresh_interval: DEFAULT_REFRESH_INTERVAL,
+            refresh_max_age: DEFAULT_REFRESH_MAX_AGE,
+            refresh_batch_size: DEFAULT_REFRESH_BATCH_SIZE,
+        }
+    }
+}
+
+/// Background work on places, stored in a jobs repository so it survives
+/// restarts and upstream failures. Failed jobs are retried with exponential
+/// backoff until they run out of attempts.
+#[derive(Clone)]
+pub struct JobQueue {
+    jobs_repo: DynJobsRepo,
+    places_repo: DynPlacesRepo,
+    places_search: DynPlacesSearch,
+    options: JobOptions,
+    wake: Arc<Notify>,
+}
+
+impl JobQueue {
+    pub fn new(
+        jobs_repo: DynJobsRepo,
+        places_repo: DynPlacesRepo,
+        places_search: DynPlacesSearch,
+        options: JobOptions,
+    ) -> Self {
+        JobQueue {
+            jobs_repo,
+            places_repo,
+            places_search,
+            options,
+            wake: Arc::new(Notify::new()),
+        }
+    }
+
+    /// Queues a job for the place, unless one is already waiting.
+    pub async fn enqueue(&self, kind: JobKind, place_id: u64) -> Result<Job, String> {
+        let job = self
+            .jobs_repo
+            .lock()
+            .await
+            .enqueue(kind, place_id, Utc::now())
+            .await?;
+        self.wake.notify_one();
+        Ok(job)
+    }
+
+    pub async fn dead_letters(&self) -> Result<Vec<Job>, String> {
+        self.jobs_repo.lock().await.dead_letters().await
+    }
+
+    /// The wait before retrying a job that has failed `attempts` times.
+    pub fn retry_delay(&self, attempts: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+        self.options
+            .backoff
+            .saturating_mul(factor)
+            .min(MAX_RETRY_BACKOFF)
+    }
+
+    /// Runs the jobs that are due, at most `concurrency` of them, and returns
+    /// how many ran.
+    pub async fn run_due(&self) -> Result<usize, String> {
+        let jobs = self.claim(self.options.concurrency).await?;
+        let ran = jobs.len();
+        future::join_all(jobs.into_iter().map(|job| self.run_to_end(job))).await;
+        Ok(ran)
+    }
+
+    async fn claim(&self, limit: usize) -> Result<Vec<Job>, String> {
+        self.jobs_repo.lock().await.claim(Utc::now(), limit).await
+    }
+
+    /// Runs a claimed job and records whether it completed or failed.
+    async fn run_to_end(&self, job: Job) {
+        let result = match self.run(&job).await {
+            Ok(()) => self.jobs_repo.lock().await.complete(job.id).await,
+            Err(e) => self.fail(&job, &e).await,
+        };
+        if let Err(e) = result {
+            eprintln!("Error recording the result of job {}: {}", job.id, e);
+        }
+    }
+
+    async fn fail(&self, job: &Job, error: &str) -> Result<(), String> {
+        let attempts = job.attempts + 1;
+        let now = Utc::now();
+        let retry_at = if attempts < self.options.max_attempts {
+            chrono::Duration::from_std(self.retry_delay(attempts))
+                .ok()
+                .map(|delay| now + delay)
+        } else {
+            eprintln!(
+                "Job {} for place {} failed {} times, giving up: {}",
+                job.id, job.place_id, attempts, error
+            );
+            None
+        };
+        self.jobs_repo
+            .lock()
+            .await
+            .fail(job.id, error, retry_at, now)
+            .await?;
+        Ok(())
+    }
+
+    async fn run(&self, job: &Job) -> Result<(), String> {
+        match job.kind {
+            JobKind::FetchPhotos => self.fetch_photos(job.place_id).await,
+            JobKind::RefreshPlace => {
+                let fetched = self.places_search.get_details(job.place_id).await?;
+                // When no provider knows more, that is recorded too, so the
+                // place waits out the refresh age before it is asked about
+                // again.
+                let details = fetched
+                    .details
+                    .clone()
+                    .unwrap_or_else(|| PlaceDetails::unknown(Utc::now()));
+                update_place(&self.places_repo, job.place_id, |place| {
+                    place.details = Some(details)
+                })
+                .await?;
+                if fetched.photos_missing() {
+                    self.fetch_photos(job.place_id).await?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    async fn fetch_photos(&self, place_id: u64) -> Result<(), String> {
+        let place = read_place(&self.places_repo, place_id).await?;
+        if place.photos.as_ref().is_some_and(|p| !p.is_empty()) {
+            return Ok(());
+        }
+
+        let fetched = self.places_search.get_photos(place_id).await?;
+        if fetched.photos.as_ref().is_some_and(|p| !p.is_empty()) {
+            update_place(&self.places_repo, place_id, |place| {
+                place.photos = fetched.photos
+            })
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Queues jobs again that have been running for longer than the lease,
+    /// left by a worker that stopped, and returns how many there were.
+    pub async fn requeue_abandoned(&self) -> Result<u64, String> {
+        let lease = match chrono::Duration::from_std(self.options.lease) {
+            Ok(lease) => lease,
+            Err(_) => return Err("Job lease not valid".to_string()),
+        };
+        self.jobs_repo
+            .lock()
+            .await
+            .requeue_running(Utc::now() - lease)
+            .await
+    }
+
+    /// Runs jobs in the background until `shutdown`, letting the jobs already
+    /// running finish. Jobs abandoned by workers that stopped, on this
+    /// instance or another, are picked up again once their lease runs out.
+    pub fn start(&self, shutdown: &Shutdown) {
+        let queue = self.clone();
+        let stop = shutdown.clone();
+        shutdown.spawn(async move {
+            // Claims more as soon as any job finishes, so a slow job only
+            // holds up its own slot.
+            let mut running = FuturesUnordered::new();
+            let mut requeued_at: Option<Instant> = None;
+            while !stop.is_triggered() {
+                if requeued_at.is_none_or(|at| at.elapsed() >= queue.options.lease) {
+                    match queue.requeue_abandoned().await {
+                        Ok(0) => {}
+                        Ok(n) => println!("Requeued {} abandoned jobs", n),
+                        Err(e) => eprintln!("Error requeueing abandoned jobs: {}", e),
+                    }
+                    requeued_at = Some(Instant::now());
+                }
+                let free = queue.options.concurrency.saturating_sub(running.len());
+                if free > 0 {
+                    match queue.claim(free).await {
+                        Ok(jobs) => {
+                            for job in jobs {
+                                running.push(queue.run_to_end(job));
+                            }
+                        }
+                        Err(e) => eprintln!("Error claiming jobs: {}", e),
+                    }
+                }
+                tokio::select! {
+                    Some(()) = running.next(), if !running.is_empty() => {}
+                    _ = tokio::time::sleep(queue.options.poll_interval) => {}
+                    _ = queue.wake.notified() => {}
+                    _ = stop.triggered() => {}
+                }
+            }
+            while running.next().await.is_some() {}
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::async_trait;
+    use tokio::sync::Mutex;
+
+    use crate::{
+        places::{
+            database::DatabaseSearch,
+            photo::Photo,
+            search::{Search, SearchQuery},
+            testing::{place, salt_lake_city, FakeSearch},
+            Place,
+        },
+        repository::{
+            jobs::JobStatus,
+            local::{jobs::LocalJobsRepository, places::LocalPlacesRepository},
+            places::PlacesRepository,
+        },
+    };
+
+    use super::*;
+
+    fn with_photos(place_id: u64, name: &str) -> Place {
+        Place {
+            photos: Some(vec![Photo {
+                prefix: "https://fastly.4sqi.net/img/general/".to_string(),
+                suffix: format!("/{}.jpg", place_id),
+            }]),
+            ..place(place_id, name)
+        }
+    }
+
+    async fn queue(failures: &[(u64, u32)], max_attempts: u32) -> JobQueue {
+        let mut places_repo = LocalPlacesRepository::new();
+        let names = ["Arlo", "Red Iguana", "Communal"];
+        for name in names {
+            places_repo.create(&place(0, name)).await.unwrap();
+        }
+        let found = (1..).zip(names).map(|(id, name)| with_photos(id, name));
+        JobQueue::new(
+            Arc::new(Mutex::new(LocalJobsRepository::new())),
+            Arc::new(Mutex::new(places_repo)),
+            Arc::new(FakeSearch::new(Ok(found.collect())).with_photo_failures(failures)),
+            JobOptions {
+                concurrency: 2,
+                max_attempts,
+                backoff: Duration::ZERO,
+                poll_interval: Duration::from_millis(10),
+                lease: Duration::ZERO,
+                ..JobOptions::default()
+            },
+        )
+    }
+
+    /// Finds photos, and coordinates are stored for the place while it looks.
+    struct LocatedWhileSearching {
+        places_repo: DynPlacesRepo,
+    }
+
+    #[async_trait]
+    impl Search for LocatedWhileSearching {
+        async fn search_for_place(&self, _query: SearchQuery) -> Result<Vec<Place>, String> {
+            Ok(Vec::new())
+        }
+
+        async fn get_photos(&self, place_id: u64) -> Result<Place, String> {
+            let located = update_place(&self.places_repo, place_id, |place| {
+                place.coordinates = Some(salt_lake_city())
+            })
+            .await?;
+            Ok(Place {
+                coordinates: None,
+                ..with_photos(place_id, &located.name)
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fetch_photos_keeps_what_was_stored_meanwhile() {
+        let mut places_repo = LocalPlacesRepository::new();
+        places_repo.create(&place(0, "Arlo")).await.unwrap();
+        let places_repo: DynPlacesRepo = Arc::new(Mutex::new(places_repo));
+        let queue = JobQueue::new(
+            Arc::new(Mutex::new(LocalJobsRepository::new())),
+            places_repo.clone(),
+            Arc::new(LocatedWhileSearching {
+                places_repo: places_repo.clone(),
+            }),
+            JobOptions::default(),
+        );
+        queue.enqueue(JobKind::FetchPhotos, 1).await.unwrap();
+
+        assert_eq!(queue.run_due().await.unwrap(), 1);
+
+        let arlo = read_place(&places_repo, 1).await.unwrap();
+        assert_eq!(arlo.photos.unwrap().len(), 1);
+        assert_eq!(arlo.coordinates, Some(salt_lake_city()));
+    }
+
+    #[tokio::test]
+    async fn test_refresh_marks_places_no_provider_knows() {
+        let mut places_repo = LocalPlacesRepository::new();
+        places_repo.create(&place(0, "Arlo")).await.unwrap();
+        let places_repo: DynPlacesRepo = Arc::new(Mutex::new(places_repo));
+        let queue = JobQueue::new(
+            Arc::new(Mutex::new(LocalJobsRepository::new())),
+            places_repo.clone(),
+            Arc::new(DatabaseSearch::new(places_repo.clone())),
+            JobOptions::default(),
+        );
+        queue.enqueue(JobKind::RefreshPlace, 1).await.unwrap();
+
+        assert_eq!(queue.run_due().await.unwrap(), 1);
+
+        let arlo = read_place(&places_repo, 1).await.unwrap();
+        assert_eq!(arlo.details.as_ref().unwrap().hours, None);
+        assert!(!arlo.details_stale(Utc::now()));
+        assert!(queue.dead_letters().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_retry_delay_doubles_up_to_the_maximum() {
+        let mut queue = queue(&[], 1).await;
+        queue.options = JobOptions::default();
+
+        assert_eq!(queue.retry_delay(1), Duration::from_secs(2));
+        assert_eq!(queue.retry_delay(2), Duration::from_secs(4));
+        assert_eq!(queue.retry_delay(5), Duration::from_secs(32));
+        assert_eq!(queue.retry_delay(40), MAX_RETRY_BACKOFF);
+    }
+
+    #[tokio::test]
+    async fn test_run_due_retries_then_gives_up() {
+        let queue = queue(&[(1, 1), (2, 10)], 3).await;
+        for place_id in [1, 2, 3] {
+            queue.enqueue(JobKind::FetchPhotos, place_id).await.unwrap();
+        }
+        // Already queued.
+        queue.enqueue(JobKind::FetchPhotos, 1).await.unwrap();
+
+        // At most two at a time.
+        assert_eq!(queue.run_due().await.unwrap(), 2);
+        assert_eq!(queue.run_due().await.unwrap(), 2);
+        assert_eq!(queue.run_due().await.unwrap(), 1);
+        assert_eq!(queue.run_due().await.unwrap(), 1);
+        assert_eq!(queue.run_due().await.unwrap(), 0);
+
+        for place_id in [1, 3] {
+            let place = read_place(&queue.places_repo, place_id).await.unwrap();
+            assert_eq!(place.photos.unwrap().len(), 1);
+        }
+        let dead = queue.dead_letters().await.unwrap();
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].place_id, 2);
+        assert_eq!(dead[0].status, JobStatus::Dead);
+        assert_eq!(dead[0].attempts, 3);
+        assert_eq!(
+            dead[0].last_error,
+            Some("Error getting pictures".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_requeue_abandoned_leaves_jobs_within_their_lease() {
+        let mut queue = queue(&[], 3).await;
+        queue.enqueue(JobKind::FetchPhotos, 1).await.unwrap();
+        // Claimed by another instance, which is still running it.
+        queue
+            .jobs_repo
+            .lock()
+            .await
+            .claim(Utc::now(), 10)
+            .await
+            .unwrap();
+
+        queue.options.lease = Duration::from_secs(60);
+        assert_eq!(queue.requeue_abandoned().await.unwrap(), 0);
+        assert_eq!(queue.run_due().await.unwrap(), 0);
+
+        queue.options.lease = Duration::ZERO;
+        assert_eq!(queue.requeue_abandoned().await.unwrap(), 1);
+        assert_eq!(queue.run_due().await.unwrap(), 1);
+    }
+
+    /// Finds photos for every place but the first only once released.
+    struct SlowFirstPlace {
+        photos: FakeSearch,
+        release: Arc<Notify>,
+    }
+
+    #[async_trait]
+    impl Search for SlowFirstPlace {
+        async fn search_for_place(&self, query: SearchQuery) -> Result<Vec<Place>, String> {
+            self.photos.search_for_place(query).await
+        }
+
+        async fn get_photos(&self, place_id: u64) -> Result<Place, String> {
+            if place_id == 1 {
+                self.release.notified().await;
+            }
+            self.photos.get_photos(place_id).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_start_runs_other_jobs_while_one_is_slow() {
+        let queue = queue(&[], 3).await;
+        let release = Arc::new(Notify::new());
+        let names = ["Arlo", "Red Iguana", "Communal"];
+        let found = (1..).zip(names).map(|(id, name)| with_photos(id, name));
+        let queue = JobQueue {
+            places_search: Arc::new(SlowFirstPlace {
+                photos: FakeSearch::new(Ok(found.collect())),
+                release: release.clone(),
+            }),
+            options: JobOptions {
+                lease: Duration::from_secs(60),
+                ..queue.options
+            },
+            ..queue
+        };
+        for place_id in [1, 2, 3] {
+            queue.enqueue(JobKind::FetchPhotos, place_id).await.unwrap();
+        }
+
+        let shutdown = Shutdown::new();
+        queue.start(&shutdown);
+        // Two at a time, and Arlo's job is still running.
+        let mut communal = read_place(&queue.places_repo, 3).await.unwrap();
+        for _ in 0..100 {
+            if communal.photos.is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            communal = read_place(&queue.places_repo, 3).await.unwrap();
+        }
+        assert!(communal.photos.is_some());
+        let arlo = read_place(&queue.places_repo, 1).await.unwrap();
+        assert!(arlo.photos.is_none());
+
+        release.notify_one();
+        shutdown.shutdown().await;
+        let arlo = read_place(&queue.places_repo, 1).await.unwrap();
+        assert!(arlo.photos.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_start_runs_queued_and_interrupted_jobs_until_shutdown() {
+        let queue = queue(&[], 3).await;
+        queue
+            .jobs_repo
+            .lock()
+            .await
+            .enqueue(JobKind::FetchPhotos, 1, Utc::now())
+            .await
+            .unwrap();
+        // Left running by a worker that stopped.
+        queue
+            .jobs_repo
+            .lock()
+            .await
+            .claim(Utc::now(), 10)
+            .await
+            .unwrap();
+
+        let shutdown = Shutdown::new();
+        queue.start(&shutdown);
+        queue.enqueue(JobKind::FetchPhotos, 2).await.unwrap();
+        for _ in 0..100 {
+            let arlo = read_place(&queue.places_repo, 1).await.unwrap();
+            let red_iguana = read_place(&queue.places_repo, 2).await.unwrap();
+            if arlo.photos.is_some() && red_iguana.photos.is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        shutdown.shutdown().await;
+
+        for place_id in [1, 2] {
+            let place = read_place(&queue.places_repo, place_id).await.unwrap();
+            assert!(place.photos.is_some());
+        }
+        // Nothing runs after the worker stopped.
+        queue.enqueue(JobKind::FetchPhotos, 3).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        let communal = read_place(&queue.places_repo, 3).await.unwrap();
+        assert!(communal.photos.is_none());
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    jobs::{JobOptions, JobQueue},
+    places::{activity::PlaceActivity, Place},
+    repository::{
+        jobs::JobKind,
+        places::{DynPlacesRepo, StalePlaceOptions},
+    },
+    shutdown::Shutdown,
+};
+
+/// How many recently active places are read at once when looking for stale
+/// ones among them.
+const RECENT_READ_SIZE: usize = 500;
+
+/// Periodically queues `RefreshPlace` jobs for places whose details are
+/// older than `refresh_max_age`.
+#[derive(Clone)]
+pub struct RefreshScheduler {
+    places_repo: DynPlacesRepo,
+    jobs: JobQueue,
+    activity: PlaceActivity,
+    options: JobOptions,
+}
+
+impl RefreshScheduler {
+    pub fn new(
+        places_repo: DynPlacesRepo,
+        jobs: JobQueue,
+        activity: PlaceActivity,
+        options: JobOptions,
+    ) -> Self {
+        RefreshScheduler {
+            places_repo,
+            jobs,
+            activity,
+            options,
+        }
+    }
+
+    /// Queues refreshes for at most `refresh_batch_size` stale places,
+    /// recently searched for, looked at or rated ones first, then those stale
+    /// the longest. Returns the ids of the places queued.
+    pub async fn schedule(&self, now: DateTime<Utc>) -> Result<Vec<u64>, String> {
+        let max_age = match chrono::Duration::from_std(self.options.refresh_max_age) {
+            Ok(age) => age,
+            Err(_) => return Err("Refresh age not valid".to_string()),
+        };
+        let batch_size = self.options.refresh_batch_size;
+
+        let mut place_ids = Vec::new();
+        for recent in self.activity.recent().chunks(RECENT_READ_SIZE) {
+            if place_ids.len() == batch_size {
+                break;
+            }
+            // Places seen in a search may not have been stored.
+            let stored: HashMap<u64, Place> = self
+                .places_repo
+                .lock()
+                .await
+                .read_many(recent)
+                .await?
+                .into_iter()
+                .map(|place| (place.id, place))
+                .collect();
+            let stale = recent.iter().filter(|id| {
+                stored
+                    .get(id)
+                    .is_some_and(|place| place.details_older_than(max_age, now))
+            });
+            for place_id in stale.take(batch_size - place_ids.len()) {
+                place_ids.push(*place_id);
+            }
+        }
+        if place_ids.len() < batch_size {
+            let stale = self
+                .places_repo
+                .lock()
+                .await
+                .stale(StalePlaceOptions {
+                    updated_before: now - max_age,
+                    limit: batch_size,
+                })
+                .await?;
+            for place in stale {
+                if place_ids.len() == batch_size {
+                    break;
+                }
+                if !place_ids.contains(&place.id) {
+                    place_ids.push(place.id);
+                }
+            }
+        }
+
+        for place_id in &place_ids {
+            self.jobs.enqueue(JobKind::RefreshPlace, *place_id).await?;
+        }
+        Ok(place_ids)
+    }
+
+    /// Schedules refreshes every `refresh_interval`, starting now, until
+    /// `shutdown`.
+    pub fn start(&self, shutdown: &Shutdown) {
+        let scheduler = self.clone();
+        let stop = shutdown.clone();
+        shutdown.spawn(async move {
+            let mut interval = tokio::time::interval(scheduler.options.refresh_interval);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = stop.triggered() => break,
+                }
+                match scheduler.schedule(Utc::now()).await {
+                    Ok(place_ids) if !place_ids.is_empty() => {
+                        println!("Queued {} stale places for a refresh", place_ids.len())
+                    }
+                    Ok(_) => {}
+                    Err(e) => eprintln!("Error scheduling place refreshes: {}", e),
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use tokio::sync::Mutex;
+
+    use crate::{
+        places::testing::{details, place, FakeSearch},
+        repository::{
+            local::{jobs::LocalJobsRepository, places::LocalPlacesRepository},
+            places::PlacesRepository,
+        },
+    };
+
+    use super::*;
+
+    fn foursquare_place(name: &str, details_age_days: Option<i64>) -> Place {
+        Place {
+            foursquare_id: Some(format!("fsq-{}", name)),
+            details: details_age_days
+                .map(|days| details(Utc::now() - chrono::Duration::days(days))),
+            ..place(0, name)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_schedule_prefers_recently_active_places() {
+        let mut places_repo = LocalPlacesRepository::new();
+        for place in [
+            foursquare_place("Fresh", Some(1)),
+            foursquare_place("Old", Some(40)),
+            foursquare_place("Older", Some(60)),
+            foursquare_place("Missing", None),
+            foursquare_place("Active", Some(31)),
+        ] {
+            places_repo.create(&place).await.unwrap();
+        }
+        let places_repo: DynPlacesRepo = Arc::new(Mutex::new(places_repo));
+        let options = JobOptions {
+            refresh_batch_size: 3,
+            ..JobOptions::default()
+        };
+        let jobs = JobQueue::new(
+            Arc::new(Mutex::new(LocalJobsRepository::new())),
+            places_repo.clone(),
+            Arc::new(FakeSearch::new(Ok(Vec::new()))),
+            options,
+        );
+        let activity = PlaceActivity::default();
+        // Fresh details don't need a refresh however popular the place, and
+        // place 99 was only ever a search result.
+        activity.record([1, 5, 99], Utc::now());
+        let scheduler = RefreshScheduler::new(places_repo, jobs.clone(), activity, options);
+
+        assert_eq!(scheduler.schedule(Utc::now()).await.unwrap(), vec![5, 4, 3]);
+        // Queued jobs aren't queued twice.
+        assert_eq!(jobs.enqueue(JobKind::RefreshPlace, 5).await.unwrap().id, 1);
+
+        let scheduler = RefreshScheduler {
+            options: JobOptions {
+                refresh_max_age: Duration::from_secs(50 * 24 * 60 * 60),
+                ..options
+            },
+            ..scheduler
+        };
+        assert_eq!(scheduler.schedule(Utc::now()).await.unwrap(), vec![4, 3]);
+    }
+}
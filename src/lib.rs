@@ -1,32 +1,102 @@
 pub mod app_state;
+pub mod blob;
 pub mod geo;
+pub mod jobs;
 pub mod oauth;
 pub mod places;
+pub mod replay;
 pub mod repository;
 mod router;
 mod routes;
+pub mod shutdown;
 pub mod sms;
 
 use std::net::SocketAddr;
 
+use blob::DynBlobStore;
+use jobs::JobOptions;
 use oauth::OAuth;
-use places::search::Search;
-use repository::{places::PlacesRepository, user::UserRepository};
-use router::create_router;
+use places::{photo::PhotoProxy, search::Search};
+use repository::{
+    jobs::JobsRepository, places::PlacesRepository, ratings::RatingsRepository,
+    user::UserRepository,
+};
+pub use router::create_router;
+use shutdown::Shutdown;
 use sms::SMSVerify;
 
-pub async fn run<U: UserRepository, P: PlacesRepository, V: SMSVerify, S: Search>(
+#[allow(clippy::too_many_arguments)]
+pub async fn run<
+    U: UserRepository,
+    P: PlacesRepository,
+    R: RatingsRepository,
+    J: JobsRepository,
+    V: SMSVerify,
+    S: Search,
+>(
     user_repo: U,
     places_repo: P,
+    ratings_repo: R,
+    jobs_repo: J,
     sms_verify: V,
     places_search: S,
     oauth: OAuth,
+    photo_proxy: PhotoProxy,
+    blob_store: DynBlobStore,
+    job_options: JobOptions,
 ) {
-    let app = create_router(user_repo, places_repo, sms_verify, places_search, oauth);
+    let shutdown = Shutdown::new();
+    let app = create_router(
+        user_repo,
+        places_repo,
+        ratings_repo,
+        jobs_repo,
+        sms_verify,
+        places_search,
+        oauth,
+        photo_proxy,
+        blob_store,
+        job_options,
+        shutdown.clone(),
+    );
     let address = SocketAddr::from(([0, 0, 0, 0], 8080));
 
     axum::Server::bind(&address)
         .serve(app.into_make_service())
+        .with_graceful_shutdown(shutdown_signal())
         .await
         .unwrap();
+
+    println!("Waiting for background jobs to finish");
+    shutdown.shutdown().await;
+}
+
+/// Completes on Ctrl+C, or on SIGTERM from `docker stop` and the like.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            eprintln!("error listening for Ctrl+C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                eprintln!("error listening for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
 }
@@ -1,10 +1,38 @@
-mod sms;
-
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use critiq_backend::{
-    oauth::OAuth, places::mapbox::search::MapboxSearchApi, repository::subabase::SupabaseRepo, run,
-    sms::twilio::TwilioSMS,
+    blob::{
+        local::LocalBlobStore,
+        s3::{S3BlobStore, DEFAULT_S3_REGION},
+        DynBlobStore,
+    },
+    jobs::{
+        JobOptions, DEFAULT_JOB_CONCURRENCY, DEFAULT_JOB_LEASE, DEFAULT_MAX_ATTEMPTS,
+        DEFAULT_REFRESH_BATCH_SIZE, DEFAULT_REFRESH_INTERVAL, DEFAULT_REFRESH_MAX_AGE,
+        DEFAULT_RETRY_BACKOFF,
+    },
+    oauth::OAuth,
+    places::{
+        cache::{CachedSearch, InMemorySearchCache, DEFAULT_CACHE_MAX_ENTRIES, DEFAULT_CACHE_TTL},
+        composite::{CompositeSearch, DEFAULT_PROVIDER_TIMEOUT},
+        database::DatabaseSearch,
+        foursquare::{search::FoursquareSearchApi, FOURSQUARE_BASE_URL},
+        local_first::{LocalFirstSearch, DEFAULT_MIN_LOCAL_RESULTS},
+        mapbox::search::{MapboxSearchApi, MAPBOX_BASE_URL},
+        osm::{search::OsmSearchApi, DEFAULT_USER_AGENT},
+        photo::{set_token_key, PhotoProxy, DEFAULT_PHOTO_CACHE_BYTES},
+    },
+    repository::{
+        jobs::JobsRepository,
+        places::{DynPlacesRepo, PlacesRepository},
+        postgres::PostgresRepo,
+        ratings::RatingsRepository,
+        sqlite::SqliteRepo,
+        subabase::SupabaseRepo,
+        user::UserRepository,
+    },
+    run,
+    sms::twilio::{TwilioSMS, TWILIO_VERIFY_BASE_URL},
 };
 use tokio::sync::Mutex;
 
@@ -12,6 +40,75 @@ use tokio::sync::Mutex;
 async fn main() {
     dotenv::dotenv().ok();
 
+    let repository = std::env::var("REPOSITORY").unwrap_or_else(|_| "supabase".to_string());
+    match repository.as_str() {
+        "supabase" => {
+            let supabase_url = std::env::var("SUPABASE_URL").expect("SUPABASE_URL must be set.");
+            let supabase_api_key =
+                std::env::var("SUPABASE_API_KEY").expect("SUPABASE_API_KEY must be set.");
+
+            start(
+                SupabaseRepo::new(&supabase_url, &supabase_api_key),
+                SupabaseRepo::new(&supabase_url, &supabase_api_key),
+                SupabaseRepo::new(&supabase_url, &supabase_api_key),
+                SupabaseRepo::new(&supabase_url, &supabase_api_key),
+                Arc::new(Mutex::new(SupabaseRepo::new(
+                    &supabase_url,
+                    &supabase_api_key,
+                ))),
+            )
+            .await
+        }
+        "sqlite" => {
+            let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set.");
+            let repo = SqliteRepo::new(&database_url)
+                .await
+                .expect("SQLite database to be available.");
+
+            start(
+                repo.clone(),
+                repo.clone(),
+                repo.clone(),
+                repo.clone(),
+                Arc::new(Mutex::new(repo)),
+            )
+            .await
+        }
+        "postgres" => {
+            let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set.");
+            let max_connections = match std::env::var("DATABASE_MAX_CONNECTIONS") {
+                Ok(n) => n
+                    .parse()
+                    .expect("DATABASE_MAX_CONNECTIONS must be a number."),
+                Err(_) => 10,
+            };
+            let repo = PostgresRepo::new(&database_url, max_connections)
+                .await
+                .expect("Postgres database to be available.");
+
+            start(
+                repo.clone(),
+                repo.clone(),
+                repo.clone(),
+                repo.clone(),
+                Arc::new(Mutex::new(repo)),
+            )
+            .await
+        }
+        other => panic!(
+            "REPOSITORY must be one of supabase, sqlite or postgres, got {}.",
+            other
+        ),
+    }
+}
+
+async fn start<U: UserRepository, P: PlacesRepository, R: RatingsRepository, J: JobsRepository>(
+    user_repo: U,
+    places_repo: P,
+    ratings_repo: R,
+    jobs_repo: J,
+    search_places_repo: DynPlacesRepo,
+) {
     let twilio_account_sid =
         std::env::var("TWILIO_ACCOUNT_SID").expect("TWILIO_ACCOUNT_SID must be set.");
     let twilio_service_sid =
@@ -19,25 +116,211 @@ async fn main() {
     let twilio_auth_token =
         std::env::var("TWILIO_AUTH_TOKEN").expect("TWILIO_AUTH_TOKEN must be set.");
     let jwt_key = std::env::var("JWT_KEY").expect("JWT_KEY must be set.");
-    let supabase_url = std::env::var("SUPABASE_URL").expect("SUPABASE_URL must be set.");
-    let supabase_api_key =
-        std::env::var("SUPABASE_API_KEY").expect("SUPABASE_API_KEY must be set.");
-    let mapbox_api_key = std::env::var("MAPBOX_API_KEY").expect("MAPBOX_API_KEY must be set.");
     let foursquare_api_key =
         std::env::var("FOURSQUARE_API_KEY").expect("FOURSQUARE_API_KEY must be set.");
 
-    let user_repo = SupabaseRepo::new(&supabase_url, &supabase_api_key);
-    let places_repo = SupabaseRepo::new(&supabase_url, &supabase_api_key);
-    let sms_verify = TwilioSMS::new(&twilio_account_sid, &twilio_service_sid, &twilio_auth_token);
-    let places_search = MapboxSearchApi::new(
-        &mapbox_api_key,
-        &foursquare_api_key,
-        Arc::new(Mutex::new(SupabaseRepo::new(
-            &supabase_url,
-            &supabase_api_key,
-        ))),
-    );
-    let oauth = OAuth::new(&jwt_key);
-
-    run(user_repo, places_repo, sms_verify, places_search, oauth).await
+    let twilio_verify_url = std::env::var("TWILIO_VERIFY_BASE_URL")
+        .unwrap_or_else(|_| TWILIO_VERIFY_BASE_URL.to_string());
+    let foursquare_url =
+        std::env::var("FOURSQUARE_BASE_URL").unwrap_or_else(|_| FOURSQUARE_BASE_URL.to_string());
+
+    let sms_verify = TwilioSMS::new(&twilio_account_sid, &twilio_service_sid, &twilio_auth_token)
+        .with_base_url(&twilio_verify_url);
+    let oauth = match std::env::var("ADMIN_TOKEN") {
+        Ok(admin_token) => OAuth::new(&jwt_key).with_admin_token(&admin_token),
+        Err(_) => OAuth::new(&jwt_key),
+    };
+
+    let provider_timeout = match std::env::var("SEARCH_PROVIDER_TIMEOUT_MS") {
+        Ok(ms) => Duration::from_millis(
+            ms.parse()
+                .expect("SEARCH_PROVIDER_TIMEOUT_MS must be a number."),
+        ),
+        Err(_) => DEFAULT_PROVIDER_TIMEOUT,
+    };
+
+    // A comma separated list such as `mapbox,foursquare,database`, in order
+    // of precedence when providers find the same place.
+    let search_providers =
+        std::env::var("SEARCH_PROVIDER").unwrap_or_else(|_| "mapbox".to_string());
+    let mut places_search = CompositeSearch::new(search_places_repo.clone());
+    for provider in search_providers.split(',').map(str::trim) {
+        places_search = match provider {
+            "mapbox" => {
+                let mapbox_api_key =
+                    std::env::var("MAPBOX_API_KEY").expect("MAPBOX_API_KEY must be set.");
+                let mapbox_url = std::env::var("MAPBOX_BASE_URL")
+                    .unwrap_or_else(|_| MAPBOX_BASE_URL.to_string());
+                let mapbox = MapboxSearchApi::new(
+                    &mapbox_api_key,
+                    &foursquare_api_key,
+                    search_places_repo.clone(),
+                )
+                .with_base_urls(&mapbox_url, &foursquare_url);
+
+                places_search.with_provider(provider, mapbox, provider_timeout)
+            }
+            "foursquare" => {
+                let foursquare =
+                    FoursquareSearchApi::new(&foursquare_api_key, search_places_repo.clone())
+                        .with_base_url(&foursquare_url);
+
+                places_search.with_provider(provider, foursquare, provider_timeout)
+            }
+            "osm" => {
+                // The public Nominatim forbids searching as users type.
+                let osm_url = std::env::var("OSM_BASE_URL")
+                    .expect("OSM_BASE_URL must be set to a self-hosted Nominatim.");
+                let osm_user_agent = std::env::var("OSM_USER_AGENT")
+                    .unwrap_or_else(|_| DEFAULT_USER_AGENT.to_string());
+                let osm = OsmSearchApi::new(&osm_url, search_places_repo.clone())
+                    .with_user_agent(&osm_user_agent);
+
+                places_search.with_provider(provider, osm, provider_timeout)
+            }
+            "database" => places_search.with_provider(
+                provider,
+                DatabaseSearch::new(search_places_repo.clone()),
+                provider_timeout,
+            ),
+            other => panic!(
+                "SEARCH_PROVIDER must be a comma separated list of mapbox, foursquare, osm or database, got {}.",
+                other
+            ),
+        };
+    }
+
+    let cache_ttl = match std::env::var("SEARCH_CACHE_TTL_SECONDS") {
+        Ok(s) => Duration::from_secs(
+            s.parse()
+                .expect("SEARCH_CACHE_TTL_SECONDS must be a number."),
+        ),
+        Err(_) => DEFAULT_CACHE_TTL,
+    };
+    let cache_max_entries = match std::env::var("SEARCH_CACHE_MAX_ENTRIES") {
+        Ok(n) => n
+            .parse()
+            .expect("SEARCH_CACHE_MAX_ENTRIES must be a number."),
+        Err(_) => DEFAULT_CACHE_MAX_ENTRIES,
+    };
+    let places_search = CachedSearch::new(places_search).with_cache(Arc::new(
+        InMemorySearchCache::new(cache_ttl, cache_max_entries),
+    ));
+
+    let min_local_results = match std::env::var("LOCAL_SEARCH_MIN_RESULTS") {
+        Ok(n) => n
+            .parse()
+            .expect("LOCAL_SEARCH_MIN_RESULTS must be a number."),
+        Err(_) => DEFAULT_MIN_LOCAL_RESULTS,
+    };
+    let places_search = LocalFirstSearch::new(search_places_repo, places_search)
+        .with_min_results(min_local_results);
+
+    let job_concurrency = match std::env::var("JOB_CONCURRENCY") {
+        Ok(n) => n.parse().expect("JOB_CONCURRENCY must be a number."),
+        Err(_) => DEFAULT_JOB_CONCURRENCY,
+    };
+    let job_max_attempts = match std::env::var("JOB_MAX_ATTEMPTS") {
+        Ok(n) => n.parse().expect("JOB_MAX_ATTEMPTS must be a number."),
+        Err(_) => DEFAULT_MAX_ATTEMPTS,
+    };
+    let job_retry_backoff = match std::env::var("JOB_RETRY_BACKOFF_MS") {
+        Ok(ms) => {
+            Duration::from_millis(ms.parse().expect("JOB_RETRY_BACKOFF_MS must be a number."))
+        }
+        Err(_) => DEFAULT_RETRY_BACKOFF,
+    };
+    let job_lease = match std::env::var("JOB_LEASE_SECONDS") {
+        Ok(s) => Duration::from_secs(s.parse().expect("JOB_LEASE_SECONDS must be a number.")),
+        Err(_) => DEFAULT_JOB_LEASE,
+    };
+    let refresh_interval = match std::env::var("REFRESH_INTERVAL_SECONDS") {
+        Ok(s) => Duration::from_secs(
+            s.parse()
+                .expect("REFRESH_INTERVAL_SECONDS must be a number."),
+        ),
+        Err(_) => DEFAULT_REFRESH_INTERVAL,
+    };
+    let refresh_max_age = match std::env::var("REFRESH_MAX_AGE_DAYS") {
+        Ok(days) => Duration::from_secs(
+            days.parse::<u64>()
+                .expect("REFRESH_MAX_AGE_DAYS must be a number.")
+                * 24
+                * 60
+                * 60,
+        ),
+        Err(_) => DEFAULT_REFRESH_MAX_AGE,
+    };
+    let refresh_batch_size = match std::env::var("REFRESH_BATCH_SIZE") {
+        Ok(n) => n.parse().expect("REFRESH_BATCH_SIZE must be a number."),
+        Err(_) => DEFAULT_REFRESH_BATCH_SIZE,
+    };
+    let job_options = JobOptions {
+        concurrency: job_concurrency,
+        max_attempts: job_max_attempts,
+        backoff: job_retry_backoff,
+        lease: job_lease,
+        refresh_interval,
+        refresh_max_age,
+        refresh_batch_size,
+        ..JobOptions::default()
+    };
+
+    let photo_cache_dir = match std::env::var("PHOTO_CACHE_DIR") {
+        Ok(dir) => dir.into(),
+        Err(_) => std::env::temp_dir().join("critiq-photos"),
+    };
+    let photo_cache_bytes = match std::env::var("PHOTO_CACHE_MAX_BYTES") {
+        Ok(bytes) => bytes
+            .parse()
+            .expect("PHOTO_CACHE_MAX_BYTES must be a number of bytes."),
+        Err(_) => DEFAULT_PHOTO_CACHE_BYTES,
+    };
+    let photo_proxy = PhotoProxy::new(photo_cache_dir).with_max_cache_bytes(photo_cache_bytes);
+    // Every instance signs photo URLs with the same key, so any of them can
+    // serve the others' URLs.
+    let photo_url_key = std::env::var("PHOTO_URL_KEY").unwrap_or_else(|_| jwt_key.clone());
+    set_token_key(photo_url_key.as_bytes()).unwrap();
+
+    let blob_store = std::env::var("BLOB_STORE").unwrap_or_else(|_| "local".to_string());
+    let blob_store: DynBlobStore = match blob_store.as_str() {
+        "local" => {
+            let blob_dir = std::env::var("BLOB_DIR").unwrap_or_else(|_| "blobs".to_string());
+            Arc::new(LocalBlobStore::new(blob_dir))
+        }
+        "s3" => {
+            let s3_endpoint = std::env::var("S3_ENDPOINT").expect("S3_ENDPOINT must be set.");
+            let s3_bucket = std::env::var("S3_BUCKET").expect("S3_BUCKET must be set.");
+            let s3_access_key_id =
+                std::env::var("S3_ACCESS_KEY_ID").expect("S3_ACCESS_KEY_ID must be set.");
+            let s3_secret_access_key =
+                std::env::var("S3_SECRET_ACCESS_KEY").expect("S3_SECRET_ACCESS_KEY must be set.");
+            let s3_region =
+                std::env::var("S3_REGION").unwrap_or_else(|_| DEFAULT_S3_REGION.to_string());
+            let s3 = S3BlobStore::new(
+                &s3_endpoint,
+                &s3_bucket,
+                &s3_access_key_id,
+                &s3_secret_access_key,
+            )
+            .expect("S3_ENDPOINT must be a URL.")
+            .with_region(&s3_region);
+            Arc::new(s3)
+        }
+        other => panic!("BLOB_STORE must be one of local or s3, got {}.", other),
+    };
+
+    run(
+        user_repo,
+        places_repo,
+        ratings_repo,
+        jobs_repo,
+        sms_verify,
+        places_search,
+        oauth,
+        photo_proxy,
+        blob_store,
+        job_options,
+    )
+    .await
 }
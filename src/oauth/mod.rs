@@ -8,13 +8,39 @@ use std::collections::BTreeMap;
 #[derive(Clone)]
 pub struct OAuth {
     key: Hmac<Sha256>,
+    admin_token: Option<String>,
 }
 
 impl OAuth {
     pub fn new(key: &str) -> Self {
         let key = bs58::decode(key).into_vec().unwrap();
         let key: Hmac<Sha256> = hmac::Mac::new_from_slice(&key).unwrap();
-        OAuth { key }
+        OAuth {
+            key,
+            admin_token: None,
+        }
+    }
+
+    /// The token operators send to reach internal routes such as the job
+    /// dead letters. Without one, those routes reject everyone.
+    pub fn with_admin_token(mut self, admin_token: &str) -> Self {
+        self.admin_token = Some(admin_token.to_string());
+        self
+    }
+
+    /// Compares in constant time, so the token can't be guessed byte by byte.
+    pub fn verify_admin_token(&self, token: &str) -> bool {
+        match &self.admin_token {
+            Some(admin_token) => {
+                admin_token.len() == token.len()
+                    && admin_token
+                        .bytes()
+                        .zip(token.bytes())
+                        .fold(0, |diff, (a, b)| diff | (a ^ b))
+                        == 0
+            }
+            None => false,
+        }
     }
 
     pub fn generate_jwt(
@@ -35,9 +61,8 @@ impl OAuth {
     }
 
     pub fn verify_jwt(&self, token_str: &str) -> Result<User, String> {
-        let claims: BTreeMap<String, String>;
-        match token_str.verify_with_key(&self.key) {
-            Ok(c) => claims = c,
+        let claims: BTreeMap<String, String> = match token_str.verify_with_key(&self.key) {
+            Ok(c) => c,
             Err(_) => return Err("Error verifying token string".to_string()),
         };
 
@@ -52,16 +77,14 @@ impl OAuth {
             }
         };
 
-        let phone_number: u64;
-        let is_verified: bool;
-        match claims["phone_number"].parse() {
-            Ok(number) => phone_number = number,
+        let phone_number: u64 = match claims["phone_number"].parse() {
+            Ok(number) => number,
             Err(_) => return Err("Error parsing phone number".to_string()),
-        }
-        match claims["is_verified"].parse() {
-            Ok(verified) => is_verified = verified,
+        };
+        let is_verified: bool = match claims["is_verified"].parse() {
+            Ok(verified) => verified,
             Err(_) => return Err("Error parsing is_verified".to_string()),
-        }
+        };
 
         Ok(User {
             first_name: claims["first_name"].clone(),
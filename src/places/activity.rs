@@ -0,0 +1,79 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, Utc};
+
+/// How many places are remembered before the least recently seen are
+/// forgotten.
+pub const DEFAULT_ACTIVITY_MAX_ENTRIES: usize = 10_000;
+
+/// When places were last searched for, looked at or rated, so the ones
+/// people care about now can be refreshed first. Kept in memory only.
+#[derive(Clone)]
+pub struct PlaceActivity {
+    seen: Arc<Mutex<HashMap<u64, DateTime<Utc>>>>,
+    max_entries: usize,
+}
+
+impl Default for PlaceActivity {
+    fn default() -> Self {
+        Self::new(DEFAULT_ACTIVITY_MAX_ENTRIES)
+    }
+}
+
+impl PlaceActivity {
+    pub fn new(max_entries: usize) -> Self {
+        PlaceActivity {
+            seen: Arc::new(Mutex::new(HashMap::new())),
+            max_entries,
+        }
+    }
+
+    pub fn record(&self, place_ids: impl IntoIterator<Item = u64>, now: DateTime<Utc>) {
+        let mut seen = self.seen.lock().unwrap();
+        for place_id in place_ids {
+            seen.insert(place_id, now);
+        }
+        if seen.len() > self.max_entries {
+            let mut by_age: Vec<(u64, DateTime<Utc>)> =
+                seen.iter().map(|(id, at)| (*id, *at)).collect();
+            by_age.sort_by_key(|(id, at)| (*at, *id));
+            let excess = seen.len() - self.max_entries;
+            for (id, _) in by_age.into_iter().take(excess) {
+                seen.remove(&id);
+            }
+        }
+    }
+
+    /// Place ids, most recently seen first.
+    pub fn recent(&self) -> Vec<u64> {
+        let seen = self.seen.lock().unwrap();
+        let mut recent: Vec<(u64, DateTime<Utc>)> =
+            seen.iter().map(|(id, at)| (*id, *at)).collect();
+        recent.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        recent.into_iter().map(|(id, _)| id).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_recent_forgets_the_oldest() {
+        let activity = PlaceActivity::new(3);
+        let now = Utc::now();
+
+        activity.record([1, 2], now);
+        activity.record([3], now + Duration::seconds(1));
+        activity.record([1], now + Duration::seconds(2));
+        assert_eq!(activity.recent(), vec![1, 3, 2]);
+
+        activity.record([4], now + Duration::seconds(3));
+        assert_eq!(activity.recent(), vec![4, 1, 3]);
+    }
+}
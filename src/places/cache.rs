@@ -0,0 +1,357 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use axum::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::places::{
+    composite::words,
+    search::{PlaceCategory, Search, SearchQuery},
+    Place,
+};
+
+pub type DynSearchCache = Arc<dyn SearchCache>;
+
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(15 * 60);
+pub const DEFAULT_CACHE_MAX_ENTRIES: usize = 10_000;
+
+/// Six characters is a cell of roughly 1.2km by 0.6km, so users searching
+/// from the same neighbourhood share results.
+pub const DEFAULT_GEOHASH_PRECISION: usize = 6;
+
+/// Identifies searches that return the same results. The session token is
+/// left out, since it only matters for billing.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SearchCacheKey {
+    pub text: String,
+    pub geohash: String,
+    pub categories: Vec<PlaceCategory>,
+    pub radius_meters: u64,
+    pub limit: Option<u8>,
+    pub language: String,
+}
+
+impl SearchCacheKey {
+    /// Case, punctuation and extra whitespace in the search text are ignored.
+    pub fn new(query: &SearchQuery, geohash_precision: usize) -> Self {
+        SearchCacheKey {
+            text: words(&query.search_string).join(" "),
+            geohash: query.coordinates.geohash(geohash_precision),
+            categories: query.categories.clone(),
+            radius_meters: query.radius_meters.round() as u64,
+            limit: query.limit,
+            language: query.language.clone(),
+        }
+    }
+}
+
+#[async_trait]
+pub trait SearchCache: Send + Sync + 'static {
+    async fn get(&self, key: &SearchCacheKey) -> Option<Vec<Place>>;
+
+    async fn insert(&self, key: SearchCacheKey, places: Vec<Place>);
+
+    async fn entries(&self) -> usize;
+}
+
+struct CacheEntry {
+    places: Vec<Place>,
+    inserted_at: Instant,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct CacheEntries {
+    entries: HashMap<SearchCacheKey, CacheEntry>,
+    uses: u64,
+}
+
+/// Keeps results for `ttl`, evicting the least recently used entry once
+/// `max_entries` is reached.
+pub struct InMemorySearchCache {
+    cache: Mutex<CacheEntries>,
+    ttl: Duration,
+    max_entries: usize,
+}
+
+impl Default for InMemorySearchCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CACHE_TTL, DEFAULT_CACHE_MAX_ENTRIES)
+    }
+}
+
+impl InMemorySearchCache {
+    pub fn new(ttl: Duration, max_entries: usize) -> Self {
+        InMemorySearchCache {
+            cache: Mutex::new(CacheEntries::default()),
+            ttl,
+            max_entries,
+        }
+    }
+}
+
+#[async_trait]
+impl SearchCache for InMemorySearchCache {
+    async fn get(&self, key: &SearchCacheKey) -> Option<Vec<Place>> {
+        let mut cache = self.cache.lock().unwrap();
+        cache.uses += 1;
+        let uses = cache.uses;
+        let entry = cache.entries.get_mut(key)?;
+        if entry.inserted_at.elapsed() >= self.ttl {
+            cache.entries.remove(key);
+            return None;
+        }
+        entry.last_used = uses;
+        Some(entry.places.clone())
+    }
+
+    async fn insert(&self, key: SearchCacheKey, places: Vec<Place>) {
+        if self.max_entries == 0 {
+            return;
+        }
+        let mut cache = self.cache.lock().unwrap();
+        cache.uses += 1;
+        let uses = cache.uses;
+
+        if !cache.entries.contains_key(&key) && cache.entries.len() >= self.max_entries {
+            let ttl = self.ttl;
+            cache.entries.retain(|_, e| e.inserted_at.elapsed() < ttl);
+        }
+        if !cache.entries.contains_key(&key) && cache.entries.len() >= self.max_entries {
+            let least_recently_used = cache
+                .entries
+                .iter()
+                .min_by_key(|(_, e)| e.last_used)
+                .map(|(k, _)| k.clone());
+            if let Some(k) = least_recently_used {
+                cache.entries.remove(&k);
+            }
+        }
+        cache.entries.insert(
+            key,
+            CacheEntry {
+                places,
+                inserted_at: Instant::now(),
+                last_used: uses,
+            },
+        );
+    }
+
+    async fn entries(&self) -> usize {
+        self.cache.lock().unwrap().entries.len()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// The share of searches answered from the cache, 0 before any search.
+    pub hit_rate: f64,
+    pub entries: usize,
+}
+
+/// Serves repeated searches from a `SearchCache` instead of `upstream`.
+/// Failed searches aren't cached.
+pub struct CachedSearch<S: Search> {
+    upstream: S,
+    cache: DynSearchCache,
+    geohash_precision: usize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl<S: Search> CachedSearch<S> {
+    pub fn new(upstream: S) -> Self {
+        CachedSearch {
+            upstream,
+            cache: Arc::new(InMemorySearchCache::default()),
+            geohash_precision: DEFAULT_GEOHASH_PRECISION,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn with_cache(mut self, cache: DynSearchCache) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    pub fn with_geohash_precision(mut self, geohash_precision: usize) -> Self {
+        self.geohash_precision = geohash_precision;
+        self
+    }
+}
+
+#[async_trait]
+impl<S: Search> Search for CachedSearch<S> {
+    async fn search_for_place(&self, query: SearchQuery) -> Result<Vec<Place>, String> {
+        let key = SearchCacheKey::new(&query, self.geohash_precision);
+        if let Some(places) = self.cache.get(&key).await {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(places);
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        let places = self.upstream.search_for_place(query).await?;
+        self.cache.insert(key, places.clone()).await;
+        Ok(places)
+    }
+
+    async fn get_photos(&self, place_id: u64) -> Result<Place, String> {
+        self.upstream.get_photos(place_id).await
+    }
+
+    async fn get_details(&self, place_id: u64) -> Result<Place, String> {
+        self.upstream.get_details(place_id).await
+    }
+
+    async fn get_coordinates(&self, place_id: u64, session_token: &str) -> Result<Place, String> {
+        self.upstream.get_coordinates(place_id, session_token).await
+    }
+
+    async fn cache_stats(&self) -> Option<CacheStats> {
+        let hits = self.hits.load(Ordering::Relaxed);
+        let misses = self.misses.load(Ordering::Relaxed);
+        let hit_rate = match hits + misses {
+            0 => 0.0,
+            total => hits as f64 / total as f64,
+        };
+        Some(CacheStats {
+            hits,
+            misses,
+            hit_rate,
+            entries: self.cache.entries().await,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use crate::{
+        geo::Coordinates,
+        places::testing::{place, FakeSearch},
+    };
+
+    use super::*;
+
+    fn cached(result: Result<Vec<Place>, String>) -> (CachedSearch<FakeSearch>, Arc<AtomicUsize>) {
+        let upstream = FakeSearch::new(result);
+        let calls = upstream.calls();
+        (CachedSearch::new(upstream), calls)
+    }
+
+    fn query(text: &str, latitude: f64) -> SearchQuery {
+        SearchQuery::new(
+            Coordinates {
+                latitude,
+                longitude: -111.891,
+            },
+            text,
+            "session",
+        )
+    }
+
+    fn key(name: &str) -> SearchCacheKey {
+        SearchCacheKey::new(&query(name, 40.7608), DEFAULT_GEOHASH_PRECISION)
+    }
+
+    #[test]
+    fn test_key_normalizes_text_and_location() {
+        let key = SearchCacheKey::new(&query("  Arlo's   Restaurant!", 40.7608), 6);
+
+        assert_eq!(key.text, "arlos restaurant");
+        assert_eq!(
+            key,
+            SearchCacheKey::new(&query("arlos restaurant", 40.76085), 6)
+        );
+        assert_ne!(
+            key,
+            SearchCacheKey::new(&query("arlos restaurant", 40.9), 6)
+        );
+
+        let mut other_radius = query("arlos restaurant", 40.7608);
+        other_radius.radius_meters = 1_000.0;
+        assert_ne!(key, SearchCacheKey::new(&other_radius, 6));
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_cache_expires_entries() {
+        let cache = InMemorySearchCache::new(Duration::ZERO, 10);
+        cache.insert(key("arlo"), vec![place(1, "Arlo")]).await;
+
+        assert!(cache.get(&key("arlo")).await.is_none());
+        assert_eq!(cache.entries().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_cache_evicts_least_recently_used() {
+        let cache = InMemorySearchCache::new(DEFAULT_CACHE_TTL, 2);
+        cache.insert(key("arlo"), vec![place(1, "Arlo")]).await;
+        cache.insert(key("red iguana"), vec![]).await;
+        assert!(cache.get(&key("arlo")).await.is_some());
+
+        cache.insert(key("publik"), vec![]).await;
+
+        assert_eq!(cache.entries().await, 2);
+        assert!(cache.get(&key("arlo")).await.is_some());
+        assert!(cache.get(&key("red iguana")).await.is_none());
+        assert!(cache.get(&key("publik")).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_cached_search_counts_hits_and_misses() {
+        let (search, calls) = cached(Ok(vec![place(1, "Arlo")]));
+
+        assert_eq!(
+            search
+                .search_for_place(query("Arlo", 40.7608))
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(
+            search
+                .search_for_place(query("arlo ", 40.7608))
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+        search.search_for_place(query("arlo", 41.5)).await.unwrap();
+
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        let stats = search.cache_stats().await.unwrap();
+        assert_eq!(stats.hits, 1);
+        assert_eq!(stats.misses, 2);
+        assert_eq!(stats.entries, 2);
+        assert!((stats.hit_rate - 1.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_cached_search_does_not_cache_errors() {
+        let (search, calls) = cached(Err("Error requesting data from Mapbox".to_string()));
+
+        assert!(search
+            .search_for_place(query("arlo", 40.7608))
+            .await
+            .is_err());
+        assert!(search
+            .search_for_place(query("arlo", 40.7608))
+            .await
+            .is_err());
+
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(search.cache_stats().await.unwrap().entries, 0);
+    }
+}
@@ -0,0 +1,542 @@
+use std::{collections::HashSet, time::Duration};
+
+use axum::async_trait;
+use futures::future;
+
+use crate::{
+    places::{
+        search::{Search, SearchQuery},
+        Address, Place,
+    },
+    repository::places::DynPlacesRepo,
+};
+
+pub const DEFAULT_PROVIDER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Places with similar names this close together are the same place, even
+/// when their providers format the address differently.
+const DUPLICATE_DISTANCE_METERS: f64 = 100.0;
+
+/// The share of name words two places need in common to be the same place.
+const NAME_SIMILARITY_THRESHOLD: f64 = 0.6;
+
+struct SearchProvider {
+    name: String,
+    search: Box<dyn Search>,
+    timeout: Duration,
+}
+
+/// Queries several providers at once and merges what they find. A provider
+/// that fails or times out is skipped, so a search only fails when all of
+/// them do.
+pub struct CompositeSearch {
+    places_repo: DynPlacesRepo,
+    providers: Vec<SearchProvider>,
+}
+
+impl CompositeSearch {
+    pub fn new(places_repo: DynPlacesRepo) -> Self {
+        CompositeSearch {
+            places_repo,
+            providers: Vec::new(),
+        }
+    }
+
+    /// Adds a provider. When two providers find the same place, the one
+    /// added first wins and the others only fill in what it is missing.
+    pub fn with_provider(mut self, name: &str, search: impl Search, timeout: Duration) -> Self {
+        self.providers.push(SearchProvider {
+            name: name.to_string(),
+            search: Box::new(search),
+            timeout,
+        });
+        self
+    }
+}
+
+#[async_trait]
+impl Search for CompositeSearch {
+    async fn search_for_place(&self, query: SearchQuery) -> Result<Vec<Place>, String> {
+        let results = future::join_all(self.providers.iter().map(|provider| {
+            let query = query.clone();
+            async move {
+                match tokio::time::timeout(
+                    provider.timeout,
+                    provider.search.search_for_place(query),
+                )
+                .await
+                {
+                    Ok(result) => result,
+                    Err(_) => Err(format!("timed out after {:?}", provider.timeout)),
+                }
+            }
+        }))
+        .await;
+
+        let mut found = Vec::new();
+        for (provider, result) in self.providers.iter().zip(results) {
+            match result {
+                Ok(places) => found.push(places),
+                Err(e) => eprintln!("search provider {} failed: {}", provider.name, e),
+            }
+        }
+        if found.is_empty() {
+            return Err("Error searching for places".to_string());
+        }
+
+        let mut places = Vec::new();
+        for (place, enriched) in merge_places(found) {
+            if !enriched {
+                places.push(place);
+                continue;
+            }
+            match self.places_repo.lock().await.update(place.clone()).await {
+                Ok(updated) => places.push(updated),
+                Err(e) => {
+                    eprintln!("error storing merged place {}: {}", place.id, e);
+                    places.push(place);
+                }
+            }
+        }
+        if let Some(limit) = query.limit {
+            places.truncate(usize::from(limit));
+        }
+        Ok(places)
+    }
+
+    /// Asks each provider in turn, settling for a place without photos only
+    /// when none of them has any.
+    async fn get_photos(&self, place_id: u64) -> Result<Place, String> {
+        let mut fallback = Err("Error getting place".to_string());
+        for provider in &self.providers {
+            match tokio::time::timeout(provider.timeout, provider.search.get_photos(place_id)).await
+            {
+                Ok(Ok(place)) => {
+                    if place.photos.as_ref().is_some_and(|p| !p.is_empty()) {
+                        return Ok(place);
+                    }
+                    if fallback.is_err() {
+                        fallback = Ok(place);
+                    }
+                }
+                Ok(Err(e)) => eprintln!("photo provider {} failed: {}", provider.name, e),
+                Err(_) => eprintln!("photo provider {} timed out", provider.name),
+            }
+        }
+        fallback
+    }
+
+    /// Asks each provider in turn, settling for a place without details only
+    /// when none of them has any.
+    async fn get_details(&self, place_id: u64) -> Result<Place, String> {
+        let mut fallback = Err("Error getting place".to_string());
+        for provider in &self.providers {
+            match tokio::time::timeout(provider.timeout, provider.search.get_details(place_id))
+                .await
+            {
+                Ok(Ok(place)) => {
+                    if place.details.is_some() {
+                        return Ok(place);
+                    }
+                    if fallback.is_err() {
+                        fallback = Ok(place);
+                    }
+                }
+                Ok(Err(e)) => eprintln!("details provider {} failed: {}", provider.name, e),
+                Err(_) => eprintln!("details provider {} timed out", provider.name),
+            }
+        }
+        fallback
+    }
+
+    /// Asks each provider in turn, settling for a place without coordinates
+    /// only when none of them can locate it.
+    async fn get_coordinates(&self, place_id: u64, session_token: &str) -> Result<Place, String> {
+        let mut fallback = Err("Error getting place".to_string());
+        for provider in &self.providers {
+            match tokio::time::timeout(
+                provider.timeout,
+                provider.search.get_coordinates(place_id, session_token),
+            )
+            .await
+            {
+                Ok(Ok(place)) => {
+                    if place.coordinates.is_some() {
+                        return Ok(place);
+                    }
+                    if fallback.is_err() {
+                        fallback = Ok(place);
+                    }
+                }
+                Ok(Err(e)) => eprintln!("coordinates provider {} failed: {}", provider.name, e),
+                Err(_) => eprintln!("coordinates provider {} timed out", provider.name),
+            }
+        }
+        fallback
+    }
+}
+
+/// Flattens the results of each provider, in priority order, into one list
+/// without duplicates. Each place is returned with whether a duplicate filled
+/// in any of its fields.
+fn merge_places(results: Vec<Vec<Place>>) -> Vec<(Place, bool)> {
+    let mut merged: Vec<(Place, bool)> = Vec::new();
+    for place in results.into_iter().flatten() {
+        match merged
+            .iter_mut()
+            .find(|(existing, _)| is_same_place(existing, &place))
+        {
+            Some((existing, enriched)) => {
+                if fill_missing(existing, &place) {
+                    *enriched = true;
+                }
+            }
+            None => merged.push((place, false)),
+        }
+    }
+    merged
+}
+
+fn is_same_place(a: &Place, b: &Place) -> bool {
+    if a.id != 0 && a.id == b.id {
+        return true;
+    }
+    if name_similarity(&a.name, &b.name) < NAME_SIMILARITY_THRESHOLD {
+        return false;
+    }
+    if same_address(&a.address.address, &b.address.address) {
+        return true;
+    }
+    match (a.coordinates, b.coordinates) {
+        (Some(a), Some(b)) => a.distance_meters(&b) <= DUPLICATE_DISTANCE_METERS,
+        _ => false,
+    }
+}
+
+/// Copies the fields `place` is missing from `duplicate`, returning whether
+/// anything changed.
+fn fill_missing(place: &mut Place, duplicate: &Place) -> bool {
+    fn fill<T: Clone>(field: &mut Option<T>, other: &Option<T>) -> bool {
+        if field.is_none() && other.is_some() {
+            *field = other.clone();
+            return true;
+        }
+        false
+    }
+
+    let Address {
+        full_address,
+        country,
+        region,
+        postcode,
+        place: locality,
+        street,
+        ..
+    } = &duplicate.address;
+    [
+        fill(&mut place.photos, &duplicate.photos),
+        fill(&mut place.website, &duplicate.website),
+        fill(&mut place.foursquare_id, &duplicate.foursquare_id),
+        fill(&mut place.coordinates, &duplicate.coordinates),
+        fill(&mut place.categories, &duplicate.categories),
+        fill(&mut place.details, &duplicate.details),
+        fill(&mut place.address.full_address, full_address),
+        fill(&mut place.address.country, country),
+        fill(&mut place.address.region, region),
+        fill(&mut place.address.postcode, postcode),
+        fill(&mut place.address.place, locality),
+        fill(&mut place.address.street, street),
+    ]
+    .contains(&true)
+}
+
+/// Lowercase words with apostrophes dropped, so `Arlo's` is one word.
+pub(crate) fn words(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .replace(['\'', '’'], "")
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Jaccard similarity of the name words, ignoring filler words. A name whose
+/// words all appear in the other, like `Arlo` and `Arlo Restaurant`, counts
+/// as the same name.
+fn name_similarity(a: &str, b: &str) -> f64 {
+    let name_words = |name: &str| -> HashSet<String> {
+        words(name)
+            .into_iter()
+            .filter(|w| !matches!(w.as_str(), "the" | "and"))
+            .collect()
+    };
+    let a = name_words(a);
+    let b = name_words(b);
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    if a.is_subset(&b) || b.is_subset(&a) {
+        return 1.0;
+    }
+    a.intersection(&b).count() as f64 / a.union(&b).count() as f64
+}
+
+fn expand_abbreviation(word: String) -> String {
+    let expanded = match word.as_str() {
+        "n" => "north",
+        "s" => "south",
+        "e" => "east",
+        "w" => "west",
+        "ne" => "northeast",
+        "nw" => "northwest",
+        "se" => "southeast",
+        "sw" => "southwest",
+        "st" => "street",
+        "ave" | "av" => "avenue",
+        "rd" => "road",
+        "blvd" => "boulevard",
+        "dr" => "drive",
+        "ln" => "lane",
+        "ct" => "court",
+        "pl" => "place",
+        "hwy" => "highway",
+        "pkwy" => "parkway",
+        "ste" => "suite",
+        _ => return word,
+    };
+    expanded.to_string()
+}
+
+/// Street addresses match when they are the same once abbreviations are
+/// expanded, or when one only adds a suffix such as a suite number.
+fn same_address(a: &str, b: &str) -> bool {
+    let address_words = |address: &str| -> Vec<String> {
+        words(address)
+            .into_iter()
+            .map(expand_abbreviation)
+            .collect()
+    };
+    let a = address_words(a);
+    let b = address_words(b);
+    let (shorter, longer) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    shorter.len() >= 2 && longer.starts_with(&shorter)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tokio::sync::Mutex;
+
+    use crate::{
+        geo::Coordinates,
+        places::{
+            photo::Photo,
+            testing::{place, salt_lake_city, FakeSearch},
+            Address,
+        },
+        repository::{local::places::LocalPlacesRepository, places::ReadPlaceOptions},
+    };
+
+    use super::*;
+
+    fn listing(id: u64, name: &str, address: &str, coordinates: Option<(f64, f64)>) -> Place {
+        Place {
+            address: Address {
+                address: address.to_string(),
+                ..Address::default()
+            },
+            coordinates: coordinates.map(|(latitude, longitude)| Coordinates {
+                latitude,
+                longitude,
+            }),
+            ..place(id, name)
+        }
+    }
+
+    fn query() -> SearchQuery {
+        SearchQuery::new(salt_lake_city(), "arlo", "session")
+    }
+
+    fn places_repo() -> DynPlacesRepo {
+        Arc::new(Mutex::new(LocalPlacesRepository::new()))
+    }
+
+    #[test]
+    fn test_name_similarity() {
+        assert_eq!(name_similarity("Arlo", "ARLO restaurant"), 1.0);
+        assert_eq!(name_similarity("The Red Iguana", "Red Iguana"), 1.0);
+        assert!(name_similarity("Red Iguana 2", "Red Iguana Cantina") >= 0.5);
+        assert!(name_similarity("Arlo", "Arlo's Market") < NAME_SIMILARITY_THRESHOLD);
+        assert_eq!(name_similarity("Arlo", ""), 0.0);
+    }
+
+    #[test]
+    fn test_same_address_expands_abbreviations() {
+        assert!(same_address("271 N Center St", "271 North Center Street"));
+        assert!(same_address("271 N Center St", "271 N. Center St Ste 2"));
+        assert!(!same_address("271 N Center St", "272 N Center St"));
+        assert!(!same_address("Main", "Main Street"));
+    }
+
+    #[test]
+    fn test_merge_places_fills_missing_fields_from_duplicates() {
+        let mut mapbox = listing(1, "Arlo", "271 N Center St", None);
+        mapbox.website = Some("https://arlo.example".to_string());
+        let mut foursquare = listing(2, "Arlo Restaurant", "271 North Center Street", None);
+        foursquare.foursquare_id = Some("5c2aab5bb9a389002cf7b4a3".to_string());
+        foursquare.website = Some("https://other.example".to_string());
+        let red_iguana = listing(3, "Red Iguana", "736 W North Temple", None);
+
+        let merged = merge_places(vec![vec![mapbox], vec![foursquare, red_iguana]]);
+
+        assert_eq!(merged.len(), 2);
+        let (arlo, enriched) = &merged[0];
+        assert!(enriched);
+        assert_eq!(arlo.id, 1);
+        assert_eq!(arlo.website.as_deref(), Some("https://arlo.example"));
+        assert_eq!(
+            arlo.foursquare_id.as_deref(),
+            Some("5c2aab5bb9a389002cf7b4a3")
+        );
+        assert_eq!(merged[1].0.id, 3);
+        assert!(!merged[1].1);
+    }
+
+    #[test]
+    fn test_merge_places_uses_distance_when_addresses_differ() {
+        let mapbox = listing(1, "Arlo", "271 N Center St", Some((40.775563, -111.893962)));
+        let osm = listing(2, "Arlo", "Center Street", Some((40.7756, -111.8940)));
+        let far = listing(3, "Arlo", "Somewhere Else", Some((40.2338, -111.6585)));
+        let other = listing(
+            4,
+            "Publik Coffee",
+            "Center Street",
+            Some((40.7756, -111.8940)),
+        );
+
+        let merged = merge_places(vec![vec![mapbox], vec![osm, far, other]]);
+
+        assert_eq!(
+            merged.iter().map(|(p, _)| p.id).collect::<Vec<_>>(),
+            vec![1, 3, 4]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search_falls_back_and_stores_merged_places() {
+        let places_repo = places_repo();
+        let stored = places_repo
+            .lock()
+            .await
+            .create(&listing(0, "Arlo", "271 N Center St", None))
+            .await
+            .unwrap();
+        let mut duplicate = listing(0, "Arlo", "271 North Center Street", Some((40.77, -111.89)));
+        duplicate.categories = Some(vec!["american".to_string()]);
+        let slow = FakeSearch::new(Ok(vec![place(9, "Slow")])).with_delay(Duration::from_secs(5));
+
+        let search = CompositeSearch::new(places_repo.clone())
+            .with_provider(
+                "failing",
+                FakeSearch::new(Err("down".to_string())),
+                DEFAULT_PROVIDER_TIMEOUT,
+            )
+            .with_provider("slow", slow, Duration::from_millis(10))
+            .with_provider(
+                "primary",
+                FakeSearch::new(Ok(vec![stored.clone()])),
+                DEFAULT_PROVIDER_TIMEOUT,
+            )
+            .with_provider(
+                "secondary",
+                FakeSearch::new(Ok(vec![duplicate])),
+                DEFAULT_PROVIDER_TIMEOUT,
+            );
+
+        let places = search.search_for_place(query()).await.unwrap();
+
+        assert_eq!(places.len(), 1);
+        assert_eq!(places[0].id, stored.id);
+        assert_eq!(places[0].categories, Some(vec!["american".to_string()]));
+        let read = places_repo
+            .lock()
+            .await
+            .read(ReadPlaceOptions {
+                id: Some(stored.id),
+                name: None,
+                address: None,
+                postcode: None,
+            })
+            .await
+            .unwrap();
+        assert!(read[0].coordinates.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_search_fails_when_every_provider_fails() {
+        let search = CompositeSearch::new(places_repo())
+            .with_provider(
+                "a",
+                FakeSearch::new(Err("down".to_string())),
+                DEFAULT_PROVIDER_TIMEOUT,
+            )
+            .with_provider(
+                "b",
+                FakeSearch::new(Err("down".to_string())),
+                DEFAULT_PROVIDER_TIMEOUT,
+            );
+
+        assert_eq!(
+            search.search_for_place(query()).await.unwrap_err(),
+            "Error searching for places"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search_applies_limit_after_merging() {
+        let search = CompositeSearch::new(places_repo()).with_provider(
+            "a",
+            FakeSearch::new(Ok(vec![
+                listing(1, "Arlo", "271 N Center St", None),
+                listing(2, "Red Iguana", "736 W North Temple", None),
+            ])),
+            DEFAULT_PROVIDER_TIMEOUT,
+        );
+        let mut query = query();
+        query.limit = Some(1);
+
+        assert_eq!(search.search_for_place(query).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_get_photos_prefers_provider_with_photos() {
+        let without = listing(1, "Arlo", "271 N Center St", None);
+        let mut with = without.clone();
+        with.photos = Some(vec![Photo {
+            prefix: "https://fastly.4sqi.net/img/general/".to_string(),
+            suffix: "/arlo.jpg".to_string(),
+        }]);
+
+        let search = CompositeSearch::new(places_repo())
+            .with_provider(
+                "failing",
+                FakeSearch::new(Err("down".to_string())),
+                DEFAULT_PROVIDER_TIMEOUT,
+            )
+            .with_provider(
+                "without",
+                FakeSearch::new(Ok(vec![without])),
+                DEFAULT_PROVIDER_TIMEOUT,
+            )
+            .with_provider(
+                "with",
+                FakeSearch::new(Ok(vec![with])),
+                DEFAULT_PROVIDER_TIMEOUT,
+            );
+
+        let place = search.get_photos(1).await.unwrap();
+        assert_eq!(place.photos.unwrap().len(), 1);
+        assert!(search.get_photos(2).await.is_err());
+    }
+}
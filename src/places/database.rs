@@ -0,0 +1,53 @@
+use axum::async_trait;
+
+use crate::{
+    places::{
+        search::{read_place, Search, SearchQuery, MAX_SEARCH_LIMIT},
+        Place,
+    },
+    repository::places::{DynPlacesRepo, SearchPlaceOptions},
+};
+
+/// Searches the places we have already stored, so searches keep working when
+/// every external provider is down.
+pub struct DatabaseSearch {
+    places_repo: DynPlacesRepo,
+}
+
+impl DatabaseSearch {
+    pub fn new(places_repo: DynPlacesRepo) -> Self {
+        DatabaseSearch { places_repo }
+    }
+}
+
+#[async_trait]
+impl Search for DatabaseSearch {
+    /// Stored places carry no provider categories, so only the name, street
+    /// and town and the search area are matched, closest first. Places not yet located match
+    /// in the towns of those that are, after them.
+    async fn search_for_place(&self, query: SearchQuery) -> Result<Vec<Place>, String> {
+        self.places_repo
+            .lock()
+            .await
+            .search(SearchPlaceOptions {
+                text: query.search_string.clone(),
+                bounding_box: Some(query.bounding_box()),
+                include_unlocated: true,
+                closest_to: Some(query.coordinates),
+                limit: usize::from(query.limit.unwrap_or(MAX_SEARCH_LIMIT)),
+            })
+            .await
+    }
+
+    async fn get_photos(&self, place_id: u64) -> Result<Place, String> {
+        read_place(&self.places_repo, place_id).await
+    }
+
+    async fn get_details(&self, place_id: u64) -> Result<Place, String> {
+        read_place(&self.places_repo, place_id).await
+    }
+
+    async fn get_coordinates(&self, place_id: u64, _session_token: &str) -> Result<Place, String> {
+        read_place(&self.places_repo, place_id).await
+    }
+}
@@ -0,0 +1,226 @@
+use serde_json::{json, Map, Value};
+
+use crate::{places::Place, repository::ratings::Rating};
+
+pub const GEOJSON_CONTENT_TYPE: &str = "application/geo+json";
+pub const KML_CONTENT_TYPE: &str = "application/vnd.google-earth.kml+xml";
+
+/// A place together with the user's rating of it.
+#[derive(Clone, Debug)]
+pub struct RatedPlace {
+    pub place: Place,
+    pub rating: Rating,
+}
+
+fn address_lines(place: &Place) -> String {
+    place
+        .address
+        .full_address
+        .clone()
+        .unwrap_or_else(|| place.address.address.clone())
+}
+
+fn properties(rated: &RatedPlace) -> Map<String, Value> {
+    let address = &rated.place.address;
+    let mut properties = Map::new();
+    properties.insert("name".to_string(), json!(rated.place.name));
+    properties.insert("address".to_string(), json!(address.address));
+    for (key, value) in [
+        ("fullAddress", &address.full_address),
+        ("street", &address.street),
+        ("place", &address.place),
+        ("region", &address.region),
+        ("postcode", &address.postcode),
+        ("country", &address.country),
+        ("notes", &rated.rating.review),
+    ] {
+        if let Some(value) = value {
+            properties.insert(key.to_string(), json!(value));
+        }
+    }
+    properties.insert("score".to_string(), json!(rated.rating.score));
+    properties.insert(
+        "ratedAt".to_string(),
+        json!(rated.rating.updated_at.to_rfc3339()),
+    );
+    properties
+}
+
+/// A GeoJSON FeatureCollection with a point for every place. Places we don't
+/// know the coordinates of get a null geometry, which GeoJSON allows.
+pub fn to_geojson(places: &[RatedPlace]) -> Value {
+    let features: Vec<Value> = places
+        .iter()
+        .map(|rated| {
+            let geometry = match rated.place.coordinates {
+                Some(c) => json!({
+                    "type": "Point",
+                    "coordinates": [c.longitude, c.latitude],
+                }),
+                None => Value::Null,
+            };
+            json!({
+                "type": "Feature",
+                "id": rated.place.id,
+                "geometry": geometry,
+                "properties": properties(rated),
+            })
+        })
+        .collect();
+    json!({
+        "type": "FeatureCollection",
+        "features": features,
+    })
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// A KML document Google My Maps can import. Places without coordinates
+/// keep their address, which My Maps geocodes on import.
+pub fn to_kml(document_name: &str, places: &[RatedPlace]) -> String {
+    let mut kml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<kml xmlns=\"http://www.opengis.net/kml/2.2\">\n<Document>\n",
+    );
+    kml.push_str(&format!("<name>{}</name>\n", escape_xml(document_name)));
+    for rated in places {
+        let address = address_lines(&rated.place);
+        let mut description = format!("Score: {}/10", rated.rating.score);
+        if let Some(review) = &rated.rating.review {
+            description.push_str(&format!("\n{}", review));
+        }
+
+        kml.push_str("<Placemark>\n");
+        kml.push_str(&format!("<name>{}</name>\n", escape_xml(&rated.place.name)));
+        kml.push_str(&format!(
+            "<description>{}</description>\n",
+            escape_xml(&description)
+        ));
+        kml.push_str(&format!("<address>{}</address>\n", escape_xml(&address)));
+        kml.push_str("<ExtendedData>\n");
+        kml.push_str(&format!(
+            "<Data name=\"score\"><value>{}</value></Data>\n",
+            rated.rating.score
+        ));
+        if let Some(review) = &rated.rating.review {
+            kml.push_str(&format!(
+                "<Data name=\"notes\"><value>{}</value></Data>\n",
+                escape_xml(review)
+            ));
+        }
+        kml.push_str("</ExtendedData>\n");
+        if let Some(c) = rated.place.coordinates {
+            kml.push_str(&format!(
+                "<Point><coordinates>{},{}</coordinates></Point>\n",
+                c.longitude, c.latitude
+            ));
+        }
+        kml.push_str("</Placemark>\n");
+    }
+    kml.push_str("</Document>\n</kml>\n");
+    kml
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use crate::{geo::Coordinates, places::Address};
+
+    use super::*;
+
+    fn rated(name: &str, coordinates: Option<Coordinates>, review: Option<&str>) -> RatedPlace {
+        let rated_at = Utc.with_ymd_and_hms(2026, 10, 18, 12, 0, 0).unwrap();
+        RatedPlace {
+            place: Place {
+                id: 1,
+                name: name.to_string(),
+                address: Address {
+                    address: "271 N Center St".to_string(),
+                    full_address: Some("271 N Center St, Salt Lake City, Utah 84103".to_string()),
+                    country: Some("United States".to_string()),
+                    region: Some("Utah".to_string()),
+                    postcode: Some("84103".to_string()),
+                    place: Some("Salt Lake City".to_string()),
+                    street: None,
+                },
+                photos: None,
+                website: None,
+                foursquare_id: None,
+                mapbox_id: None,
+                coordinates,
+                categories: None,
+                details: None,
+            },
+            rating: Rating {
+                id: 1,
+                place_id: 1,
+                phone_number: 12028098681,
+                score: 8,
+                review: review.map(|r| r.to_string()),
+                created_at: rated_at,
+                updated_at: rated_at,
+            },
+        }
+    }
+
+    fn arlo_coordinates() -> Option<Coordinates> {
+        Some(Coordinates {
+            latitude: 40.7756,
+            longitude: -111.894,
+        })
+    }
+
+    #[test]
+    fn test_to_geojson() {
+        let geojson = to_geojson(&[
+            rated("Arlo", arlo_coordinates(), Some("Great pasta")),
+            rated("Somewhere", None, None),
+        ]);
+
+        assert_eq!(geojson["type"], "FeatureCollection");
+        let arlo = &geojson["features"][0];
+        assert_eq!(arlo["geometry"]["coordinates"], json!([-111.894, 40.7756]));
+        assert_eq!(arlo["properties"]["name"], "Arlo");
+        assert_eq!(arlo["properties"]["score"], 8);
+        assert_eq!(arlo["properties"]["notes"], "Great pasta");
+        assert_eq!(arlo["properties"]["postcode"], "84103");
+        assert_eq!(arlo["properties"]["ratedAt"], "2026-10-18T12:00:00+00:00");
+        assert!(arlo["properties"].get("street").is_none());
+
+        let unlocated = &geojson["features"][1];
+        assert_eq!(unlocated["geometry"], Value::Null);
+        assert!(unlocated["properties"].get("notes").is_none());
+    }
+
+    #[test]
+    fn test_to_kml_escapes_text() {
+        let kml = to_kml(
+            "Hunter's ratings",
+            &[
+                rated("Fish & <Chips>", arlo_coordinates(), Some("\"Crispy\"")),
+                rated("Somewhere", None, None),
+            ],
+        );
+
+        assert!(kml.contains("<name>Hunter&apos;s ratings</name>"));
+        assert!(kml.contains("<name>Fish &amp; &lt;Chips&gt;</name>"));
+        assert!(kml.contains("<description>Score: 8/10\n&quot;Crispy&quot;</description>"));
+        assert!(kml.contains("<coordinates>-111.894,40.7756</coordinates>"));
+        assert!(kml.contains("<address>271 N Center St, Salt Lake City, Utah 84103</address>"));
+        assert_eq!(kml.matches("<Placemark>").count(), 2);
+        assert_eq!(kml.matches("<Point>").count(), 1);
+    }
+}
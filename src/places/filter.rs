@@ -0,0 +1,202 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDateTime, Timelike, Utc};
+use chrono_tz::Tz;
+
+use crate::places::{OpeningHours, Place};
+
+pub const MIN_PRICE: u8 = 1;
+pub const MAX_PRICE: u8 = 4;
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+/// Narrows search results by opening hours and price. Places whose hours or
+/// price we don't know can't be shown to match, so they are left out by the
+/// filters that need them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PlaceFilters {
+    /// Only places open at this moment, in their own time zone.
+    pub open_at: Option<DateTime<Utc>>,
+    pub min_price: Option<u8>,
+    pub max_price: Option<u8>,
+}
+
+impl PlaceFilters {
+    pub fn is_empty(&self) -> bool {
+        self.open_at.is_none() && self.min_price.is_none() && self.max_price.is_none()
+    }
+
+    pub fn matches(&self, place: &Place) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+        let Some(details) = &place.details else {
+            return false;
+        };
+
+        if self.min_price.is_some() || self.max_price.is_some() {
+            let Some(price) = details.price else {
+                return false;
+            };
+            if self.min_price.is_some_and(|min| price < min)
+                || self.max_price.is_some_and(|max| price > max)
+            {
+                return false;
+            }
+        }
+
+        if let Some(open_at) = self.open_at {
+            let timezone = details
+                .timezone
+                .as_ref()
+                .and_then(|timezone| timezone.parse::<Tz>().ok());
+            let (Some(hours), Some(timezone)) = (&details.hours, timezone) else {
+                return false;
+            };
+            if !is_open_at(hours, open_at.with_timezone(&timezone).naive_local()) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Minutes since midnight of an `HHMM` time. Foursquare marks closing times
+/// on the next day with a leading `+`, which is returned as the second value.
+fn parse_time(time: &str) -> Option<(u32, bool)> {
+    let (time, next_day) = match time.strip_prefix('+') {
+        Some(time) => (time, true),
+        None => (time, false),
+    };
+    if time.len() != 4 || !time.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let hours: u32 = time[..2].parse().ok()?;
+    let minutes: u32 = time[2..].parse().ok()?;
+    if hours > 24 || minutes > 59 {
+        return None;
+    }
+    Some((hours * 60 + minutes, next_day))
+}
+
+/// Whether any period of `hours` covers `local`, the place's local time.
+/// Periods closing at or before their opening time run past midnight.
+pub fn is_open_at(hours: &OpeningHours, local: NaiveDateTime) -> bool {
+    let today = local.weekday().number_from_monday() as u8;
+    let yesterday = (local - Duration::days(1)).weekday().number_from_monday() as u8;
+    let minute = local.hour() * 60 + local.minute();
+
+    hours.periods.iter().any(|period| {
+        let (Some((open, _)), Some((close, next_day))) =
+            (parse_time(&period.open), parse_time(&period.close))
+        else {
+            return false;
+        };
+        if !next_day && close > open {
+            return period.day == today && (open..close).contains(&minute);
+        }
+        let close = close.min(MINUTES_PER_DAY);
+        (period.day == today && minute >= open) || (period.day == yesterday && minute < close)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, TimeZone};
+
+    use crate::places::{
+        testing::{details, place},
+        OpeningPeriod, PlaceDetails,
+    };
+
+    use super::*;
+
+    fn period(day: u8, open: &str, close: &str) -> OpeningPeriod {
+        OpeningPeriod {
+            day,
+            open: open.to_string(),
+            close: close.to_string(),
+        }
+    }
+
+    fn hours() -> OpeningHours {
+        OpeningHours {
+            display: None,
+            periods: vec![
+                period(2, "1700", "2200"),
+                period(5, "1700", "0100"),
+                period(6, "1100", "+0200"),
+            ],
+        }
+    }
+
+    /// 2026-10-19 is a Monday.
+    fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 10, 18 + day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    fn arlo(price: Option<u8>, timezone: Option<&str>) -> Place {
+        Place {
+            details: Some(PlaceDetails {
+                hours: Some(hours()),
+                price,
+                timezone: timezone.map(|t| t.to_string()),
+                ..details(Utc::now())
+            }),
+            ..place(1, "Arlo")
+        }
+    }
+
+    #[test]
+    fn test_is_open_at() {
+        let hours = hours();
+
+        assert!(is_open_at(&hours, at(2, 17, 0)));
+        assert!(is_open_at(&hours, at(2, 21, 59)));
+        assert!(!is_open_at(&hours, at(2, 22, 0)));
+        assert!(!is_open_at(&hours, at(1, 18, 0)));
+        assert!(!is_open_at(&hours, at(3, 18, 0)));
+
+        // Friday's hours run into Saturday morning.
+        assert!(is_open_at(&hours, at(5, 23, 30)));
+        assert!(is_open_at(&hours, at(6, 0, 30)));
+        assert!(!is_open_at(&hours, at(6, 1, 0)));
+
+        // Saturday closes at 2am on Sunday.
+        assert!(is_open_at(&hours, at(7, 1, 59)));
+        assert!(!is_open_at(&hours, at(7, 2, 0)));
+    }
+
+    #[test]
+    fn test_filters_use_the_place_time_zone() {
+        // Tuesday 18:00 in Salt Lake City is Wednesday 00:00 UTC.
+        let filters = PlaceFilters {
+            open_at: Some(Utc.with_ymd_and_hms(2026, 10, 21, 0, 0, 0).unwrap()),
+            ..PlaceFilters::default()
+        };
+
+        assert!(filters.matches(&arlo(None, Some("America/Denver"))));
+        assert!(!filters.matches(&arlo(None, Some("Europe/London"))));
+        assert!(!filters.matches(&arlo(None, None)));
+    }
+
+    #[test]
+    fn test_filters_by_price() {
+        let filters = PlaceFilters {
+            min_price: Some(2),
+            max_price: Some(3),
+            ..PlaceFilters::default()
+        };
+
+        assert!(filters.matches(&arlo(Some(2), None)));
+        assert!(filters.matches(&arlo(Some(3), None)));
+        assert!(!filters.matches(&arlo(Some(4), None)));
+        assert!(!filters.matches(&arlo(None, None)));
+
+        let mut unknown = arlo(Some(2), None);
+        unknown.details = None;
+        assert!(!filters.matches(&unknown));
+        assert!(PlaceFilters::default().matches(&unknown));
+    }
+}
@@ -0,0 +1,151 @@
+use chrono::Utc;
+use reqwest::{StatusCode, Url};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    places::{
+        search::{read_place, update_place},
+        OpeningHours, OpeningPeriod, Place, PlaceDetails, SocialLinks,
+    },
+    repository::places::DynPlacesRepo,
+};
+
+const DETAILS_FIELDS: &str = "hours,price,categories,tel,website,social_media,timezone";
+
+#[derive(Deserialize, Serialize)]
+struct FoursquareDetails {
+    hours: Option<FoursquareHours>,
+    price: Option<u8>,
+    categories: Option<Vec<FoursquareCategory>>,
+    tel: Option<String>,
+    website: Option<String>,
+    social_media: Option<FoursquareSocialMedia>,
+    timezone: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct FoursquareHours {
+    display: Option<String>,
+    regular: Option<Vec<FoursquarePeriod>>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct FoursquarePeriod {
+    day: u8,
+    open: String,
+    close: String,
+}
+
+#[derive(Deserialize, Serialize)]
+struct FoursquareCategory {
+    name: String,
+    short_name: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct FoursquareSocialMedia {
+    facebook_id: Option<String>,
+    instagram: Option<String>,
+    twitter: Option<String>,
+}
+
+impl FoursquareDetails {
+    /// Fills in `place` with these details. Categories are added to the ones
+    /// the place already has, as lowercase short names like `italian`.
+    fn apply_to(self, place: &mut Place) {
+        if let Some(categories) = self.categories {
+            let known = place.categories.get_or_insert_with(Vec::new);
+            for category in categories {
+                let name = category.short_name.unwrap_or(category.name).to_lowercase();
+                if !known.contains(&name) {
+                    known.push(name);
+                }
+            }
+        }
+        if self.website.is_some() {
+            place.website = self.website;
+        }
+        place.details = Some(PlaceDetails {
+            hours: self.hours.map(|hours| OpeningHours {
+                display: hours.display,
+                periods: hours
+                    .regular
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|period| OpeningPeriod {
+                        day: period.day,
+                        open: period.open,
+                        close: period.close,
+                    })
+                    .collect(),
+            }),
+            price: self.price,
+            phone: self.tel,
+            social: self.social_media.map(|social| SocialLinks {
+                facebook_id: social.facebook_id,
+                instagram: social.instagram,
+                twitter: social.twitter,
+            }),
+            timezone: self.timezone,
+            updated_at: Utc::now(),
+        });
+    }
+}
+
+/// Fetches the Foursquare details of a stored place and saves them on it.
+/// Places without a Foursquare id are returned unchanged.
+pub async fn get_details(
+    places_repo: &DynPlacesRepo,
+    foursquare_url: &str,
+    foursquare_token: &str,
+    place_id: u64,
+) -> Result<Place, String> {
+    let place = read_place(places_repo, place_id).await?;
+    let foursquare_id = match place.foursquare_id.clone() {
+        Some(id) => id,
+        None => return Ok(place),
+    };
+
+    let mut url = match format!("{}/v3/places", foursquare_url).parse::<Url>() {
+        Ok(u) => u,
+        Err(_) => return Err("Invalid Foursquare url".to_string()),
+    };
+    match url.path_segments_mut() {
+        Ok(mut segments) => {
+            segments.push(&foursquare_id);
+        }
+        Err(_) => return Err("Invalid Foursquare url".to_string()),
+    };
+    url.query_pairs_mut().append_pair("fields", DETAILS_FIELDS);
+
+    let client = reqwest::Client::new();
+    let res = match client
+        .get(url)
+        .header("Authorization", foursquare_token)
+        .header("accept", "application/json")
+        .send()
+        .await
+    {
+        Ok(res) => res,
+        Err(e) => {
+            eprintln!("error sending to foursquare: {}", e);
+            return Err("Error getting place details".to_string());
+        }
+    };
+    if res.status() != StatusCode::OK {
+        eprintln!("Status code: {}, when getting place details", res.status());
+        return Err("Error getting place details".to_string());
+    }
+    let details: FoursquareDetails = match res.text().await {
+        Ok(t) => match serde_json::from_str(&t) {
+            Ok(details) => details,
+            Err(_) => {
+                eprintln!("Error parsing JSON from foursquare, {}", t);
+                return Err("Error parsing JSON".to_string());
+            }
+        },
+        Err(_) => return Err("Error parsing JSON".to_string()),
+    };
+
+    update_place(places_repo, place_id, |place| details.apply_to(place)).await
+}
@@ -0,0 +1,5 @@
+pub mod details;
+pub mod photos;
+pub mod search;
+
+pub const FOURSQUARE_BASE_URL: &str = "https://api.foursquare.com";
@@ -0,0 +1,102 @@
+use reqwest::{StatusCode, Url};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    places::{photo::Photo, search::update_place, Place},
+    repository::places::{DynPlacesRepo, ReadPlaceOptions},
+};
+
+#[derive(Deserialize, Serialize)]
+struct FoursquarePhoto {
+    id: String,
+    prefix: String,
+    suffix: String,
+}
+
+/// Fetches the Foursquare photos of a stored place and saves them on it.
+/// Places without a Foursquare id are returned unchanged.
+pub async fn get_photos(
+    places_repo: &DynPlacesRepo,
+    foursquare_url: &str,
+    foursquare_token: &str,
+    place_id: u64,
+) -> Result<Place, String> {
+    let place: Place = match places_repo
+        .lock()
+        .await
+        .read(ReadPlaceOptions {
+            id: Some(place_id),
+            name: None,
+            address: None,
+            postcode: None,
+        })
+        .await
+    {
+        Ok(places) => {
+            if places.len() != 1 {
+                return Err("Error getting place".to_string());
+            }
+            places.first().unwrap().clone()
+        }
+        Err(_) => return Err("Error getting place".to_string()),
+    };
+
+    let foursquare_id: String = match place.foursquare_id.clone() {
+        Some(id) => id.clone(),
+        None => return Ok(place),
+    };
+
+    let mut url = match format!("{}/v3/places", foursquare_url).parse::<Url>() {
+        Ok(u) => u,
+        Err(_) => return Err("Invalid Foursquare url".to_string()),
+    };
+    match url.path_segments_mut() {
+        Ok(mut segments) => {
+            segments.push(&foursquare_id).push("photos");
+        }
+        Err(_) => return Err("Invalid Foursquare url".to_string()),
+    };
+    let client = reqwest::Client::new();
+    let foursquare_photos: Vec<FoursquarePhoto>;
+    match client
+        .get(url)
+        .header("Authorization", foursquare_token)
+        .header("accept", "application/json")
+        .send()
+        .await
+    {
+        Ok(res) => {
+            if res.status() != StatusCode::OK {
+                eprintln!("Status code: {}, when getting pictures", res.status());
+                return Err("Error getting pictures".to_string());
+            }
+            match res.text().await {
+                Ok(t) => {
+                    let body: Result<Vec<FoursquarePhoto>, serde_json::Error> =
+                        serde_json::from_str(&t);
+                    match body {
+                        Ok(body) => foursquare_photos = body,
+                        Err(_) => {
+                            eprintln!("Error parsing JSON from foursquare, {}", t);
+                            return Err("Error parsing JSON".to_string());
+                        }
+                    }
+                }
+                Err(_) => return Err("Error parsing JSON".to_string()),
+            }
+        }
+        Err(e) => {
+            eprintln!("error sending to foursquare: {}", e);
+            return Err("Error getting pictures".to_string());
+        }
+    };
+
+    let photos = foursquare_photos
+        .into_iter()
+        .map(|photo| Photo {
+            prefix: photo.prefix,
+            suffix: photo.suffix,
+        })
+        .collect();
+    update_place(places_repo, place_id, |place| place.photos = Some(photos)).await
+}
@@ -0,0 +1,228 @@
+use axum::async_trait;
+use futures::future;
+use reqwest::{StatusCode, Url};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    geo::Coordinates,
+    places::{
+        search::{store_search_result, Search, SearchQuery},
+        Address, Place,
+    },
+    repository::places::DynPlacesRepo,
+};
+
+use super::{details, photos, FOURSQUARE_BASE_URL};
+
+/// Foursquare rejects search radii above 100km.
+const MAX_RADIUS_METERS: f64 = 100_000.0;
+
+/// Searches places with the Foursquare Places API directly, rather than
+/// through Mapbox suggestions.
+pub struct FoursquareSearchApi {
+    foursquare_token: String,
+    places_repo: DynPlacesRepo,
+    foursquare_url: String,
+}
+
+#[derive(Deserialize, Serialize)]
+struct FoursquareResults {
+    results: Vec<FoursquarePlace>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct FoursquarePlace {
+    fsq_id: String,
+    name: String,
+    location: FoursquareLocation,
+    geocodes: Option<FoursquareGeocodes>,
+    website: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct FoursquareLocation {
+    address: Option<String>,
+    formatted_address: Option<String>,
+    country: Option<String>,
+    region: Option<String>,
+    postcode: Option<String>,
+    locality: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct FoursquareGeocodes {
+    main: Option<Coordinates>,
+}
+
+impl FoursquarePlace {
+    /// Places without a street address can't be stored, since the address is
+    /// what identifies a place.
+    fn convert_to_place(&self) -> Option<Place> {
+        let address = self
+            .location
+            .address
+            .clone()
+            .or_else(|| self.location.formatted_address.clone())?;
+
+        Some(Place {
+            id: 0,
+            name: self.name.clone(),
+            address: Address {
+                address,
+                full_address: self.location.formatted_address.clone(),
+                country: self.location.country.clone(),
+                region: self.location.region.clone(),
+                postcode: self.location.postcode.clone(),
+                place: self.location.locality.clone(),
+                street: None,
+            },
+            photos: None,
+            website: self.website.clone(),
+            foursquare_id: Some(self.fsq_id.clone()),
+            mapbox_id: None,
+            coordinates: self.geocodes.as_ref().and_then(|g| g.main),
+            categories: None,
+            details: None,
+        })
+    }
+}
+
+impl FoursquareSearchApi {
+    pub fn new(foursquare_token: &str, places_repo: DynPlacesRepo) -> Self {
+        FoursquareSearchApi {
+            foursquare_token: foursquare_token.to_string(),
+            places_repo,
+            foursquare_url: FOURSQUARE_BASE_URL.to_string(),
+        }
+    }
+
+    /// Points the client at another Foursquare host, such as a local mock
+    /// server. The URL is an origin without a trailing path.
+    pub fn with_base_url(mut self, foursquare_url: &str) -> Self {
+        self.foursquare_url = foursquare_url.trim_end_matches('/').to_string();
+        self
+    }
+}
+
+#[async_trait]
+impl Search for FoursquareSearchApi {
+    async fn search_for_place(&self, query: SearchQuery) -> Result<Vec<Place>, String> {
+        let mut url = match format!("{}/v3/places/search", self.foursquare_url).parse::<Url>() {
+            Ok(u) => u,
+            Err(_) => return Err("Invalid Foursquare url".to_string()),
+        };
+        let categories: Vec<&str> = query.categories.iter().map(|c| c.foursquare_id()).collect();
+        url.query_pairs_mut()
+            .append_pair("query", &query.search_string)
+            .append_pair(
+                "ll",
+                &format!(
+                    "{},{}",
+                    query.coordinates.latitude, query.coordinates.longitude
+                ),
+            )
+            .append_pair(
+                "radius",
+                &(query.radius_meters.min(MAX_RADIUS_METERS).round() as u64).to_string(),
+            )
+            .append_pair("fields", "fsq_id,name,location,geocodes,website");
+        if !categories.is_empty() {
+            url.query_pairs_mut()
+                .append_pair("categories", &categories.join(","));
+        }
+        if let Some(limit) = query.limit {
+            url.query_pairs_mut()
+                .append_pair("limit", &limit.to_string());
+        }
+
+        let client = reqwest::Client::new();
+        let res = match client
+            .get(url)
+            .header("Authorization", self.foursquare_token.clone())
+            .header("accept", "application/json")
+            .header("Accept-Language", query.language.clone())
+            .send()
+            .await
+        {
+            Ok(res) => res,
+            Err(e) => {
+                eprintln!("error sending search to foursquare: {}", e);
+                return Err("Error requesting data from Foursquare".to_string());
+            }
+        };
+        if res.status() != StatusCode::OK {
+            eprintln!(
+                "Status code from Foursquare not 200, it was: {}",
+                res.status()
+            );
+            eprintln!("Message for error was, {:?}", res.text().await);
+            return Err("Error requesting data from Foursquare".to_string());
+        }
+
+        let raw_body = match res.text().await {
+            Ok(b) => b,
+            Err(e) => {
+                eprintln!("error getting text from Foursquare res: {}", e);
+                return Err("Error requesting data from Foursquare".to_string());
+            }
+        };
+        let foursquare_places: Vec<FoursquarePlace> =
+            match serde_json::from_str::<FoursquareResults>(&raw_body) {
+                Ok(r) => r.results,
+                Err(e) => {
+                    eprintln!(
+                        "error unmarshalling response from Foursquare: {}. RawBody was {}",
+                        e, raw_body
+                    );
+                    return Err("Error requesting data from Foursquare".to_string());
+                }
+            };
+
+        // A place that can't be stored is left out rather than failing the
+        // search.
+        let found: Vec<Place> = foursquare_places
+            .iter()
+            .filter_map(|p| p.convert_to_place())
+            .collect();
+        let stored = future::join_all(
+            found
+                .iter()
+                .map(|p| store_search_result(&self.places_repo, p)),
+        )
+        .await;
+        let mut places = Vec::with_capacity(stored.len());
+        for (place, result) in found.iter().zip(stored) {
+            match result {
+                Ok(place) => places.push(place),
+                Err(e) => eprintln!(
+                    "error storing Foursquare place {}: {}",
+                    place.address.address, e
+                ),
+            }
+        }
+        if places.is_empty() && !found.is_empty() {
+            return Err("Error storing places from Foursquare".to_string());
+        }
+        Ok(places)
+    }
+
+    async fn get_photos(&self, place_id: u64) -> Result<Place, String> {
+        photos::get_photos(
+            &self.places_repo,
+            &self.foursquare_url,
+            &self.foursquare_token,
+            place_id,
+        )
+        .await
+    }
+
+    async fn get_details(&self, place_id: u64) -> Result<Place, String> {
+        details::get_details(
+            &self.places_repo,
+            &self.foursquare_url,
+            &self.foursquare_token,
+            place_id,
+        )
+        .await
+    }
+}
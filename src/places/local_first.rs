@@ -0,0 +1,235 @@
+use axum::async_trait;
+
+use crate::{
+    places::{
+        cache::CacheStats,
+        database::DatabaseSearch,
+        search::{Search, SearchQuery, MAX_SEARCH_LIMIT},
+        Place,
+    },
+    repository::places::DynPlacesRepo,
+};
+
+pub const DEFAULT_MIN_LOCAL_RESULTS: usize = 3;
+
+/// Answers searches from the places we have already stored, and only asks
+/// `upstream` when the database has too few matches. Providers store what
+/// they find, so repeated searches stop reaching them.
+///
+/// Stored places don't carry provider categories, so local results are
+/// matched on name, street and town only, closest first.
+pub struct LocalFirstSearch<S: Search> {
+    local: DatabaseSearch,
+    upstream: S,
+    min_results: usize,
+}
+
+impl<S: Search> LocalFirstSearch<S> {
+    pub fn new(places_repo: DynPlacesRepo, upstream: S) -> Self {
+        LocalFirstSearch {
+            local: DatabaseSearch::new(places_repo),
+            upstream,
+            min_results: DEFAULT_MIN_LOCAL_RESULTS,
+        }
+    }
+
+    /// How many local matches are enough to skip `upstream`. A search asking
+    /// for fewer results than this only needs as many as it asked for.
+    pub fn with_min_results(mut self, min_results: usize) -> Self {
+        self.min_results = min_results;
+        self
+    }
+
+    /// Stored matches, closest to the search first and places not located
+    /// yet last. A failed lookup counts as no matches.
+    async fn search_local(&self, query: &SearchQuery) -> Vec<Place> {
+        match self.local.search_for_place(query.clone()).await {
+            Ok(places) => places,
+            Err(e) => {
+                eprintln!("local search failed, searching upstream: {}", e);
+                Vec::new()
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<S: Search> Search for LocalFirstSearch<S> {
+    async fn search_for_place(&self, query: SearchQuery) -> Result<Vec<Place>, String> {
+        let limit = usize::from(query.limit.unwrap_or(MAX_SEARCH_LIMIT));
+        let local = self.search_local(&query).await;
+        // Places not located yet may be in another part of town, so only
+        // located ones are enough to skip upstream.
+        let located = local.iter().filter(|p| p.coordinates.is_some()).count();
+        if located >= self.min_results.min(limit) {
+            return Ok(local);
+        }
+
+        let found = match self.upstream.search_for_place(query).await {
+            Ok(found) => found,
+            Err(e) if !local.is_empty() => {
+                eprintln!("upstream search failed, serving local results: {}", e);
+                return Ok(local);
+            }
+            Err(e) => return Err(e),
+        };
+
+        // Local matches keep their place in the list, but with the fresher
+        // copy when upstream found them too.
+        let mut places: Vec<Place> = local
+            .into_iter()
+            .map(|place| match found.iter().find(|p| p.id == place.id) {
+                Some(fresh) => fresh.clone(),
+                None => place,
+            })
+            .collect();
+        for place in found {
+            if !places.iter().any(|p| p.id == place.id) {
+                places.push(place);
+            }
+        }
+        places.truncate(limit);
+        Ok(places)
+    }
+
+    async fn get_photos(&self, place_id: u64) -> Result<Place, String> {
+        self.upstream.get_photos(place_id).await
+    }
+
+    async fn get_details(&self, place_id: u64) -> Result<Place, String> {
+        self.upstream.get_details(place_id).await
+    }
+
+    async fn get_coordinates(&self, place_id: u64, session_token: &str) -> Result<Place, String> {
+        self.upstream.get_coordinates(place_id, session_token).await
+    }
+
+    async fn cache_stats(&self) -> Option<CacheStats> {
+        self.upstream.cache_stats().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use tokio::sync::Mutex;
+
+    use crate::{
+        places::{
+            testing::{place, salt_lake_city, FakeSearch},
+            Address,
+        },
+        repository::{local::places::LocalPlacesRepository, places::PlacesRepository},
+    };
+
+    use super::*;
+
+    fn upstream(result: Result<Vec<Place>, String>) -> (FakeSearch, Arc<AtomicUsize>) {
+        let upstream = FakeSearch::new(result);
+        let calls = upstream.calls();
+        (upstream, calls)
+    }
+
+    fn located(id: u64, name: &str) -> Place {
+        Place {
+            coordinates: Some(salt_lake_city()),
+            ..place(id, name)
+        }
+    }
+
+    fn query(text: &str) -> SearchQuery {
+        SearchQuery::new(salt_lake_city(), text, "session")
+    }
+
+    async fn places_repo(places: &[Place]) -> DynPlacesRepo {
+        let mut repo = LocalPlacesRepository::new();
+        for place in places {
+            repo.create(place).await.unwrap();
+        }
+        Arc::new(Mutex::new(repo))
+    }
+
+    #[tokio::test]
+    async fn test_search_serves_enough_local_results_without_upstream() {
+        let repo = places_repo(&[located(0, "Arlo"), located(0, "Arlo Bakehouse")]).await;
+        let (upstream, calls) = upstream(Ok(vec![]));
+        let search = LocalFirstSearch::new(repo, upstream).with_min_results(2);
+
+        let places = search.search_for_place(query("arl")).await.unwrap();
+
+        assert_eq!(places.len(), 2);
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_search_only_needs_as_many_results_as_the_limit() {
+        let repo = places_repo(&[located(0, "Arlo")]).await;
+        let (upstream, calls) = upstream(Ok(vec![]));
+        let search = LocalFirstSearch::new(repo, upstream);
+        let mut query = query("arlo");
+        query.limit = Some(1);
+
+        assert_eq!(search.search_for_place(query).await.unwrap().len(), 1);
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_search_does_not_count_unlocated_results() {
+        let town = |place: Place| Place {
+            address: Address {
+                place: Some("Salt Lake City".to_string()),
+                region: Some("Utah".to_string()),
+                ..place.address.clone()
+            },
+            ..place
+        };
+        let repo = places_repo(&[
+            town(located(0, "Communal")),
+            town(place(0, "Arlo")),
+            town(place(0, "Arlo Bakehouse")),
+        ])
+        .await;
+        let (upstream, calls) = upstream(Ok(vec![]));
+        let search = LocalFirstSearch::new(repo, upstream).with_min_results(2);
+
+        let places = search.search_for_place(query("arlo")).await.unwrap();
+
+        assert_eq!(places.len(), 2);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_search_falls_back_to_upstream_and_merges_results() {
+        let repo = places_repo(&[located(0, "Arlo")]).await;
+        let mut fresh = located(1, "Arlo");
+        fresh.website = Some("https://www.arlorestaurant.com".to_string());
+        let (upstream, calls) = upstream(Ok(vec![located(7, "Arlo Bakehouse"), fresh]));
+        let search = LocalFirstSearch::new(repo, upstream).with_min_results(2);
+
+        let places = search.search_for_place(query("arlo")).await.unwrap();
+
+        assert_eq!(places.iter().map(|p| p.id).collect::<Vec<_>>(), vec![1, 7]);
+        assert!(places[0].website.is_some());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_search_serves_local_results_when_upstream_fails() {
+        let repo = places_repo(&[located(0, "Arlo")]).await;
+        let (upstream, _) = upstream(Err("Error requesting data from Mapbox".to_string()));
+        let search = LocalFirstSearch::new(repo, upstream);
+
+        assert_eq!(
+            search.search_for_place(query("arlo")).await.unwrap().len(),
+            1
+        );
+        assert_eq!(
+            search.search_for_place(query("red")).await.unwrap_err(),
+            "Error requesting data from Mapbox"
+        );
+    }
+}
@@ -5,14 +5,22 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     geo::Coordinates,
-    places::{search::Search, Address, Place},
-    repository::places::{DynPlacesRepo, ReadPlaceOptions},
+    places::{
+        foursquare::{details, photos, FOURSQUARE_BASE_URL},
+        search::{read_place, store_search_result, update_place, Search, SearchQuery},
+        Address, Place,
+    },
+    repository::places::DynPlacesRepo,
 };
 
+pub const MAPBOX_BASE_URL: &str = "https://api.mapbox.com";
+
 pub struct MapboxSearchApi {
     access_token: String,
     foursquare_token: String,
     places_repo: DynPlacesRepo,
+    mapbox_url: String,
+    foursquare_url: String,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -64,10 +72,19 @@ struct MapboxExternalIds {
 }
 
 #[derive(Deserialize, Serialize)]
-struct FoursquarePhoto {
-    id: String,
-    prefix: String,
-    suffix: String,
+struct MapboxFeatureCollection {
+    features: Vec<MapboxFeature>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct MapboxFeature {
+    geometry: MapboxGeometry,
+}
+
+#[derive(Deserialize, Serialize)]
+struct MapboxGeometry {
+    /// GeoJSON order: longitude, then latitude.
+    coordinates: [f64; 2],
 }
 
 impl MapboxPlace {
@@ -87,6 +104,10 @@ impl MapboxPlace {
             photos: None,
             website: None,
             foursquare_id: self.external_ids.foursquare.clone(),
+            mapbox_id: Some(self.mapbox_id.clone()),
+            coordinates: None,
+            categories: None,
+            details: None,
         };
 
         if let Some(country) = &self.context.country {
@@ -104,7 +125,7 @@ impl MapboxPlace {
         if let Some(street) = &self.context.street {
             place.address.street = Some(street.name.clone());
         };
-        return place;
+        place
     }
 }
 
@@ -114,25 +135,102 @@ impl MapboxSearchApi {
             access_token: access_token.to_string(),
             foursquare_token: foursquare_token.to_string(),
             places_repo,
+            mapbox_url: MAPBOX_BASE_URL.to_string(),
+            foursquare_url: FOURSQUARE_BASE_URL.to_string(),
         }
     }
+
+    /// Points the client at other Mapbox and Foursquare hosts, such as a local
+    /// mock server. Both URLs are origins without a trailing path.
+    pub fn with_base_urls(mut self, mapbox_url: &str, foursquare_url: &str) -> Self {
+        self.mapbox_url = mapbox_url.trim_end_matches('/').to_string();
+        self.foursquare_url = foursquare_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Looks up the coordinates of a suggestion with the Search Box `/retrieve`
+    /// endpoint. `/suggest` results carry no location of their own.
+    async fn retrieve_coordinates(
+        &self,
+        mapbox_id: &str,
+        session_token: &str,
+    ) -> Result<Coordinates, String> {
+        let mut url =
+            match format!("{}/search/searchbox/v1/retrieve", self.mapbox_url).parse::<Url>() {
+                Ok(u) => u,
+                Err(_) => return Err("Invalid Mapbox url".to_string()),
+            };
+        url.path_segments_mut()
+            .map_err(|_| "Invalid Mapbox url".to_string())?
+            .push(mapbox_id);
+        url.query_pairs_mut()
+            .append_pair("access_token", &self.access_token)
+            .append_pair("session_token", session_token);
+
+        let client = reqwest::Client::new();
+        let res = match client.get(url).send().await {
+            Ok(res) => res,
+            Err(e) => {
+                eprintln!("error sending retrieve request to Mapbox: {}", e);
+                return Err("Error retrieving place from Mapbox".to_string());
+            }
+        };
+        if res.status() != StatusCode::OK {
+            eprintln!(
+                "Status code from Mapbox retrieve not 200, it was: {}",
+                res.status()
+            );
+            return Err("Error retrieving place from Mapbox".to_string());
+        }
+        let raw_body = match res.text().await {
+            Ok(b) => b,
+            Err(e) => {
+                eprintln!("error getting text from Mapbox retrieve res: {}", e);
+                return Err("Error retrieving place from Mapbox".to_string());
+            }
+        };
+        let body: MapboxFeatureCollection = match serde_json::from_str(&raw_body) {
+            Ok(b) => b,
+            Err(e) => {
+                eprintln!(
+                    "error unmarshalling retrieve response from Mapbox: {}. RawBody was {}",
+                    e, raw_body
+                );
+                return Err("Error retrieving place from Mapbox".to_string());
+            }
+        };
+
+        match body.features.first() {
+            Some(feature) => Ok(Coordinates {
+                latitude: feature.geometry.coordinates[1],
+                longitude: feature.geometry.coordinates[0],
+            }),
+            None => Err("Place not found in Mapbox".to_string()),
+        }
+    }
+
+    /// Stores a suggestion as it came, without coordinates.
+    async fn store_place(&self, mapbox_place: &MapboxPlace) -> Result<Place, String> {
+        store_search_result(&self.places_repo, &mapbox_place.convert_to_place()).await
+    }
 }
 
 #[async_trait]
 impl Search for MapboxSearchApi {
-    async fn search_for_place(
-        &self,
-        coordinates: Coordinates,
-        search_string: String,
-    ) -> Result<Vec<Place>, String> {
-        let mut url = "https://api.mapbox.com/search/searchbox/v1/suggest"
-            .parse::<Url>()
-            .unwrap();
+    async fn search_for_place(&self, query: SearchQuery) -> Result<Vec<Place>, String> {
+        let mut url =
+            match format!("{}/search/searchbox/v1/suggest", self.mapbox_url).parse::<Url>() {
+                Ok(u) => u,
+                Err(_) => return Err("Invalid Mapbox url".to_string()),
+            };
+        let coordinates = query.coordinates;
+        let bbox = query.bounding_box();
+        let categories: Vec<&str> = query.categories.iter().map(|c| c.mapbox_id()).collect();
         url.query_pairs_mut()
-            .append_pair("q", &search_string)
+            .append_pair("q", &query.search_string)
             .append_pair("access_token", &self.access_token)
-            .append_pair("session_token", "[GENERATED-UUID]")
-            .append_pair("language", "en")
+            .append_pair("session_token", &query.session_token)
+            .append_pair("language", &query.language)
             .append_pair(
                 "proximity",
                 &format!("{},{}", coordinates.longitude, coordinates.latitude),
@@ -141,18 +239,22 @@ impl Search for MapboxSearchApi {
                 "bbox",
                 &format!(
                     "{},{},{},{}",
-                    coordinates.longitude - 0.5,
-                    coordinates.latitude - 0.5,
-                    coordinates.longitude + 0.5,
-                    coordinates.latitude + 0.5
+                    bbox.min_longitude, bbox.min_latitude, bbox.max_longitude, bbox.max_latitude
                 ),
             )
             .append_pair("types", "poi")
             .append_pair(
                 "origin",
                 &format!("{},{}", coordinates.longitude, coordinates.latitude),
-            )
-            .append_pair("poi_category", "food");
+            );
+        if !categories.is_empty() {
+            url.query_pairs_mut()
+                .append_pair("poi_category", &categories.join(","));
+        }
+        if let Some(limit) = query.limit {
+            url.query_pairs_mut()
+                .append_pair("limit", &limit.to_string());
+        }
 
         let client = reqwest::Client::new();
         match client.get(url).send().await {
@@ -162,19 +264,19 @@ impl Search for MapboxSearchApi {
                     eprintln!("Message for error was, {:?}", res.text().await);
                     return Err("Error requesting data from Mapbox".to_string());
                 };
-                let raw_body: String;
-                match res.text().await {
-                    Ok(b) => raw_body = b,
+
+                let raw_body: String = match res.text().await {
+                    Ok(b) => b,
                     Err(e) => {
                         eprintln!("error getting text from Mapbox res: {}", e);
                         return Err("Error requesting data from Mapbox".to_string());
                     }
-                }
+                };
                 let body: Result<MapboxSuggestions, serde_json::Error> =
                     serde_json::from_str(&raw_body);
-                let mapbox_places: Vec<MapboxPlace>;
-                match body {
-                    Ok(p) => mapbox_places = p.suggestions,
+
+                let mapbox_places: Vec<MapboxPlace> = match body {
+                    Ok(p) => p.suggestions,
                     Err(e) => {
                         eprintln!(
                             "error unmarshalling response from Mapbox: {}. RawBody was {}",
@@ -182,17 +284,26 @@ impl Search for MapboxSearchApi {
                         );
                         return Err("Error requesting data from Mapbox".to_string());
                     }
+                };
+                // Suggestions are stored without coordinates, which are only
+                // retrieved for the place the user picks. One that can't be
+                // stored is left out rather than failing the search.
+                let stored =
+                    future::join_all(mapbox_places.iter().map(|p| self.store_place(p))).await;
+                let mut places = Vec::with_capacity(stored.len());
+                for (mapbox_place, result) in mapbox_places.iter().zip(stored) {
+                    match result {
+                        Ok(place) => places.push(place),
+                        Err(e) => eprintln!(
+                            "error storing Mapbox suggestion {}: {}",
+                            mapbox_place.mapbox_id, e
+                        ),
+                    }
                 }
-                let places = future::try_join_all(mapbox_places.iter().map(|p| async move {
-                    self.places_repo
-                        .lock()
-                        .await
-                        .create(&p.convert_to_place())
-                        .await
-                }))
-                .await;
-
-                return places;
+                if places.is_empty() && !mapbox_places.is_empty() {
+                    return Err("Error storing places from Mapbox".to_string());
+                }
+                Ok(places)
             }
             Err(e) => {
                 eprintln!("error sending response to Mapbox: {}", e);
@@ -202,87 +313,39 @@ impl Search for MapboxSearchApi {
     }
 
     async fn get_photos(&self, place_id: u64) -> Result<Place, String> {
-        let mut place: Place;
-        match self
-            .places_repo
-            .lock()
-            .await
-            .read(ReadPlaceOptions {
-                id: Some(place_id),
-                name: None,
-                address: None,
-                postcode: None,
-            })
-            .await
-        {
-            Ok(places) => {
-                if places.len() != 1 {
-                    return Err("Error getting place".to_string());
-                }
-                place = places.first().unwrap().clone();
-            }
-            Err(_) => return Err("Error getting place".to_string()),
-        };
+        photos::get_photos(
+            &self.places_repo,
+            &self.foursquare_url,
+            &self.foursquare_token,
+            place_id,
+        )
+        .await
+    }
 
-        let foursquare_id: String;
-        match place.foursquare_id.clone() {
-            Some(id) => foursquare_id = id.clone(),
-            None => return Ok(place),
+    /// Retrieves the coordinates of a suggestion the user picked, which ends
+    /// the Mapbox session it was suggested in.
+    async fn get_coordinates(&self, place_id: u64, session_token: &str) -> Result<Place, String> {
+        let place = read_place(&self.places_repo, place_id).await?;
+        let mapbox_id = match (&place.coordinates, &place.mapbox_id) {
+            (None, Some(mapbox_id)) => mapbox_id.clone(),
+            _ => return Ok(place),
         };
+        let coordinates = self.retrieve_coordinates(&mapbox_id, session_token).await?;
+        update_place(&self.places_repo, place_id, |place| {
+            place.coordinates = Some(coordinates)
+        })
+        .await
+    }
 
-        let mut url = "https://api.foursquare.com/v3/places"
-            .parse::<Url>()
-            .unwrap();
-        url.path_segments_mut()
-            .map_err(|_| "cannot be base")
-            .unwrap()
-            .push(&foursquare_id)
-            .push("photos");
-        let client = reqwest::Client::new();
-        let mut foursquare_photos: Vec<FoursquarePhoto>;
-        match client
-            .get(url)
-            .header("Authorization", self.foursquare_token.clone())
-            .header("accept", "application/json")
-            .send()
-            .await
-        {
-            Ok(res) => {
-                if res.status() != StatusCode::OK {
-                    eprintln!("Status code: {}, when getting pictures", res.status());
-                    return Err("Error getting pictures".to_string());
-                }
-                match res.text().await {
-                    Ok(t) => {
-                        let body: Result<Vec<FoursquarePhoto>, serde_json::Error> =
-                            serde_json::from_str(&t);
-                        match body {
-                            Ok(body) => foursquare_photos = body,
-                            Err(_) => {
-                                eprintln!("Error parsing JSON from foursquare, {}", t);
-                                return Err("Error parsing JSON".to_string());
-                            }
-                        }
-                    }
-                    Err(_) => return Err("Error parsing JSON".to_string()),
-                }
-            }
-            Err(e) => {
-                eprintln!("error sending to foursquare: {}", e);
-                return Err("Error getting pictures".to_string());
-            }
-        };
-
-        let photos = foursquare_photos
-            .iter_mut()
-            .map(|photo| {
-                photo.prefix.pop();
-                return format!("{}/original{}", photo.prefix, photo.suffix);
-            })
-            .collect();
-        place.photos = Some(photos);
-
-        return self.places_repo.lock().await.update(place).await;
+    /// Mapbox results carry Foursquare ids, so details come from Foursquare.
+    async fn get_details(&self, place_id: u64) -> Result<Place, String> {
+        details::get_details(
+            &self.places_repo,
+            &self.foursquare_url,
+            &self.foursquare_token,
+            place_id,
+        )
+        .await
     }
 }
 
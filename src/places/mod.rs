@@ -1,20 +1,126 @@
+pub mod activity;
+pub mod cache;
+pub mod composite;
+pub mod database;
+pub mod export;
+pub mod filter;
+pub mod foursquare;
+pub mod local_first;
 pub mod mapbox;
+pub mod osm;
+pub mod photo;
 pub mod search;
+pub mod session;
+#[cfg(test)]
+pub mod testing;
+pub mod upload;
 
+use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+use crate::geo::Coordinates;
+
+use self::photo::Photo;
+
+/// How long details are trusted before they are fetched again.
+pub const DETAILS_MAX_AGE_DAYS: i64 = 30;
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct Place {
     pub id: u64,
     pub name: String,
     pub address: Address,
-    pub photos: Option<Vec<String>>,
+    #[serde(serialize_with = "photo::serialize_with_variants")]
+    pub photos: Option<Vec<Photo>>,
     pub website: Option<String>,
     pub foursquare_id: Option<String>,
+    /// Set for Mapbox suggestions, which come without coordinates until
+    /// someone picks the place.
+    pub mapbox_id: Option<String>,
+    pub coordinates: Option<Coordinates>,
+    /// Free-form descriptions such as cuisines, e.g. `["mexican", "tacos"]`.
+    pub categories: Option<Vec<String>>,
+    /// Unset until a provider has been asked for the details.
+    pub details: Option<PlaceDetails>,
+}
+
+impl Place {
+    /// Whether the details are missing or older than `DETAILS_MAX_AGE_DAYS`.
+    pub fn details_stale(&self, now: DateTime<Utc>) -> bool {
+        self.details_older_than(Duration::days(DETAILS_MAX_AGE_DAYS), now)
+    }
+
+    /// Whether the details are missing or older than `max_age`.
+    pub fn details_older_than(&self, max_age: Duration, now: DateTime<Utc>) -> bool {
+        self.details
+            .as_ref()
+            .is_none_or(|details| now - details.updated_at > max_age)
+    }
+
+    /// Whether Foursquare knows this place and its photos haven't been
+    /// fetched. Photos only come from Foursquare, so others never have any.
+    pub fn photos_missing(&self) -> bool {
+        self.foursquare_id.is_some() && self.photos.is_none()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaceDetails {
+    pub hours: Option<OpeningHours>,
+    /// From 1 (cheapest) to 4 (most expensive).
+    pub price: Option<u8>,
+    pub phone: Option<String>,
+    pub social: Option<SocialLinks>,
+    /// The IANA time zone `hours` are in, e.g. `America/Denver`.
+    pub timezone: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl PlaceDetails {
+    /// Details asked for at `updated_at` that no provider had, so the place
+    /// isn't asked about again until they are stale.
+    pub fn unknown(updated_at: DateTime<Utc>) -> Self {
+        PlaceDetails {
+            hours: None,
+            price: None,
+            phone: None,
+            social: None,
+            timezone: None,
+            updated_at,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct OpeningHours {
+    /// Hours for people to read, e.g. `Mon-Sat 11:00-22:00`.
+    pub display: Option<String>,
+    pub periods: Vec<OpeningPeriod>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct OpeningPeriod {
+    /// 1 for Monday through 7 for Sunday.
+    pub day: u8,
+    /// Local time as `HHMM`.
+    pub open: String,
+    /// Local time as `HHMM`, on the next day when earlier than `open`.
+    pub close: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SocialLinks {
+    pub facebook_id: Option<String>,
+    pub instagram: Option<String>,
+    pub twitter: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct Address {
     pub address: String,
@@ -25,3 +131,27 @@ pub struct Address {
     pub place: Option<String>,
     pub street: Option<String>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        testing::{details, place},
+        *,
+    };
+
+    fn arlo(details_age_days: Option<i64>) -> Place {
+        Place {
+            details: details_age_days.map(|days| details(Utc::now() - Duration::days(days))),
+            ..place(1, "Arlo")
+        }
+    }
+
+    #[test]
+    fn test_details_stale() {
+        let now = Utc::now();
+
+        assert!(arlo(None).details_stale(now));
+        assert!(arlo(Some(DETAILS_MAX_AGE_DAYS + 1)).details_stale(now));
+        assert!(!arlo(Some(1)).details_stale(now));
+    }
+}
@@ -0,0 +1,4 @@
+pub mod search;
+
+/// Nominatim's usage policy requires an identifying User-Agent.
+pub const DEFAULT_USER_AGENT: &str = "critiq_backend";
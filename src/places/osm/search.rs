@@ -0,0 +1,346 @@
+use std::collections::HashMap;
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use futures::future;
+use reqwest::{StatusCode, Url};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    geo::Coordinates,
+    places::{
+        search::{
+            read_place, store_search_result, update_place, PlaceCategory, Search, SearchQuery,
+        },
+        Address, OpeningHours, Place, PlaceDetails,
+    },
+    repository::places::DynPlacesRepo,
+};
+
+use super::DEFAULT_USER_AGENT;
+
+/// The most results Nominatim returns for one search. We ask for all of them
+/// since results outside the requested categories are dropped afterwards.
+const MAX_NOMINATIM_LIMIT: u8 = 40;
+const DEFAULT_LIMIT: usize = 10;
+/// Enough to find a place again by its full address among others at it.
+const LOOKUP_LIMIT: u8 = 5;
+
+/// Searches places with a Nominatim-compatible geocoding API backed by
+/// OpenStreetMap data, such as a self-hosted instance. Searches run as users
+/// type, which nominatim.openstreetmap.org's usage policy forbids, along with
+/// more than one request a second, so there is no default host.
+pub struct OsmSearchApi {
+    places_repo: DynPlacesRepo,
+    nominatim_url: String,
+    user_agent: String,
+}
+
+#[derive(Deserialize, Serialize)]
+struct NominatimPlace {
+    lat: String,
+    lon: String,
+    category: String,
+    #[serde(rename = "type")]
+    place_type: String,
+    name: Option<String>,
+    display_name: String,
+    address: Option<NominatimAddress>,
+    extratags: Option<HashMap<String, String>>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct NominatimAddress {
+    house_number: Option<String>,
+    road: Option<String>,
+    city: Option<String>,
+    town: Option<String>,
+    village: Option<String>,
+    hamlet: Option<String>,
+    state: Option<String>,
+    postcode: Option<String>,
+    country: Option<String>,
+}
+
+/// The OSM `key=value` tags that count as each category.
+fn osm_tags(category: &PlaceCategory) -> &'static [(&'static str, &'static str)] {
+    match category {
+        PlaceCategory::Food => &[
+            ("amenity", "restaurant"),
+            ("amenity", "cafe"),
+            ("amenity", "fast_food"),
+            ("amenity", "food_court"),
+            ("amenity", "ice_cream"),
+            ("amenity", "bar"),
+            ("amenity", "pub"),
+            ("shop", "bakery"),
+            ("shop", "pastry"),
+        ],
+        PlaceCategory::Restaurant => &[("amenity", "restaurant")],
+        PlaceCategory::Coffee => &[("amenity", "cafe"), ("shop", "coffee")],
+        PlaceCategory::Cafe => &[("amenity", "cafe")],
+        PlaceCategory::Bar => &[("amenity", "bar"), ("amenity", "pub")],
+        PlaceCategory::Bakery => &[("shop", "bakery")],
+        PlaceCategory::Dessert => &[
+            ("amenity", "ice_cream"),
+            ("shop", "pastry"),
+            ("shop", "confectionery"),
+        ],
+        PlaceCategory::FastFood => &[("amenity", "fast_food")],
+    }
+}
+
+impl NominatimPlace {
+    fn matches(&self, categories: &[PlaceCategory]) -> bool {
+        categories.is_empty()
+            || categories.iter().any(|c| {
+                osm_tags(c)
+                    .iter()
+                    .any(|(key, value)| self.category == *key && self.place_type == *value)
+            })
+    }
+
+    /// OSM `cuisine` tags are `;` separated, e.g. `mexican;tacos`.
+    fn cuisines(&self) -> Option<Vec<String>> {
+        let cuisine = self.extratags.as_ref()?.get("cuisine")?;
+        let cuisines: Vec<String> = cuisine
+            .split(';')
+            .map(|c| c.trim().to_string())
+            .filter(|c| !c.is_empty())
+            .collect();
+        if cuisines.is_empty() {
+            return None;
+        }
+        Some(cuisines)
+    }
+
+    fn tag(&self, keys: &[&str]) -> Option<String> {
+        let extratags = self.extratags.as_ref()?;
+        keys.iter().find_map(|key| extratags.get(*key)).cloned()
+    }
+
+    /// OSM `opening_hours` are kept as written, e.g. `We-Su 17:00-21:00`,
+    /// since their syntax is richer than weekly periods.
+    fn details(&self, updated_at: DateTime<Utc>) -> PlaceDetails {
+        PlaceDetails {
+            hours: self.tag(&["opening_hours"]).map(|hours| OpeningHours {
+                display: Some(hours),
+                periods: Vec::new(),
+            }),
+            phone: self.tag(&["phone", "contact:phone"]),
+            ..PlaceDetails::unknown(updated_at)
+        }
+    }
+
+    /// Results without a road and house number can't be stored, since the
+    /// address is what identifies a place and a road alone is shared by
+    /// every place on it.
+    fn convert_to_place(&self) -> Option<Place> {
+        let name = self.name.clone().filter(|n| !n.is_empty())?;
+        let address = self.address.as_ref()?;
+        let road = address.road.clone()?;
+        let street_address = format!("{} {}", address.house_number.as_ref()?, road);
+        let coordinates = match (self.lat.parse(), self.lon.parse()) {
+            (Ok(latitude), Ok(longitude)) => Some(Coordinates {
+                latitude,
+                longitude,
+            }),
+            _ => None,
+        };
+
+        Some(Place {
+            id: 0,
+            name,
+            address: Address {
+                address: street_address,
+                full_address: Some(self.display_name.clone()),
+                country: address.country.clone(),
+                region: address.state.clone(),
+                postcode: address.postcode.clone(),
+                place: address
+                    .city
+                    .clone()
+                    .or_else(|| address.town.clone())
+                    .or_else(|| address.village.clone())
+                    .or_else(|| address.hamlet.clone()),
+                street: Some(road),
+            },
+            photos: None,
+            website: self.tag(&["website", "contact:website"]),
+            foursquare_id: None,
+            mapbox_id: None,
+            coordinates,
+            categories: self.cuisines(),
+            details: None,
+        })
+    }
+}
+
+impl OsmSearchApi {
+    /// `nominatim_url` is the origin of the Nominatim-compatible host, such as
+    /// a local instance or a mock server, without a trailing path.
+    pub fn new(nominatim_url: &str, places_repo: DynPlacesRepo) -> Self {
+        OsmSearchApi {
+            places_repo,
+            nominatim_url: nominatim_url.trim_end_matches('/').to_string(),
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+        }
+    }
+
+    pub fn with_user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = user_agent.to_string();
+        self
+    }
+
+    async fn request(&self, url: Url) -> Result<Vec<NominatimPlace>, String> {
+        let client = reqwest::Client::new();
+        let res = match client
+            .get(url)
+            .header("User-Agent", self.user_agent.clone())
+            .header("accept", "application/json")
+            .send()
+            .await
+        {
+            Ok(res) => res,
+            Err(e) => {
+                eprintln!("error sending search to Nominatim: {}", e);
+                return Err("Error requesting data from OpenStreetMap".to_string());
+            }
+        };
+        if res.status() != StatusCode::OK {
+            eprintln!(
+                "Status code from Nominatim not 200, it was: {}",
+                res.status()
+            );
+            eprintln!("Message for error was, {:?}", res.text().await);
+            return Err("Error requesting data from OpenStreetMap".to_string());
+        }
+
+        let raw_body = match res.text().await {
+            Ok(b) => b,
+            Err(e) => {
+                eprintln!("error getting text from Nominatim res: {}", e);
+                return Err("Error requesting data from OpenStreetMap".to_string());
+            }
+        };
+        match serde_json::from_str(&raw_body) {
+            Ok(p) => Ok(p),
+            Err(e) => {
+                eprintln!(
+                    "error unmarshalling response from Nominatim: {}. RawBody was {}",
+                    e, raw_body
+                );
+                Err("Error requesting data from OpenStreetMap".to_string())
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Search for OsmSearchApi {
+    async fn search_for_place(&self, query: SearchQuery) -> Result<Vec<Place>, String> {
+        let mut url = match format!("{}/search", self.nominatim_url).parse::<Url>() {
+            Ok(u) => u,
+            Err(_) => return Err("Invalid Nominatim url".to_string()),
+        };
+        let bbox = query.bounding_box();
+        url.query_pairs_mut()
+            .append_pair("q", &query.search_string)
+            .append_pair("format", "jsonv2")
+            .append_pair("addressdetails", "1")
+            .append_pair("extratags", "1")
+            .append_pair("layer", "poi")
+            .append_pair("limit", &MAX_NOMINATIM_LIMIT.to_string())
+            .append_pair(
+                "viewbox",
+                &format!(
+                    "{},{},{},{}",
+                    bbox.min_longitude, bbox.max_latitude, bbox.max_longitude, bbox.min_latitude
+                ),
+            )
+            .append_pair("bounded", "1")
+            .append_pair("accept-language", &query.language);
+
+        let osm_places = self.request(url).await?;
+
+        let limit = query.limit.map(usize::from).unwrap_or(DEFAULT_LIMIT);
+        // A place that can't be stored is left out rather than failing the
+        // search.
+        let found: Vec<Place> = osm_places
+            .iter()
+            .filter(|p| p.matches(&query.categories))
+            .filter_map(|p| p.convert_to_place())
+            .take(limit)
+            .collect();
+        let stored = future::join_all(
+            found
+                .iter()
+                .map(|p| store_search_result(&self.places_repo, p)),
+        )
+        .await;
+        let mut places = Vec::with_capacity(stored.len());
+        for (place, result) in found.iter().zip(stored) {
+            match result {
+                Ok(place) => places.push(place),
+                Err(e) => eprintln!(
+                    "error storing OpenStreetMap place {}: {}",
+                    place.address.address, e
+                ),
+            }
+        }
+        if places.is_empty() && !found.is_empty() {
+            return Err("Error storing places from OpenStreetMap".to_string());
+        }
+        Ok(places)
+    }
+
+    /// OpenStreetMap has no photos, so this only returns the stored place.
+    async fn get_photos(&self, place_id: u64) -> Result<Place, String> {
+        read_place(&self.places_repo, place_id).await
+    }
+
+    /// Looks a place found on OpenStreetMap up again by its full address,
+    /// and stores its opening hours, phone and website. Places found
+    /// elsewhere are returned unchanged.
+    async fn get_details(&self, place_id: u64) -> Result<Place, String> {
+        let place = read_place(&self.places_repo, place_id).await?;
+        if place.foursquare_id.is_some() || place.mapbox_id.is_some() {
+            return Ok(place);
+        }
+        let full_address = match &place.address.full_address {
+            Some(address) => address.clone(),
+            None => format!("{}, {}", place.name, place.address.address),
+        };
+
+        let mut url = match format!("{}/search", self.nominatim_url).parse::<Url>() {
+            Ok(u) => u,
+            Err(_) => return Err("Invalid Nominatim url".to_string()),
+        };
+        url.query_pairs_mut()
+            .append_pair("q", &full_address)
+            .append_pair("format", "jsonv2")
+            .append_pair("addressdetails", "1")
+            .append_pair("extratags", "1")
+            .append_pair("layer", "poi")
+            .append_pair("limit", &LOOKUP_LIMIT.to_string());
+        let osm_places = self.request(url).await?;
+
+        let now = Utc::now();
+        // A place no longer on OpenStreetMap has nothing to add until it is
+        // looked up again.
+        let found = osm_places.iter().find(|p| {
+            p.convert_to_place().is_some_and(|found| {
+                found.name == place.name && found.address.address == place.address.address
+            })
+        });
+        update_place(&self.places_repo, place_id, |place| match found {
+            Some(found) => {
+                place.details = Some(found.details(now));
+                place.website = found.tag(&["website", "contact:website"]);
+                place.categories = found.cuisines().or(place.categories.take());
+            }
+            None => place.details = Some(PlaceDetails::unknown(now)),
+        })
+        .await
+    }
+}
@@ -0,0 +1,468 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock},
+    time::SystemTime,
+};
+
+use base64::{engine::general_purpose, Engine};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize, Serializer};
+use sha2::{Digest, Sha256};
+use tokio::sync::Mutex;
+
+/// Where Foursquare serves photos from. The proxy fetches nothing else.
+pub const FOURSQUARE_PHOTO_ORIGINS: [&str; 2] =
+    ["https://fastly.4sqi.net/", "https://igx.4sqi.net/"];
+
+/// Shown alongside every photo, as Foursquare's terms require.
+pub const PHOTO_ATTRIBUTION: &str = "Photo from Foursquare";
+
+/// How much of the disk cached photos may take up, unless configured.
+pub const DEFAULT_PHOTO_CACHE_BYTES: u64 = 1024 * 1024 * 1024;
+
+/// Signs photo tokens, so the proxy only fetches photos the API has listed
+/// on a place. Set once at startup with `set_token_key`.
+static TOKEN_KEY: OnceLock<Vec<u8>> = OnceLock::new();
+
+/// Sets the key photo tokens are signed with. Every instance of the API needs
+/// the same key for their photo URLs to work on the others. Fails if tokens
+/// were already signed with another key.
+pub fn set_token_key(key: &[u8]) -> Result<(), String> {
+    match TOKEN_KEY.set(key.to_vec()) {
+        Ok(_) => Ok(()),
+        Err(_) if token_key() == key => Ok(()),
+        Err(_) => Err("Photo token key already set".to_string()),
+    }
+}
+
+/// Without `set_token_key`, a random key that lasts until the API restarts.
+fn token_key() -> &'static [u8] {
+    TOKEN_KEY.get_or_init(|| {
+        let mut key = vec![0; 32];
+        rand::thread_rng().fill_bytes(&mut key);
+        key
+    })
+}
+
+fn token_mac(payload: &str) -> Hmac<Sha256> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(token_key()).expect("HMAC to take keys of any size");
+    mac.update(payload.as_bytes());
+    mac
+}
+
+/// A Foursquare photo. The image at a given size is at
+/// `{prefix}{size}{suffix}`, e.g. `https://fastly.4sqi.net/img/general/` +
+/// `300x300` + `/123_abc.jpg`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Photo {
+    pub prefix: String,
+    pub suffix: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PhotoSize {
+    /// A 200 pixel square crop, for lists and map pins.
+    Thumbnail,
+    /// At most 600 pixels on the longer side.
+    Medium,
+    Original,
+}
+
+impl PhotoSize {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PhotoSize::Thumbnail => "thumbnail",
+            PhotoSize::Medium => "medium",
+            PhotoSize::Original => "original",
+        }
+    }
+
+    pub fn parse(size: &str) -> Result<Self, String> {
+        match size {
+            "thumbnail" => Ok(PhotoSize::Thumbnail),
+            "medium" => Ok(PhotoSize::Medium),
+            "original" => Ok(PhotoSize::Original),
+            other => Err(format!("Unknown photo size {}", other)),
+        }
+    }
+
+    /// How Foursquare names the size in photo URLs.
+    fn foursquare_size(&self) -> &'static str {
+        match self {
+            PhotoSize::Thumbnail => "200x200",
+            PhotoSize::Medium => "cap600",
+            PhotoSize::Original => "original",
+        }
+    }
+}
+
+/// Proxy URLs of a photo at each size, relative to the API.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PhotoVariants {
+    pub thumbnail: String,
+    pub medium: String,
+    pub original: String,
+}
+
+impl Photo {
+    /// The photo on Foursquare's CDN.
+    pub fn url(&self, size: PhotoSize) -> String {
+        format!("{}{}{}", self.prefix, size.foursquare_size(), self.suffix)
+    }
+
+    /// Identifies the photo in proxy URLs, signed so that clients can't make
+    /// up tokens for photos the API never listed.
+    pub fn token(&self) -> String {
+        let payload =
+            general_purpose::URL_SAFE_NO_PAD.encode(format!("{}\n{}", self.prefix, self.suffix));
+        let signature =
+            general_purpose::URL_SAFE_NO_PAD.encode(token_mac(&payload).finalize().into_bytes());
+        format!("{}.{}", payload, signature)
+    }
+
+    pub fn from_token(token: &str) -> Result<Self, String> {
+        let (payload, signature) = match token.split_once('.') {
+            Some(parts) => parts,
+            None => return Err("Photo not valid".to_string()),
+        };
+        let signed = general_purpose::URL_SAFE_NO_PAD
+            .decode(signature)
+            .is_ok_and(|signature| token_mac(payload).verify_slice(&signature).is_ok());
+        if !signed {
+            return Err("Photo not valid".to_string());
+        }
+
+        let decoded = general_purpose::URL_SAFE_NO_PAD
+            .decode(payload)
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok());
+        match decoded.as_deref().and_then(|d| d.split_once('\n')) {
+            Some((prefix, suffix)) => Ok(Photo {
+                prefix: prefix.to_string(),
+                suffix: suffix.to_string(),
+            }),
+            None => Err("Photo not valid".to_string()),
+        }
+    }
+
+    pub fn variants(&self) -> PhotoVariants {
+        let token = self.token();
+        let url = |size: PhotoSize| format!("/photos/{}/{}", size.as_str(), token);
+        PhotoVariants {
+            thumbnail: url(PhotoSize::Thumbnail),
+            medium: url(PhotoSize::Medium),
+            original: url(PhotoSize::Original),
+        }
+    }
+
+    /// Guessed from the file extension, which is what Foursquare goes by.
+    pub fn content_type(&self) -> &'static str {
+        let extension = self.suffix.rsplit_once('.').map(|(_, e)| e.to_lowercase());
+        match extension.as_deref() {
+            Some("png") => "image/png",
+            Some("gif") => "image/gif",
+            Some("webp") => "image/webp",
+            _ => "image/jpeg",
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PhotoResponse<'a> {
+    #[serde(flatten)]
+    photo: &'a Photo,
+    variants: PhotoVariants,
+    attribution: &'static str,
+}
+
+/// Writes photos for clients, with the proxy URLs of each size. Stored photos
+/// are only their prefix and suffix.
+pub fn serialize_with_variants<S: Serializer>(
+    photos: &Option<Vec<Photo>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    photos
+        .as_ref()
+        .map(|photos| {
+            photos
+                .iter()
+                .map(|photo| PhotoResponse {
+                    photo,
+                    variants: photo.variants(),
+                    attribution: PHOTO_ATTRIBUTION,
+                })
+                .collect::<Vec<_>>()
+        })
+        .serialize(serializer)
+}
+
+/// Fetches photos from Foursquare's CDN for clients, keeping a copy of each
+/// on disk so it is only downloaded once. When the copies outgrow
+/// `max_cache_bytes`, the least recently served are deleted.
+#[derive(Clone)]
+pub struct PhotoProxy {
+    cache_dir: PathBuf,
+    origins: Vec<String>,
+    client: reqwest::Client,
+    max_cache_bytes: u64,
+    /// The size of the cache, counted from the directory the first time a
+    /// photo is cached.
+    cache_bytes: Arc<Mutex<Option<u64>>>,
+}
+
+impl PhotoProxy {
+    pub fn new(cache_dir: impl Into<PathBuf>) -> Self {
+        PhotoProxy {
+            cache_dir: cache_dir.into(),
+            origins: FOURSQUARE_PHOTO_ORIGINS
+                .iter()
+                .map(|o| o.to_string())
+                .collect(),
+            client: reqwest::Client::new(),
+            max_cache_bytes: DEFAULT_PHOTO_CACHE_BYTES,
+            cache_bytes: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub fn with_max_cache_bytes(mut self, max_cache_bytes: u64) -> Self {
+        self.max_cache_bytes = max_cache_bytes;
+        self
+    }
+
+    /// Replaces the origins photos may be fetched from.
+    pub fn with_origins(mut self, origins: Vec<String>) -> Self {
+        self.origins = origins;
+        self
+    }
+
+    /// Whether the photo is served from one of the allowed origins, so the
+    /// proxy can't be used to fetch anything else.
+    pub fn allows(&self, photo: &Photo) -> bool {
+        self.origins
+            .iter()
+            .any(|origin| photo.prefix.starts_with(origin.as_str()))
+    }
+
+    fn cache_path(&self, url: &str) -> PathBuf {
+        let digest = Sha256::digest(url.as_bytes());
+        let name: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+        self.cache_dir.join(name)
+    }
+
+    pub async fn get(&self, photo: &Photo, size: PhotoSize) -> Result<Vec<u8>, String> {
+        if !self.allows(photo) {
+            return Err("Photo not allowed".to_string());
+        }
+        let url = photo.url(size);
+        let path = self.cache_path(&url);
+        if let Ok(bytes) = tokio::fs::read(&path).await {
+            touch(&path).await;
+            return Ok(bytes);
+        }
+
+        let bytes = match self.client.get(&url).send().await {
+            Ok(res) => {
+                if res.status() != StatusCode::OK {
+                    eprintln!("Status code: {}, when getting photo {}", res.status(), url);
+                    return Err("Error getting photo".to_string());
+                }
+                match res.bytes().await {
+                    Ok(b) => b.to_vec(),
+                    Err(_) => return Err("Error getting photo".to_string()),
+                }
+            }
+            Err(e) => {
+                eprintln!("error getting photo {}: {}", url, e);
+                return Err("Error getting photo".to_string());
+            }
+        };
+
+        // Written under a temporary name first so a half written file is
+        // never served. A photo that can't be cached is still returned.
+        let partial = path.with_extension(uuid::Uuid::new_v4().to_string());
+        let cached = async {
+            tokio::fs::create_dir_all(&self.cache_dir).await?;
+            tokio::fs::write(&partial, &bytes).await?;
+            tokio::fs::rename(&partial, &path).await
+        };
+        match cached.await {
+            Ok(_) => self.count_cached(bytes.len() as u64).await,
+            Err(e) => {
+                eprintln!("Error caching photo {}: {}", url, e);
+                let _ = tokio::fs::remove_file(&partial).await;
+            }
+        }
+        Ok(bytes)
+    }
+
+    /// Adds a newly cached photo to the size of the cache, evicting photos if
+    /// that takes it over the limit.
+    async fn count_cached(&self, bytes: u64) {
+        let mut cache_bytes = self.cache_bytes.lock().await;
+        let size = match *cache_bytes {
+            Some(size) => size + bytes,
+            // The directory already includes the new photo.
+            None => self.cached_photos().await.iter().map(|p| p.1).sum(),
+        };
+        *cache_bytes = Some(if size > self.max_cache_bytes {
+            self.evict().await
+        } else {
+            size
+        });
+    }
+
+    /// Deletes the least recently served photos until the cache is down to
+    /// three quarters of its limit, so it isn't evicting on every new photo.
+    /// Returns the size of what's left.
+    async fn evict(&self) -> u64 {
+        let mut photos = self.cached_photos().await;
+        photos.sort_by_key(|(_, _, served_at)| *served_at);
+        let mut size: u64 = photos.iter().map(|p| p.1).sum();
+        for (path, bytes, _) in photos {
+            if size <= self.max_cache_bytes - self.max_cache_bytes / 4 {
+                break;
+            }
+            match tokio::fs::remove_file(&path).await {
+                Ok(_) => size -= bytes,
+                Err(e) => eprintln!("Error evicting photo {}: {}", path.display(), e),
+            }
+        }
+        size
+    }
+
+    /// The path, size and last time served of every photo in the cache.
+    /// Photos still being written are left out.
+    async fn cached_photos(&self) -> Vec<(PathBuf, u64, SystemTime)> {
+        let mut photos = Vec::new();
+        let mut entries = match tokio::fs::read_dir(&self.cache_dir).await {
+            Ok(entries) => entries,
+            Err(_) => return photos,
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            if path.extension().is_some() {
+                continue;
+            }
+            if let Ok(metadata) = entry.metadata().await {
+                let served_at = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                photos.push((path, metadata.len(), served_at));
+            }
+        }
+        photos
+    }
+}
+
+/// Marks a cached photo as just served, so it's the last to be evicted.
+async fn touch(path: &Path) {
+    let path = path.to_path_buf();
+    let touched = tokio::task::spawn_blocking(move || {
+        std::fs::File::options()
+            .append(true)
+            .open(path)?
+            .set_modified(SystemTime::now())
+    })
+    .await;
+    if let Ok(Err(e)) = touched {
+        eprintln!("Error marking cached photo as served: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn photo() -> Photo {
+        Photo {
+            prefix: "https://fastly.4sqi.net/img/general/".to_string(),
+            suffix: "/123_abc.png".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_variants_round_trip_through_tokens() {
+        let photo = photo();
+        assert_eq!(
+            photo.url(PhotoSize::Thumbnail),
+            "https://fastly.4sqi.net/img/general/200x200/123_abc.png"
+        );
+        assert_eq!(photo.content_type(), "image/png");
+
+        let variants = photo.variants();
+        let (size, token) = variants
+            .medium
+            .strip_prefix("/photos/")
+            .unwrap()
+            .split_once('/')
+            .unwrap();
+        assert_eq!(PhotoSize::parse(size).unwrap(), PhotoSize::Medium);
+        assert_eq!(Photo::from_token(token).unwrap(), photo);
+        assert!(Photo::from_token("not a token").is_err());
+
+        // Tokens can't be made up or pointed at another photo.
+        let (_, signature) = token.split_once('.').unwrap();
+        let forged = general_purpose::URL_SAFE_NO_PAD.encode("https://example.com/\n/1.jpg");
+        assert!(Photo::from_token(&forged).is_err());
+        assert!(Photo::from_token(&format!("{}.{}", forged, signature)).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_proxy_serves_cached_photos_from_allowed_origins() {
+        let cache_dir = std::env::temp_dir().join(format!("photos-{}", uuid::Uuid::new_v4()));
+        let proxy = PhotoProxy::new(&cache_dir);
+        let photo = photo();
+        std::fs::create_dir_all(&cache_dir).unwrap();
+        std::fs::write(proxy.cache_path(&photo.url(PhotoSize::Original)), b"png").unwrap();
+
+        assert_eq!(
+            proxy.get(&photo, PhotoSize::Original).await.unwrap(),
+            b"png".to_vec()
+        );
+
+        let elsewhere = Photo {
+            prefix: "https://example.com/".to_string(),
+            ..photo
+        };
+        assert!(!proxy.allows(&elsewhere));
+        assert_eq!(
+            proxy.get(&elsewhere, PhotoSize::Original).await,
+            Err("Photo not allowed".to_string())
+        );
+        std::fs::remove_dir_all(cache_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_proxy_evicts_the_least_recently_served_photos() {
+        let cache_dir = std::env::temp_dir().join(format!("photos-{}", uuid::Uuid::new_v4()));
+        let proxy = PhotoProxy::new(&cache_dir).with_max_cache_bytes(10);
+        std::fs::create_dir_all(&cache_dir).unwrap();
+        let photo = photo();
+        let cached = |size: PhotoSize| proxy.cache_path(&photo.url(size));
+        std::fs::write(cached(PhotoSize::Thumbnail), b"1234").unwrap();
+        std::fs::write(cached(PhotoSize::Medium), b"1234").unwrap();
+        let hour_ago = SystemTime::now() - std::time::Duration::from_secs(3600);
+        for size in [PhotoSize::Thumbnail, PhotoSize::Medium] {
+            std::fs::File::options()
+                .append(true)
+                .open(cached(size))
+                .unwrap()
+                .set_modified(hour_ago)
+                .unwrap();
+        }
+
+        // Serving the thumbnail makes the medium photo the older one.
+        proxy.get(&photo, PhotoSize::Thumbnail).await.unwrap();
+        std::fs::write(cached(PhotoSize::Original), b"1234").unwrap();
+        proxy.count_cached(4).await;
+
+        assert!(cached(PhotoSize::Thumbnail).exists());
+        assert!(!cached(PhotoSize::Medium).exists());
+        assert!(cached(PhotoSize::Original).exists());
+        assert_eq!(*proxy.cache_bytes.lock().await, Some(8));
+        std::fs::remove_dir_all(cache_dir).unwrap();
+    }
+}
@@ -1,21 +1,193 @@
 use std::sync::Arc;
 
 use axum::async_trait;
-use tokio::sync::Mutex;
+use serde::{Deserialize, Serialize};
 
-use crate::geo::Coordinates;
+use crate::{
+    geo::{BoundingBox, Coordinates},
+    repository::places::{DynPlacesRepo, ReadPlaceOptions},
+};
 
-use super::Place;
+use super::{cache::CacheStats, Place};
 
-pub type DynPlacesSearch = Arc<Mutex<dyn Search>>;
+/// Shared without a lock: searches and background jobs call providers
+/// concurrently, so implementations keep any state of their own behind locks
+/// they hold briefly.
+pub type DynPlacesSearch = Arc<dyn Search>;
+
+pub const DEFAULT_SEARCH_RADIUS_METERS: f64 = 50_000.0;
+pub const MAX_SEARCH_RADIUS_METERS: f64 = 100_000.0;
+pub const MAX_SEARCH_LIMIT: u8 = 10;
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaceCategory {
+    Food,
+    Restaurant,
+    Coffee,
+    Cafe,
+    Bar,
+    Bakery,
+    Dessert,
+    FastFood,
+}
+
+impl PlaceCategory {
+    /// The Mapbox `poi_category` id for this category.
+    pub fn mapbox_id(&self) -> &'static str {
+        match self {
+            PlaceCategory::Food => "food",
+            PlaceCategory::Restaurant => "restaurant",
+            PlaceCategory::Coffee => "coffee",
+            PlaceCategory::Cafe => "cafe",
+            PlaceCategory::Bar => "bar",
+            PlaceCategory::Bakery => "bakery",
+            PlaceCategory::Dessert => "dessert_shop",
+            PlaceCategory::FastFood => "fast_food",
+        }
+    }
+
+    /// The Foursquare Places category id for this category.
+    pub fn foursquare_id(&self) -> &'static str {
+        match self {
+            PlaceCategory::Food => "13000",
+            PlaceCategory::Restaurant => "13065",
+            PlaceCategory::Coffee => "13035",
+            PlaceCategory::Cafe => "13034",
+            PlaceCategory::Bar => "13003",
+            PlaceCategory::Bakery => "13002",
+            PlaceCategory::Dessert => "13040",
+            PlaceCategory::FastFood => "13145",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SearchQuery {
+    pub coordinates: Coordinates,
+    pub search_string: String,
+    pub session_token: String,
+    pub categories: Vec<PlaceCategory>,
+    pub radius_meters: f64,
+    pub limit: Option<u8>,
+    pub language: String,
+}
+
+impl SearchQuery {
+    /// A search for food within `DEFAULT_SEARCH_RADIUS_METERS`, in English.
+    pub fn new(coordinates: Coordinates, search_string: &str, session_token: &str) -> Self {
+        SearchQuery {
+            coordinates,
+            search_string: search_string.to_string(),
+            session_token: session_token.to_string(),
+            categories: vec![PlaceCategory::Food],
+            radius_meters: DEFAULT_SEARCH_RADIUS_METERS,
+            limit: None,
+            language: DEFAULT_LANGUAGE.to_string(),
+        }
+    }
+
+    pub fn bounding_box(&self) -> BoundingBox {
+        self.coordinates.bounding_box(self.radius_meters)
+    }
+}
 
 #[async_trait]
 pub trait Search: Send + Sync + 'static {
-    async fn search_for_place(
-        &self,
-        coordinates: Coordinates,
-        search_string: String,
-    ) -> Result<Vec<Place>, String>;
+    async fn search_for_place(&self, query: SearchQuery) -> Result<Vec<Place>, String>;
 
     async fn get_photos(&self, place_id: u64) -> Result<Place, String>;
+
+    /// Fetches and stores the hours, price, phone and links of a stored
+    /// place, for providers that know more than the search results carry.
+    async fn get_details(&self, _place_id: u64) -> Result<Place, String> {
+        Err("Place details not available".to_string())
+    }
+
+    /// Looks up and stores the coordinates of a stored place that was found
+    /// without them. `session_token` is the search session the place was
+    /// picked from, for providers that bill by session.
+    async fn get_coordinates(&self, _place_id: u64, _session_token: &str) -> Result<Place, String> {
+        Err("Place coordinates not available".to_string())
+    }
+
+    /// Hit and miss counts when searches go through a `CachedSearch`.
+    async fn cache_stats(&self) -> Option<CacheStats> {
+        None
+    }
+}
+
+/// Stores a place found by a provider. A place we already know is returned as
+/// stored, with any coordinates, categories or Mapbox id it was missing
+/// filled in.
+pub async fn store_search_result(
+    places_repo: &DynPlacesRepo,
+    place: &Place,
+) -> Result<Place, String> {
+    let mut places_repo = places_repo.lock().await;
+    let stored = places_repo.create(place).await?;
+
+    let missing_coordinates = stored.coordinates.is_none() && place.coordinates.is_some();
+    let missing_categories = stored.categories.is_none() && place.categories.is_some();
+    let missing_mapbox_id = stored.mapbox_id.is_none() && place.mapbox_id.is_some();
+    if !missing_coordinates && !missing_categories && !missing_mapbox_id {
+        return Ok(stored);
+    }
+    places_repo
+        .update(Place {
+            coordinates: stored.coordinates.or(place.coordinates),
+            categories: stored.categories.clone().or(place.categories.clone()),
+            mapbox_id: stored.mapbox_id.clone().or(place.mapbox_id.clone()),
+            ..stored
+        })
+        .await
+}
+
+/// Reads a stored place, for providers that have nothing to add to it.
+pub async fn read_place(places_repo: &DynPlacesRepo, place_id: u64) -> Result<Place, String> {
+    match places_repo
+        .lock()
+        .await
+        .read(ReadPlaceOptions {
+            id: Some(place_id),
+            name: None,
+            address: None,
+            postcode: None,
+        })
+        .await
+    {
+        Ok(places) => match places.into_iter().next() {
+            Some(place) => Ok(place),
+            None => Err("Error getting place".to_string()),
+        },
+        Err(_) => Err("Error getting place".to_string()),
+    }
+}
+
+/// Changes a stored place as read while the repository is held, so fetched
+/// fields are saved without writing over what was stored in the meantime.
+pub async fn update_place(
+    places_repo: &DynPlacesRepo,
+    place_id: u64,
+    change: impl FnOnce(&mut Place),
+) -> Result<Place, String> {
+    let mut places_repo = places_repo.lock().await;
+    let mut place = match places_repo
+        .read(ReadPlaceOptions {
+            id: Some(place_id),
+            name: None,
+            address: None,
+            postcode: None,
+        })
+        .await
+    {
+        Ok(places) => match places.into_iter().next() {
+            Some(place) => place,
+            None => return Err("Error getting place".to_string()),
+        },
+        Err(_) => return Err("Error getting place".to_string()),
+    };
+    change(&mut place);
+    places_repo.update(place).await
 }
@@ -0,0 +1,184 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, Duration, Utc};
+use uuid::Uuid;
+
+/// Mapbox bills Search Box usage per session, which lasts at most 60 minutes
+/// from its first `/suggest` call and covers at most 50 of them.
+pub const SESSION_WINDOW_MINUTES: i64 = 60;
+pub const MAX_SUGGESTIONS_PER_SESSION: u32 = 50;
+
+#[derive(Clone, Debug)]
+struct SearchSession {
+    phone_number: u64,
+    started_at: DateTime<Utc>,
+    suggestions: u32,
+}
+
+/// Hands out Mapbox session tokens per user. A client passes the id it was
+/// given back on every keystroke; an unknown, foreign, expired or used up id
+/// starts a new session. Retrieving the place the user picked ends the
+/// session, so the next keystroke starts a new one.
+#[derive(Clone)]
+pub struct SearchSessions {
+    sessions: Arc<Mutex<HashMap<String, SearchSession>>>,
+    window: Duration,
+}
+
+impl Default for SearchSessions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SearchSessions {
+    pub fn new() -> Self {
+        Self::with_window(Duration::minutes(SESSION_WINDOW_MINUTES))
+    }
+
+    pub fn with_window(window: Duration) -> Self {
+        SearchSessions {
+            sessions: Arc::new(Mutex::new(HashMap::new())),
+            window,
+        }
+    }
+
+    /// Returns the session token to use for the next `/suggest` call and
+    /// counts that call against it.
+    pub fn resolve(&self, phone_number: u64, session_id: Option<&str>) -> String {
+        let now = Utc::now();
+        let mut sessions = self.sessions.lock().unwrap();
+
+        if let Some(id) = session_id {
+            if let Some(session) = sessions.get_mut(id) {
+                if session.phone_number == phone_number
+                    && now - session.started_at < self.window
+                    && session.suggestions < MAX_SUGGESTIONS_PER_SESSION
+                {
+                    session.suggestions += 1;
+                    return id.to_string();
+                }
+            }
+        }
+
+        let window = self.window;
+        sessions.retain(|_, s| now - s.started_at < window);
+
+        let id = Uuid::new_v4().to_string();
+        sessions.insert(
+            id.clone(),
+            SearchSession {
+                phone_number,
+                started_at: now,
+                suggestions: 1,
+            },
+        );
+        id
+    }
+
+    /// The session token to retrieve a picked suggestion with, if `session_id`
+    /// is an unexpired session of this user.
+    pub fn retrieve_token(&self, phone_number: u64, session_id: &str) -> Option<String> {
+        let now = Utc::now();
+        let sessions = self.sessions.lock().unwrap();
+        let session = sessions.get(session_id)?;
+        if session.phone_number != phone_number || now - session.started_at >= self.window {
+            return None;
+        }
+        Some(session_id.to_string())
+    }
+
+    /// Retires a session once a suggestion has been retrieved with it.
+    pub fn end(&self, session_id: &str) {
+        self.sessions.lock().unwrap().remove(session_id);
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_reuses_session_for_same_user() {
+        let sessions = SearchSessions::new();
+
+        let first = sessions.resolve(2028098681, None);
+        let second = sessions.resolve(2028098681, Some(&first));
+
+        assert_eq!(first, second);
+        assert!(Uuid::parse_str(&first).is_ok());
+    }
+
+    #[test]
+    fn test_resolve_starts_new_session_for_other_user_or_unknown_id() {
+        let sessions = SearchSessions::new();
+
+        let first = sessions.resolve(2028098681, None);
+
+        assert_ne!(sessions.resolve(2028098682, Some(&first)), first);
+        assert_ne!(sessions.resolve(2028098681, Some("not-a-session")), first);
+        assert_ne!(sessions.resolve(2028098681, None), first);
+    }
+
+    #[test]
+    fn test_resolve_expires_sessions() {
+        let sessions = SearchSessions::with_window(Duration::zero());
+
+        let first = sessions.resolve(2028098681, None);
+        let second = sessions.resolve(2028098681, Some(&first));
+
+        assert_ne!(first, second);
+        assert_eq!(sessions.len(), 1);
+    }
+
+    #[test]
+    fn test_resolve_limits_suggestions_per_session() {
+        let sessions = SearchSessions::new();
+
+        let first = sessions.resolve(2028098681, None);
+        for _ in 1..MAX_SUGGESTIONS_PER_SESSION {
+            assert_eq!(sessions.resolve(2028098681, Some(&first)), first);
+        }
+
+        assert_ne!(sessions.resolve(2028098681, Some(&first)), first);
+    }
+
+    #[test]
+    fn test_retrieve_token_only_for_own_live_sessions() {
+        let sessions = SearchSessions::new();
+        let first = sessions.resolve(2028098681, None);
+
+        assert_eq!(
+            sessions.retrieve_token(2028098681, &first),
+            Some(first.clone())
+        );
+        assert_eq!(sessions.retrieve_token(2028098682, &first), None);
+        assert_eq!(sessions.retrieve_token(2028098681, "not-a-session"), None);
+
+        let expired = SearchSessions::with_window(Duration::zero());
+        let first = expired.resolve(2028098681, None);
+        assert_eq!(expired.retrieve_token(2028098681, &first), None);
+    }
+
+    #[test]
+    fn test_end_starts_a_new_session_on_next_suggest() {
+        let sessions = SearchSessions::new();
+        let first = sessions.resolve(2028098681, None);
+
+        sessions.end(&first);
+
+        assert_eq!(sessions.retrieve_token(2028098681, &first), None);
+        assert_ne!(sessions.resolve(2028098681, Some(&first)), first);
+    }
+}
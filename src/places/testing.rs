@@ -0,0 +1,106 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+
+use crate::geo::Coordinates;
+
+use super::{
+    search::{Search, SearchQuery},
+    Address, Place, PlaceDetails,
+};
+
+/// Where test searches are made from.
+pub fn salt_lake_city() -> Coordinates {
+    Coordinates {
+        latitude: 40.7608,
+        longitude: -111.891,
+    }
+}
+
+/// A place with only a name and an address. Tests set whatever else they
+/// need with struct update syntax.
+pub fn place(id: u64, name: &str) -> Place {
+    Place {
+        id,
+        name: name.to_string(),
+        address: Address {
+            address: format!("1 {} Street", name),
+            ..Address::default()
+        },
+        ..Place::default()
+    }
+}
+
+/// Details fetched at `updated_at` that say nothing about the place.
+pub fn details(updated_at: DateTime<Utc>) -> PlaceDetails {
+    PlaceDetails::unknown(updated_at)
+}
+
+/// Answers every search with the same result and finds photos for the places
+/// in it, counting the searches it gets.
+pub struct FakeSearch {
+    result: Result<Vec<Place>, String>,
+    delay: Duration,
+    photo_failures: Mutex<HashMap<u64, u32>>,
+    calls: Arc<AtomicUsize>,
+}
+
+impl FakeSearch {
+    pub fn new(result: Result<Vec<Place>, String>) -> Self {
+        FakeSearch {
+            result,
+            delay: Duration::ZERO,
+            photo_failures: Mutex::new(HashMap::new()),
+            calls: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Fails to find photos for each place the given number of times before
+    /// finding them.
+    pub fn with_photo_failures(mut self, failures: &[(u64, u32)]) -> Self {
+        self.photo_failures = Mutex::new(failures.iter().copied().collect());
+        self
+    }
+
+    /// The number of searches made, which can still be read once the search
+    /// has been moved.
+    pub fn calls(&self) -> Arc<AtomicUsize> {
+        self.calls.clone()
+    }
+}
+
+#[async_trait]
+impl Search for FakeSearch {
+    async fn search_for_place(&self, _query: SearchQuery) -> Result<Vec<Place>, String> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        tokio::time::sleep(self.delay).await;
+        self.result.clone()
+    }
+
+    async fn get_photos(&self, place_id: u64) -> Result<Place, String> {
+        if let Some(remaining) = self.photo_failures.lock().unwrap().get_mut(&place_id) {
+            if *remaining > 0 {
+                *remaining -= 1;
+                return Err("Error getting pictures".to_string());
+            }
+        }
+        self.result
+            .clone()?
+            .into_iter()
+            .find(|p| p.id == place_id)
+            .ok_or("Error getting place".to_string())
+    }
+}
@@ -0,0 +1,221 @@
+use std::io::Cursor;
+
+use image::{
+    io::{Limits, Reader},
+    DynamicImage, ImageFormat, ImageOutputFormat,
+};
+
+/// The largest photo a user can upload, in bytes.
+pub const MAX_PHOTO_BYTES: usize = 8 * 1024 * 1024;
+
+/// The most photos a rating can have.
+pub const MAX_RATING_PHOTOS: usize = 10;
+
+/// The longest side of a thumbnail, in pixels.
+pub const THUMBNAIL_SIZE: u32 = 400;
+
+/// The longest side of a photo we'll decode, in pixels, so a small file
+/// can't claim to be an enormous image. Big enough for a 24 megapixel
+/// camera, whose photos still take up to 144 MB once decoded.
+const MAX_PHOTO_DIMENSION: u32 = 6_000;
+
+const JPEG_QUALITY: u8 = 85;
+
+/// The kinds of image users can upload.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PhotoFormat {
+    Jpeg,
+    Png,
+}
+
+impl PhotoFormat {
+    /// Sniffed from the file's leading bytes, whatever the client said it was.
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        match image::guess_format(bytes) {
+            Ok(ImageFormat::Jpeg) => Some(PhotoFormat::Jpeg),
+            Ok(ImageFormat::Png) => Some(PhotoFormat::Png),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            PhotoFormat::Jpeg => "jpg",
+            PhotoFormat::Png => "png",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            PhotoFormat::Jpeg => "image/jpeg",
+            PhotoFormat::Png => "image/png",
+        }
+    }
+
+    fn image_format(&self) -> ImageFormat {
+        match self {
+            PhotoFormat::Jpeg => ImageFormat::Jpeg,
+            PhotoFormat::Png => ImageFormat::Png,
+        }
+    }
+}
+
+/// An uploaded photo ready to store: turned upright and re-encoded without
+/// any of the original's metadata, GPS coordinates included.
+pub struct ProcessedPhoto {
+    pub format: PhotoFormat,
+    pub image: Vec<u8>,
+    pub thumbnail: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// The EXIF orientation of the photo, 1 (upright) when it has none.
+fn orientation(bytes: &[u8]) -> u32 {
+    exif::Reader::new()
+        .read_from_container(&mut Cursor::new(bytes))
+        .ok()
+        .and_then(|exif| {
+            exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)
+                .and_then(|field| field.value.get_uint(0))
+        })
+        .unwrap_or(1)
+}
+
+/// Turns the image the way its EXIF orientation says it should be shown.
+fn apply_orientation(image: DynamicImage, orientation: u32) -> DynamicImage {
+    match orientation {
+        2 => image.fliph(),
+        3 => image.rotate180(),
+        4 => image.flipv(),
+        5 => image.rotate90().fliph(),
+        6 => image.rotate90(),
+        7 => image.rotate270().fliph(),
+        8 => image.rotate270(),
+        _ => image,
+    }
+}
+
+fn encode(image: &DynamicImage, format: PhotoFormat) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    let written = match format {
+        PhotoFormat::Jpeg => DynamicImage::ImageRgb8(image.to_rgb8()).write_to(
+            &mut Cursor::new(&mut bytes),
+            ImageOutputFormat::Jpeg(JPEG_QUALITY),
+        ),
+        PhotoFormat::Png => image.write_to(&mut Cursor::new(&mut bytes), ImageOutputFormat::Png),
+    };
+    match written {
+        Ok(_) => Ok(bytes),
+        Err(e) => {
+            eprintln!("error encoding photo: {}", e);
+            Err("Photo not processed".to_string())
+        }
+    }
+}
+
+/// Decodes the photo, applies its orientation and re-encodes it in the same
+/// format, along with a thumbnail.
+pub fn process_photo(bytes: &[u8], format: PhotoFormat) -> Result<ProcessedPhoto, String> {
+    let mut reader = Reader::with_format(Cursor::new(bytes), format.image_format());
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_PHOTO_DIMENSION);
+    limits.max_image_height = Some(MAX_PHOTO_DIMENSION);
+    reader.limits(limits);
+    let image = match reader.decode() {
+        Ok(i) => i,
+        Err(e) => {
+            eprintln!("error decoding photo: {}", e);
+            return Err("Photo not valid".to_string());
+        }
+    };
+
+    let image = apply_orientation(image, orientation(bytes));
+    let thumbnail = image.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE);
+    Ok(ProcessedPhoto {
+        format,
+        image: encode(&image, format)?,
+        thumbnail: encode(&thumbnail, format)?,
+        width: image.width(),
+        height: image.height(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{GenericImageView, RgbImage};
+
+    use super::*;
+
+    /// An APP1 segment with orientation 6 (rotated a quarter turn clockwise)
+    /// and a GPS latitude.
+    fn exif_segment() -> Vec<u8> {
+        let mut tiff = b"MM\x00\x2a\x00\x00\x00\x08".to_vec();
+        // IFD0: the orientation, then a pointer to the GPS IFD at offset 38.
+        tiff.extend([0x00, 0x02]);
+        tiff.extend([
+            0x01, 0x12, 0x00, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00, 0x06, 0x00, 0x00,
+        ]);
+        tiff.extend([
+            0x88, 0x25, 0x00, 0x04, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x26,
+        ]);
+        tiff.extend([0x00, 0x00, 0x00, 0x00]);
+        // The GPS IFD, with GPSLatitudeRef "N".
+        tiff.extend([0x00, 0x01]);
+        tiff.extend([
+            0x00, 0x01, 0x00, 0x02, 0x00, 0x00, 0x00, 0x02, b'N', 0x00, 0x00, 0x00,
+        ]);
+        tiff.extend([0x00, 0x00, 0x00, 0x00]);
+
+        let length = (2 + 6 + tiff.len()) as u16;
+        let mut segment = vec![0xff, 0xe1];
+        segment.extend(length.to_be_bytes());
+        segment.extend(b"Exif\x00\x00");
+        segment.extend(tiff);
+        segment
+    }
+
+    /// A 40 by 20 JPEG taken with the camera turned, as phones save them.
+    fn rotated_jpeg() -> Vec<u8> {
+        let mut jpeg = Vec::new();
+        DynamicImage::ImageRgb8(RgbImage::new(40, 20))
+            .write_to(&mut Cursor::new(&mut jpeg), ImageOutputFormat::Jpeg(90))
+            .unwrap();
+        // Straight after the start of image marker.
+        jpeg.splice(2..2, exif_segment());
+        jpeg
+    }
+
+    #[test]
+    fn test_process_photo_strips_metadata() {
+        let jpeg = rotated_jpeg();
+        assert_eq!(orientation(&jpeg), 6);
+        let exif = exif::Reader::new()
+            .read_from_container(&mut Cursor::new(&jpeg))
+            .unwrap();
+        assert!(exif
+            .get_field(exif::Tag::GPSLatitudeRef, exif::In::PRIMARY)
+            .is_some());
+
+        assert_eq!(PhotoFormat::detect(&jpeg), Some(PhotoFormat::Jpeg));
+        let photo = process_photo(&jpeg, PhotoFormat::Jpeg).unwrap();
+        assert_eq!((photo.width, photo.height), (20, 40));
+        assert!(exif::Reader::new()
+            .read_from_container(&mut Cursor::new(&photo.image))
+            .is_err());
+        assert!(!photo.image.windows(4).any(|w| w == b"Exif"));
+
+        let image = image::load_from_memory(&photo.image).unwrap();
+        assert_eq!(image.dimensions(), (20, 40));
+        let thumbnail = image::load_from_memory(&photo.thumbnail).unwrap();
+        assert!(thumbnail.width() <= THUMBNAIL_SIZE && thumbnail.height() <= THUMBNAIL_SIZE);
+    }
+
+    #[test]
+    fn test_process_photo_rejects_other_files() {
+        assert_eq!(PhotoFormat::detect(b"GIF89a\x01\x00\x01\x00"), None);
+        assert_eq!(PhotoFormat::detect(b"not a photo"), None);
+        // Looks like a JPEG but isn't one.
+        assert!(process_photo(b"\xff\xd8\xff\xe0 truncated", PhotoFormat::Jpeg).is_err());
+    }
+}
@@ -0,0 +1,378 @@
+//! A small HTTP proxy for capturing and replaying upstream provider traffic.
+//!
+//! Point a provider client's base URL (see `MapboxSearchApi::with_base_urls`) at
+//! a `ReplayServer`. In `Record` mode every request is forwarded to the real
+//! upstream and the response is written to the fixture directory; in `Replay`
+//! mode responses are served from those files without touching the network.
+
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, Method, StatusCode, Uri},
+    response::{IntoResponse, Response},
+    Router,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::task::JoinHandle;
+
+/// Query parameters that never take part in matching a recorded response,
+/// either because they are secrets or because they change on every run.
+pub const DEFAULT_IGNORED_PARAMS: [&str; 2] = ["access_token", "session_token"];
+
+/// Request headers passed through to the upstream when recording.
+const FORWARDED_HEADERS: [&str; 3] = ["authorization", "accept", "content-type"];
+
+#[derive(Clone, Debug)]
+pub enum ReplayMode {
+    Record { upstream: String },
+    Replay,
+}
+
+impl ReplayMode {
+    /// Reads the mode from `PROVIDER_REPLAY_MODE`: `record` forwards to
+    /// `upstream`, anything else (or unset) replays.
+    pub fn from_env(upstream: &str) -> ReplayMode {
+        match std::env::var("PROVIDER_REPLAY_MODE") {
+            Ok(mode) if mode == "record" => ReplayMode::Record {
+                upstream: upstream.trim_end_matches('/').to_string(),
+            },
+            _ => ReplayMode::Replay,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    pub query: Vec<(String, String)>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub body: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RecordedResponse {
+    pub status: u16,
+    /// The body as JSON when the upstream sent valid JSON, otherwise the raw text
+    /// (with `raw` set), so malformed responses replay byte for byte.
+    pub body: serde_json::Value,
+    #[serde(default)]
+    pub raw: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Recording {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+impl RecordedRequest {
+    fn new(
+        method: &Method,
+        path: &str,
+        query: Option<&str>,
+        body: &[u8],
+        ignored_params: &[String],
+    ) -> Self {
+        let mut pairs: Vec<(String, String)> = match query {
+            Some(q) => form_urlencoded_pairs(q),
+            None => Vec::new(),
+        };
+        pairs.retain(|(key, _)| !ignored_params.contains(key));
+        pairs.sort();
+
+        RecordedRequest {
+            method: method.to_string(),
+            path: path.to_string(),
+            query: pairs,
+            body: String::from_utf8_lossy(body).to_string(),
+        }
+    }
+
+    /// The fixture file name, e.g. `GET_v3_places_abc_photos_1f2e3d4c5b6a7988.json`.
+    pub fn file_name(&self) -> String {
+        let mut hasher = Sha256::new();
+        for (key, value) in &self.query {
+            hasher.update(key.as_bytes());
+            hasher.update(b"=");
+            hasher.update(value.as_bytes());
+            hasher.update(b"&");
+        }
+        hasher.update(self.body.as_bytes());
+        let digest = hasher.finalize();
+        let hash: String = digest[..8].iter().map(|b| format!("{:02x}", b)).collect();
+
+        let slug: String = self
+            .path
+            .trim_matches('/')
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        format!("{}_{}_{}.json", self.method, slug, hash)
+    }
+}
+
+fn form_urlencoded_pairs(encoded: &str) -> Vec<(String, String)> {
+    match reqwest::Url::parse(&format!("http://replay/?{}", encoded)) {
+        Ok(url) => url.query_pairs().into_owned().collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+#[derive(Clone)]
+struct ReplayState {
+    mode: ReplayMode,
+    fixtures_dir: Arc<PathBuf>,
+    ignored_params: Arc<Vec<String>>,
+    client: reqwest::Client,
+}
+
+pub struct ReplayServer {
+    pub url: String,
+    handle: JoinHandle<()>,
+}
+
+impl ReplayServer {
+    /// Starts the proxy on an ephemeral local port.
+    pub async fn start(mode: ReplayMode, fixtures_dir: &Path) -> Result<ReplayServer, String> {
+        Self::start_on(
+            mode,
+            fixtures_dir,
+            SocketAddr::from(([127, 0, 0, 1], 0)),
+            &DEFAULT_IGNORED_PARAMS,
+        )
+        .await
+    }
+
+    pub async fn start_on(
+        mode: ReplayMode,
+        fixtures_dir: &Path,
+        address: SocketAddr,
+        ignored_params: &[&str],
+    ) -> Result<ReplayServer, String> {
+        if let ReplayMode::Record { .. } = mode {
+            if let Err(e) = tokio::fs::create_dir_all(fixtures_dir).await {
+                eprintln!("error creating fixture directory: {}", e);
+                return Err("Could not create fixture directory".to_string());
+            }
+        }
+
+        let state = ReplayState {
+            mode,
+            fixtures_dir: Arc::new(fixtures_dir.to_path_buf()),
+            ignored_params: Arc::new(ignored_params.iter().map(|p| p.to_string()).collect()),
+            client: reqwest::Client::new(),
+        };
+        let app = Router::new().fallback(proxy).with_state(state);
+
+        let server = match axum::Server::try_bind(&address) {
+            Ok(builder) => builder.serve(app.into_make_service()),
+            Err(e) => {
+                eprintln!("error binding replay server: {}", e);
+                return Err("Could not start replay server".to_string());
+            }
+        };
+        let url = format!("http://{}", server.local_addr());
+        let handle = tokio::spawn(async move {
+            if let Err(e) = server.await {
+                eprintln!("replay server stopped: {}", e);
+            }
+        });
+
+        Ok(ReplayServer { url, handle })
+    }
+}
+
+impl Drop for ReplayServer {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+async fn proxy(
+    State(state): State<ReplayState>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let request = RecordedRequest::new(
+        &method,
+        uri.path(),
+        uri.query(),
+        &body,
+        &state.ignored_params,
+    );
+    let fixture_path = state.fixtures_dir.join(request.file_name());
+
+    match &state.mode {
+        ReplayMode::Replay => replay(&fixture_path, &request).await,
+        ReplayMode::Record { upstream } => {
+            record(
+                &state,
+                upstream,
+                &fixture_path,
+                request,
+                method,
+                uri,
+                headers,
+                body,
+            )
+            .await
+        }
+    }
+}
+
+async fn replay(fixture_path: &Path, request: &RecordedRequest) -> Response {
+    let recording: Recording = match tokio::fs::read_to_string(fixture_path).await {
+        Ok(contents) => match serde_json::from_str(&contents) {
+            Ok(r) => r,
+            Err(e) => {
+                eprintln!("error parsing recording {}: {}", fixture_path.display(), e);
+                return (StatusCode::INTERNAL_SERVER_ERROR, "Invalid recording").into_response();
+            }
+        },
+        Err(_) => {
+            return (
+                StatusCode::NOT_FOUND,
+                format!(
+                    "no recording at {} for {} {} {:?}",
+                    fixture_path.display(),
+                    request.method,
+                    request.path,
+                    request.query
+                ),
+            )
+                .into_response()
+        }
+    };
+
+    let status = StatusCode::from_u16(recording.response.status)
+        .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    let body = match recording.response.body {
+        serde_json::Value::String(text) if recording.response.raw => text,
+        value => value.to_string(),
+    };
+    (status, [("content-type", "application/json")], body).into_response()
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn record(
+    state: &ReplayState,
+    upstream: &str,
+    fixture_path: &Path,
+    request: RecordedRequest,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let path_and_query = uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+    let mut upstream_request = state
+        .client
+        .request(method, format!("{}{}", upstream, path_and_query))
+        .body(body);
+    for name in FORWARDED_HEADERS {
+        if let Some(value) = headers.get(name) {
+            upstream_request = upstream_request.header(name, value);
+        }
+    }
+
+    let (status, text) = match upstream_request.send().await {
+        Ok(res) => {
+            let status = res.status();
+            match res.text().await {
+                Ok(t) => (status, t),
+                Err(e) => {
+                    eprintln!("error reading upstream response: {}", e);
+                    return (StatusCode::BAD_GATEWAY, "Error reading upstream").into_response();
+                }
+            }
+        }
+        Err(e) => {
+            eprintln!("error sending request upstream: {}", e);
+            return (StatusCode::BAD_GATEWAY, "Error contacting upstream").into_response();
+        }
+    };
+
+    let (body, raw) = match serde_json::from_str(&text) {
+        Ok(value) => (value, false),
+        Err(_) => (serde_json::Value::String(text.clone()), true),
+    };
+    let recording = Recording {
+        request,
+        response: RecordedResponse {
+            status: status.as_u16(),
+            body,
+            raw,
+        },
+    };
+    match serde_json::to_string_pretty(&recording) {
+        Ok(contents) => {
+            if let Err(e) = tokio::fs::write(fixture_path, contents + "\n").await {
+                eprintln!("error writing recording {}: {}", fixture_path.display(), e);
+            }
+        }
+        Err(e) => eprintln!("error serializing recording: {}", e),
+    }
+
+    (status, [("content-type", "application/json")], text).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ignored() -> Vec<String> {
+        DEFAULT_IGNORED_PARAMS
+            .iter()
+            .map(|p| p.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_recorded_request_ignores_secrets_and_param_order() {
+        let a = RecordedRequest::new(
+            &Method::GET,
+            "/search/searchbox/v1/suggest",
+            Some("q=arlo&access_token=secret&session_token=1&language=en"),
+            b"",
+            &ignored(),
+        );
+        let b = RecordedRequest::new(
+            &Method::GET,
+            "/search/searchbox/v1/suggest",
+            Some("language=en&session_token=2&q=arlo&access_token=other"),
+            b"",
+            &ignored(),
+        );
+
+        assert_eq!(a, b);
+        assert_eq!(
+            a.query,
+            vec![
+                ("language".to_string(), "en".to_string()),
+                ("q".to_string(), "arlo".to_string())
+            ]
+        );
+        assert!(a
+            .file_name()
+            .starts_with("GET_search_searchbox_v1_suggest_"));
+    }
+
+    #[test]
+    fn test_recorded_request_file_name_depends_on_query() {
+        let arlo = RecordedRequest::new(&Method::GET, "/suggest", Some("q=arlo"), b"", &ignored());
+        let iguana =
+            RecordedRequest::new(&Method::GET, "/suggest", Some("q=iguana"), b"", &ignored());
+
+        assert_ne!(arlo.file_name(), iguana.file_name());
+    }
+}
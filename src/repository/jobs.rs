@@ -0,0 +1,110 @@
+use std::sync::Arc;
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+#[derive(Clone, Copy, Deserialize, Serialize, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum JobKind {
+    /// Fetch and store the photos of a place.
+    FetchPhotos,
+    /// Fetch the place's details again, and its photos if it has none.
+    RefreshPlace,
+}
+
+impl JobKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobKind::FetchPhotos => "fetch_photos",
+            JobKind::RefreshPlace => "refresh_place",
+        }
+    }
+
+    pub fn parse(kind: &str) -> Result<Self, String> {
+        match kind {
+            "fetch_photos" => Ok(JobKind::FetchPhotos),
+            "refresh_place" => Ok(JobKind::RefreshPlace),
+            other => Err(format!("Unknown job kind {}", other)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Deserialize, Serialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum JobStatus {
+    Pending,
+    Running,
+    /// Out of attempts. Dead jobs stay around to be inspected and no longer
+    /// stop the same job from being queued again.
+    Dead,
+}
+
+impl JobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobStatus::Pending => "pending",
+            JobStatus::Running => "running",
+            JobStatus::Dead => "dead",
+        }
+    }
+
+    pub fn parse(status: &str) -> Result<Self, String> {
+        match status {
+            "pending" => Ok(JobStatus::Pending),
+            "running" => Ok(JobStatus::Running),
+            "dead" => Ok(JobStatus::Dead),
+            other => Err(format!("Unknown job status {}", other)),
+        }
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Job {
+    pub id: u64,
+    pub kind: JobKind,
+    pub place_id: u64,
+    pub status: JobStatus,
+    /// Failed attempts so far.
+    pub attempts: u32,
+    /// When the job is next due.
+    pub run_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+pub type DynJobsRepo = Arc<Mutex<dyn JobsRepository>>;
+
+#[async_trait]
+pub trait JobsRepository: Send + Sync + 'static {
+    /// Queues a job due at `now`, unless the same kind of job for the place
+    /// is already pending or running, in which case that job is returned.
+    async fn enqueue(
+        &mut self,
+        kind: JobKind,
+        place_id: u64,
+        now: DateTime<Utc>,
+    ) -> Result<Job, String>;
+    /// Marks at most `limit` pending jobs due by `now` as running, and returns
+    /// them soonest due first.
+    async fn claim(&mut self, now: DateTime<Utc>, limit: usize) -> Result<Vec<Job>, String>;
+    /// Removes a job that ran successfully.
+    async fn complete(&mut self, id: u64) -> Result<(), String>;
+    /// Records a failed attempt. The job runs again at `retry_at`, or is
+    /// moved to the dead letters when there is none.
+    async fn fail(
+        &mut self,
+        id: u64,
+        error: &str,
+        retry_at: Option<DateTime<Utc>>,
+        now: DateTime<Utc>,
+    ) -> Result<Job, String>;
+    /// Puts jobs claimed before `claimed_before` and still running back in
+    /// the queue, as the worker that claimed them has stopped.
+    async fn requeue_running(&mut self, claimed_before: DateTime<Utc>) -> Result<u64, String>;
+    /// Most recently failed first.
+    async fn dead_letters(&self) -> Result<Vec<Job>, String>;
+}
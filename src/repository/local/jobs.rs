@@ -0,0 +1,130 @@
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+
+use crate::repository::jobs::{Job, JobKind, JobStatus, JobsRepository};
+
+/// Keeps jobs in memory, so they are lost on restart.
+#[derive(Clone)]
+pub struct LocalJobsRepository {
+    jobs: Vec<Job>,
+    next_id: u64,
+}
+
+impl Default for LocalJobsRepository {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LocalJobsRepository {
+    pub fn new() -> LocalJobsRepository {
+        LocalJobsRepository {
+            jobs: Vec::new(),
+            next_id: 1,
+        }
+    }
+}
+
+#[async_trait]
+impl JobsRepository for LocalJobsRepository {
+    async fn enqueue(
+        &mut self,
+        kind: JobKind,
+        place_id: u64,
+        now: DateTime<Utc>,
+    ) -> Result<Job, String> {
+        if let Some(existing) = self
+            .jobs
+            .iter()
+            .find(|j| j.kind == kind && j.place_id == place_id && j.status != JobStatus::Dead)
+        {
+            return Ok(existing.clone());
+        }
+
+        let job = Job {
+            id: self.next_id,
+            kind,
+            place_id,
+            status: JobStatus::Pending,
+            attempts: 0,
+            run_at: now,
+            last_error: None,
+            created_at: now,
+            updated_at: now,
+        };
+        self.next_id += 1;
+        self.jobs.push(job.clone());
+        Ok(job)
+    }
+
+    async fn claim(&mut self, now: DateTime<Utc>, limit: usize) -> Result<Vec<Job>, String> {
+        let mut due: Vec<&mut Job> = self
+            .jobs
+            .iter_mut()
+            .filter(|j| j.status == JobStatus::Pending && j.run_at <= now)
+            .collect();
+        due.sort_by_key(|j| (j.run_at, j.id));
+        Ok(due
+            .into_iter()
+            .take(limit)
+            .map(|job| {
+                job.status = JobStatus::Running;
+                job.updated_at = now;
+                job.clone()
+            })
+            .collect())
+    }
+
+    async fn complete(&mut self, id: u64) -> Result<(), String> {
+        self.jobs.retain(|j| j.id != id);
+        Ok(())
+    }
+
+    async fn fail(
+        &mut self,
+        id: u64,
+        error: &str,
+        retry_at: Option<DateTime<Utc>>,
+        now: DateTime<Utc>,
+    ) -> Result<Job, String> {
+        let job = match self.jobs.iter_mut().find(|j| j.id == id) {
+            Some(job) => job,
+            None => return Err("Job not found".to_string()),
+        };
+        job.attempts += 1;
+        job.last_error = Some(error.to_string());
+        job.updated_at = now;
+        match retry_at {
+            Some(retry_at) => {
+                job.status = JobStatus::Pending;
+                job.run_at = retry_at;
+            }
+            None => job.status = JobStatus::Dead,
+        }
+        Ok(job.clone())
+    }
+
+    async fn requeue_running(&mut self, claimed_before: DateTime<Utc>) -> Result<u64, String> {
+        let mut requeued = 0;
+        for job in self
+            .jobs
+            .iter_mut()
+            .filter(|j| j.status == JobStatus::Running && j.updated_at < claimed_before)
+        {
+            job.status = JobStatus::Pending;
+            requeued += 1;
+        }
+        Ok(requeued)
+    }
+
+    async fn dead_letters(&self) -> Result<Vec<Job>, String> {
+        let mut dead: Vec<Job> = self
+            .jobs
+            .iter()
+            .filter(|j| j.status == JobStatus::Dead)
+            .cloned()
+            .collect();
+        dead.sort_by(|a, b| b.updated_at.cmp(&a.updated_at).then(b.id.cmp(&a.id)));
+        Ok(dead)
+    }
+}
@@ -1 +1,4 @@
+pub mod jobs;
+pub mod places;
+pub mod ratings;
 pub mod user;
@@ -0,0 +1,183 @@
+use std::collections::HashSet;
+
+use axum::async_trait;
+
+use crate::{
+    geo::BoundingBox,
+    places::Place,
+    repository::places::{
+        closest_places, NearbyPlace, NearbyPlaceOptions, PlacesRepository, ReadPlaceOptions,
+        SearchPlaceOptions, StalePlaceOptions,
+    },
+};
+
+#[derive(Clone)]
+pub struct LocalPlacesRepository {
+    places: Vec<Place>,
+    next_id: u64,
+}
+
+impl Default for LocalPlacesRepository {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LocalPlacesRepository {
+    pub fn new() -> LocalPlacesRepository {
+        LocalPlacesRepository {
+            places: Vec::new(),
+            next_id: 1,
+        }
+    }
+}
+
+#[async_trait]
+impl PlacesRepository for LocalPlacesRepository {
+    async fn create(&mut self, place: &Place) -> Result<Place, String> {
+        if let Some(existing) = self
+            .places
+            .iter()
+            .find(|p| p.address.address == place.address.address)
+        {
+            return Ok(existing.clone());
+        }
+
+        let mut place = place.clone();
+        place.id = self.next_id;
+        self.next_id += 1;
+        self.places.push(place.clone());
+        Ok(place)
+    }
+
+    async fn read(&self, options: ReadPlaceOptions) -> Result<Vec<Place>, String> {
+        Ok(self
+            .places
+            .iter()
+            .filter(|p| options.id.is_none_or(|id| p.id == id))
+            .filter(|p| options.name.as_ref().is_none_or(|name| &p.name == name))
+            .filter(|p| {
+                options
+                    .address
+                    .as_ref()
+                    .is_none_or(|address| &p.address.address == address)
+            })
+            .filter(|p| {
+                options
+                    .postcode
+                    .as_ref()
+                    .is_none_or(|postcode| p.address.postcode.as_ref() == Some(postcode))
+            })
+            .cloned()
+            .collect())
+    }
+
+    async fn read_many(&self, ids: &[u64]) -> Result<Vec<Place>, String> {
+        Ok(self
+            .places
+            .iter()
+            .filter(|p| ids.contains(&p.id))
+            .cloned()
+            .collect())
+    }
+
+    async fn update(&mut self, place: Place) -> Result<Place, String> {
+        self.places = self
+            .places
+            .clone()
+            .into_iter()
+            .map(|p| {
+                if p.id == place.id {
+                    return place.clone();
+                };
+                p
+            })
+            .collect();
+        Ok(place)
+    }
+
+    async fn delete(&mut self, id: u64) -> Result<Option<Place>, String> {
+        let mut place: Option<Place> = None;
+        self.places = self
+            .places
+            .clone()
+            .into_iter()
+            .filter(|p| {
+                if p.id == id {
+                    place = Some(p.clone());
+                };
+                p.id != id
+            })
+            .collect();
+        Ok(place)
+    }
+
+    async fn search(&self, options: SearchPlaceOptions) -> Result<Vec<Place>, String> {
+        let text = options.text.to_lowercase();
+        let matches =
+            |value: Option<&String>| value.is_some_and(|v| v.to_lowercase().contains(&text));
+        let localities = match (&options.bounding_box, options.include_unlocated) {
+            (Some(bbox), true) => localities_within(&self.places, bbox),
+            _ => HashSet::new(),
+        };
+        let mut places: Vec<&Place> = self
+            .places
+            .iter()
+            .filter(|p| {
+                matches(Some(&p.name))
+                    || matches(Some(&p.address.address))
+                    || matches(p.address.street.as_ref())
+                    || matches(p.address.place.as_ref())
+            })
+            .filter(|p| {
+                options.bounding_box.is_none_or(|bbox| match p.coordinates {
+                    Some(coordinates) => bbox.contains(&coordinates),
+                    None => locality(p).is_some_and(|l| localities.contains(&l)),
+                })
+            })
+            .collect();
+        if let Some(center) = options.closest_to {
+            let distance = |place: &Place| match place.coordinates {
+                Some(coordinates) => center.distance_meters(&coordinates),
+                None => f64::INFINITY,
+            };
+            places.sort_by(|a, b| distance(a).total_cmp(&distance(b)).then(a.id.cmp(&b.id)));
+        }
+        Ok(places.into_iter().take(options.limit).cloned().collect())
+    }
+
+    async fn nearby(&self, options: NearbyPlaceOptions) -> Result<Vec<NearbyPlace>, String> {
+        Ok(closest_places(self.places.clone(), &options))
+    }
+
+    async fn stale(&self, options: StalePlaceOptions) -> Result<Vec<Place>, String> {
+        let mut stale: Vec<&Place> = self
+            .places
+            .iter()
+            .filter(|p| {
+                p.details
+                    .as_ref()
+                    .is_none_or(|details| details.updated_at < options.updated_before)
+            })
+            .collect();
+        stale.sort_by_key(|p| (p.details.as_ref().map(|d| d.updated_at), p.id));
+        Ok(stale.into_iter().take(options.limit).cloned().collect())
+    }
+}
+
+fn locality(place: &Place) -> Option<(&str, &str)> {
+    Some((
+        place.address.place.as_deref()?,
+        place.address.region.as_deref()?,
+    ))
+}
+
+/// The locality and region of each place stored with coordinates inside
+/// `bbox`, where places not located yet may match a search of it.
+fn localities_within<'a>(places: &'a [Place], bbox: &BoundingBox) -> HashSet<(&'a str, &'a str)> {
+    places
+        .iter()
+        .filter(|p| p.coordinates.is_some_and(|c| bbox.contains(&c)))
+        .filter_map(locality)
+        .collect()
+}
@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+
+use axum::async_trait;
+
+use crate::repository::ratings::{
+    average_scores, PlaceScore, Rating, RatingPhoto, RatingsRepository, ReadRatingOptions,
+    ReadRatingPhotoOptions,
+};
+
+#[derive(Clone)]
+pub struct LocalRatingsRepository {
+    ratings: Vec<Rating>,
+    next_id: u64,
+    photos: Vec<RatingPhoto>,
+    next_photo_id: u64,
+}
+
+impl Default for LocalRatingsRepository {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LocalRatingsRepository {
+    pub fn new() -> LocalRatingsRepository {
+        LocalRatingsRepository {
+            ratings: Vec::new(),
+            next_id: 1,
+            photos: Vec::new(),
+            next_photo_id: 1,
+        }
+    }
+}
+
+#[async_trait]
+impl RatingsRepository for LocalRatingsRepository {
+    async fn upsert(&mut self, rating: &Rating) -> Result<Rating, String> {
+        if let Some(existing) = self
+            .ratings
+            .iter_mut()
+            .find(|r| r.place_id == rating.place_id && r.phone_number == rating.phone_number)
+        {
+            existing.score = rating.score;
+            existing.review = rating.review.clone();
+            existing.updated_at = rating.updated_at;
+            return Ok(existing.clone());
+        }
+
+        let mut rating = rating.clone();
+        rating.id = self.next_id;
+        self.next_id += 1;
+        self.ratings.push(rating.clone());
+        Ok(rating)
+    }
+
+    async fn read(&self, options: ReadRatingOptions) -> Result<Vec<Rating>, String> {
+        Ok(self
+            .ratings
+            .iter()
+            .filter(|r| options.id.is_none_or(|id| r.id == id))
+            .filter(|r| options.place_id.is_none_or(|id| r.place_id == id))
+            .filter(|r| options.phone_number.is_none_or(|n| r.phone_number == n))
+            .cloned()
+            .collect())
+    }
+
+    async fn delete(&mut self, id: u64) -> Result<Option<(Rating, Vec<RatingPhoto>)>, String> {
+        let position = match self.ratings.iter().position(|r| r.id == id) {
+            Some(i) => i,
+            None => return Ok(None),
+        };
+        let (photos, kept) = self.photos.drain(..).partition(|p| p.rating_id == id);
+        self.photos = kept;
+        Ok(Some((self.ratings.remove(position), photos)))
+    }
+
+    async fn scores(&self, place_ids: &[u64]) -> Result<HashMap<u64, PlaceScore>, String> {
+        let ratings: Vec<Rating> = self
+            .ratings
+            .iter()
+            .filter(|r| place_ids.contains(&r.place_id))
+            .cloned()
+            .collect();
+        Ok(average_scores(&ratings))
+    }
+
+    async fn add_photos(
+        &mut self,
+        photos: &[RatingPhoto],
+        max_photos: usize,
+    ) -> Result<Option<Vec<RatingPhoto>>, String> {
+        let rating_id = match photos.first() {
+            Some(p) => p.rating_id,
+            None => return Ok(Some(Vec::new())),
+        };
+        if !self.ratings.iter().any(|r| r.id == rating_id) {
+            return Err("Rating not found".to_string());
+        }
+        let count = self
+            .photos
+            .iter()
+            .filter(|p| p.rating_id == rating_id)
+            .count();
+        if count + photos.len() > max_photos {
+            return Ok(None);
+        }
+
+        let mut added = Vec::with_capacity(photos.len());
+        for photo in photos {
+            let mut photo = photo.clone();
+            photo.id = self.next_photo_id;
+            self.next_photo_id += 1;
+            self.photos.push(photo.clone());
+            added.push(photo);
+        }
+        Ok(Some(added))
+    }
+
+    async fn read_photos(
+        &self,
+        options: ReadRatingPhotoOptions,
+    ) -> Result<Vec<RatingPhoto>, String> {
+        Ok(self
+            .photos
+            .iter()
+            .filter(|p| options.id.is_none_or(|id| p.id == id))
+            .filter(|p| options.rating_id.is_none_or(|id| p.rating_id == id))
+            .filter(|p| options.place_id.is_none_or(|id| p.place_id == id))
+            .cloned()
+            .collect())
+    }
+
+    async fn delete_photo(&mut self, id: u64) -> Result<Option<RatingPhoto>, String> {
+        let position = self.photos.iter().position(|p| p.id == id);
+        Ok(position.map(|i| self.photos.remove(i)))
+    }
+}
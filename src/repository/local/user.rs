@@ -7,15 +7,28 @@ pub struct LocalUserRepository {
     users: Vec<User>,
 }
 
+impl Default for LocalUserRepository {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl LocalUserRepository {
     pub fn new() -> LocalUserRepository {
-        return LocalUserRepository { users: Vec::new() };
+        LocalUserRepository { users: Vec::new() }
     }
 }
 
 #[async_trait]
 impl UserRepository for LocalUserRepository {
     async fn create(&mut self, user: User) -> Result<User, String> {
+        if let Some(existing) = self
+            .users
+            .iter()
+            .find(|u| u.phone_number == user.phone_number)
+        {
+            return Ok(existing.clone());
+        }
         self.users.push(user.clone());
         Ok(user)
     }
@@ -1,4 +1,8 @@
+pub mod jobs;
 pub mod local;
 pub mod places;
+pub mod postgres;
+pub mod ratings;
+pub mod sqlite;
 pub mod subabase;
 pub mod user;
@@ -1,9 +1,13 @@
 use std::sync::Arc;
 
 use axum::async_trait;
+use chrono::{DateTime, Utc};
 use tokio::sync::Mutex;
 
-use crate::places::Place;
+use crate::{
+    geo::{BoundingBox, Coordinates},
+    places::Place,
+};
 
 pub struct ReadPlaceOptions {
     pub id: Option<u64>,
@@ -12,12 +16,95 @@ pub struct ReadPlaceOptions {
     pub postcode: Option<String>,
 }
 
+/// A case-insensitive search for places whose name, street address, street or
+/// town contains `text`, optionally limited to places stored with coordinates
+/// inside `bounding_box`. An empty `text` matches every place.
+pub struct SearchPlaceOptions {
+    pub text: String,
+    pub bounding_box: Option<BoundingBox>,
+    /// Also match places stored without coordinates, such as Mapbox
+    /// suggestions nobody has picked yet, when their locality and region are
+    /// those of a place stored with coordinates inside `bounding_box`.
+    pub include_unlocated: bool,
+    /// Closest to this first and places without coordinates last, rather
+    /// than by name. The limit applies after ordering.
+    pub closest_to: Option<Coordinates>,
+    pub limit: usize,
+}
+
+/// Places stored with coordinates within `radius_meters` of `center`.
+pub struct NearbyPlaceOptions {
+    pub center: Coordinates,
+    pub radius_meters: f64,
+    pub limit: usize,
+}
+
+/// Places with a Foursquare id whose details are missing or were fetched
+/// before `updated_before`.
+pub struct StalePlaceOptions {
+    pub updated_before: DateTime<Utc>,
+    pub limit: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct NearbyPlace {
+    pub place: Place,
+    pub distance_meters: f64,
+}
+
 pub type DynPlacesRepo = Arc<Mutex<dyn PlacesRepository>>;
 
 #[async_trait]
 pub trait PlacesRepository: Send + Sync + 'static {
     async fn create(&mut self, place: &Place) -> Result<Place, String>;
     async fn read(&self, options: ReadPlaceOptions) -> Result<Vec<Place>, String>;
+    /// The places with any of `ids`, in no particular order. Ids of places
+    /// that don't exist are skipped.
+    async fn read_many(&self, ids: &[u64]) -> Result<Vec<Place>, String>;
     async fn update(&mut self, place: Place) -> Result<Place, String>;
     async fn delete(&mut self, id: u64) -> Result<Option<Place>, String>;
+    async fn search(&self, options: SearchPlaceOptions) -> Result<Vec<Place>, String>;
+    /// Closest first.
+    async fn nearby(&self, options: NearbyPlaceOptions) -> Result<Vec<NearbyPlace>, String>;
+    /// Places without details first, then the longest since their details
+    /// were fetched.
+    async fn stale(&self, options: StalePlaceOptions) -> Result<Vec<Place>, String>;
+}
+
+/// Keeps the places within the radius of `options`, closest first, for
+/// stores that can only narrow a nearby query down to a bounding box.
+pub fn closest_places(places: Vec<Place>, options: &NearbyPlaceOptions) -> Vec<NearbyPlace> {
+    let mut nearby: Vec<NearbyPlace> = places
+        .into_iter()
+        .filter_map(|place| {
+            let distance_meters = options.center.distance_meters(&place.coordinates?);
+            if distance_meters > options.radius_meters {
+                return None;
+            }
+            Some(NearbyPlace {
+                place,
+                distance_meters,
+            })
+        })
+        .collect();
+    nearby.sort_by(|a, b| {
+        a.distance_meters
+            .total_cmp(&b.distance_meters)
+            .then(a.place.id.cmp(&b.place.id))
+    });
+    nearby.truncate(options.limit);
+    nearby
+}
+
+/// Escapes `%`, `_` and `\` so a user's text matches literally inside a
+/// `LIKE` pattern using `ESCAPE '\'`.
+pub fn escape_like(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
 }
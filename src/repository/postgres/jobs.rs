@@ -0,0 +1,391 @@
+use axum::async_trait;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use sqlx::{FromRow, PgExecutor};
+
+use crate::repository::jobs::{Job, JobKind, JobStatus, JobsRepository};
+
+use super::{transaction::PostgresTransaction, PostgresRepo};
+
+/// Timestamps are read and written as microseconds since the epoch, like
+/// those of ratings.
+const JOB_COLUMNS: &str = "id, kind, place_id, status, attempts, last_error,
+    (EXTRACT(EPOCH FROM run_at) * 1000000)::BIGINT AS run_at,
+    (EXTRACT(EPOCH FROM created_at) * 1000000)::BIGINT AS created_at,
+    (EXTRACT(EPOCH FROM updated_at) * 1000000)::BIGINT AS updated_at";
+
+#[derive(FromRow)]
+struct PostgresJob {
+    id: i64,
+    kind: String,
+    place_id: i64,
+    status: String,
+    attempts: i32,
+    run_at: i64,
+    last_error: Option<String>,
+    created_at: i64,
+    updated_at: i64,
+}
+
+fn from_micros(micros: i64) -> Result<DateTime<Utc>, String> {
+    match NaiveDateTime::from_timestamp_micros(micros) {
+        Some(t) => Ok(DateTime::from_utc(t, Utc)),
+        None => Err("Could not read jobs".to_string()),
+    }
+}
+
+impl PostgresJob {
+    fn convert_to_job(self) -> Result<Job, String> {
+        Ok(Job {
+            id: self.id as u64,
+            kind: JobKind::parse(&self.kind)?,
+            place_id: self.place_id as u64,
+            status: JobStatus::parse(&self.status)?,
+            attempts: self.attempts as u32,
+            run_at: from_micros(self.run_at)?,
+            last_error: self.last_error,
+            created_at: from_micros(self.created_at)?,
+            updated_at: from_micros(self.updated_at)?,
+        })
+    }
+}
+
+async fn insert_job<'e, E: PgExecutor<'e>>(
+    executor: E,
+    kind: JobKind,
+    place_id: u64,
+    now: DateTime<Utc>,
+) -> Result<Option<Job>, String> {
+    match sqlx::query_as::<_, PostgresJob>(&format!(
+        "INSERT INTO jobs (kind, place_id, status, run_at, created_at, updated_at)
+        VALUES ($1, $2, 'pending', to_timestamp($3::DOUBLE PRECISION / 1000000),
+            to_timestamp($3::DOUBLE PRECISION / 1000000),
+            to_timestamp($3::DOUBLE PRECISION / 1000000))
+        ON CONFLICT (kind, place_id) WHERE status <> 'dead' DO NOTHING
+        RETURNING {}",
+        JOB_COLUMNS
+    ))
+    .bind(kind.as_str())
+    .bind(place_id as i64)
+    .bind(now.timestamp_micros())
+    .fetch_optional(executor)
+    .await
+    {
+        Ok(job) => job.map(|j| j.convert_to_job()).transpose(),
+        Err(e) => {
+            eprintln!("error queueing job in Postgres: {}", e);
+            Err("Job not queued".to_string())
+        }
+    }
+}
+
+async fn live_job<'e, E: PgExecutor<'e>>(
+    executor: E,
+    kind: JobKind,
+    place_id: u64,
+) -> Result<Job, String> {
+    match sqlx::query_as::<_, PostgresJob>(&format!(
+        "SELECT {} FROM jobs WHERE kind = $1 AND place_id = $2 AND status <> 'dead'",
+        JOB_COLUMNS
+    ))
+    .bind(kind.as_str())
+    .bind(place_id as i64)
+    .fetch_one(executor)
+    .await
+    {
+        Ok(j) => j.convert_to_job(),
+        Err(e) => {
+            eprintln!("error reading queued job from Postgres: {}", e);
+            Err("Job not queued".to_string())
+        }
+    }
+}
+
+/// Skips rows locked by other workers, so several instances can share a queue.
+async fn claim_jobs<'e, E: PgExecutor<'e>>(
+    executor: E,
+    now: DateTime<Utc>,
+    limit: usize,
+) -> Result<Vec<Job>, String> {
+    match sqlx::query_as::<_, PostgresJob>(&format!(
+        "UPDATE jobs SET status = 'running',
+        updated_at = to_timestamp($1::DOUBLE PRECISION / 1000000)
+        WHERE id IN (
+            SELECT id FROM jobs WHERE status = 'pending'
+            AND run_at <= to_timestamp($1::DOUBLE PRECISION / 1000000)
+            ORDER BY run_at, id LIMIT $2
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING {}",
+        JOB_COLUMNS
+    ))
+    .bind(now.timestamp_micros())
+    .bind(limit as i64)
+    .fetch_all(executor)
+    .await
+    {
+        Ok(jobs) => {
+            let mut jobs = jobs
+                .into_iter()
+                .map(|j| j.convert_to_job())
+                .collect::<Result<Vec<Job>, String>>()?;
+            jobs.sort_by_key(|j| (j.run_at, j.id));
+            Ok(jobs)
+        }
+        Err(e) => {
+            eprintln!("error claiming jobs in Postgres: {}", e);
+            Err("Could not claim jobs".to_string())
+        }
+    }
+}
+
+async fn delete_job<'e, E: PgExecutor<'e>>(executor: E, id: u64) -> Result<(), String> {
+    match sqlx::query("DELETE FROM jobs WHERE id = $1")
+        .bind(id as i64)
+        .execute(executor)
+        .await
+    {
+        Ok(_) => Ok(()),
+        Err(e) => {
+            eprintln!("error completing job in Postgres: {}", e);
+            Err("Job not completed".to_string())
+        }
+    }
+}
+
+async fn fail_job<'e, E: PgExecutor<'e>>(
+    executor: E,
+    id: u64,
+    error: &str,
+    retry_at: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> Result<Job, String> {
+    let status = match retry_at {
+        Some(_) => JobStatus::Pending,
+        None => JobStatus::Dead,
+    };
+    match sqlx::query_as::<_, PostgresJob>(&format!(
+        "UPDATE jobs SET status = $1, attempts = attempts + 1,
+        run_at = COALESCE(to_timestamp($2::DOUBLE PRECISION / 1000000), run_at),
+        last_error = $3, updated_at = to_timestamp($4::DOUBLE PRECISION / 1000000)
+        WHERE id = $5
+        RETURNING {}",
+        JOB_COLUMNS
+    ))
+    .bind(status.as_str())
+    .bind(retry_at.map(|t| t.timestamp_micros()))
+    .bind(error)
+    .bind(now.timestamp_micros())
+    .bind(id as i64)
+    .fetch_optional(executor)
+    .await
+    {
+        Ok(Some(j)) => j.convert_to_job(),
+        Ok(None) => Err("Job not found".to_string()),
+        Err(e) => {
+            eprintln!("error failing job in Postgres: {}", e);
+            Err("Job not updated".to_string())
+        }
+    }
+}
+
+async fn requeue_running_jobs<'e, E: PgExecutor<'e>>(
+    executor: E,
+    claimed_before: DateTime<Utc>,
+) -> Result<u64, String> {
+    match sqlx::query(
+        "UPDATE jobs SET status = 'pending'
+        WHERE status = 'running'
+        AND updated_at < to_timestamp($1::DOUBLE PRECISION / 1000000)",
+    )
+    .bind(claimed_before.timestamp_micros())
+    .execute(executor)
+    .await
+    {
+        Ok(result) => Ok(result.rows_affected()),
+        Err(e) => {
+            eprintln!("error requeueing jobs in Postgres: {}", e);
+            Err("Could not requeue jobs".to_string())
+        }
+    }
+}
+
+async fn dead_jobs<'e, E: PgExecutor<'e>>(executor: E) -> Result<Vec<Job>, String> {
+    match sqlx::query_as::<_, PostgresJob>(&format!(
+        "SELECT {} FROM jobs WHERE status = 'dead' ORDER BY jobs.updated_at DESC, id DESC",
+        JOB_COLUMNS
+    ))
+    .fetch_all(executor)
+    .await
+    {
+        Ok(jobs) => jobs.into_iter().map(|j| j.convert_to_job()).collect(),
+        Err(e) => {
+            eprintln!("error reading dead jobs from Postgres: {}", e);
+            Err("Could not read jobs".to_string())
+        }
+    }
+}
+
+#[async_trait]
+impl JobsRepository for PostgresRepo {
+    async fn enqueue(
+        &mut self,
+        kind: JobKind,
+        place_id: u64,
+        now: DateTime<Utc>,
+    ) -> Result<Job, String> {
+        match insert_job(&self.pool, kind, place_id, now).await? {
+            Some(job) => Ok(job),
+            None => live_job(&self.pool, kind, place_id).await,
+        }
+    }
+
+    async fn claim(&mut self, now: DateTime<Utc>, limit: usize) -> Result<Vec<Job>, String> {
+        claim_jobs(&self.pool, now, limit).await
+    }
+
+    async fn complete(&mut self, id: u64) -> Result<(), String> {
+        delete_job(&self.pool, id).await
+    }
+
+    async fn fail(
+        &mut self,
+        id: u64,
+        error: &str,
+        retry_at: Option<DateTime<Utc>>,
+        now: DateTime<Utc>,
+    ) -> Result<Job, String> {
+        fail_job(&self.pool, id, error, retry_at, now).await
+    }
+
+    async fn requeue_running(&mut self, claimed_before: DateTime<Utc>) -> Result<u64, String> {
+        requeue_running_jobs(&self.pool, claimed_before).await
+    }
+
+    async fn dead_letters(&self) -> Result<Vec<Job>, String> {
+        dead_jobs(&self.pool).await
+    }
+}
+
+#[async_trait]
+impl JobsRepository for PostgresTransaction {
+    async fn enqueue(
+        &mut self,
+        kind: JobKind,
+        place_id: u64,
+        now: DateTime<Utc>,
+    ) -> Result<Job, String> {
+        let mut tx = self.lock().await;
+        match insert_job(&mut **tx, kind, place_id, now).await? {
+            Some(job) => Ok(job),
+            None => live_job(&mut **tx, kind, place_id).await,
+        }
+    }
+
+    async fn claim(&mut self, now: DateTime<Utc>, limit: usize) -> Result<Vec<Job>, String> {
+        let mut tx = self.lock().await;
+        claim_jobs(&mut **tx, now, limit).await
+    }
+
+    async fn complete(&mut self, id: u64) -> Result<(), String> {
+        let mut tx = self.lock().await;
+        delete_job(&mut **tx, id).await
+    }
+
+    async fn fail(
+        &mut self,
+        id: u64,
+        error: &str,
+        retry_at: Option<DateTime<Utc>>,
+        now: DateTime<Utc>,
+    ) -> Result<Job, String> {
+        let mut tx = self.lock().await;
+        fail_job(&mut **tx, id, error, retry_at, now).await
+    }
+
+    async fn requeue_running(&mut self, claimed_before: DateTime<Utc>) -> Result<u64, String> {
+        let mut tx = self.lock().await;
+        requeue_running_jobs(&mut **tx, claimed_before).await
+    }
+
+    async fn dead_letters(&self) -> Result<Vec<Job>, String> {
+        let mut tx = self.lock().await;
+        dead_jobs(&mut **tx).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use crate::{places::testing::place, repository::places::PlacesRepository};
+
+    use super::*;
+
+    async fn repo() -> PostgresRepo {
+        dotenv::dotenv().ok();
+        let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set.");
+        PostgresRepo::new(&database_url, 2).await.unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a Postgres database at DATABASE_URL"]
+    async fn test_enqueue_claim_and_fail() {
+        let repo = repo().await;
+        let mut tx = repo.begin().await.unwrap();
+        let place = PlacesRepository::create(&mut tx, &place(0, "Jobs Test"))
+            .await
+            .unwrap();
+        let now = DateTime::from_utc(
+            NaiveDateTime::from_timestamp_micros(Utc::now().timestamp_micros()).unwrap(),
+            Utc,
+        );
+
+        let job = tx
+            .enqueue(JobKind::FetchPhotos, place.id, now)
+            .await
+            .unwrap();
+        assert_eq!(job.run_at, now);
+        assert_eq!(
+            tx.enqueue(JobKind::FetchPhotos, place.id, now)
+                .await
+                .unwrap(),
+            job
+        );
+
+        let claimed = tx.claim(now, 100).await.unwrap();
+        assert!(claimed
+            .iter()
+            .any(|j| j.id == job.id && j.status == JobStatus::Running));
+
+        let retry_at = now + Duration::seconds(30);
+        let retried = tx
+            .fail(job.id, "Error getting pictures", Some(retry_at), now)
+            .await
+            .unwrap();
+        assert_eq!(retried.attempts, 1);
+        assert_eq!(retried.run_at, retry_at);
+        assert!(!tx
+            .claim(now, 100)
+            .await
+            .unwrap()
+            .iter()
+            .any(|j| j.id == job.id));
+
+        let dead = tx
+            .fail(job.id, "Error parsing JSON", None, retry_at)
+            .await
+            .unwrap();
+        assert_eq!(dead.status, JobStatus::Dead);
+        assert_eq!(dead.attempts, 2);
+        assert!(tx.dead_letters().await.unwrap().contains(&dead));
+        assert_ne!(
+            tx.enqueue(JobKind::FetchPhotos, place.id, now)
+                .await
+                .unwrap()
+                .id,
+            job.id
+        );
+
+        tx.rollback().await.unwrap();
+    }
+}
@@ -0,0 +1,52 @@
+pub mod jobs;
+pub mod places;
+pub mod ratings;
+pub mod transaction;
+pub mod user;
+
+use sqlx::{postgres::PgPoolOptions, PgPool};
+
+use self::transaction::PostgresTransaction;
+
+#[derive(Clone)]
+pub struct PostgresRepo {
+    pool: PgPool,
+}
+
+impl PostgresRepo {
+    /// Opens a pool of at most `max_connections` connections to the Postgres
+    /// database at `url` and runs the migrations embedded from `migrations/postgres`.
+    pub async fn new(url: &str, max_connections: u32) -> Result<Self, String> {
+        let pool = match PgPoolOptions::new()
+            .max_connections(max_connections)
+            .connect(url)
+            .await
+        {
+            Ok(p) => p,
+            Err(e) => {
+                eprintln!("error connecting to Postgres: {}", e);
+                return Err("Could not connect to Postgres".to_string());
+            }
+        };
+
+        if let Err(e) = sqlx::migrate!("./migrations/postgres").run(&pool).await {
+            eprintln!("error running Postgres migrations: {}", e);
+            return Err("Could not run Postgres migrations".to_string());
+        }
+
+        Ok(PostgresRepo { pool })
+    }
+
+    /// Starts a transaction. The returned handle implements every repository
+    /// trait, so writes to several repositories can be committed or rolled back
+    /// together.
+    pub async fn begin(&self) -> Result<PostgresTransaction, String> {
+        match self.pool.begin().await {
+            Ok(tx) => Ok(PostgresTransaction::new(tx)),
+            Err(e) => {
+                eprintln!("error starting Postgres transaction: {}", e);
+                Err("Could not start transaction".to_string())
+            }
+        }
+    }
+}
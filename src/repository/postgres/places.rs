@@ -0,0 +1,726 @@
+use axum::async_trait;
+use sqlx::{types::Json, FromRow, PgExecutor, Postgres, QueryBuilder};
+
+use crate::{
+    geo::{BoundingBox, Coordinates, EARTH_RADIUS_METERS},
+    places::{photo::Photo, Address, Place, PlaceDetails},
+    repository::places::{
+        escape_like, NearbyPlace, NearbyPlaceOptions, PlacesRepository, ReadPlaceOptions,
+        SearchPlaceOptions, StalePlaceOptions,
+    },
+};
+
+use super::{transaction::PostgresTransaction, PostgresRepo};
+
+#[derive(FromRow)]
+struct PostgresPlace {
+    id: i64,
+    name: String,
+    address: String,
+    full_address: Option<String>,
+    country: Option<String>,
+    region: Option<String>,
+    postcode: Option<String>,
+    place: Option<String>,
+    street: Option<String>,
+    photos: Option<Json<Vec<Photo>>>,
+    website: Option<String>,
+    foursquare_id: Option<String>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    categories: Option<Vec<String>>,
+    details: Option<Json<PlaceDetails>>,
+    mapbox_id: Option<String>,
+}
+
+#[derive(FromRow)]
+struct PostgresNearbyPlace {
+    #[sqlx(flatten)]
+    place: PostgresPlace,
+    distance_meters: f64,
+}
+
+impl PostgresPlace {
+    fn convert_to_place(self) -> Place {
+        Place {
+            id: self.id as u64,
+            name: self.name,
+            address: Address {
+                address: self.address,
+                full_address: self.full_address,
+                country: self.country,
+                region: self.region,
+                postcode: self.postcode,
+                place: self.place,
+                street: self.street,
+            },
+            photos: self.photos.map(|photos| photos.0),
+            website: self.website,
+            foursquare_id: self.foursquare_id,
+            mapbox_id: self.mapbox_id,
+            coordinates: self
+                .latitude
+                .zip(self.longitude)
+                .map(|(latitude, longitude)| Coordinates {
+                    latitude,
+                    longitude,
+                }),
+            categories: self.categories,
+            details: self.details.map(|details| details.0),
+        }
+    }
+}
+
+async fn create_place<'e, E: PgExecutor<'e>>(executor: E, place: &Place) -> Result<Place, String> {
+    // A place with a known address hands back the stored row, like the
+    // Supabase repository does on a conflict.
+    match sqlx::query_as::<_, PostgresPlace>(
+        "INSERT INTO places
+        (name, address, full_address, country, region, postcode, place, street, photos, website, foursquare_id, latitude, longitude, categories, details, mapbox_id)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16)
+        ON CONFLICT (address) DO UPDATE SET address = places.address
+        RETURNING *",
+    )
+    .bind(&place.name)
+    .bind(&place.address.address)
+    .bind(&place.address.full_address)
+    .bind(&place.address.country)
+    .bind(&place.address.region)
+    .bind(&place.address.postcode)
+    .bind(&place.address.place)
+    .bind(&place.address.street)
+    .bind(place.photos.as_ref().map(Json))
+    .bind(&place.website)
+    .bind(&place.foursquare_id)
+    .bind(place.coordinates.map(|c| c.latitude))
+    .bind(place.coordinates.map(|c| c.longitude))
+    .bind(&place.categories)
+    .bind(place.details.as_ref().map(Json))
+    .bind(&place.mapbox_id)
+    .fetch_one(executor)
+    .await
+    {
+        Ok(p) => Ok(p.convert_to_place()),
+        Err(e) => {
+            eprintln!("error creating place in Postgres: {}", e);
+            Err("Place not created".to_string())
+        }
+    }
+}
+
+async fn read_places<'e, E: PgExecutor<'e>>(
+    executor: E,
+    options: ReadPlaceOptions,
+) -> Result<Vec<Place>, String> {
+    let mut query: QueryBuilder<Postgres> = QueryBuilder::new("SELECT * FROM places WHERE TRUE");
+    if let Some(id) = options.id {
+        query.push(" AND id = ").push_bind(id as i64);
+    };
+    if let Some(address) = options.address {
+        query.push(" AND address = ").push_bind(address);
+    };
+    if let Some(name) = options.name {
+        query.push(" AND name = ").push_bind(name);
+    };
+    if let Some(postcode) = options.postcode {
+        query.push(" AND postcode = ").push_bind(postcode);
+    };
+
+    match query
+        .build_query_as::<PostgresPlace>()
+        .fetch_all(executor)
+        .await
+    {
+        Ok(places) => Ok(places.into_iter().map(|p| p.convert_to_place()).collect()),
+        Err(e) => {
+            eprintln!("error reading places from Postgres: {}", e);
+            Err("Could not read places".to_string())
+        }
+    }
+}
+
+async fn read_many_places<'e, E: PgExecutor<'e>>(
+    executor: E,
+    ids: &[u64],
+) -> Result<Vec<Place>, String> {
+    match sqlx::query_as::<_, PostgresPlace>("SELECT * FROM places WHERE id = ANY($1)")
+        .bind(ids.iter().map(|id| *id as i64).collect::<Vec<i64>>())
+        .fetch_all(executor)
+        .await
+    {
+        Ok(places) => Ok(places.into_iter().map(|p| p.convert_to_place()).collect()),
+        Err(e) => {
+            eprintln!("error reading places from Postgres: {}", e);
+            Err("Could not read places".to_string())
+        }
+    }
+}
+
+async fn update_place<'e, E: PgExecutor<'e>>(executor: E, place: Place) -> Result<Place, String> {
+    match sqlx::query(
+        "UPDATE places SET
+        name = $1, address = $2, full_address = $3, country = $4, region = $5, postcode = $6,
+        place = $7, street = $8, photos = $9, website = $10, foursquare_id = $11,
+        latitude = $12, longitude = $13, categories = $14, details = $15, mapbox_id = $16
+        WHERE id = $17",
+    )
+    .bind(&place.name)
+    .bind(&place.address.address)
+    .bind(&place.address.full_address)
+    .bind(&place.address.country)
+    .bind(&place.address.region)
+    .bind(&place.address.postcode)
+    .bind(&place.address.place)
+    .bind(&place.address.street)
+    .bind(place.photos.as_ref().map(Json))
+    .bind(&place.website)
+    .bind(&place.foursquare_id)
+    .bind(place.coordinates.map(|c| c.latitude))
+    .bind(place.coordinates.map(|c| c.longitude))
+    .bind(&place.categories)
+    .bind(place.details.as_ref().map(Json))
+    .bind(&place.mapbox_id)
+    .bind(place.id as i64)
+    .execute(executor)
+    .await
+    {
+        Ok(_) => Ok(place),
+        Err(e) => {
+            eprintln!("error updating place in Postgres: {}", e);
+            Err("Place not updated".to_string())
+        }
+    }
+}
+
+async fn delete_place<'e, E: PgExecutor<'e>>(
+    executor: E,
+    id: u64,
+) -> Result<Option<Place>, String> {
+    match sqlx::query_as::<_, PostgresPlace>("DELETE FROM places WHERE id = $1 RETURNING *")
+        .bind(id as i64)
+        .fetch_optional(executor)
+        .await
+    {
+        Ok(place) => Ok(place.map(|p| p.convert_to_place())),
+        Err(e) => {
+            eprintln!("error deleting place from Postgres: {}", e);
+            Err("Place not deleted".to_string())
+        }
+    }
+}
+
+/// What a search's text is matched against: the name, street address,
+/// street and town.
+const SEARCHED_COLUMNS: [&str; 4] = ["name", "address", "street", "place"];
+
+fn push_within(query: &mut QueryBuilder<Postgres>, bbox: &BoundingBox) {
+    query
+        .push("latitude BETWEEN ")
+        .push_bind(bbox.min_latitude)
+        .push(" AND ")
+        .push_bind(bbox.max_latitude)
+        .push(" AND longitude BETWEEN ")
+        .push_bind(bbox.min_longitude)
+        .push(" AND ")
+        .push_bind(bbox.max_longitude);
+}
+
+async fn search_places<'e, E: PgExecutor<'e>>(
+    executor: E,
+    options: SearchPlaceOptions,
+) -> Result<Vec<Place>, String> {
+    // Each column has a trigram index, see `migrations/postgres`.
+    let pattern = format!("%{}%", escape_like(&options.text));
+    let mut query: QueryBuilder<Postgres> = QueryBuilder::new("SELECT * FROM places WHERE (");
+    for (i, column) in SEARCHED_COLUMNS.iter().enumerate() {
+        if i > 0 {
+            query.push(" OR ");
+        }
+        query
+            .push(column)
+            .push(" ILIKE ")
+            .push_bind(pattern.clone())
+            .push(" ESCAPE '\\'");
+    }
+    query.push(")");
+    if let Some(bbox) = options.bounding_box {
+        query.push(" AND ((");
+        push_within(&mut query, &bbox);
+        query.push(")");
+        if options.include_unlocated {
+            query.push(
+                " OR (latitude IS NULL AND (place, region) IN (
+                    SELECT place, region FROM places WHERE ",
+            );
+            push_within(&mut query, &bbox);
+            query.push("))");
+        }
+        query.push(")");
+    };
+    match options.closest_to {
+        // Degrees of longitude shrink away from the equator. Scaling them is
+        // close enough to order by without trigonometry.
+        Some(center) => {
+            let scale = center.latitude.to_radians().cos().powi(2);
+            query
+                .push(" ORDER BY latitude IS NULL, (latitude - ")
+                .push_bind(center.latitude)
+                .push(") * (latitude - ")
+                .push_bind(center.latitude)
+                .push(") + (longitude - ")
+                .push_bind(center.longitude)
+                .push(") * (longitude - ")
+                .push_bind(center.longitude)
+                .push(") * ")
+                .push_bind(scale)
+                .push(", id");
+        }
+        None => {
+            query.push(" ORDER BY name");
+        }
+    }
+    query.push(" LIMIT ").push_bind(options.limit as i64);
+
+    match query
+        .build_query_as::<PostgresPlace>()
+        .fetch_all(executor)
+        .await
+    {
+        Ok(places) => Ok(places.into_iter().map(|p| p.convert_to_place()).collect()),
+        Err(e) => {
+            eprintln!("error searching places in Postgres: {}", e);
+            Err("Could not search places".to_string())
+        }
+    }
+}
+
+/// The haversine distance is worked out in SQL, after the bounding box has
+/// narrowed the places down.
+async fn nearby_places<'e, E: PgExecutor<'e>>(
+    executor: E,
+    options: NearbyPlaceOptions,
+) -> Result<Vec<NearbyPlace>, String> {
+    let bbox = options.center.bounding_box(options.radius_meters);
+    match sqlx::query_as::<_, PostgresNearbyPlace>(
+        "SELECT * FROM (
+            SELECT *, 2 * $1 * asin(least(1.0, sqrt(
+                power(sin(radians(latitude - $2) / 2), 2)
+                + cos(radians($2)) * cos(radians(latitude))
+                * power(sin(radians(longitude - $3) / 2), 2)
+            ))) AS distance_meters
+            FROM places
+            WHERE latitude BETWEEN $4 AND $5 AND longitude BETWEEN $6 AND $7
+        ) nearby
+        WHERE distance_meters <= $8
+        ORDER BY distance_meters, id
+        LIMIT $9",
+    )
+    .bind(EARTH_RADIUS_METERS)
+    .bind(options.center.latitude)
+    .bind(options.center.longitude)
+    .bind(bbox.min_latitude)
+    .bind(bbox.max_latitude)
+    .bind(bbox.min_longitude)
+    .bind(bbox.max_longitude)
+    .bind(options.radius_meters)
+    .bind(options.limit as i64)
+    .fetch_all(executor)
+    .await
+    {
+        Ok(places) => Ok(places
+            .into_iter()
+            .map(|p| NearbyPlace {
+                place: p.place.convert_to_place(),
+                distance_meters: p.distance_meters,
+            })
+            .collect()),
+        Err(e) => {
+            eprintln!("error finding nearby places in Postgres: {}", e);
+            Err("Could not find nearby places".to_string())
+        }
+    }
+}
+
+async fn stale_places<'e, E: PgExecutor<'e>>(
+    executor: E,
+    options: StalePlaceOptions,
+) -> Result<Vec<Place>, String> {
+    match sqlx::query_as::<_, PostgresPlace>(
+        "SELECT * FROM places
+        WHERE details IS NULL
+            OR (details->>'updatedAt')::TIMESTAMPTZ
+                < to_timestamp($1::DOUBLE PRECISION / 1000000)
+        ORDER BY details IS NOT NULL, (details->>'updatedAt')::TIMESTAMPTZ, id
+        LIMIT $2",
+    )
+    .bind(options.updated_before.timestamp_micros())
+    .bind(options.limit as i64)
+    .fetch_all(executor)
+    .await
+    {
+        Ok(places) => Ok(places.into_iter().map(|p| p.convert_to_place()).collect()),
+        Err(e) => {
+            eprintln!("error finding stale places in Postgres: {}", e);
+            Err("Could not find stale places".to_string())
+        }
+    }
+}
+
+#[async_trait]
+impl PlacesRepository for PostgresRepo {
+    async fn create(&mut self, place: &Place) -> Result<Place, String> {
+        create_place(&self.pool, place).await
+    }
+
+    async fn read(&self, options: ReadPlaceOptions) -> Result<Vec<Place>, String> {
+        read_places(&self.pool, options).await
+    }
+
+    async fn read_many(&self, ids: &[u64]) -> Result<Vec<Place>, String> {
+        read_many_places(&self.pool, ids).await
+    }
+
+    async fn update(&mut self, place: Place) -> Result<Place, String> {
+        update_place(&self.pool, place).await
+    }
+
+    async fn delete(&mut self, id: u64) -> Result<Option<Place>, String> {
+        delete_place(&self.pool, id).await
+    }
+
+    async fn search(&self, options: SearchPlaceOptions) -> Result<Vec<Place>, String> {
+        search_places(&self.pool, options).await
+    }
+
+    async fn nearby(&self, options: NearbyPlaceOptions) -> Result<Vec<NearbyPlace>, String> {
+        nearby_places(&self.pool, options).await
+    }
+
+    async fn stale(&self, options: StalePlaceOptions) -> Result<Vec<Place>, String> {
+        stale_places(&self.pool, options).await
+    }
+}
+
+#[async_trait]
+impl PlacesRepository for PostgresTransaction {
+    async fn create(&mut self, place: &Place) -> Result<Place, String> {
+        let mut tx = self.lock().await;
+        create_place(&mut **tx, place).await
+    }
+
+    async fn read(&self, options: ReadPlaceOptions) -> Result<Vec<Place>, String> {
+        let mut tx = self.lock().await;
+        read_places(&mut **tx, options).await
+    }
+
+    async fn read_many(&self, ids: &[u64]) -> Result<Vec<Place>, String> {
+        let mut tx = self.lock().await;
+        read_many_places(&mut **tx, ids).await
+    }
+
+    async fn update(&mut self, place: Place) -> Result<Place, String> {
+        let mut tx = self.lock().await;
+        update_place(&mut **tx, place).await
+    }
+
+    async fn delete(&mut self, id: u64) -> Result<Option<Place>, String> {
+        let mut tx = self.lock().await;
+        delete_place(&mut **tx, id).await
+    }
+
+    async fn search(&self, options: SearchPlaceOptions) -> Result<Vec<Place>, String> {
+        let mut tx = self.lock().await;
+        search_places(&mut **tx, options).await
+    }
+
+    async fn nearby(&self, options: NearbyPlaceOptions) -> Result<Vec<NearbyPlace>, String> {
+        let mut tx = self.lock().await;
+        nearby_places(&mut **tx, options).await
+    }
+
+    async fn stale(&self, options: StalePlaceOptions) -> Result<Vec<Place>, String> {
+        let mut tx = self.lock().await;
+        stale_places(&mut **tx, options).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use crate::{
+        places::{OpeningHours, OpeningPeriod},
+        repository::user::{User, UserRepository},
+    };
+
+    use super::*;
+
+    async fn repo() -> PostgresRepo {
+        dotenv::dotenv().ok();
+        let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set.");
+        PostgresRepo::new(&database_url, 2).await.unwrap()
+    }
+
+    fn arlo(address: &str) -> Place {
+        Place {
+            id: 0,
+            name: "Arlo".to_string(),
+            address: Address {
+                address: address.to_string(),
+                full_address: Some(address.to_string()),
+                country: Some("USA".to_string()),
+                region: Some("Utah".to_string()),
+                postcode: Some("84106".to_string()),
+                place: Some("Salt Lake City".to_string()),
+                street: Some("N Fake Street".to_string()),
+            },
+            photos: None,
+            website: None,
+            foursquare_id: None,
+            mapbox_id: Some("mapbox-arlo".to_string()),
+            coordinates: Some(Coordinates {
+                latitude: 40.7608,
+                longitude: -111.891,
+            }),
+            categories: Some(vec!["american".to_string()]),
+            details: None,
+        }
+    }
+
+    fn read_address(address: &str) -> ReadPlaceOptions {
+        ReadPlaceOptions {
+            id: None,
+            name: None,
+            address: Some(address.to_string()),
+            postcode: None,
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a Postgres database at DATABASE_URL"]
+    async fn test_create_read_update_delete() {
+        let mut repo = repo().await;
+
+        let created = PlacesRepository::create(&mut repo, &arlo("1 Postgres Test Street"))
+            .await
+            .unwrap();
+        let existing = PlacesRepository::create(&mut repo, &arlo("1 Postgres Test Street"))
+            .await
+            .unwrap();
+        assert_eq!(created.id, existing.id);
+
+        let mut place = created.clone();
+        place.photos = Some(vec![Photo {
+            prefix: "https://fastly.4sqi.net/img/general/".to_string(),
+            suffix: "/1.jpg".to_string(),
+        }]);
+        place.details = Some(PlaceDetails {
+            hours: Some(OpeningHours {
+                display: Some("Tue-Sat 17:00-22:00".to_string()),
+                periods: vec![OpeningPeriod {
+                    day: 2,
+                    open: "1700".to_string(),
+                    close: "2200".to_string(),
+                }],
+            }),
+            price: Some(3),
+            phone: Some("(385) 266-8845".to_string()),
+            social: None,
+            timezone: Some("America/Denver".to_string()),
+            updated_at: Utc::now(),
+        });
+        PlacesRepository::update(&mut repo, place.clone())
+            .await
+            .unwrap();
+
+        let places = PlacesRepository::read(&repo, read_address("1 Postgres Test Street"))
+            .await
+            .unwrap();
+        assert_eq!(places.len(), 1);
+        assert_eq!(places[0].photos, place.photos);
+        assert_eq!(places[0].details, place.details);
+        assert_eq!(places[0].coordinates, arlo("").coordinates);
+        assert_eq!(places[0].categories, arlo("").categories);
+        assert_eq!(places[0].mapbox_id, arlo("").mapbox_id);
+
+        let deleted = PlacesRepository::delete(&mut repo, created.id)
+            .await
+            .unwrap();
+        assert_eq!(deleted.map(|p| p.id), Some(created.id));
+        assert!(PlacesRepository::delete(&mut repo, created.id)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a Postgres database at DATABASE_URL"]
+    async fn test_search() {
+        let mut repo = repo().await;
+        let place = PlacesRepository::create(
+            &mut repo,
+            &Place {
+                name: "Postgres Search 100% Tacos".to_string(),
+                ..arlo("3 Postgres Test Street")
+            },
+        )
+        .await
+        .unwrap();
+
+        let search = |text: &str, bounding_box| SearchPlaceOptions {
+            text: text.to_string(),
+            bounding_box,
+            include_unlocated: false,
+            closest_to: None,
+            limit: 10,
+        };
+        let found = PlacesRepository::search(&repo, search("search 100% t", None))
+            .await
+            .unwrap();
+        assert_eq!(
+            found.iter().map(|p| p.id).collect::<Vec<_>>(),
+            vec![place.id]
+        );
+        let found = PlacesRepository::search(&repo, search("3 postgres test st", None))
+            .await
+            .unwrap();
+        assert!(found.iter().any(|p| p.id == place.id));
+        assert!(PlacesRepository::search(&repo, search("search 1000", None))
+            .await
+            .unwrap()
+            .is_empty());
+        let far = Coordinates {
+            latitude: 0.0,
+            longitude: 0.0,
+        }
+        .bounding_box(1_000.0);
+        assert!(
+            PlacesRepository::search(&repo, search("postgres search", Some(far)))
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        PlacesRepository::delete(&mut repo, place.id).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a Postgres database at DATABASE_URL"]
+    async fn test_nearby() {
+        let mut repo = repo().await;
+        let center = Coordinates {
+            latitude: -45.0,
+            longitude: 170.0,
+        };
+        let mut place = arlo("4 Postgres Test Street");
+        place.coordinates = Some(Coordinates {
+            latitude: -45.001,
+            longitude: 170.0,
+        });
+        let place = PlacesRepository::create(&mut repo, &place).await.unwrap();
+
+        let nearby = |radius_meters| NearbyPlaceOptions {
+            center,
+            radius_meters,
+            limit: 10,
+        };
+        let found = PlacesRepository::nearby(&repo, nearby(1_000.0))
+            .await
+            .unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].place.id, place.id);
+        assert!(
+            (found[0].distance_meters - center.distance_meters(&place.coordinates.unwrap())).abs()
+                < 0.01
+        );
+        assert!(PlacesRepository::nearby(&repo, nearby(100.0))
+            .await
+            .unwrap()
+            .is_empty());
+
+        PlacesRepository::delete(&mut repo, place.id).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a Postgres database at DATABASE_URL"]
+    async fn test_stale() {
+        let repo = repo().await;
+        let mut tx = repo.begin().await.unwrap();
+        let now = Utc::now();
+        let mut ids = Vec::new();
+        for (address, age_days) in [
+            ("8 Postgres Fresh Street", Some(1)),
+            ("8 Postgres Old Street", Some(40)),
+            ("8 Postgres Missing Street", None),
+        ] {
+            let place = PlacesRepository::create(
+                &mut tx,
+                &Place {
+                    foursquare_id: Some(format!("stale-{}", address)),
+                    details: age_days.map(|days| PlaceDetails {
+                        hours: None,
+                        price: None,
+                        phone: None,
+                        social: None,
+                        timezone: None,
+                        updated_at: now - chrono::Duration::days(days),
+                    }),
+                    ..arlo(address)
+                },
+            )
+            .await
+            .unwrap();
+            ids.push(place.id);
+        }
+
+        let stale: Vec<u64> = tx
+            .stale(StalePlaceOptions {
+                updated_before: now - chrono::Duration::days(30),
+                limit: 10_000,
+            })
+            .await
+            .unwrap()
+            .iter()
+            .map(|p| p.id)
+            .filter(|id| ids.contains(id))
+            .collect();
+        assert_eq!(stale, vec![ids[2], ids[1]]);
+
+        tx.rollback().await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a Postgres database at DATABASE_URL"]
+    async fn test_transaction_spans_repositories() {
+        let mut repo = repo().await;
+        UserRepository::delete(&mut repo, 2028098684).await.unwrap();
+
+        let mut tx = repo.begin().await.unwrap();
+        UserRepository::create(
+            &mut tx,
+            User {
+                first_name: "Hunter".to_string(),
+                last_name: "Simmons".to_string(),
+                phone_number: 2028098684,
+                is_verified: false,
+            },
+        )
+        .await
+        .unwrap();
+        PlacesRepository::create(&mut tx, &arlo("2 Postgres Test Street"))
+            .await
+            .unwrap();
+        tx.rollback().await.unwrap();
+
+        assert!(UserRepository::read(&repo, 2028098684)
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(
+            PlacesRepository::read(&repo, read_address("2 Postgres Test Street"))
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,604 @@
+use std::collections::HashMap;
+
+use axum::async_trait;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use sqlx::{FromRow, PgConnection, PgExecutor, Postgres, QueryBuilder};
+
+use crate::repository::ratings::{
+    PlaceScore, Rating, RatingPhoto, RatingsRepository, ReadRatingOptions, ReadRatingPhotoOptions,
+};
+
+use super::{transaction::PostgresTransaction, PostgresRepo};
+
+/// Timestamps are read and written as microseconds since the epoch, since
+/// sqlx's chrono support needs a newer chrono than we build with.
+const RATING_COLUMNS: &str = "id, place_id, phone_number, score, review,
+    (EXTRACT(EPOCH FROM created_at) * 1000000)::BIGINT AS created_at,
+    (EXTRACT(EPOCH FROM updated_at) * 1000000)::BIGINT AS updated_at";
+
+#[derive(FromRow)]
+struct PostgresRating {
+    id: i64,
+    place_id: i64,
+    phone_number: i64,
+    score: i16,
+    review: Option<String>,
+    created_at: i64,
+    updated_at: i64,
+}
+
+const RATING_PHOTO_COLUMNS: &str = "id, rating_id, place_id, phone_number, key,
+    thumbnail_key, width, height,
+    (EXTRACT(EPOCH FROM created_at) * 1000000)::BIGINT AS created_at";
+
+#[derive(FromRow)]
+struct PostgresRatingPhoto {
+    id: i64,
+    rating_id: i64,
+    place_id: i64,
+    phone_number: i64,
+    key: String,
+    thumbnail_key: String,
+    width: i32,
+    height: i32,
+    created_at: i64,
+}
+
+#[derive(FromRow)]
+struct PostgresPlaceScore {
+    place_id: i64,
+    rating_count: i64,
+    average_score: f64,
+}
+
+fn from_micros(micros: i64) -> Result<DateTime<Utc>, String> {
+    match NaiveDateTime::from_timestamp_micros(micros) {
+        Some(t) => Ok(DateTime::from_utc(t, Utc)),
+        None => Err("Could not read ratings".to_string()),
+    }
+}
+
+impl PostgresRating {
+    fn convert_to_rating(self) -> Result<Rating, String> {
+        Ok(Rating {
+            id: self.id as u64,
+            place_id: self.place_id as u64,
+            phone_number: self.phone_number as u64,
+            score: self.score as u8,
+            review: self.review,
+            created_at: from_micros(self.created_at)?,
+            updated_at: from_micros(self.updated_at)?,
+        })
+    }
+}
+
+impl PostgresRatingPhoto {
+    fn convert_to_photo(self) -> Result<RatingPhoto, String> {
+        Ok(RatingPhoto {
+            id: self.id as u64,
+            rating_id: self.rating_id as u64,
+            place_id: self.place_id as u64,
+            phone_number: self.phone_number as u64,
+            key: self.key,
+            thumbnail_key: self.thumbnail_key,
+            width: self.width as u32,
+            height: self.height as u32,
+            created_at: from_micros(self.created_at)?,
+        })
+    }
+}
+
+async fn upsert_rating<'e, E: PgExecutor<'e>>(
+    executor: E,
+    rating: &Rating,
+) -> Result<Rating, String> {
+    match sqlx::query_as::<_, PostgresRating>(&format!(
+        "INSERT INTO ratings
+        (place_id, phone_number, score, review, created_at, updated_at)
+        VALUES ($1, $2, $3, $4,
+            to_timestamp($5::DOUBLE PRECISION / 1000000),
+            to_timestamp($6::DOUBLE PRECISION / 1000000))
+        ON CONFLICT (place_id, phone_number) DO UPDATE SET
+        score = excluded.score, review = excluded.review, updated_at = excluded.updated_at
+        RETURNING {}",
+        RATING_COLUMNS
+    ))
+    .bind(rating.place_id as i64)
+    .bind(rating.phone_number as i64)
+    .bind(i16::from(rating.score))
+    .bind(&rating.review)
+    .bind(rating.created_at.timestamp_micros())
+    .bind(rating.updated_at.timestamp_micros())
+    .fetch_one(executor)
+    .await
+    {
+        Ok(r) => r.convert_to_rating(),
+        Err(e) => {
+            eprintln!("error storing rating in Postgres: {}", e);
+            Err("Rating not stored".to_string())
+        }
+    }
+}
+
+async fn read_ratings<'e, E: PgExecutor<'e>>(
+    executor: E,
+    options: ReadRatingOptions,
+) -> Result<Vec<Rating>, String> {
+    let mut query: QueryBuilder<Postgres> = QueryBuilder::new(format!(
+        "SELECT {} FROM ratings WHERE 1 = 1",
+        RATING_COLUMNS
+    ));
+    if let Some(id) = options.id {
+        query.push(" AND id = ").push_bind(id as i64);
+    };
+    if let Some(place_id) = options.place_id {
+        query.push(" AND place_id = ").push_bind(place_id as i64);
+    };
+    if let Some(phone_number) = options.phone_number {
+        query
+            .push(" AND phone_number = ")
+            .push_bind(phone_number as i64);
+    };
+    query.push(" ORDER BY id");
+
+    match query
+        .build_query_as::<PostgresRating>()
+        .fetch_all(executor)
+        .await
+    {
+        Ok(ratings) => ratings.into_iter().map(|r| r.convert_to_rating()).collect(),
+        Err(e) => {
+            eprintln!("error reading ratings from Postgres: {}", e);
+            Err("Could not read ratings".to_string())
+        }
+    }
+}
+
+/// Deletes the rating and its photos on `conn`, which must be in a
+/// transaction so that the lock taken on the rating is held until the
+/// deletes are committed.
+async fn delete_rating(
+    conn: &mut PgConnection,
+    id: u64,
+) -> Result<Option<(Rating, Vec<RatingPhoto>)>, String> {
+    // Locking the rating first waits for any photos being added to it, so
+    // none are missed by the photo delete and removed by the cascade instead.
+    match sqlx::query("SELECT id FROM ratings WHERE id = $1 FOR UPDATE")
+        .bind(id as i64)
+        .fetch_optional(&mut *conn)
+        .await
+    {
+        Ok(Some(_)) => {}
+        Ok(None) => return Ok(None),
+        Err(e) => {
+            eprintln!("error locking rating in Postgres: {}", e);
+            return Err("Rating not deleted".to_string());
+        }
+    }
+
+    let photos = match sqlx::query_as::<_, PostgresRatingPhoto>(&format!(
+        "DELETE FROM rating_photos WHERE rating_id = $1 RETURNING {}",
+        RATING_PHOTO_COLUMNS
+    ))
+    .bind(id as i64)
+    .fetch_all(&mut *conn)
+    .await
+    {
+        Ok(photos) => {
+            let mut photos = photos
+                .into_iter()
+                .map(|p| p.convert_to_photo())
+                .collect::<Result<Vec<_>, _>>()?;
+            photos.sort_by_key(|p| p.id);
+            photos
+        }
+        Err(e) => {
+            eprintln!("error deleting rating photos from Postgres: {}", e);
+            return Err("Rating not deleted".to_string());
+        }
+    };
+    match sqlx::query_as::<_, PostgresRating>(&format!(
+        "DELETE FROM ratings WHERE id = $1 RETURNING {}",
+        RATING_COLUMNS
+    ))
+    .bind(id as i64)
+    .fetch_optional(&mut *conn)
+    .await
+    {
+        Ok(rating) => Ok(rating
+            .map(|r| r.convert_to_rating())
+            .transpose()?
+            .map(|r| (r, photos))),
+        Err(e) => {
+            eprintln!("error deleting rating from Postgres: {}", e);
+            Err("Rating not deleted".to_string())
+        }
+    }
+}
+
+async fn place_scores<'e, E: PgExecutor<'e>>(
+    executor: E,
+    place_ids: &[u64],
+) -> Result<HashMap<u64, PlaceScore>, String> {
+    let place_ids: Vec<i64> = place_ids.iter().map(|id| *id as i64).collect();
+    match sqlx::query_as::<_, PostgresPlaceScore>(
+        "SELECT place_id, COUNT(*) AS rating_count,
+        AVG(score)::DOUBLE PRECISION AS average_score
+        FROM ratings WHERE place_id = ANY($1) GROUP BY place_id",
+    )
+    .bind(place_ids)
+    .fetch_all(executor)
+    .await
+    {
+        Ok(scores) => Ok(scores
+            .into_iter()
+            .map(|s| {
+                (
+                    s.place_id as u64,
+                    PlaceScore {
+                        rating_count: s.rating_count as u64,
+                        average_score: s.average_score,
+                    },
+                )
+            })
+            .collect()),
+        Err(e) => {
+            eprintln!("error reading scores from Postgres: {}", e);
+            Err("Could not read scores".to_string())
+        }
+    }
+}
+
+async fn add_photo<'e, E: PgExecutor<'e>>(
+    executor: E,
+    photo: &RatingPhoto,
+) -> Result<RatingPhoto, String> {
+    match sqlx::query_as::<_, PostgresRatingPhoto>(&format!(
+        "INSERT INTO rating_photos
+        (rating_id, place_id, phone_number, key, thumbnail_key, width, height, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, to_timestamp($8::DOUBLE PRECISION / 1000000))
+        RETURNING {}",
+        RATING_PHOTO_COLUMNS
+    ))
+    .bind(photo.rating_id as i64)
+    .bind(photo.place_id as i64)
+    .bind(photo.phone_number as i64)
+    .bind(&photo.key)
+    .bind(&photo.thumbnail_key)
+    .bind(photo.width as i32)
+    .bind(photo.height as i32)
+    .bind(photo.created_at.timestamp_micros())
+    .fetch_one(executor)
+    .await
+    {
+        Ok(p) => p.convert_to_photo(),
+        Err(e) => {
+            eprintln!("error storing rating photo in Postgres: {}", e);
+            Err("Photo not stored".to_string())
+        }
+    }
+}
+
+/// Adds the photos on `conn`, which must be in a transaction so that the
+/// lock taken on the rating is held until the photos are committed.
+async fn add_photos(
+    conn: &mut PgConnection,
+    photos: &[RatingPhoto],
+    max_photos: usize,
+) -> Result<Option<Vec<RatingPhoto>>, String> {
+    let rating_id = match photos.first() {
+        Some(p) => p.rating_id as i64,
+        None => return Ok(Some(Vec::new())),
+    };
+    // Locking the rating makes concurrent uploads to it count one after
+    // another.
+    match sqlx::query("SELECT id FROM ratings WHERE id = $1 FOR UPDATE")
+        .bind(rating_id)
+        .fetch_optional(&mut *conn)
+        .await
+    {
+        Ok(Some(_)) => {}
+        Ok(None) => return Err("Rating not found".to_string()),
+        Err(e) => {
+            eprintln!("error locking rating in Postgres: {}", e);
+            return Err("Photo not stored".to_string());
+        }
+    }
+    match sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM rating_photos WHERE rating_id = $1")
+        .bind(rating_id)
+        .fetch_one(&mut *conn)
+        .await
+    {
+        Ok(count) if count as usize + photos.len() > max_photos => return Ok(None),
+        Ok(_) => {}
+        Err(e) => {
+            eprintln!("error counting rating photos in Postgres: {}", e);
+            return Err("Photo not stored".to_string());
+        }
+    }
+
+    let mut added = Vec::with_capacity(photos.len());
+    for photo in photos {
+        added.push(add_photo(&mut *conn, photo).await?);
+    }
+    Ok(Some(added))
+}
+
+async fn read_photos<'e, E: PgExecutor<'e>>(
+    executor: E,
+    options: ReadRatingPhotoOptions,
+) -> Result<Vec<RatingPhoto>, String> {
+    let mut query: QueryBuilder<Postgres> = QueryBuilder::new(format!(
+        "SELECT {} FROM rating_photos WHERE 1 = 1",
+        RATING_PHOTO_COLUMNS
+    ));
+    if let Some(id) = options.id {
+        query.push(" AND id = ").push_bind(id as i64);
+    };
+    if let Some(rating_id) = options.rating_id {
+        query.push(" AND rating_id = ").push_bind(rating_id as i64);
+    };
+    if let Some(place_id) = options.place_id {
+        query.push(" AND place_id = ").push_bind(place_id as i64);
+    };
+    query.push(" ORDER BY id");
+
+    match query
+        .build_query_as::<PostgresRatingPhoto>()
+        .fetch_all(executor)
+        .await
+    {
+        Ok(photos) => photos.into_iter().map(|p| p.convert_to_photo()).collect(),
+        Err(e) => {
+            eprintln!("error reading rating photos from Postgres: {}", e);
+            Err("Could not read photos".to_string())
+        }
+    }
+}
+
+async fn delete_photo<'e, E: PgExecutor<'e>>(
+    executor: E,
+    id: u64,
+) -> Result<Option<RatingPhoto>, String> {
+    match sqlx::query_as::<_, PostgresRatingPhoto>(&format!(
+        "DELETE FROM rating_photos WHERE id = $1 RETURNING {}",
+        RATING_PHOTO_COLUMNS
+    ))
+    .bind(id as i64)
+    .fetch_optional(executor)
+    .await
+    {
+        Ok(photo) => photo.map(|p| p.convert_to_photo()).transpose(),
+        Err(e) => {
+            eprintln!("error deleting rating photo from Postgres: {}", e);
+            Err("Photo not deleted".to_string())
+        }
+    }
+}
+
+#[async_trait]
+impl RatingsRepository for PostgresRepo {
+    async fn upsert(&mut self, rating: &Rating) -> Result<Rating, String> {
+        upsert_rating(&self.pool, rating).await
+    }
+
+    async fn read(&self, options: ReadRatingOptions) -> Result<Vec<Rating>, String> {
+        read_ratings(&self.pool, options).await
+    }
+
+    async fn delete(&mut self, id: u64) -> Result<Option<(Rating, Vec<RatingPhoto>)>, String> {
+        let mut tx = self.begin().await?;
+        let deleted = RatingsRepository::delete(&mut tx, id).await?;
+        tx.commit().await?;
+        Ok(deleted)
+    }
+
+    async fn scores(&self, place_ids: &[u64]) -> Result<HashMap<u64, PlaceScore>, String> {
+        place_scores(&self.pool, place_ids).await
+    }
+
+    async fn add_photos(
+        &mut self,
+        photos: &[RatingPhoto],
+        max_photos: usize,
+    ) -> Result<Option<Vec<RatingPhoto>>, String> {
+        let mut tx = self.begin().await?;
+        match tx.add_photos(photos, max_photos).await? {
+            Some(added) => {
+                tx.commit().await?;
+                Ok(Some(added))
+            }
+            None => {
+                tx.rollback().await?;
+                Ok(None)
+            }
+        }
+    }
+
+    async fn read_photos(
+        &self,
+        options: ReadRatingPhotoOptions,
+    ) -> Result<Vec<RatingPhoto>, String> {
+        read_photos(&self.pool, options).await
+    }
+
+    async fn delete_photo(&mut self, id: u64) -> Result<Option<RatingPhoto>, String> {
+        delete_photo(&self.pool, id).await
+    }
+}
+
+#[async_trait]
+impl RatingsRepository for PostgresTransaction {
+    async fn upsert(&mut self, rating: &Rating) -> Result<Rating, String> {
+        let mut tx = self.lock().await;
+        upsert_rating(&mut **tx, rating).await
+    }
+
+    async fn read(&self, options: ReadRatingOptions) -> Result<Vec<Rating>, String> {
+        let mut tx = self.lock().await;
+        read_ratings(&mut **tx, options).await
+    }
+
+    async fn delete(&mut self, id: u64) -> Result<Option<(Rating, Vec<RatingPhoto>)>, String> {
+        let mut tx = self.lock().await;
+        delete_rating(&mut tx, id).await
+    }
+
+    async fn scores(&self, place_ids: &[u64]) -> Result<HashMap<u64, PlaceScore>, String> {
+        let mut tx = self.lock().await;
+        place_scores(&mut **tx, place_ids).await
+    }
+
+    async fn add_photos(
+        &mut self,
+        photos: &[RatingPhoto],
+        max_photos: usize,
+    ) -> Result<Option<Vec<RatingPhoto>>, String> {
+        let mut tx = self.lock().await;
+        add_photos(&mut tx, photos, max_photos).await
+    }
+
+    async fn read_photos(
+        &self,
+        options: ReadRatingPhotoOptions,
+    ) -> Result<Vec<RatingPhoto>, String> {
+        let mut tx = self.lock().await;
+        read_photos(&mut **tx, options).await
+    }
+
+    async fn delete_photo(&mut self, id: u64) -> Result<Option<RatingPhoto>, String> {
+        let mut tx = self.lock().await;
+        delete_photo(&mut **tx, id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        places::testing::place,
+        repository::{
+            places::PlacesRepository,
+            user::{User, UserRepository},
+        },
+    };
+
+    use super::*;
+
+    async fn repo() -> PostgresRepo {
+        dotenv::dotenv().ok();
+        let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set.");
+        PostgresRepo::new(&database_url, 2).await.unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a Postgres database at DATABASE_URL"]
+    async fn test_upsert_read_scores_and_delete() {
+        let repo = repo().await;
+        let mut tx = repo.begin().await.unwrap();
+        UserRepository::create(
+            &mut tx,
+            User {
+                first_name: "Ratings".to_string(),
+                last_name: "Test".to_string(),
+                phone_number: 2028090041,
+                is_verified: true,
+            },
+        )
+        .await
+        .unwrap();
+        let place = PlacesRepository::create(&mut tx, &place(0, "Ratings Test"))
+            .await
+            .unwrap();
+        let now = Utc::now();
+        let rating = Rating {
+            id: 0,
+            place_id: place.id,
+            phone_number: 2028090041,
+            score: 7,
+            review: None,
+            created_at: now,
+            updated_at: now,
+        };
+
+        let created = tx.upsert(&rating).await.unwrap();
+        assert_eq!(
+            created.created_at.timestamp_micros(),
+            now.timestamp_micros()
+        );
+        let updated = tx
+            .upsert(&Rating {
+                score: 9,
+                created_at: now + chrono::Duration::days(1),
+                ..rating.clone()
+            })
+            .await
+            .unwrap();
+        assert_eq!(updated.id, created.id);
+        assert_eq!(updated.score, 9);
+        assert_eq!(updated.created_at, created.created_at);
+
+        let read = RatingsRepository::read(
+            &tx,
+            ReadRatingOptions {
+                id: None,
+                place_id: Some(place.id),
+                phone_number: None,
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(read, vec![updated.clone()]);
+        assert_eq!(
+            tx.scores(&[place.id]).await.unwrap()[&place.id],
+            PlaceScore {
+                rating_count: 1,
+                average_score: 9.0,
+            }
+        );
+
+        let new_photo = RatingPhoto {
+            id: 0,
+            rating_id: created.id,
+            place_id: place.id,
+            phone_number: 2028090041,
+            key: "ratings/1/a.jpg".to_string(),
+            thumbnail_key: "ratings/1/a-thumbnail.jpg".to_string(),
+            width: 800,
+            height: 600,
+            created_at: now,
+        };
+        let photo = tx
+            .add_photos(std::slice::from_ref(&new_photo), 2)
+            .await
+            .unwrap()
+            .unwrap()
+            .remove(0);
+        assert_eq!(
+            tx.add_photos(&[new_photo.clone(), new_photo], 2)
+                .await
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            tx.read_photos(ReadRatingPhotoOptions {
+                id: None,
+                rating_id: Some(created.id),
+                place_id: None,
+            })
+            .await
+            .unwrap(),
+            vec![photo.clone()]
+        );
+
+        assert_eq!(
+            RatingsRepository::delete(&mut tx, created.id)
+                .await
+                .unwrap(),
+            Some((updated, vec![photo.clone()]))
+        );
+        // Photos go with their rating.
+        assert_eq!(tx.delete_photo(photo.id).await.unwrap(), None);
+
+        tx.rollback().await.unwrap();
+    }
+}
@@ -0,0 +1,39 @@
+use sqlx::{Postgres, Transaction};
+use tokio::sync::{Mutex, MutexGuard};
+
+/// A unit of work spanning any of the Postgres repositories. Dropping it
+/// without calling `commit` rolls back every write made through it.
+pub struct PostgresTransaction {
+    // Reads only get `&self`, but still need the connection mutably.
+    tx: Mutex<Transaction<'static, Postgres>>,
+}
+
+impl PostgresTransaction {
+    pub(super) fn new(tx: Transaction<'static, Postgres>) -> Self {
+        PostgresTransaction { tx: Mutex::new(tx) }
+    }
+
+    pub async fn commit(self) -> Result<(), String> {
+        match self.tx.into_inner().commit().await {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                eprintln!("error committing Postgres transaction: {}", e);
+                Err("Could not commit transaction".to_string())
+            }
+        }
+    }
+
+    pub async fn rollback(self) -> Result<(), String> {
+        match self.tx.into_inner().rollback().await {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                eprintln!("error rolling back Postgres transaction: {}", e);
+                Err("Could not roll back transaction".to_string())
+            }
+        }
+    }
+
+    pub(super) async fn lock(&self) -> MutexGuard<'_, Transaction<'static, Postgres>> {
+        self.tx.lock().await
+    }
+}
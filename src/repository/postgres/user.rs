@@ -0,0 +1,205 @@
+use axum::async_trait;
+use sqlx::{FromRow, PgExecutor};
+
+use crate::repository::user::{User, UserRepository};
+
+use super::{transaction::PostgresTransaction, PostgresRepo};
+
+#[derive(FromRow)]
+struct PostgresUser {
+    phone_number: i64,
+    first_name: String,
+    last_name: String,
+    is_verified: bool,
+}
+
+impl PostgresUser {
+    fn convert_to_user(self) -> User {
+        User {
+            first_name: self.first_name,
+            last_name: self.last_name,
+            phone_number: self.phone_number as u64,
+            is_verified: self.is_verified,
+        }
+    }
+}
+
+async fn create_user<'e, E: PgExecutor<'e>>(executor: E, user: User) -> Result<User, String> {
+    // The no-op update makes a conflicting insert return the stored row, which
+    // matches the Supabase repository handing back the existing user.
+    match sqlx::query_as::<_, PostgresUser>(
+        "INSERT INTO users (phone_number, first_name, last_name, is_verified)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (phone_number) DO UPDATE SET phone_number = users.phone_number
+        RETURNING *",
+    )
+    .bind(user.phone_number as i64)
+    .bind(&user.first_name)
+    .bind(&user.last_name)
+    .bind(user.is_verified)
+    .fetch_one(executor)
+    .await
+    {
+        Ok(u) => Ok(u.convert_to_user()),
+        Err(e) => {
+            eprintln!("error creating user in Postgres: {}", e);
+            Err("User not created".to_string())
+        }
+    }
+}
+
+async fn read_users<'e, E: PgExecutor<'e>>(
+    executor: E,
+    phone_number: u64,
+) -> Result<Vec<User>, String> {
+    match sqlx::query_as::<_, PostgresUser>("SELECT * FROM users WHERE phone_number = $1")
+        .bind(phone_number as i64)
+        .fetch_all(executor)
+        .await
+    {
+        Ok(users) => Ok(users.into_iter().map(|u| u.convert_to_user()).collect()),
+        Err(e) => {
+            eprintln!("error reading users from Postgres: {}", e);
+            Err("Could not read users".to_string())
+        }
+    }
+}
+
+async fn update_user<'e, E: PgExecutor<'e>>(executor: E, user: User) -> Result<User, String> {
+    match sqlx::query(
+        "UPDATE users SET first_name = $1, last_name = $2, is_verified = $3
+        WHERE phone_number = $4",
+    )
+    .bind(&user.first_name)
+    .bind(&user.last_name)
+    .bind(user.is_verified)
+    .bind(user.phone_number as i64)
+    .execute(executor)
+    .await
+    {
+        Ok(_) => Ok(user),
+        Err(e) => {
+            eprintln!("error updating user in Postgres: {}", e);
+            Err("User not updated".to_string())
+        }
+    }
+}
+
+async fn delete_user<'e, E: PgExecutor<'e>>(
+    executor: E,
+    phone_number: u64,
+) -> Result<Option<User>, String> {
+    match sqlx::query_as::<_, PostgresUser>("DELETE FROM users WHERE phone_number = $1 RETURNING *")
+        .bind(phone_number as i64)
+        .fetch_optional(executor)
+        .await
+    {
+        Ok(user) => Ok(user.map(|u| u.convert_to_user())),
+        Err(e) => {
+            eprintln!("error deleting user from Postgres: {}", e);
+            Err("User not deleted".to_string())
+        }
+    }
+}
+
+#[async_trait]
+impl UserRepository for PostgresRepo {
+    async fn create(&mut self, user: User) -> Result<User, String> {
+        create_user(&self.pool, user).await
+    }
+
+    async fn read(&self, phone_number: u64) -> Result<Vec<User>, String> {
+        read_users(&self.pool, phone_number).await
+    }
+
+    async fn update(&mut self, user: User) -> Result<User, String> {
+        update_user(&self.pool, user).await
+    }
+
+    async fn delete(&mut self, phone_number: u64) -> Result<Option<User>, String> {
+        delete_user(&self.pool, phone_number).await
+    }
+}
+
+#[async_trait]
+impl UserRepository for PostgresTransaction {
+    async fn create(&mut self, user: User) -> Result<User, String> {
+        let mut tx = self.lock().await;
+        create_user(&mut **tx, user).await
+    }
+
+    async fn read(&self, phone_number: u64) -> Result<Vec<User>, String> {
+        let mut tx = self.lock().await;
+        read_users(&mut **tx, phone_number).await
+    }
+
+    async fn update(&mut self, user: User) -> Result<User, String> {
+        let mut tx = self.lock().await;
+        update_user(&mut **tx, user).await
+    }
+
+    async fn delete(&mut self, phone_number: u64) -> Result<Option<User>, String> {
+        let mut tx = self.lock().await;
+        delete_user(&mut **tx, phone_number).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn repo() -> PostgresRepo {
+        dotenv::dotenv().ok();
+        let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set.");
+        PostgresRepo::new(&database_url, 2).await.unwrap()
+    }
+
+    fn user(phone_number: u64, is_verified: bool) -> User {
+        User {
+            first_name: "Hunter".to_string(),
+            last_name: "Simmons".to_string(),
+            phone_number,
+            is_verified,
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a Postgres database at DATABASE_URL"]
+    async fn test_create_read_update_delete() {
+        let mut user_repo = repo().await;
+        user_repo.delete(2028098682).await.unwrap();
+
+        user_repo.create(user(2028098682, false)).await.unwrap();
+        let existing = user_repo.create(user(2028098682, true)).await.unwrap();
+        assert_eq!(existing, user(2028098682, false));
+
+        user_repo.update(user(2028098682, true)).await.unwrap();
+        let users = user_repo.read(2028098682).await.unwrap();
+        assert_eq!(users, vec![user(2028098682, true)]);
+
+        let deleted = user_repo.delete(2028098682).await.unwrap();
+        assert_eq!(deleted, Some(user(2028098682, true)));
+        assert!(user_repo.read(2028098682).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a Postgres database at DATABASE_URL"]
+    async fn test_transaction_commit_and_rollback() {
+        let mut user_repo = repo().await;
+        user_repo.delete(2028098683).await.unwrap();
+
+        let mut tx = user_repo.begin().await.unwrap();
+        tx.create(user(2028098683, false)).await.unwrap();
+        assert_eq!(tx.read(2028098683).await.unwrap().len(), 1);
+        assert!(user_repo.read(2028098683).await.unwrap().is_empty());
+        tx.rollback().await.unwrap();
+        assert!(user_repo.read(2028098683).await.unwrap().is_empty());
+
+        let mut tx = user_repo.begin().await.unwrap();
+        tx.create(user(2028098683, false)).await.unwrap();
+        tx.commit().await.unwrap();
+        assert_eq!(user_repo.read(2028098683).await.unwrap().len(), 1);
+
+        user_repo.delete(2028098683).await.unwrap();
+    }
+}
@@ -0,0 +1,108 @@
+use std::{collections::HashMap, sync::Arc};
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+pub const MIN_SCORE: u8 = 1;
+pub const MAX_SCORE: u8 = 10;
+
+/// A user's score for a place. Each user has at most one rating per place.
+#[derive(Clone, Deserialize, Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Rating {
+    pub id: u64,
+    pub place_id: u64,
+    pub phone_number: u64,
+    pub score: u8,
+    pub review: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+pub struct ReadRatingOptions {
+    pub id: Option<u64>,
+    pub place_id: Option<u64>,
+    pub phone_number: Option<u64>,
+}
+
+/// A photo a user attached to their rating of a place. The image and its
+/// thumbnail are in the blob store under `key` and `thumbnail_key`.
+#[derive(Clone, Deserialize, Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RatingPhoto {
+    pub id: u64,
+    pub rating_id: u64,
+    pub place_id: u64,
+    pub phone_number: u64,
+    pub key: String,
+    pub thumbnail_key: String,
+    pub width: u32,
+    pub height: u32,
+    pub created_at: DateTime<Utc>,
+}
+
+pub struct ReadRatingPhotoOptions {
+    pub id: Option<u64>,
+    pub rating_id: Option<u64>,
+    pub place_id: Option<u64>,
+}
+
+#[derive(Clone, Copy, Deserialize, Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaceScore {
+    pub rating_count: u64,
+    pub average_score: f64,
+}
+
+pub type DynRatingsRepo = Arc<Mutex<dyn RatingsRepository>>;
+
+#[async_trait]
+pub trait RatingsRepository: Send + Sync + 'static {
+    /// Stores the rating, replacing the score, review and `updated_at` of the
+    /// user's earlier rating of the place if there is one.
+    async fn upsert(&mut self, rating: &Rating) -> Result<Rating, String>;
+    async fn read(&self, options: ReadRatingOptions) -> Result<Vec<Rating>, String>;
+    /// Deletes the rating along with its photos, returning both so the
+    /// photos' images can be removed from the blob store too.
+    async fn delete(&mut self, id: u64) -> Result<Option<(Rating, Vec<RatingPhoto>)>, String>;
+    /// The scores of those places with at least one rating.
+    async fn scores(&self, place_ids: &[u64]) -> Result<HashMap<u64, PlaceScore>, String>;
+    /// Adds the photos, all to the same rating, in one go: either every photo
+    /// is added or none is. Adds nothing and returns `None` when the rating
+    /// would then have more than `max_photos`.
+    async fn add_photos(
+        &mut self,
+        photos: &[RatingPhoto],
+        max_photos: usize,
+    ) -> Result<Option<Vec<RatingPhoto>>, String>;
+    /// Oldest first.
+    async fn read_photos(
+        &self,
+        options: ReadRatingPhotoOptions,
+    ) -> Result<Vec<RatingPhoto>, String>;
+    async fn delete_photo(&mut self, id: u64) -> Result<Option<RatingPhoto>, String>;
+}
+
+/// Averages the scores of each place, for stores that can't aggregate.
+pub fn average_scores(ratings: &[Rating]) -> HashMap<u64, PlaceScore> {
+    let mut totals: HashMap<u64, (u64, u64)> = HashMap::new();
+    for rating in ratings {
+        let total = totals.entry(rating.place_id).or_default();
+        total.0 += 1;
+        total.1 += u64::from(rating.score);
+    }
+    totals
+        .into_iter()
+        .map(|(place_id, (count, sum))| {
+            (
+                place_id,
+                PlaceScore {
+                    rating_count: count,
+                    average_score: sum as f64 / count as f64,
+                },
+            )
+        })
+        .collect()
+}
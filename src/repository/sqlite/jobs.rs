@@ -0,0 +1,328 @@
+use axum::async_trait;
+use chrono::{DateTime, SecondsFormat, Utc};
+use sqlx::FromRow;
+
+use crate::repository::jobs::{Job, JobKind, JobStatus, JobsRepository};
+
+use super::SqliteRepo;
+
+#[derive(FromRow)]
+struct SqliteJob {
+    id: i64,
+    kind: String,
+    place_id: i64,
+    status: String,
+    attempts: i64,
+    run_at: String,
+    last_error: Option<String>,
+    created_at: String,
+    updated_at: String,
+}
+
+/// Fixed width, so timestamps compare correctly as text.
+fn format_timestamp(timestamp: DateTime<Utc>) -> String {
+    timestamp.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+fn parse_timestamp(timestamp: &str) -> Result<DateTime<Utc>, String> {
+    match DateTime::parse_from_rfc3339(timestamp) {
+        Ok(t) => Ok(t.with_timezone(&Utc)),
+        Err(e) => {
+            eprintln!("invalid job timestamp {} in SQLite: {}", timestamp, e);
+            Err("Could not read jobs".to_string())
+        }
+    }
+}
+
+impl SqliteJob {
+    fn convert_to_job(self) -> Result<Job, String> {
+        Ok(Job {
+            id: self.id as u64,
+            kind: JobKind::parse(&self.kind)?,
+            place_id: self.place_id as u64,
+            status: JobStatus::parse(&self.status)?,
+            attempts: self.attempts as u32,
+            run_at: parse_timestamp(&self.run_at)?,
+            last_error: self.last_error,
+            created_at: parse_timestamp(&self.created_at)?,
+            updated_at: parse_timestamp(&self.updated_at)?,
+        })
+    }
+}
+
+#[async_trait]
+impl JobsRepository for SqliteRepo {
+    async fn enqueue(
+        &mut self,
+        kind: JobKind,
+        place_id: u64,
+        now: DateTime<Utc>,
+    ) -> Result<Job, String> {
+        let now = format_timestamp(now);
+        let created = sqlx::query_as::<_, SqliteJob>(
+            "INSERT INTO jobs (kind, place_id, status, run_at, created_at, updated_at)
+            VALUES (?, ?, 'pending', ?, ?, ?)
+            ON CONFLICT (kind, place_id) WHERE status <> 'dead' DO NOTHING
+            RETURNING *",
+        )
+        .bind(kind.as_str())
+        .bind(place_id as i64)
+        .bind(&now)
+        .bind(&now)
+        .bind(&now)
+        .fetch_optional(&self.pool)
+        .await;
+        let job = match created {
+            Ok(Some(job)) => Ok(job),
+            Ok(None) => {
+                sqlx::query_as::<_, SqliteJob>(
+                    "SELECT * FROM jobs WHERE kind = ? AND place_id = ? AND status <> 'dead'",
+                )
+                .bind(kind.as_str())
+                .bind(place_id as i64)
+                .fetch_one(&self.pool)
+                .await
+            }
+            Err(e) => Err(e),
+        };
+
+        match job {
+            Ok(j) => j.convert_to_job(),
+            Err(e) => {
+                eprintln!("error queueing job in SQLite: {}", e);
+                Err("Job not queued".to_string())
+            }
+        }
+    }
+
+    async fn claim(&mut self, now: DateTime<Utc>, limit: usize) -> Result<Vec<Job>, String> {
+        let now = format_timestamp(now);
+        match sqlx::query_as::<_, SqliteJob>(
+            "UPDATE jobs SET status = 'running', updated_at = ?
+            WHERE id IN (
+                SELECT id FROM jobs WHERE status = 'pending' AND run_at <= ?
+                ORDER BY run_at, id LIMIT ?
+            )
+            RETURNING *",
+        )
+        .bind(&now)
+        .bind(&now)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await
+        {
+            Ok(jobs) => {
+                let mut jobs = jobs
+                    .into_iter()
+                    .map(|j| j.convert_to_job())
+                    .collect::<Result<Vec<Job>, String>>()?;
+                jobs.sort_by_key(|j| (j.run_at, j.id));
+                Ok(jobs)
+            }
+            Err(e) => {
+                eprintln!("error claiming jobs in SQLite: {}", e);
+                Err("Could not claim jobs".to_string())
+            }
+        }
+    }
+
+    async fn complete(&mut self, id: u64) -> Result<(), String> {
+        match sqlx::query("DELETE FROM jobs WHERE id = ?")
+            .bind(id as i64)
+            .execute(&self.pool)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                eprintln!("error completing job in SQLite: {}", e);
+                Err("Job not completed".to_string())
+            }
+        }
+    }
+
+    async fn fail(
+        &mut self,
+        id: u64,
+        error: &str,
+        retry_at: Option<DateTime<Utc>>,
+        now: DateTime<Utc>,
+    ) -> Result<Job, String> {
+        let status = match retry_at {
+            Some(_) => JobStatus::Pending,
+            None => JobStatus::Dead,
+        };
+        match sqlx::query_as::<_, SqliteJob>(
+            "UPDATE jobs SET status = ?, attempts = attempts + 1,
+            run_at = COALESCE(?, run_at), last_error = ?, updated_at = ?
+            WHERE id = ?
+            RETURNING *",
+        )
+        .bind(status.as_str())
+        .bind(retry_at.map(format_timestamp))
+        .bind(error)
+        .bind(format_timestamp(now))
+        .bind(id as i64)
+        .fetch_optional(&self.pool)
+        .await
+        {
+            Ok(Some(j)) => j.convert_to_job(),
+            Ok(None) => Err("Job not found".to_string()),
+            Err(e) => {
+                eprintln!("error failing job in SQLite: {}", e);
+                Err("Job not updated".to_string())
+            }
+        }
+    }
+
+    async fn requeue_running(&mut self, claimed_before: DateTime<Utc>) -> Result<u64, String> {
+        match sqlx::query(
+            "UPDATE jobs SET status = 'pending' WHERE status = 'running' AND updated_at < ?",
+        )
+        .bind(format_timestamp(claimed_before))
+        .execute(&self.pool)
+        .await
+        {
+            Ok(result) => Ok(result.rows_affected()),
+            Err(e) => {
+                eprintln!("error requeueing jobs in SQLite: {}", e);
+                Err("Could not requeue jobs".to_string())
+            }
+        }
+    }
+
+    async fn dead_letters(&self) -> Result<Vec<Job>, String> {
+        match sqlx::query_as::<_, SqliteJob>(
+            "SELECT * FROM jobs WHERE status = 'dead' ORDER BY updated_at DESC, id DESC",
+        )
+        .fetch_all(&self.pool)
+        .await
+        {
+            Ok(jobs) => jobs.into_iter().map(|j| j.convert_to_job()).collect(),
+            Err(e) => {
+                eprintln!("error reading dead jobs from SQLite: {}", e);
+                Err("Could not read jobs".to_string())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use crate::{places::testing::place, repository::places::PlacesRepository};
+
+    use super::*;
+
+    async fn add_place(repo: &mut SqliteRepo, name: &str) -> u64 {
+        PlacesRepository::create(repo, &place(0, name))
+            .await
+            .unwrap()
+            .id
+    }
+
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2026-10-18T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[tokio::test]
+    async fn test_enqueue_dedupes_live_jobs() {
+        let mut repo = SqliteRepo::new("sqlite::memory:").await.unwrap();
+        let arlo = add_place(&mut repo, "Arlo").await;
+
+        let job = repo
+            .enqueue(JobKind::FetchPhotos, arlo, now())
+            .await
+            .unwrap();
+        assert_eq!(job.status, JobStatus::Pending);
+        assert_eq!(job.run_at, now());
+        let again = repo
+            .enqueue(JobKind::FetchPhotos, arlo, now() + Duration::seconds(1))
+            .await
+            .unwrap();
+        assert_eq!(again, job);
+
+        // Once dead, the place can be queued again.
+        repo.claim(now(), 10).await.unwrap();
+        repo.fail(job.id, "Error getting pictures", None, now())
+            .await
+            .unwrap();
+        let queued = repo
+            .enqueue(JobKind::FetchPhotos, arlo, now())
+            .await
+            .unwrap();
+        assert_ne!(queued.id, job.id);
+
+        assert!(repo
+            .enqueue(JobKind::FetchPhotos, 999, now())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_claim_retry_and_dead_letters() {
+        let mut repo = SqliteRepo::new("sqlite::memory:").await.unwrap();
+        let arlo = add_place(&mut repo, "Arlo").await;
+        let red_iguana = add_place(&mut repo, "Red Iguana").await;
+        let communal = add_place(&mut repo, "Communal").await;
+        for (place_id, seconds) in [(arlo, 2), (red_iguana, 0), (communal, 1)] {
+            repo.enqueue(
+                JobKind::FetchPhotos,
+                place_id,
+                now() + Duration::seconds(seconds),
+            )
+            .await
+            .unwrap();
+        }
+
+        let claimed = repo.claim(now() + Duration::seconds(5), 2).await.unwrap();
+        let place_ids: Vec<u64> = claimed.iter().map(|j| j.place_id).collect();
+        assert_eq!(place_ids, vec![red_iguana, communal]);
+        assert!(claimed.iter().all(|j| j.status == JobStatus::Running));
+
+        let retry_at = now() + Duration::seconds(30);
+        let retried = repo
+            .fail(
+                claimed[0].id,
+                "Error getting pictures",
+                Some(retry_at),
+                now(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(retried.status, JobStatus::Pending);
+        assert_eq!(retried.attempts, 1);
+        assert_eq!(retried.run_at, retry_at);
+        assert_eq!(
+            retried.last_error,
+            Some("Error getting pictures".to_string())
+        );
+        repo.complete(claimed[1].id).await.unwrap();
+
+        // Not due yet, and Arlo's job is still waiting.
+        let claimed = repo.claim(now() + Duration::seconds(29), 10).await.unwrap();
+        assert_eq!(claimed.len(), 1);
+        assert_eq!(claimed[0].place_id, arlo);
+
+        // A worker that stopped with Arlo's job running leaves it to the next
+        // one, once the job has been running for longer than any job takes.
+        let claimed_at = now() + Duration::seconds(29);
+        assert_eq!(repo.requeue_running(claimed_at).await.unwrap(), 0);
+        let lease_expired = claimed_at + Duration::seconds(1);
+        assert_eq!(repo.requeue_running(lease_expired).await.unwrap(), 1);
+        let claimed = repo.claim(retry_at, 10).await.unwrap();
+        let place_ids: Vec<u64> = claimed.iter().map(|j| j.place_id).collect();
+        assert_eq!(place_ids, vec![arlo, red_iguana]);
+
+        let dead = repo
+            .fail(claimed[1].id, "Error parsing JSON", None, retry_at)
+            .await
+            .unwrap();
+        assert_eq!(dead.status, JobStatus::Dead);
+        assert_eq!(dead.attempts, 2);
+        assert_eq!(repo.dead_letters().await.unwrap(), vec![dead]);
+        assert!(repo.fail(999, "error", None, now()).await.is_err());
+    }
+}
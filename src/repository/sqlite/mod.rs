@@ -0,0 +1,54 @@
+pub mod jobs;
+pub mod places;
+pub mod ratings;
+pub mod user;
+
+use std::str::FromStr;
+
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqlitePoolOptions},
+    SqlitePool,
+};
+
+#[derive(Clone)]
+pub struct SqliteRepo {
+    pool: SqlitePool,
+}
+
+impl SqliteRepo {
+    /// Connects to the SQLite database at `url` (e.g. `sqlite://critiq.db` or
+    /// `sqlite::memory:`), creating it if needed, and runs the embedded migrations.
+    pub async fn new(url: &str) -> Result<Self, String> {
+        let options = match SqliteConnectOptions::from_str(url) {
+            Ok(o) => o.create_if_missing(true).foreign_keys(true),
+            Err(e) => {
+                eprintln!("invalid SQLite url {}: {}", url, e);
+                return Err("Invalid SQLite url".to_string());
+            }
+        };
+
+        // Every connection to an in-memory database gets its own database, so
+        // the pool must hold on to a single connection for the data to be shared.
+        let max_connections = if url.contains(":memory:") { 1 } else { 5 };
+        let pool = match SqlitePoolOptions::new()
+            .max_connections(max_connections)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect_with(options)
+            .await
+        {
+            Ok(p) => p,
+            Err(e) => {
+                eprintln!("error connecting to SQLite: {}", e);
+                return Err("Could not connect to SQLite".to_string());
+            }
+        };
+
+        if let Err(e) = sqlx::migrate!("./migrations/sqlite").run(&pool).await {
+            eprintln!("error running SQLite migrations: {}", e);
+            return Err("Could not run SQLite migrations".to_string());
+        }
+
+        Ok(SqliteRepo { pool })
+    }
+}
@@ -0,0 +1,754 @@
+use axum::async_trait;
+use serde::Serialize;
+use sqlx::{FromRow, QueryBuilder, Sqlite};
+
+use crate::{
+    geo::{BoundingBox, Coordinates},
+    places::{Address, Place},
+    repository::places::{
+        closest_places, escape_like, NearbyPlace, NearbyPlaceOptions, PlacesRepository,
+        ReadPlaceOptions, SearchPlaceOptions, StalePlaceOptions,
+    },
+};
+
+use super::SqliteRepo;
+
+#[derive(FromRow)]
+struct SqlitePlace {
+    id: i64,
+    name: String,
+    address: String,
+    full_address: Option<String>,
+    country: Option<String>,
+    region: Option<String>,
+    postcode: Option<String>,
+    place: Option<String>,
+    street: Option<String>,
+    photos: Option<String>,
+    website: Option<String>,
+    foursquare_id: Option<String>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    categories: Option<String>,
+    details: Option<String>,
+    mapbox_id: Option<String>,
+}
+
+impl SqlitePlace {
+    fn convert_to_place(self) -> Place {
+        Place {
+            id: self.id as u64,
+            name: self.name,
+            address: Address {
+                address: self.address,
+                full_address: self.full_address,
+                country: self.country,
+                region: self.region,
+                postcode: self.postcode,
+                place: self.place,
+                street: self.street,
+            },
+            photos: self
+                .photos
+                .and_then(|photos| serde_json::from_str(&photos).ok()),
+            website: self.website,
+            foursquare_id: self.foursquare_id,
+            mapbox_id: self.mapbox_id,
+            coordinates: self
+                .latitude
+                .zip(self.longitude)
+                .map(|(latitude, longitude)| Coordinates {
+                    latitude,
+                    longitude,
+                }),
+            categories: self
+                .categories
+                .and_then(|categories| serde_json::from_str(&categories).ok()),
+            details: self
+                .details
+                .and_then(|details| serde_json::from_str(&details).ok()),
+        }
+    }
+}
+
+/// SQLite has no array or JSON type, so lists and details are stored as JSON
+/// text.
+fn encode_json<T: Serialize>(value: &Option<T>) -> Option<String> {
+    value
+        .as_ref()
+        .and_then(|value| serde_json::to_string(value).ok())
+}
+
+/// What a search's text is matched against: the name, street address,
+/// street and town.
+const SEARCHED_COLUMNS: [&str; 4] = ["name", "address", "street", "place"];
+
+fn push_within(query: &mut QueryBuilder<Sqlite>, bbox: &BoundingBox) {
+    query
+        .push("latitude BETWEEN ")
+        .push_bind(bbox.min_latitude)
+        .push(" AND ")
+        .push_bind(bbox.max_latitude)
+        .push(" AND longitude BETWEEN ")
+        .push_bind(bbox.min_longitude)
+        .push(" AND ")
+        .push_bind(bbox.max_longitude);
+}
+
+#[async_trait]
+impl PlacesRepository for SqliteRepo {
+    async fn create(&mut self, place: &Place) -> Result<Place, String> {
+        match sqlx::query_as::<_, SqlitePlace>(
+            "INSERT INTO places
+            (name, address, full_address, country, region, postcode, place, street, photos, website, foursquare_id, latitude, longitude, categories, details, mapbox_id)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT (address) DO NOTHING
+            RETURNING *",
+        )
+        .bind(&place.name)
+        .bind(&place.address.address)
+        .bind(&place.address.full_address)
+        .bind(&place.address.country)
+        .bind(&place.address.region)
+        .bind(&place.address.postcode)
+        .bind(&place.address.place)
+        .bind(&place.address.street)
+        .bind(encode_json(&place.photos))
+        .bind(&place.website)
+        .bind(&place.foursquare_id)
+        .bind(place.coordinates.map(|c| c.latitude))
+        .bind(place.coordinates.map(|c| c.longitude))
+        .bind(encode_json(&place.categories))
+        .bind(encode_json(&place.details))
+        .bind(&place.mapbox_id)
+        .fetch_optional(&self.pool)
+        .await
+        {
+            Ok(Some(p)) => Ok(p.convert_to_place()),
+            Ok(None) => {
+                let places = self
+                    .read(ReadPlaceOptions {
+                        id: None,
+                        name: None,
+                        address: Some(place.address.address.clone()),
+                        postcode: None,
+                    })
+                    .await?;
+                match places.into_iter().next() {
+                    Some(p) => Ok(p),
+                    None => Err("Expected len of places to be greater than 0".to_string()),
+                }
+            }
+            Err(e) => {
+                eprintln!("error creating place in SQLite: {}", e);
+                Err("Place not created".to_string())
+            }
+        }
+    }
+
+    async fn read(&self, options: ReadPlaceOptions) -> Result<Vec<Place>, String> {
+        let mut query: QueryBuilder<Sqlite> = QueryBuilder::new("SELECT * FROM places WHERE 1 = 1");
+        if let Some(id) = options.id {
+            query.push(" AND id = ").push_bind(id as i64);
+        };
+        if let Some(address) = options.address {
+            query.push(" AND address = ").push_bind(address);
+        };
+        if let Some(name) = options.name {
+            query.push(" AND name = ").push_bind(name);
+        };
+        if let Some(postcode) = options.postcode {
+            query.push(" AND postcode = ").push_bind(postcode);
+        };
+
+        match query
+            .build_query_as::<SqlitePlace>()
+            .fetch_all(&self.pool)
+            .await
+        {
+            Ok(places) => Ok(places.into_iter().map(|p| p.convert_to_place()).collect()),
+            Err(e) => {
+                eprintln!("error reading places from SQLite: {}", e);
+                Err("Could not read places".to_string())
+            }
+        }
+    }
+
+    async fn read_many(&self, ids: &[u64]) -> Result<Vec<Place>, String> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut query: QueryBuilder<Sqlite> =
+            QueryBuilder::new("SELECT * FROM places WHERE id IN (");
+        let mut separated = query.separated(", ");
+        for id in ids {
+            separated.push_bind(*id as i64);
+        }
+        query.push(")");
+
+        match query
+            .build_query_as::<SqlitePlace>()
+            .fetch_all(&self.pool)
+            .await
+        {
+            Ok(places) => Ok(places.into_iter().map(|p| p.convert_to_place()).collect()),
+            Err(e) => {
+                eprintln!("error reading places from SQLite: {}", e);
+                Err("Could not read places".to_string())
+            }
+        }
+    }
+
+    async fn update(&mut self, place: Place) -> Result<Place, String> {
+        match sqlx::query(
+            "UPDATE places SET
+            name = ?, address = ?, full_address = ?, country = ?, region = ?, postcode = ?,
+            place = ?, street = ?, photos = ?, website = ?, foursquare_id = ?,
+            latitude = ?, longitude = ?, categories = ?, details = ?, mapbox_id = ?
+            WHERE id = ?",
+        )
+        .bind(&place.name)
+        .bind(&place.address.address)
+        .bind(&place.address.full_address)
+        .bind(&place.address.country)
+        .bind(&place.address.region)
+        .bind(&place.address.postcode)
+        .bind(&place.address.place)
+        .bind(&place.address.street)
+        .bind(encode_json(&place.photos))
+        .bind(&place.website)
+        .bind(&place.foursquare_id)
+        .bind(place.coordinates.map(|c| c.latitude))
+        .bind(place.coordinates.map(|c| c.longitude))
+        .bind(encode_json(&place.categories))
+        .bind(encode_json(&place.details))
+        .bind(&place.mapbox_id)
+        .bind(place.id as i64)
+        .execute(&self.pool)
+        .await
+        {
+            Ok(_) => Ok(place),
+            Err(e) => {
+                eprintln!("error updating place in SQLite: {}", e);
+                Err("Place not updated".to_string())
+            }
+        }
+    }
+
+    async fn delete(&mut self, id: u64) -> Result<Option<Place>, String> {
+        match sqlx::query_as::<_, SqlitePlace>("DELETE FROM places WHERE id = ? RETURNING *")
+            .bind(id as i64)
+            .fetch_optional(&self.pool)
+            .await
+        {
+            Ok(place) => Ok(place.map(|p| p.convert_to_place())),
+            Err(e) => {
+                eprintln!("error deleting place from SQLite: {}", e);
+                Err("Place not deleted".to_string())
+            }
+        }
+    }
+
+    async fn search(&self, options: SearchPlaceOptions) -> Result<Vec<Place>, String> {
+        let mut query: QueryBuilder<Sqlite> = QueryBuilder::new("SELECT * FROM places WHERE ");
+        if options.text.chars().count() >= 3 {
+            // A quoted phrase matches as a substring in the trigram index,
+            // see `migrations/sqlite`, without case.
+            query
+                .push("id IN (SELECT rowid FROM places_search WHERE places_search MATCH ")
+                .push_bind(format!("\"{}\"", options.text.replace('"', "\"\"")))
+                .push(")");
+        } else {
+            // The index can't match fewer than three characters. SQLite's LIKE
+            // is already case-insensitive for ASCII.
+            let pattern = format!("%{}%", escape_like(&options.text));
+            query.push("(");
+            for (i, column) in SEARCHED_COLUMNS.iter().enumerate() {
+                if i > 0 {
+                    query.push(" OR ");
+                }
+                query
+                    .push(column)
+                    .push(" LIKE ")
+                    .push_bind(pattern.clone())
+                    .push(" ESCAPE '\\'");
+            }
+            query.push(")");
+        }
+        if let Some(bbox) = options.bounding_box {
+            query.push(" AND ((");
+            push_within(&mut query, &bbox);
+            query.push(")");
+            if options.include_unlocated {
+                query.push(
+                    " OR (latitude IS NULL AND (place, region) IN (
+                        SELECT place, region FROM places WHERE ",
+                );
+                push_within(&mut query, &bbox);
+                query.push("))");
+            }
+            query.push(")");
+        };
+        match options.closest_to {
+            // Degrees of longitude shrink away from the equator. Scaling them is
+            // close enough to order by without trigonometry.
+            Some(center) => {
+                let scale = center.latitude.to_radians().cos().powi(2);
+                query
+                    .push(" ORDER BY latitude IS NULL, (latitude - ")
+                    .push_bind(center.latitude)
+                    .push(") * (latitude - ")
+                    .push_bind(center.latitude)
+                    .push(") + (longitude - ")
+                    .push_bind(center.longitude)
+                    .push(") * (longitude - ")
+                    .push_bind(center.longitude)
+                    .push(") * ")
+                    .push_bind(scale)
+                    .push(", id");
+            }
+            None => {
+                query.push(" ORDER BY name");
+            }
+        }
+        query.push(" LIMIT ").push_bind(options.limit as i64);
+
+        match query
+            .build_query_as::<SqlitePlace>()
+            .fetch_all(&self.pool)
+            .await
+        {
+            Ok(places) => Ok(places.into_iter().map(|p| p.convert_to_place()).collect()),
+            Err(e) => {
+                eprintln!("error searching places in SQLite: {}", e);
+                Err("Could not search places".to_string())
+            }
+        }
+    }
+
+    /// SQLite has no trigonometry, so the bounding box narrows the places down
+    /// and the distances are worked out here.
+    async fn nearby(&self, options: NearbyPlaceOptions) -> Result<Vec<NearbyPlace>, String> {
+        let bbox = options.center.bounding_box(options.radius_meters);
+        match sqlx::query_as::<_, SqlitePlace>(
+            "SELECT * FROM places
+            WHERE latitude BETWEEN ? AND ? AND longitude BETWEEN ? AND ?",
+        )
+        .bind(bbox.min_latitude)
+        .bind(bbox.max_latitude)
+        .bind(bbox.min_longitude)
+        .bind(bbox.max_longitude)
+        .fetch_all(&self.pool)
+        .await
+        {
+            Ok(places) => Ok(closest_places(
+                places.into_iter().map(|p| p.convert_to_place()).collect(),
+                &options,
+            )),
+            Err(e) => {
+                eprintln!("error finding nearby places in SQLite: {}", e);
+                Err("Could not find nearby places".to_string())
+            }
+        }
+    }
+
+    async fn stale(&self, options: StalePlaceOptions) -> Result<Vec<Place>, String> {
+        match sqlx::query_as::<_, SqlitePlace>(
+            "SELECT * FROM places
+            WHERE details IS NULL
+                OR julianday(json_extract(details, '$.updatedAt')) < julianday(?)
+            ORDER BY details IS NOT NULL, julianday(json_extract(details, '$.updatedAt')), id
+            LIMIT ?",
+        )
+        .bind(options.updated_before.to_rfc3339())
+        .bind(options.limit as i64)
+        .fetch_all(&self.pool)
+        .await
+        {
+            Ok(places) => Ok(places.into_iter().map(|p| p.convert_to_place()).collect()),
+            Err(e) => {
+                eprintln!("error finding stale places in SQLite: {}", e);
+                Err("Could not find stale places".to_string())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use crate::{
+        geo::BoundingBox,
+        places::{photo::Photo, OpeningHours, OpeningPeriod, PlaceDetails},
+    };
+
+    use super::*;
+
+    fn arlo(address: &str) -> Place {
+        Place {
+            id: 0,
+            name: "Arlo".to_string(),
+            address: Address {
+                address: address.to_string(),
+                full_address: Some(address.to_string()),
+                country: Some("USA".to_string()),
+                region: Some("Utah".to_string()),
+                postcode: Some("84106".to_string()),
+                place: Some("Salt Lake City".to_string()),
+                street: Some("N Fake Street".to_string()),
+            },
+            photos: None,
+            website: None,
+            foursquare_id: Some("4b5a0c1ef964a520a0a928e3".to_string()),
+            mapbox_id: Some("mapbox-arlo".to_string()),
+            coordinates: Some(Coordinates {
+                latitude: 40.7608,
+                longitude: -111.891,
+            }),
+            categories: Some(vec!["american".to_string()]),
+            details: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_create_and_read() {
+        let mut repo = SqliteRepo::new("sqlite::memory:").await.unwrap();
+
+        let created = repo.create(&arlo("123 N Fake Street")).await.unwrap();
+        assert_ne!(created.id, 0);
+
+        let places = repo
+            .read(ReadPlaceOptions {
+                id: None,
+                name: Some("Arlo".to_string()),
+                address: None,
+                postcode: Some("84106".to_string()),
+            })
+            .await
+            .unwrap();
+        assert_eq!(places.len(), 1);
+        assert_eq!(places[0].id, created.id);
+        assert_eq!(places[0].address.address, "123 N Fake Street");
+        assert_eq!(
+            places[0].foursquare_id,
+            Some("4b5a0c1ef964a520a0a928e3".to_string())
+        );
+        assert_eq!(places[0].coordinates, arlo("").coordinates);
+        assert_eq!(places[0].categories, arlo("").categories);
+        assert_eq!(places[0].mapbox_id, arlo("").mapbox_id);
+    }
+
+    #[tokio::test]
+    async fn test_read_many() {
+        let mut repo = SqliteRepo::new("sqlite::memory:").await.unwrap();
+        let first = repo.create(&arlo("123 N Fake Street")).await.unwrap();
+        let second = repo.create(&arlo("456 N Fake Street")).await.unwrap();
+        repo.create(&arlo("789 N Fake Street")).await.unwrap();
+
+        let mut ids: Vec<u64> = repo
+            .read_many(&[second.id, first.id, 999])
+            .await
+            .unwrap()
+            .iter()
+            .map(|p| p.id)
+            .collect();
+        ids.sort();
+        assert_eq!(ids, vec![first.id, second.id]);
+        assert!(repo.read_many(&[]).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_create_existing_address_returns_stored_place() {
+        let mut repo = SqliteRepo::new("sqlite::memory:").await.unwrap();
+
+        let first = repo.create(&arlo("123 N Fake Street")).await.unwrap();
+        let second = repo.create(&arlo("123 N Fake Street")).await.unwrap();
+
+        assert_eq!(first.id, second.id);
+    }
+
+    #[tokio::test]
+    async fn test_update() {
+        let mut repo = SqliteRepo::new("sqlite::memory:").await.unwrap();
+
+        let mut place = repo.create(&arlo("123 N Fake Street")).await.unwrap();
+        place.address.address = "123 N Another Fake Street".to_string();
+        place.photos = Some(vec![
+            Photo {
+                prefix: "https://fastly.4sqi.net/img/general/".to_string(),
+                suffix: "/1.jpg".to_string(),
+            },
+            Photo {
+                prefix: "https://fastly.4sqi.net/img/general/".to_string(),
+                suffix: "/2.jpg".to_string(),
+            },
+        ]);
+        place.details = Some(PlaceDetails {
+            hours: Some(OpeningHours {
+                display: Some("Tue-Sat 17:00-22:00".to_string()),
+                periods: vec![OpeningPeriod {
+                    day: 2,
+                    open: "1700".to_string(),
+                    close: "2200".to_string(),
+                }],
+            }),
+            price: Some(3),
+            phone: Some("(385) 266-8845".to_string()),
+            social: None,
+            timezone: Some("America/Denver".to_string()),
+            updated_at: Utc::now(),
+        });
+        repo.update(place.clone()).await.unwrap();
+
+        let places = repo
+            .read(ReadPlaceOptions {
+                id: Some(place.id),
+                name: None,
+                address: None,
+                postcode: None,
+            })
+            .await
+            .unwrap();
+        assert_eq!(places[0].address.address, "123 N Another Fake Street");
+        assert_eq!(places[0].photos, place.photos);
+        assert_eq!(places[0].details, place.details);
+    }
+
+    #[tokio::test]
+    async fn test_stale() {
+        let mut repo = SqliteRepo::new("sqlite::memory:").await.unwrap();
+        let now = Utc::now();
+        let details = |days: i64| PlaceDetails {
+            hours: None,
+            price: None,
+            phone: None,
+            social: None,
+            timezone: None,
+            updated_at: now - chrono::Duration::days(days),
+        };
+        let mut fresh = arlo_place_named("Fresh", "1 Fresh Street");
+        fresh.details = Some(details(1));
+        let mut old = arlo_place_named("Old", "1 Old Street");
+        old.details = Some(details(40));
+        let mut older = arlo_place_named("Older", "1 Older Street");
+        older.details = Some(details(60));
+        let missing = arlo_place_named("Missing", "1 Missing Street");
+        // Places from other providers get refreshed as well.
+        let mut osm = arlo_place_named("OpenStreetMap", "1 OpenStreetMap Street");
+        osm.foursquare_id = None;
+        for place in [fresh, old, older, missing, osm] {
+            repo.create(&place).await.unwrap();
+        }
+
+        let stale = repo
+            .stale(StalePlaceOptions {
+                updated_before: now - chrono::Duration::days(30),
+                limit: 10,
+            })
+            .await
+            .unwrap();
+        let names: Vec<&str> = stale.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["Missing", "OpenStreetMap", "Older", "Old"]);
+
+        let stale = repo
+            .stale(StalePlaceOptions {
+                updated_before: now,
+                limit: 2,
+            })
+            .await
+            .unwrap();
+        assert_eq!(stale.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_delete() {
+        let mut repo = SqliteRepo::new("sqlite::memory:").await.unwrap();
+
+        let place = repo.create(&arlo("123 N Fake Street")).await.unwrap();
+
+        let deleted = repo.delete(place.id).await.unwrap().unwrap();
+        assert_eq!(deleted.name, "Arlo");
+        assert!(repo.delete(place.id).await.unwrap().is_none());
+    }
+
+    fn search_options(text: &str, bounding_box: Option<BoundingBox>) -> SearchPlaceOptions {
+        SearchPlaceOptions {
+            text: text.to_string(),
+            bounding_box,
+            include_unlocated: false,
+            closest_to: None,
+            limit: 10,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_search() {
+        let mut repo = SqliteRepo::new("sqlite::memory:").await.unwrap();
+        let arlo = repo.create(&arlo("123 N Fake Street")).await.unwrap();
+        let mut percent = arlo_place_named("100% Tacos", "1 Percent Street");
+        percent.coordinates = None;
+        repo.create(&percent).await.unwrap();
+
+        let places = repo.search(search_options("ARL", None)).await.unwrap();
+        assert_eq!(places.len(), 1);
+        assert_eq!(places[0].id, arlo.id);
+
+        let places = repo.search(search_options("0% t", None)).await.unwrap();
+        assert_eq!(places[0].name, "100% Tacos");
+        assert!(repo
+            .search(search_options("1_0", None))
+            .await
+            .unwrap()
+            .is_empty());
+
+        let near = arlo.coordinates.unwrap().bounding_box(1_000.0);
+        let far = Coordinates {
+            latitude: 0.0,
+            longitude: 0.0,
+        }
+        .bounding_box(1_000.0);
+        assert_eq!(
+            repo.search(search_options("arlo", Some(near)))
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(repo
+            .search(search_options("arlo", Some(far)))
+            .await
+            .unwrap()
+            .is_empty());
+
+        // Unlocated places only match a bounding box when asked for, and
+        // only in the towns of located places inside it.
+        assert!(repo
+            .search(search_options("tacos", Some(near)))
+            .await
+            .unwrap()
+            .is_empty());
+        let unlocated = |bounding_box| SearchPlaceOptions {
+            include_unlocated: true,
+            ..search_options("a", Some(bounding_box))
+        };
+        let places = repo.search(unlocated(near)).await.unwrap();
+        assert_eq!(places.len(), 2);
+        assert!(repo.search(unlocated(far)).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_search_streets_and_towns() {
+        let mut repo = SqliteRepo::new("sqlite::memory:").await.unwrap();
+        let arlo = repo.create(&arlo("271 N Center St")).await.unwrap();
+        let ids = |places: Vec<Place>| places.iter().map(|p| p.id).collect::<Vec<_>>();
+
+        for text in ["center st", "fake street", "salt lake", "Sa", "27"] {
+            let found = repo.search(search_options(text, None)).await.unwrap();
+            assert_eq!(ids(found), vec![arlo.id], "{}", text);
+        }
+
+        // The index follows renames.
+        repo.update(Place {
+            name: "Arlo Bakehouse".to_string(),
+            ..arlo.clone()
+        })
+        .await
+        .unwrap();
+        let found = repo
+            .search(search_options("bakehouse", None))
+            .await
+            .unwrap();
+        assert_eq!(ids(found), vec![arlo.id]);
+        repo.delete(arlo.id).await.unwrap();
+        assert!(repo
+            .search(search_options("bakehouse", None))
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_search_closest_first() {
+        let mut repo = SqliteRepo::new("sqlite::memory:").await.unwrap();
+        let arlo = repo.create(&arlo("123 N Fake Street")).await.unwrap();
+        let center = arlo.coordinates.unwrap();
+        let mut unlocated = arlo_place_named("Arlo Unlocated", "1 Unlocated Street");
+        unlocated.coordinates = None;
+        let unlocated = repo.create(&unlocated).await.unwrap();
+        let mut far = arlo_place_named("Arlo Far", "1 Far Street");
+        far.coordinates = Some(Coordinates {
+            longitude: center.longitude + 0.01,
+            ..center
+        });
+        let far = repo.create(&far).await.unwrap();
+        let mut close = arlo_place_named("Arlo Close", "1 Close Street");
+        close.coordinates = Some(Coordinates {
+            latitude: center.latitude + 0.005,
+            ..center
+        });
+        let close = repo.create(&close).await.unwrap();
+
+        let search = |limit| SearchPlaceOptions {
+            include_unlocated: true,
+            closest_to: Some(center),
+            limit,
+            ..search_options("arlo", Some(center.bounding_box(5_000.0)))
+        };
+        let ids = |places: Vec<Place>| places.iter().map(|p| p.id).collect::<Vec<_>>();
+        assert_eq!(
+            ids(repo.search(search(10)).await.unwrap()),
+            vec![arlo.id, close.id, far.id, unlocated.id]
+        );
+        // The limit keeps the closest, whatever their names.
+        assert_eq!(
+            ids(repo.search(search(2)).await.unwrap()),
+            vec![arlo.id, close.id]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_nearby() {
+        let mut repo = SqliteRepo::new("sqlite::memory:").await.unwrap();
+        let arlo = repo.create(&arlo("123 N Fake Street")).await.unwrap();
+        let center = arlo.coordinates.unwrap();
+        let mut close = arlo_place_named("Close", "1 Close Street");
+        close.coordinates = Some(Coordinates {
+            latitude: center.latitude + 0.001,
+            ..center
+        });
+        let close = repo.create(&close).await.unwrap();
+        let mut corner = arlo_place_named("Corner", "1 Corner Street");
+        corner.coordinates = Some(Coordinates {
+            latitude: center.latitude + 0.0085,
+            longitude: center.longitude + 0.0115,
+        });
+        repo.create(&corner).await.unwrap();
+        let mut unlocated = arlo_place_named("Unlocated", "1 Unlocated Street");
+        unlocated.coordinates = None;
+        repo.create(&unlocated).await.unwrap();
+
+        let nearby = repo
+            .nearby(NearbyPlaceOptions {
+                center,
+                radius_meters: 1_000.0,
+                limit: 10,
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(
+            nearby.iter().map(|n| n.place.id).collect::<Vec<_>>(),
+            vec![arlo.id, close.id]
+        );
+        assert_eq!(nearby[0].distance_meters, 0.0);
+        assert!((100.0..120.0).contains(&nearby[1].distance_meters));
+    }
+
+    fn arlo_place_named(name: &str, address: &str) -> Place {
+        Place {
+            name: name.to_string(),
+            ..arlo(address)
+        }
+    }
+}
@@ -0,0 +1,555 @@
+use std::collections::HashMap;
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{FromRow, QueryBuilder, Sqlite};
+
+use crate::repository::ratings::{
+    PlaceScore, Rating, RatingPhoto, RatingsRepository, ReadRatingOptions, ReadRatingPhotoOptions,
+};
+
+use super::SqliteRepo;
+
+#[derive(FromRow)]
+struct SqliteRating {
+    id: i64,
+    place_id: i64,
+    phone_number: i64,
+    score: i64,
+    review: Option<String>,
+    created_at: String,
+    updated_at: String,
+}
+
+#[derive(FromRow)]
+struct SqliteRatingPhoto {
+    id: i64,
+    rating_id: i64,
+    place_id: i64,
+    phone_number: i64,
+    key: String,
+    thumbnail_key: String,
+    width: i64,
+    height: i64,
+    created_at: String,
+}
+
+#[derive(FromRow)]
+struct SqlitePlaceScore {
+    place_id: i64,
+    rating_count: i64,
+    average_score: f64,
+}
+
+fn parse_timestamp(timestamp: &str) -> Result<DateTime<Utc>, String> {
+    match DateTime::parse_from_rfc3339(timestamp) {
+        Ok(t) => Ok(t.with_timezone(&Utc)),
+        Err(e) => {
+            eprintln!("invalid rating timestamp {} in SQLite: {}", timestamp, e);
+            Err("Could not read ratings".to_string())
+        }
+    }
+}
+
+impl SqliteRating {
+    fn convert_to_rating(self) -> Result<Rating, String> {
+        Ok(Rating {
+            id: self.id as u64,
+            place_id: self.place_id as u64,
+            phone_number: self.phone_number as u64,
+            score: self.score as u8,
+            review: self.review,
+            created_at: parse_timestamp(&self.created_at)?,
+            updated_at: parse_timestamp(&self.updated_at)?,
+        })
+    }
+}
+
+impl SqliteRatingPhoto {
+    fn convert_to_photo(self) -> Result<RatingPhoto, String> {
+        Ok(RatingPhoto {
+            id: self.id as u64,
+            rating_id: self.rating_id as u64,
+            place_id: self.place_id as u64,
+            phone_number: self.phone_number as u64,
+            key: self.key,
+            thumbnail_key: self.thumbnail_key,
+            width: self.width as u32,
+            height: self.height as u32,
+            created_at: parse_timestamp(&self.created_at)?,
+        })
+    }
+}
+
+#[async_trait]
+impl RatingsRepository for SqliteRepo {
+    async fn upsert(&mut self, rating: &Rating) -> Result<Rating, String> {
+        match sqlx::query_as::<_, SqliteRating>(
+            "INSERT INTO ratings
+            (place_id, phone_number, score, review, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            ON CONFLICT (place_id, phone_number) DO UPDATE SET
+            score = excluded.score, review = excluded.review, updated_at = excluded.updated_at
+            RETURNING *",
+        )
+        .bind(rating.place_id as i64)
+        .bind(rating.phone_number as i64)
+        .bind(i64::from(rating.score))
+        .bind(&rating.review)
+        .bind(rating.created_at.to_rfc3339())
+        .bind(rating.updated_at.to_rfc3339())
+        .fetch_one(&self.pool)
+        .await
+        {
+            Ok(r) => r.convert_to_rating(),
+            Err(e) => {
+                eprintln!("error storing rating in SQLite: {}", e);
+                Err("Rating not stored".to_string())
+            }
+        }
+    }
+
+    async fn read(&self, options: ReadRatingOptions) -> Result<Vec<Rating>, String> {
+        let mut query: QueryBuilder<Sqlite> =
+            QueryBuilder::new("SELECT * FROM ratings WHERE 1 = 1");
+        if let Some(id) = options.id {
+            query.push(" AND id = ").push_bind(id as i64);
+        };
+        if let Some(place_id) = options.place_id {
+            query.push(" AND place_id = ").push_bind(place_id as i64);
+        };
+        if let Some(phone_number) = options.phone_number {
+            query
+                .push(" AND phone_number = ")
+                .push_bind(phone_number as i64);
+        };
+        query.push(" ORDER BY id");
+
+        match query
+            .build_query_as::<SqliteRating>()
+            .fetch_all(&self.pool)
+            .await
+        {
+            Ok(ratings) => ratings.into_iter().map(|r| r.convert_to_rating()).collect(),
+            Err(e) => {
+                eprintln!("error reading ratings from SQLite: {}", e);
+                Err("Could not read ratings".to_string())
+            }
+        }
+    }
+
+    async fn delete(&mut self, id: u64) -> Result<Option<(Rating, Vec<RatingPhoto>)>, String> {
+        let mut tx = match self.pool.begin().await {
+            Ok(tx) => tx,
+            Err(e) => {
+                eprintln!("error starting SQLite transaction: {}", e);
+                return Err("Rating not deleted".to_string());
+            }
+        };
+
+        // Deleting the photos first takes the database's write lock, so none
+        // can be added between reading them and deleting the rating.
+        let photos = match sqlx::query_as::<_, SqliteRatingPhoto>(
+            "DELETE FROM rating_photos WHERE rating_id = ? RETURNING *",
+        )
+        .bind(id as i64)
+        .fetch_all(&mut *tx)
+        .await
+        {
+            Ok(photos) => photos
+                .into_iter()
+                .map(|p| p.convert_to_photo())
+                .collect::<Result<Vec<_>, _>>()?,
+            Err(e) => {
+                eprintln!("error deleting rating photos from SQLite: {}", e);
+                return Err("Rating not deleted".to_string());
+            }
+        };
+        let rating =
+            match sqlx::query_as::<_, SqliteRating>("DELETE FROM ratings WHERE id = ? RETURNING *")
+                .bind(id as i64)
+                .fetch_optional(&mut *tx)
+                .await
+            {
+                Ok(rating) => rating.map(|r| r.convert_to_rating()).transpose()?,
+                Err(e) => {
+                    eprintln!("error deleting rating from SQLite: {}", e);
+                    return Err("Rating not deleted".to_string());
+                }
+            };
+
+        match tx.commit().await {
+            Ok(_) => Ok(rating.map(|r| (r, photos))),
+            Err(e) => {
+                eprintln!("error committing rating deletion in SQLite: {}", e);
+                Err("Rating not deleted".to_string())
+            }
+        }
+    }
+
+    async fn scores(&self, place_ids: &[u64]) -> Result<HashMap<u64, PlaceScore>, String> {
+        if place_ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let mut query: QueryBuilder<Sqlite> = QueryBuilder::new(
+            "SELECT place_id, COUNT(*) AS rating_count, AVG(score) AS average_score
+            FROM ratings WHERE place_id IN (",
+        );
+        let mut ids = query.separated(", ");
+        for id in place_ids {
+            ids.push_bind(*id as i64);
+        }
+        query.push(") GROUP BY place_id");
+
+        match query
+            .build_query_as::<SqlitePlaceScore>()
+            .fetch_all(&self.pool)
+            .await
+        {
+            Ok(scores) => Ok(scores
+                .into_iter()
+                .map(|s| {
+                    (
+                        s.place_id as u64,
+                        PlaceScore {
+                            rating_count: s.rating_count as u64,
+                            average_score: s.average_score,
+                        },
+                    )
+                })
+                .collect()),
+            Err(e) => {
+                eprintln!("error reading scores from SQLite: {}", e);
+                Err("Could not read scores".to_string())
+            }
+        }
+    }
+
+    async fn add_photos(
+        &mut self,
+        photos: &[RatingPhoto],
+        max_photos: usize,
+    ) -> Result<Option<Vec<RatingPhoto>>, String> {
+        let mut tx = match self.pool.begin().await {
+            Ok(tx) => tx,
+            Err(e) => {
+                eprintln!("error starting SQLite transaction: {}", e);
+                return Err("Photo not stored".to_string());
+            }
+        };
+
+        // Each insert counts the rating's photos itself. The first one takes
+        // the database's write lock, so no other upload can add photos
+        // between the count and the commit.
+        let mut added = Vec::with_capacity(photos.len());
+        for photo in photos {
+            let inserted = sqlx::query_as::<_, SqliteRatingPhoto>(
+                "INSERT INTO rating_photos
+                (rating_id, place_id, phone_number, key, thumbnail_key, width, height, created_at)
+                SELECT ?, ?, ?, ?, ?, ?, ?, ?
+                WHERE (SELECT COUNT(*) FROM rating_photos WHERE rating_id = ?) < ?
+                RETURNING *",
+            )
+            .bind(photo.rating_id as i64)
+            .bind(photo.place_id as i64)
+            .bind(photo.phone_number as i64)
+            .bind(&photo.key)
+            .bind(&photo.thumbnail_key)
+            .bind(i64::from(photo.width))
+            .bind(i64::from(photo.height))
+            .bind(photo.created_at.to_rfc3339())
+            .bind(photo.rating_id as i64)
+            .bind(max_photos as i64)
+            .fetch_optional(&mut *tx)
+            .await;
+            match inserted {
+                Ok(Some(p)) => added.push(p.convert_to_photo()?),
+                // Dropping the transaction rolls back the photos already added.
+                Ok(None) => return Ok(None),
+                Err(e) => {
+                    eprintln!("error storing rating photo in SQLite: {}", e);
+                    return Err("Photo not stored".to_string());
+                }
+            }
+        }
+
+        match tx.commit().await {
+            Ok(_) => Ok(Some(added)),
+            Err(e) => {
+                eprintln!("error committing rating photos in SQLite: {}", e);
+                Err("Photo not stored".to_string())
+            }
+        }
+    }
+
+    async fn read_photos(
+        &self,
+        options: ReadRatingPhotoOptions,
+    ) -> Result<Vec<RatingPhoto>, String> {
+        let mut query: QueryBuilder<Sqlite> =
+            QueryBuilder::new("SELECT * FROM rating_photos WHERE 1 = 1");
+        if let Some(id) = options.id {
+            query.push(" AND id = ").push_bind(id as i64);
+        };
+        if let Some(rating_id) = options.rating_id {
+            query.push(" AND rating_id = ").push_bind(rating_id as i64);
+        };
+        if let Some(place_id) = options.place_id {
+            query.push(" AND place_id = ").push_bind(place_id as i64);
+        };
+        query.push(" ORDER BY id");
+
+        match query
+            .build_query_as::<SqliteRatingPhoto>()
+            .fetch_all(&self.pool)
+            .await
+        {
+            Ok(photos) => photos.into_iter().map(|p| p.convert_to_photo()).collect(),
+            Err(e) => {
+                eprintln!("error reading rating photos from SQLite: {}", e);
+                Err("Could not read photos".to_string())
+            }
+        }
+    }
+
+    async fn delete_photo(&mut self, id: u64) -> Result<Option<RatingPhoto>, String> {
+        match sqlx::query_as::<_, SqliteRatingPhoto>(
+            "DELETE FROM rating_photos WHERE id = ? RETURNING *",
+        )
+        .bind(id as i64)
+        .fetch_optional(&self.pool)
+        .await
+        {
+            Ok(photo) => photo.map(|p| p.convert_to_photo()).transpose(),
+            Err(e) => {
+                eprintln!("error deleting rating photo from SQLite: {}", e);
+                Err("Photo not deleted".to_string())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use crate::{
+        places::testing::place,
+        repository::{places::PlacesRepository, user::User, user::UserRepository},
+    };
+
+    use super::*;
+
+    async fn repo_with_place(name: &str) -> (SqliteRepo, u64) {
+        let mut repo = SqliteRepo::new("sqlite::memory:").await.unwrap();
+        UserRepository::create(
+            &mut repo,
+            User {
+                first_name: "Hunter".to_string(),
+                last_name: "Simmons".to_string(),
+                phone_number: 2028098681,
+                is_verified: true,
+            },
+        )
+        .await
+        .unwrap();
+        let place_id = add_place(&mut repo, name).await;
+        (repo, place_id)
+    }
+
+    async fn add_place(repo: &mut SqliteRepo, name: &str) -> u64 {
+        PlacesRepository::create(repo, &place(0, name))
+            .await
+            .unwrap()
+            .id
+    }
+
+    fn rating(place_id: u64, score: u8) -> Rating {
+        let now = DateTime::parse_from_rfc3339("2026-10-18T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        Rating {
+            id: 0,
+            place_id,
+            phone_number: 2028098681,
+            score,
+            review: Some("Great".to_string()),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_upsert_and_read() {
+        let (mut repo, place_id) = repo_with_place("Arlo").await;
+
+        let created = repo.upsert(&rating(place_id, 8)).await.unwrap();
+        assert_ne!(created.id, 0);
+        assert_eq!(created.score, 8);
+
+        let mut changed = rating(place_id, 9);
+        changed.review = None;
+        changed.created_at = created.created_at + Duration::days(1);
+        changed.updated_at = created.updated_at + Duration::days(1);
+        let updated = repo.upsert(&changed).await.unwrap();
+
+        assert_eq!(updated.id, created.id);
+        assert_eq!(updated.score, 9);
+        assert_eq!(updated.review, None);
+        assert_eq!(updated.created_at, created.created_at);
+        assert_eq!(updated.updated_at, changed.updated_at);
+
+        let ratings = RatingsRepository::read(
+            &repo,
+            ReadRatingOptions {
+                id: None,
+                place_id: Some(place_id),
+                phone_number: Some(2028098681),
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(ratings, vec![updated]);
+    }
+
+    #[tokio::test]
+    async fn test_upsert_rejects_unknown_place_and_bad_score() {
+        let (mut repo, place_id) = repo_with_place("Arlo").await;
+
+        assert!(repo.upsert(&rating(place_id + 1, 8)).await.is_err());
+        assert!(repo.upsert(&rating(place_id, 11)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_scores() {
+        let (mut repo, arlo) = repo_with_place("Arlo").await;
+        let red_iguana = add_place(&mut repo, "Red Iguana").await;
+        let unrated = add_place(&mut repo, "Unrated").await;
+        UserRepository::create(
+            &mut repo,
+            User {
+                first_name: "Other".to_string(),
+                last_name: "User".to_string(),
+                phone_number: 2028098682,
+                is_verified: true,
+            },
+        )
+        .await
+        .unwrap();
+        repo.upsert(&rating(arlo, 8)).await.unwrap();
+        repo.upsert(&Rating {
+            phone_number: 2028098682,
+            ..rating(arlo, 5)
+        })
+        .await
+        .unwrap();
+        repo.upsert(&rating(red_iguana, 10)).await.unwrap();
+
+        let scores = repo.scores(&[arlo, unrated]).await.unwrap();
+
+        assert_eq!(scores.len(), 1);
+        assert_eq!(
+            scores[&arlo],
+            PlaceScore {
+                rating_count: 2,
+                average_score: 6.5,
+            }
+        );
+        assert!(repo.scores(&[]).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_delete() {
+        let (mut repo, place_id) = repo_with_place("Arlo").await;
+        let rating = repo.upsert(&rating(place_id, 8)).await.unwrap();
+
+        assert_eq!(
+            RatingsRepository::delete(&mut repo, rating.id)
+                .await
+                .unwrap(),
+            Some((rating.clone(), Vec::new()))
+        );
+        assert_eq!(
+            RatingsRepository::delete(&mut repo, rating.id)
+                .await
+                .unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_photos() {
+        let (mut repo, place_id) = repo_with_place("Arlo").await;
+        let rating = repo.upsert(&rating(place_id, 8)).await.unwrap();
+        let photo = RatingPhoto {
+            id: 0,
+            rating_id: rating.id,
+            place_id,
+            phone_number: rating.phone_number,
+            key: "ratings/1/a.jpg".to_string(),
+            thumbnail_key: "ratings/1/a-thumbnail.jpg".to_string(),
+            width: 800,
+            height: 600,
+            created_at: rating.created_at,
+        };
+
+        let added = repo
+            .add_photos(std::slice::from_ref(&photo), 2)
+            .await
+            .unwrap()
+            .unwrap()
+            .remove(0);
+        assert_ne!(added.id, 0);
+        assert_eq!(
+            RatingPhoto {
+                id: 0,
+                ..added.clone()
+            },
+            photo
+        );
+        assert!(repo
+            .add_photos(
+                &[RatingPhoto {
+                    rating_id: rating.id + 1,
+                    ..photo.clone()
+                }],
+                2
+            )
+            .await
+            .is_err());
+        // Either every photo fits or none is added.
+        assert_eq!(
+            repo.add_photos(&[photo.clone(), photo.clone()], 2)
+                .await
+                .unwrap(),
+            None
+        );
+
+        let read = repo
+            .read_photos(ReadRatingPhotoOptions {
+                id: None,
+                rating_id: None,
+                place_id: Some(place_id),
+            })
+            .await
+            .unwrap();
+        assert_eq!(read, vec![added.clone()]);
+        assert_eq!(repo.delete_photo(added.id).await.unwrap(), Some(added));
+
+        let both = repo
+            .add_photos(&[photo.clone(), photo.clone()], 2)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(both.len(), 2);
+
+        // Photos go with their rating.
+        assert_eq!(
+            RatingsRepository::delete(&mut repo, rating.id)
+                .await
+                .unwrap(),
+            Some((rating, both.clone()))
+        );
+        assert_eq!(repo.delete_photo(both[0].id).await.unwrap(), None);
+    }
+}
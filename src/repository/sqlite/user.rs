@@ -0,0 +1,172 @@
+use axum::async_trait;
+use sqlx::FromRow;
+
+use crate::repository::user::{User, UserRepository};
+
+use super::SqliteRepo;
+
+#[derive(FromRow)]
+struct SqliteUser {
+    phone_number: i64,
+    first_name: String,
+    last_name: String,
+    is_verified: bool,
+}
+
+impl SqliteUser {
+    fn convert_to_user(self) -> User {
+        User {
+            first_name: self.first_name,
+            last_name: self.last_name,
+            phone_number: self.phone_number as u64,
+            is_verified: self.is_verified,
+        }
+    }
+}
+
+#[async_trait]
+impl UserRepository for SqliteRepo {
+    async fn create(&mut self, user: User) -> Result<User, String> {
+        match sqlx::query(
+            "INSERT INTO users (phone_number, first_name, last_name, is_verified)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT (phone_number) DO NOTHING",
+        )
+        .bind(user.phone_number as i64)
+        .bind(&user.first_name)
+        .bind(&user.last_name)
+        .bind(user.is_verified)
+        .execute(&self.pool)
+        .await
+        {
+            Ok(r) => {
+                if r.rows_affected() == 1 {
+                    return Ok(user);
+                }
+                unwrap_read_user(self.read(user.phone_number).await)
+            }
+            Err(e) => {
+                eprintln!("error creating user in SQLite: {}", e);
+                Err("User not created".to_string())
+            }
+        }
+    }
+
+    async fn read(&self, phone_number: u64) -> Result<Vec<User>, String> {
+        match sqlx::query_as::<_, SqliteUser>("SELECT * FROM users WHERE phone_number = ?")
+            .bind(phone_number as i64)
+            .fetch_all(&self.pool)
+            .await
+        {
+            Ok(users) => Ok(users.into_iter().map(|u| u.convert_to_user()).collect()),
+            Err(e) => {
+                eprintln!("error reading users from SQLite: {}", e);
+                Err("Could not read users".to_string())
+            }
+        }
+    }
+
+    async fn update(&mut self, user: User) -> Result<User, String> {
+        match sqlx::query(
+            "UPDATE users SET first_name = ?, last_name = ?, is_verified = ?
+            WHERE phone_number = ?",
+        )
+        .bind(&user.first_name)
+        .bind(&user.last_name)
+        .bind(user.is_verified)
+        .bind(user.phone_number as i64)
+        .execute(&self.pool)
+        .await
+        {
+            Ok(_) => Ok(user),
+            Err(e) => {
+                eprintln!("error updating user in SQLite: {}", e);
+                Err("User not updated".to_string())
+            }
+        }
+    }
+
+    async fn delete(&mut self, phone_number: u64) -> Result<Option<User>, String> {
+        match sqlx::query_as::<_, SqliteUser>(
+            "DELETE FROM users WHERE phone_number = ? RETURNING *",
+        )
+        .bind(phone_number as i64)
+        .fetch_optional(&self.pool)
+        .await
+        {
+            Ok(user) => Ok(user.map(|u| u.convert_to_user())),
+            Err(e) => {
+                eprintln!("error deleting user from SQLite: {}", e);
+                Err("User not deleted".to_string())
+            }
+        }
+    }
+}
+
+fn unwrap_read_user(res: Result<Vec<User>, String>) -> Result<User, String> {
+    match res {
+        Ok(users) => match users.first() {
+            Some(user) => Ok(user.clone()),
+            None => Err("Expected len of users to be greater than 0".to_string()),
+        },
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hunter(is_verified: bool) -> User {
+        User {
+            first_name: "Hunter".to_string(),
+            last_name: "Simmons".to_string(),
+            phone_number: 2028098681,
+            is_verified,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_create_and_read() {
+        let mut user_repo = SqliteRepo::new("sqlite::memory:").await.unwrap();
+
+        user_repo.create(hunter(false)).await.unwrap();
+
+        let users = user_repo.read(2028098681).await.unwrap();
+        assert_eq!(users, vec![hunter(false)]);
+    }
+
+    #[tokio::test]
+    async fn test_create_existing_returns_stored_user() {
+        let mut user_repo = SqliteRepo::new("sqlite::memory:").await.unwrap();
+
+        user_repo.create(hunter(true)).await.unwrap();
+        let user = user_repo.create(hunter(false)).await.unwrap();
+
+        assert_eq!(user, hunter(true));
+        assert_eq!(user_repo.read(2028098681).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_update() {
+        let mut user_repo = SqliteRepo::new("sqlite::memory:").await.unwrap();
+
+        user_repo.create(hunter(false)).await.unwrap();
+        user_repo.update(hunter(true)).await.unwrap();
+
+        let users = user_repo.read(2028098681).await.unwrap();
+        assert_eq!(users, vec![hunter(true)]);
+    }
+
+    #[tokio::test]
+    async fn test_delete() {
+        let mut user_repo = SqliteRepo::new("sqlite::memory:").await.unwrap();
+
+        user_repo.create(hunter(false)).await.unwrap();
+
+        let deleted_user = user_repo.delete(2028098681).await.unwrap();
+        assert_eq!(deleted_user, Some(hunter(false)));
+        assert_eq!(user_repo.delete(2028098681).await.unwrap(), None);
+        assert!(user_repo.read(2028098681).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,215 @@
+use axum::async_trait;
+use chrono::{DateTime, SecondsFormat, Utc};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::repository::jobs::{Job, JobKind, JobStatus, JobsRepository};
+
+use super::SupabaseRepo;
+
+#[derive(Serialize, Deserialize, Debug)]
+struct RepoJob {
+    id: u64,
+    kind: String,
+    place_id: u64,
+    status: String,
+    attempts: u32,
+    run_at: DateTime<Utc>,
+    last_error: Option<String>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Debug)]
+struct EnqueueJobParams {
+    job_kind: &'static str,
+    job_place_id: u64,
+    queued_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Debug)]
+struct ClaimJobsParams {
+    claimed_at: DateTime<Utc>,
+    max_jobs: usize,
+}
+
+#[derive(Serialize, Debug)]
+struct FailJobParams<'a> {
+    job_id: u64,
+    job_error: &'a str,
+    retry_at: Option<DateTime<Utc>>,
+    failed_at: DateTime<Utc>,
+}
+
+impl RepoJob {
+    fn convert_to_job(self) -> Result<Job, String> {
+        Ok(Job {
+            id: self.id,
+            kind: JobKind::parse(&self.kind)?,
+            place_id: self.place_id,
+            status: JobStatus::parse(&self.status)?,
+            attempts: self.attempts,
+            run_at: self.run_at,
+            last_error: self.last_error,
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+        })
+    }
+}
+
+fn parse_jobs(text: Result<String, reqwest::Error>) -> Result<Vec<Job>, String> {
+    match text {
+        Ok(t) => match serde_json::from_str::<Vec<RepoJob>>(&t) {
+            Ok(b) => b.into_iter().map(|j| j.convert_to_job()).collect(),
+            Err(_) => Err("Could not read jobs".to_string()),
+        },
+        Err(_) => Err("Could not read jobs".to_string()),
+    }
+}
+
+/// Jobs are claimed and failed through the functions in
+/// `supabase/migrations`, since PostgREST can't lock rows or increment a
+/// column on its own.
+#[async_trait]
+impl JobsRepository for SupabaseRepo {
+    async fn enqueue(
+        &mut self,
+        kind: JobKind,
+        place_id: u64,
+        now: DateTime<Utc>,
+    ) -> Result<Job, String> {
+        let params = match serde_json::to_string(&EnqueueJobParams {
+            job_kind: kind.as_str(),
+            job_place_id: place_id,
+            queued_at: now,
+        }) {
+            Ok(p) => p,
+            Err(_) => return Err("Error serializing job".to_string()),
+        };
+
+        match self.client.rpc("enqueue_job", params).execute().await {
+            Ok(r) => {
+                if r.status() != StatusCode::OK {
+                    eprintln!(
+                        "Status code not what was expected when queueing job: {}",
+                        r.status()
+                    );
+                    return Err("Job not queued".to_string());
+                }
+                match parse_jobs(r.text().await)?.into_iter().next() {
+                    Some(job) => Ok(job),
+                    None => Err("Job not queued".to_string()),
+                }
+            }
+            Err(_) => Err("Job not queued".to_string()),
+        }
+    }
+
+    async fn claim(&mut self, now: DateTime<Utc>, limit: usize) -> Result<Vec<Job>, String> {
+        let params = match serde_json::to_string(&ClaimJobsParams {
+            claimed_at: now,
+            max_jobs: limit,
+        }) {
+            Ok(p) => p,
+            Err(_) => return Err("Could not claim jobs".to_string()),
+        };
+
+        match self.client.rpc("claim_jobs", params).execute().await {
+            Ok(r) => {
+                if r.status() != StatusCode::OK {
+                    eprintln!(
+                        "Status code not what was expected when claiming jobs: {}",
+                        r.status()
+                    );
+                    return Err("Could not claim jobs".to_string());
+                }
+                let mut jobs = parse_jobs(r.text().await)?;
+                jobs.sort_by_key(|j| (j.run_at, j.id));
+                Ok(jobs)
+            }
+            Err(_) => Err("Could not claim jobs".to_string()),
+        }
+    }
+
+    async fn complete(&mut self, id: u64) -> Result<(), String> {
+        match self
+            .client
+            .from("jobs")
+            .eq("id", id.to_string())
+            .delete()
+            .execute()
+            .await
+        {
+            Ok(r) if r.status().is_success() => Ok(()),
+            _ => Err("Job not completed".to_string()),
+        }
+    }
+
+    async fn fail(
+        &mut self,
+        id: u64,
+        error: &str,
+        retry_at: Option<DateTime<Utc>>,
+        now: DateTime<Utc>,
+    ) -> Result<Job, String> {
+        let params = match serde_json::to_string(&FailJobParams {
+            job_id: id,
+            job_error: error,
+            retry_at,
+            failed_at: now,
+        }) {
+            Ok(p) => p,
+            Err(_) => return Err("Job not updated".to_string()),
+        };
+
+        match self.client.rpc("fail_job", params).execute().await {
+            Ok(r) => {
+                if r.status() != StatusCode::OK {
+                    eprintln!(
+                        "Status code not what was expected when failing job: {}",
+                        r.status()
+                    );
+                    return Err("Job not updated".to_string());
+                }
+                match parse_jobs(r.text().await)?.into_iter().next() {
+                    Some(job) => Ok(job),
+                    None => Err("Job not found".to_string()),
+                }
+            }
+            Err(_) => Err("Job not updated".to_string()),
+        }
+    }
+
+    async fn requeue_running(&mut self, claimed_before: DateTime<Utc>) -> Result<u64, String> {
+        match self
+            .client
+            .from("jobs")
+            .eq("status", JobStatus::Running.as_str())
+            .lt(
+                "updated_at",
+                claimed_before.to_rfc3339_opts(SecondsFormat::Micros, true),
+            )
+            .update(format!(r#"{{"status":"{}"}}"#, JobStatus::Pending.as_str()))
+            .execute()
+            .await
+        {
+            Ok(r) => Ok(parse_jobs(r.text().await)?.len() as u64),
+            Err(_) => Err("Could not requeue jobs".to_string()),
+        }
+    }
+
+    async fn dead_letters(&self) -> Result<Vec<Job>, String> {
+        match self
+            .client
+            .from("jobs")
+            .eq("status", JobStatus::Dead.as_str())
+            .select("*")
+            .order("updated_at.desc,id.desc")
+            .execute()
+            .await
+        {
+            Ok(r) => parse_jobs(r.text().await),
+            Err(_) => Err("Could not read jobs".to_string()),
+        }
+    }
+}
@@ -1,4 +1,6 @@
+pub mod jobs;
 pub mod places;
+pub mod ratings;
 pub mod user;
 
 pub struct SupabaseRepo {
@@ -1,10 +1,15 @@
 use axum::async_trait;
+use chrono::SecondsFormat;
 use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    places::{Address, Place},
-    repository::places::{PlacesRepository, ReadPlaceOptions},
+    geo::Coordinates,
+    places::{photo::Photo, Address, Place, PlaceDetails},
+    repository::places::{
+        escape_like, NearbyPlace, NearbyPlaceOptions, PlacesRepository, ReadPlaceOptions,
+        SearchPlaceOptions, StalePlaceOptions,
+    },
 };
 
 use super::SupabaseRepo;
@@ -21,9 +26,101 @@ pub struct RepoPlace {
     pub postcode: Option<String>,
     pub place: Option<String>,
     pub street: Option<String>,
-    pub photos: Option<Vec<String>>,
+    pub photos: Option<Vec<Photo>>,
     pub website: Option<String>,
     pub foursquare_id: Option<String>,
+    pub mapbox_id: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub categories: Option<Vec<String>>,
+    pub details: Option<PlaceDetails>,
+}
+
+/// The columns written when inserting or updating a place. Unset optional
+/// fields are left out so they keep their stored value on update.
+#[derive(Serialize, Debug)]
+struct NewRepoPlace<'a> {
+    name: &'a str,
+    address: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    full_address: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    country: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    region: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    postcode: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    place: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    street: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    website: Option<&'a str>,
+    #[serde(rename = "foursquareId", skip_serializing_if = "Option::is_none")]
+    foursquare_id: Option<&'a str>,
+    #[serde(rename = "mapboxId", skip_serializing_if = "Option::is_none")]
+    mapbox_id: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    photos: Option<&'a [Photo]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    latitude: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    longitude: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    categories: Option<&'a [String]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details: Option<&'a PlaceDetails>,
+}
+
+/// A row returned by the `nearby_places` database function.
+#[derive(Deserialize, Debug)]
+struct RepoNearbyPlace {
+    place: RepoPlace,
+    distance_meters: f64,
+}
+
+#[derive(Serialize, Debug)]
+struct SearchPlacesParams {
+    text_pattern: String,
+    min_lat: Option<f64>,
+    max_lat: Option<f64>,
+    min_lng: Option<f64>,
+    max_lng: Option<f64>,
+    include_unlocated: bool,
+    center_lat: Option<f64>,
+    center_lng: Option<f64>,
+    max_results: usize,
+}
+
+#[derive(Serialize, Debug)]
+struct NearbyPlacesParams {
+    lat: f64,
+    lng: f64,
+    radius_meters: f64,
+    max_results: usize,
+}
+
+impl<'a> From<&'a Place> for NewRepoPlace<'a> {
+    fn from(place: &'a Place) -> Self {
+        NewRepoPlace {
+            name: &place.name,
+            address: &place.address.address,
+            full_address: place.address.full_address.as_deref(),
+            country: place.address.country.as_deref(),
+            region: place.address.region.as_deref(),
+            postcode: place.address.postcode.as_deref(),
+            place: place.address.place.as_deref(),
+            street: place.address.street.as_deref(),
+            website: place.website.as_deref(),
+            foursquare_id: place.foursquare_id.as_deref(),
+            mapbox_id: place.mapbox_id.as_deref(),
+            photos: place.photos.as_deref(),
+            latitude: place.coordinates.map(|c| c.latitude),
+            longitude: place.coordinates.map(|c| c.longitude),
+            categories: place.categories.as_deref(),
+            details: place.details.as_ref(),
+        }
+    }
 }
 
 impl RepoPlace {
@@ -43,6 +140,16 @@ impl RepoPlace {
             photos: self.photos.clone(),
             website: self.website.clone(),
             foursquare_id: self.foursquare_id.clone(),
+            mapbox_id: self.mapbox_id.clone(),
+            coordinates: self
+                .latitude
+                .zip(self.longitude)
+                .map(|(latitude, longitude)| Coordinates {
+                    latitude,
+                    longitude,
+                }),
+            categories: self.categories.clone(),
+            details: self.details.clone(),
         }
     }
 }
@@ -53,7 +160,7 @@ impl PlacesRepository for SupabaseRepo {
         match self
             .client
             .from("places")
-            .insert(format_create_command(&place))
+            .insert(format_create_command(place)?)
             .execute()
             .await
         {
@@ -111,13 +218,37 @@ impl PlacesRepository for SupabaseRepo {
             Err(_) => return Err("Could not read places".to_string()),
         }
     }
+
+    async fn read_many(&self, ids: &[u64]) -> Result<Vec<Place>, String> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        match self
+            .client
+            .from("places")
+            .in_("id", ids.iter().map(|id| id.to_string()))
+            .select("*")
+            .execute()
+            .await
+        {
+            Ok(r) => match r.text().await {
+                Ok(t) => match serde_json::from_str::<Vec<RepoPlace>>(&t) {
+                    Ok(b) => Ok(b.iter().map(|p| p.convert_to_place()).collect()),
+                    Err(_) => Err("Could not read places".to_string()),
+                },
+                Err(_) => Err("Could not read places".to_string()),
+            },
+            Err(_) => Err("Could not read places".to_string()),
+        }
+    }
+
     async fn update(&mut self, place: Place) -> Result<Place, String> {
         let return_place = place.clone();
         match self
             .client
             .from("places")
             .eq("id", place.id.to_string())
-            .update(format_create_command(&place))
+            .update(format_create_command(&place)?)
             .execute()
             .await
         {
@@ -130,7 +261,6 @@ impl PlacesRepository for SupabaseRepo {
                     r.status()
                 );
                 eprintln!("{:?}", r.text().await);
-                eprintln!("{}", format_create_command(&place));
                 return Err("Place not updated".to_string());
             }
             Err(_) => return Err("Place not updated".to_string()),
@@ -150,7 +280,7 @@ impl PlacesRepository for SupabaseRepo {
                     let body: Result<Vec<RepoPlace>, serde_json::Error> = serde_json::from_str(&t);
                     match body {
                         Ok(places) => {
-                            if places.len() == 0 {
+                            if places.is_empty() {
                                 return Err("Place not deleted".to_string());
                             }
                             return Ok(Some(places[0].clone().convert_to_place()));
@@ -163,66 +293,136 @@ impl PlacesRepository for SupabaseRepo {
             Err(_) => return Err("Place not deleted".to_string()),
         }
     }
-}
+    /// Calls the `search_places` function, see `supabase/migrations`, since
+    /// PostgREST can't match unlocated places by locality or order by
+    /// distance.
+    async fn search(&self, options: SearchPlaceOptions) -> Result<Vec<Place>, String> {
+        let bbox = options.bounding_box;
+        let params = match serde_json::to_string(&SearchPlacesParams {
+            text_pattern: format_text_pattern(&options.text),
+            min_lat: bbox.map(|b| b.min_latitude),
+            max_lat: bbox.map(|b| b.max_latitude),
+            min_lng: bbox.map(|b| b.min_longitude),
+            max_lng: bbox.map(|b| b.max_longitude),
+            include_unlocated: options.include_unlocated,
+            center_lat: options.closest_to.map(|c| c.latitude),
+            center_lng: options.closest_to.map(|c| c.longitude),
+            max_results: options.limit,
+        }) {
+            Ok(p) => p,
+            Err(_) => return Err("Could not search places".to_string()),
+        };
 
-fn format_create_command(place: &Place) -> String {
-    let p = place.clone();
-    let mut beginning = format!(
-        r#"[{{"name": "{}", "address": "{}"#,
-        place.name, place.address.address
-    );
-    if let Some(full_address) = p.address.full_address {
-        beginning = beginning + &format!(r#"", "full_address": "{}"#, full_address)
-    }
-    if let Some(country) = p.address.country {
-        beginning = beginning + &format!(r#"", "country": "{}"#, country)
-    }
-    if let Some(region) = p.address.region {
-        beginning = beginning + &format!(r#"", "region": "{}"#, region)
-    }
-    if let Some(postcode) = p.address.postcode {
-        beginning = beginning + &format!(r#"", "postcode": "{}"#, postcode)
-    }
-    if let Some(place) = p.address.place {
-        beginning = beginning + &format!(r#"", "place": "{}"#, place)
-    }
-    if let Some(street) = p.address.street {
-        beginning = beginning + &format!(r#"", "street": "{}"#, street)
+        match self.client.rpc("search_places", params).execute().await {
+            Ok(r) => match r.text().await {
+                Ok(t) => {
+                    let body: Result<Vec<RepoPlace>, serde_json::Error> = serde_json::from_str(&t);
+                    match body {
+                        Ok(b) => Ok(b.iter().map(|p| p.convert_to_place()).collect()),
+                        Err(_) => Err("Could not search places".to_string()),
+                    }
+                }
+                Err(_) => Err("Could not search places".to_string()),
+            },
+            Err(_) => Err("Could not search places".to_string()),
+        }
     }
-    if let Some(website) = p.website {
-        beginning = beginning + &format!(r#"", "website": "{}"#, website)
+
+    /// Calls the PostGIS backed `nearby_places` function, see
+    /// `supabase/migrations`.
+    async fn nearby(&self, options: NearbyPlaceOptions) -> Result<Vec<NearbyPlace>, String> {
+        let params = match serde_json::to_string(&NearbyPlacesParams {
+            lat: options.center.latitude,
+            lng: options.center.longitude,
+            radius_meters: options.radius_meters,
+            max_results: options.limit,
+        }) {
+            Ok(p) => p,
+            Err(_) => return Err("Could not find nearby places".to_string()),
+        };
+
+        match self.client.rpc("nearby_places", params).execute().await {
+            Ok(r) => match r.text().await {
+                Ok(t) => {
+                    let body: Result<Vec<RepoNearbyPlace>, serde_json::Error> =
+                        serde_json::from_str(&t);
+                    match body {
+                        Ok(b) => Ok(b
+                            .iter()
+                            .map(|p| NearbyPlace {
+                                place: p.place.convert_to_place(),
+                                distance_meters: p.distance_meters,
+                            })
+                            .collect()),
+                        Err(_) => Err("Could not find nearby places".to_string()),
+                    }
+                }
+                Err(_) => Err("Could not find nearby places".to_string()),
+            },
+            Err(_) => Err("Could not find nearby places".to_string()),
+        }
     }
-    if let Some(foursquare_id) = p.foursquare_id {
-        beginning = beginning + &format!(r#"", "foursquareId": "{}"#, foursquare_id)
+
+    async fn stale(&self, options: StalePlaceOptions) -> Result<Vec<Place>, String> {
+        let updated_before = options
+            .updated_before
+            .to_rfc3339_opts(SecondsFormat::Secs, true);
+        match self
+            .client
+            .from("places")
+            .or(format!(
+                "details.is.null,details->>updatedAt.lt.{}",
+                updated_before
+            ))
+            .select("*")
+            .order("details->>updatedAt.asc.nullsfirst,id")
+            .limit(options.limit)
+            .execute()
+            .await
+        {
+            Ok(r) => match r.text().await {
+                Ok(t) => {
+                    let body: Result<Vec<RepoPlace>, serde_json::Error> = serde_json::from_str(&t);
+                    match body {
+                        Ok(b) => Ok(b.iter().map(|p| p.convert_to_place()).collect()),
+                        Err(_) => Err("Could not find stale places".to_string()),
+                    }
+                }
+                Err(_) => Err("Could not find stale places".to_string()),
+            },
+            Err(_) => Err("Could not find stale places".to_string()),
+        }
     }
-    if let Some(photos) = p.photos.clone() {
-        beginning = beginning + &format!(r#"", "photos": {:?}"#, photos)
+}
+
+fn format_text_pattern(name: &str) -> String {
+    format!("%{}%", escape_like(name))
+}
+
+fn format_create_command(place: &Place) -> Result<String, String> {
+    match serde_json::to_string(&[NewRepoPlace::from(place)]) {
+        Ok(body) => Ok(body),
+        Err(_) => Err("Error serializing place".to_string()),
     }
-    let end: &str;
-    match p.photos {
-        Some(_) => end = r#"}]"#,
-        None => end = r#""}]"#,
-    };
-    return beginning + &end;
 }
 
 fn parse_places(res: Result<String, reqwest::Error>) -> Result<Place, String> {
     match res {
         Ok(r) => {
             let body: Result<Vec<RepoPlace>, serde_json::Error> = serde_json::from_str(&r);
-            return unwrap_read_places_json(body);
+            unwrap_read_places_json(body)
         }
-        Err(_) => return Err("Error with request".to_string()),
+        Err(_) => Err("Error with request".to_string()),
     }
 }
 
 pub fn unwrap_read_places(res: Result<Vec<Place>, String>) -> Result<Place, String> {
     match res {
         Ok(places) => {
-            if places.len() == 0 {
+            if places.is_empty() {
                 return Err("Expected len of places to be greater than 0".to_string());
             }
-            return Ok(places[0].clone());
+            Ok(places[0].clone())
         }
         Err(e) => Err(e),
     }
@@ -233,10 +433,10 @@ pub fn unwrap_read_places_json(
 ) -> Result<Place, String> {
     match res {
         Ok(places) => {
-            if places.len() == 0 {
+            if places.is_empty() {
                 return Err("Expected len of places to be greater than 0".to_string());
             };
-            return Ok(places[0].clone().convert_to_place());
+            Ok(places[0].clone().convert_to_place())
         }
         Err(_) => Err("Error unmarshaling JSON".to_string()),
     }
@@ -248,7 +448,123 @@ mod tests {
 
     use super::*;
 
+    fn hostile_place() -> Place {
+        Place {
+            id: 0,
+            name: r#"Joe's "Famous" \ Diner", "photos": null, "x": "#.to_string(),
+            address: Address {
+                address: "123 \"Main\" St\\".to_string(),
+                full_address: None,
+                country: Some("Brasil 🇧🇷".to_string()),
+                region: Some("São Paulo".to_string()),
+                postcode: Some("01310-100\n".to_string()),
+                place: Some("東京\t".to_string()),
+                street: Some("Rua \u{0000} Augusta".to_string()),
+            },
+            photos: Some(vec![
+                Photo {
+                    prefix: "https://fastly.4sqi.net/img/general/".to_string(),
+                    suffix: r#"/"quoted".jpg"#.to_string(),
+                },
+                Photo {
+                    prefix: "https://fastly.4sqi.net/img/general/".to_string(),
+                    suffix: "/back\\slash.jpg".to_string(),
+                },
+            ]),
+            website: Some("https://example.com/?q=\"}]".to_string()),
+            foursquare_id: Some("4b5a0c1ef964a520a0a928e3".to_string()),
+            mapbox_id: None,
+            coordinates: None,
+            categories: None,
+            details: None,
+        }
+    }
+
+    #[test]
+    fn test_format_create_command_escapes_hostile_values() {
+        let place = hostile_place();
+
+        let body: serde_json::Value =
+            serde_json::from_str(&format_create_command(&place).unwrap()).unwrap();
+
+        let rows = body.as_array().unwrap();
+        assert_eq!(rows.len(), 1);
+        let row = &rows[0];
+        assert_eq!(row["name"], place.name);
+        assert_eq!(row["address"], place.address.address);
+        assert_eq!(row["country"], "Brasil 🇧🇷");
+        assert_eq!(row["region"], "São Paulo");
+        assert_eq!(row["postcode"], "01310-100\n");
+        assert_eq!(row["place"], "東京\t");
+        assert_eq!(row["street"], "Rua \u{0000} Augusta");
+        assert_eq!(row["website"], "https://example.com/?q=\"}]");
+        assert_eq!(row["foursquareId"], "4b5a0c1ef964a520a0a928e3");
+        assert_eq!(
+            row["photos"],
+            serde_json::json!(place.photos.clone().unwrap())
+        );
+        assert_eq!(row.as_object().unwrap().len(), 10);
+    }
+
+    #[test]
+    fn test_format_create_command_omits_unset_fields() {
+        let mut place = hostile_place();
+        place.address.country = None;
+        place.photos = None;
+        place.website = None;
+        place.foursquare_id = None;
+
+        let body: serde_json::Value =
+            serde_json::from_str(&format_create_command(&place).unwrap()).unwrap();
+
+        let row = body[0].as_object().unwrap();
+        assert!(!row.contains_key("country"));
+        assert!(!row.contains_key("photos"));
+        assert!(!row.contains_key("website"));
+        assert!(!row.contains_key("foursquareId"));
+        assert!(!row.contains_key("full_address"));
+        assert!(!row.contains_key("latitude"));
+        assert!(!row.contains_key("longitude"));
+        assert_eq!(row["name"], place.name);
+    }
+
+    #[test]
+    fn test_format_create_command_includes_coordinates() {
+        let mut place = hostile_place();
+        place.coordinates = Some(Coordinates {
+            latitude: 40.7608,
+            longitude: -111.891,
+        });
+
+        let body: serde_json::Value =
+            serde_json::from_str(&format_create_command(&place).unwrap()).unwrap();
+
+        assert_eq!(body[0]["latitude"], 40.7608);
+        assert_eq!(body[0]["longitude"], -111.891);
+    }
+
+    #[test]
+    fn test_format_text_pattern_escapes_wildcards() {
+        assert_eq!(format_text_pattern("arlo"), "%arlo%");
+        assert_eq!(format_text_pattern("100%_*\\"), "%100\\%\\_*\\\\%");
+    }
+
+    #[test]
+    fn test_format_create_command_includes_categories() {
+        let mut place = hostile_place();
+        place.categories = Some(vec!["mexican".to_string(), "tex\"mex".to_string()]);
+
+        let body: serde_json::Value =
+            serde_json::from_str(&format_create_command(&place).unwrap()).unwrap();
+
+        assert_eq!(
+            body[0]["categories"],
+            serde_json::json!(["mexican", "tex\"mex"])
+        );
+    }
+
     #[tokio::test]
+    #[ignore = "requires a live Supabase instance configured via .env"]
     async fn test_create() {
         dotenv::dotenv().expect("dotenv to work");
         let supabase_url = std::env::var("SUPABASE_URL").expect("SUPABASE_URL must be set.");
@@ -271,12 +587,17 @@ mod tests {
             photos: None,
             website: None,
             foursquare_id: None,
+            mapbox_id: None,
+            coordinates: None,
+            categories: None,
+            details: None,
         })
         .await
         .unwrap();
     }
 
     #[tokio::test]
+    #[ignore = "requires a live Supabase instance configured via .env"]
     async fn test_read() {
         dotenv::dotenv().expect("dotenv to work");
 
@@ -299,6 +620,7 @@ mod tests {
     }
 
     #[tokio::test]
+    #[ignore = "requires a live Supabase instance configured via .env"]
     async fn test_update() {
         dotenv::dotenv().expect("dotenv to work");
         let supabase_url = std::env::var("SUPABASE_URL").expect("SUPABASE_URL must be set.");
@@ -321,6 +643,10 @@ mod tests {
             photos: None,
             website: None,
             foursquare_id: None,
+            mapbox_id: None,
+            coordinates: None,
+            categories: None,
+            details: None,
         })
         .await
         .unwrap();
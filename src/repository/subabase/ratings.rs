@@ -0,0 +1,342 @@
+use std::collections::HashMap;
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::repository::ratings::{
+    average_scores, PlaceScore, Rating, RatingPhoto, RatingsRepository, ReadRatingOptions,
+    ReadRatingPhotoOptions,
+};
+
+use super::SupabaseRepo;
+
+#[derive(Serialize, Deserialize, Debug)]
+struct RepoRating {
+    id: u64,
+    place_id: u64,
+    phone_number: u64,
+    score: u8,
+    review: Option<String>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Debug)]
+struct NewRepoRating<'a> {
+    place_id: u64,
+    phone_number: u64,
+    score: u8,
+    review: Option<&'a str>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct RepoRatingPhoto {
+    id: u64,
+    rating_id: u64,
+    place_id: u64,
+    phone_number: u64,
+    key: String,
+    thumbnail_key: String,
+    width: u32,
+    height: u32,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Debug)]
+struct NewRepoRatingPhoto<'a> {
+    rating_id: u64,
+    place_id: u64,
+    phone_number: u64,
+    key: &'a str,
+    thumbnail_key: &'a str,
+    width: u32,
+    height: u32,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Debug)]
+struct DeleteRatingParams {
+    deleted_rating_id: u64,
+}
+
+#[derive(Deserialize, Debug)]
+struct DeletedRepoRating {
+    rating: Option<RepoRating>,
+    photos: Vec<RepoRatingPhoto>,
+}
+
+#[derive(Serialize, Debug)]
+struct AddRatingPhotosParams<'a> {
+    photos: Vec<NewRepoRatingPhoto<'a>>,
+    max_photos: usize,
+}
+
+impl RepoRatingPhoto {
+    fn convert_to_photo(self) -> RatingPhoto {
+        RatingPhoto {
+            id: self.id,
+            rating_id: self.rating_id,
+            place_id: self.place_id,
+            phone_number: self.phone_number,
+            key: self.key,
+            thumbnail_key: self.thumbnail_key,
+            width: self.width,
+            height: self.height,
+            created_at: self.created_at,
+        }
+    }
+}
+
+fn parse_photos(text: Result<String, reqwest::Error>) -> Result<Vec<RatingPhoto>, String> {
+    match text {
+        Ok(t) => match serde_json::from_str::<Vec<RepoRatingPhoto>>(&t) {
+            Ok(b) => Ok(b.into_iter().map(|p| p.convert_to_photo()).collect()),
+            Err(_) => Err("Could not read photos".to_string()),
+        },
+        Err(_) => Err("Could not read photos".to_string()),
+    }
+}
+
+impl RepoRating {
+    fn convert_to_rating(self) -> Rating {
+        Rating {
+            id: self.id,
+            place_id: self.place_id,
+            phone_number: self.phone_number,
+            score: self.score,
+            review: self.review,
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+        }
+    }
+}
+
+fn parse_ratings(text: Result<String, reqwest::Error>) -> Result<Vec<Rating>, String> {
+    match text {
+        Ok(t) => match serde_json::from_str::<Vec<RepoRating>>(&t) {
+            Ok(b) => Ok(b.into_iter().map(|r| r.convert_to_rating()).collect()),
+            Err(_) => Err("Could not read ratings".to_string()),
+        },
+        Err(_) => Err("Could not read ratings".to_string()),
+    }
+}
+
+#[async_trait]
+impl RatingsRepository for SupabaseRepo {
+    /// PostgREST can't keep `created_at` on conflict, so an existing rating
+    /// is updated instead of upserted.
+    async fn upsert(&mut self, rating: &Rating) -> Result<Rating, String> {
+        let existing = self
+            .read(ReadRatingOptions {
+                id: None,
+                place_id: Some(rating.place_id),
+                phone_number: Some(rating.phone_number),
+            })
+            .await?;
+        let created_at = match existing.first() {
+            Some(r) => r.created_at,
+            None => rating.created_at,
+        };
+        let body = match serde_json::to_string(&[NewRepoRating {
+            place_id: rating.place_id,
+            phone_number: rating.phone_number,
+            score: rating.score,
+            review: rating.review.as_deref(),
+            created_at,
+            updated_at: rating.updated_at,
+        }]) {
+            Ok(b) => b,
+            Err(_) => return Err("Error serializing rating".to_string()),
+        };
+
+        match self
+            .client
+            .from("ratings")
+            .upsert(body)
+            .on_conflict("place_id,phone_number")
+            .execute()
+            .await
+        {
+            Ok(r) => {
+                if r.status() != StatusCode::CREATED && r.status() != StatusCode::OK {
+                    eprintln!(
+                        "Status code not what was expected when storing rating: {}",
+                        r.status()
+                    );
+                    return Err("Rating not stored".to_string());
+                }
+                match parse_ratings(r.text().await)?.into_iter().next() {
+                    Some(rating) => Ok(rating),
+                    None => Err("Rating not stored".to_string()),
+                }
+            }
+            Err(_) => Err("Rating not stored".to_string()),
+        }
+    }
+
+    async fn read(&self, options: ReadRatingOptions) -> Result<Vec<Rating>, String> {
+        let mut client = self.client.from("ratings");
+        if let Some(id) = options.id {
+            client = client.eq("id", id.to_string())
+        };
+        if let Some(place_id) = options.place_id {
+            client = client.eq("place_id", place_id.to_string())
+        };
+        if let Some(phone_number) = options.phone_number {
+            client = client.eq("phone_number", phone_number.to_string())
+        };
+
+        match client.select("*").order("id").execute().await {
+            Ok(r) => parse_ratings(r.text().await),
+            Err(_) => Err("Could not read ratings".to_string()),
+        }
+    }
+
+    /// Calls the `delete_rating` function, see `supabase/migrations`, so the
+    /// photos are read and deleted along with the rating in one transaction.
+    async fn delete(&mut self, id: u64) -> Result<Option<(Rating, Vec<RatingPhoto>)>, String> {
+        let params = match serde_json::to_string(&DeleteRatingParams {
+            deleted_rating_id: id,
+        }) {
+            Ok(p) => p,
+            Err(_) => return Err("Rating not deleted".to_string()),
+        };
+
+        match self.client.rpc("delete_rating", params).execute().await {
+            Ok(r) => {
+                if r.status() != StatusCode::OK {
+                    eprintln!(
+                        "Status code not what was expected when deleting rating: {}",
+                        r.status()
+                    );
+                    return Err("Rating not deleted".to_string());
+                }
+                let text = match r.text().await {
+                    Ok(t) => t,
+                    Err(_) => return Err("Rating not deleted".to_string()),
+                };
+                match serde_json::from_str::<DeletedRepoRating>(&text) {
+                    Ok(deleted) => Ok(deleted.rating.map(|rating| {
+                        (
+                            rating.convert_to_rating(),
+                            deleted
+                                .photos
+                                .into_iter()
+                                .map(|p| p.convert_to_photo())
+                                .collect(),
+                        )
+                    })),
+                    Err(_) => Err("Rating not deleted".to_string()),
+                }
+            }
+            Err(_) => Err("Rating not deleted".to_string()),
+        }
+    }
+
+    async fn scores(&self, place_ids: &[u64]) -> Result<HashMap<u64, PlaceScore>, String> {
+        if place_ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        match self
+            .client
+            .from("ratings")
+            .in_("place_id", place_ids.iter().map(|id| id.to_string()))
+            .select("*")
+            .execute()
+            .await
+        {
+            Ok(r) => Ok(average_scores(&parse_ratings(r.text().await)?)),
+            Err(_) => Err("Could not read scores".to_string()),
+        }
+    }
+
+    /// Calls the `add_rating_photos` function, see `supabase/migrations`,
+    /// since PostgREST can't count and insert in one transaction.
+    async fn add_photos(
+        &mut self,
+        photos: &[RatingPhoto],
+        max_photos: usize,
+    ) -> Result<Option<Vec<RatingPhoto>>, String> {
+        if photos.is_empty() {
+            return Ok(Some(Vec::new()));
+        }
+        let params = match serde_json::to_string(&AddRatingPhotosParams {
+            photos: photos
+                .iter()
+                .map(|photo| NewRepoRatingPhoto {
+                    rating_id: photo.rating_id,
+                    place_id: photo.place_id,
+                    phone_number: photo.phone_number,
+                    key: &photo.key,
+                    thumbnail_key: &photo.thumbnail_key,
+                    width: photo.width,
+                    height: photo.height,
+                    created_at: photo.created_at,
+                })
+                .collect(),
+            max_photos,
+        }) {
+            Ok(p) => p,
+            Err(_) => return Err("Error serializing photo".to_string()),
+        };
+
+        match self.client.rpc("add_rating_photos", params).execute().await {
+            Ok(r) => {
+                if r.status() != StatusCode::OK {
+                    eprintln!(
+                        "Status code not what was expected when storing rating photos: {}",
+                        r.status()
+                    );
+                    return Err("Photo not stored".to_string());
+                }
+                // The function adds nothing when the photos don't fit.
+                let added = parse_photos(r.text().await)?;
+                if added.is_empty() {
+                    Ok(None)
+                } else {
+                    Ok(Some(added))
+                }
+            }
+            Err(_) => Err("Photo not stored".to_string()),
+        }
+    }
+
+    async fn read_photos(
+        &self,
+        options: ReadRatingPhotoOptions,
+    ) -> Result<Vec<RatingPhoto>, String> {
+        let mut client = self.client.from("rating_photos");
+        if let Some(id) = options.id {
+            client = client.eq("id", id.to_string())
+        };
+        if let Some(rating_id) = options.rating_id {
+            client = client.eq("rating_id", rating_id.to_string())
+        };
+        if let Some(place_id) = options.place_id {
+            client = client.eq("place_id", place_id.to_string())
+        };
+
+        match client.select("*").order("id").execute().await {
+            Ok(r) => parse_photos(r.text().await),
+            Err(_) => Err("Could not read photos".to_string()),
+        }
+    }
+
+    async fn delete_photo(&mut self, id: u64) -> Result<Option<RatingPhoto>, String> {
+        match self
+            .client
+            .from("rating_photos")
+            .eq("id", id.to_string())
+            .delete()
+            .execute()
+            .await
+        {
+            Ok(r) => Ok(parse_photos(r.text().await)?.into_iter().next()),
+            Err(_) => Err("Photo not deleted".to_string()),
+        }
+    }
+}
@@ -2,53 +2,139 @@ use std::sync::Arc;
 
 use crate::{
     app_state::AppState,
+    blob::DynBlobStore,
+    geo::tile::TileCache,
+    jobs::{refresh::RefreshScheduler, JobOptions, JobQueue},
     oauth::OAuth,
-    places::search::{DynPlacesSearch, Search},
+    places::{
+        activity::PlaceActivity,
+        photo::PhotoProxy,
+        search::{DynPlacesSearch, Search},
+        session::SearchSessions,
+    },
     repository::{
+        jobs::{DynJobsRepo, JobsRepository},
         places::{DynPlacesRepo, PlacesRepository},
+        ratings::{DynRatingsRepo, RatingsRepository},
         user::{DynUserRepo, UserRepository},
     },
     routes::{
-        auth::{auth, authenticate, refresh_token, verify_phone},
-        ratings::search_for_place,
+        auth::{admin_auth, auth, authenticate, refresh_token, verify_phone},
+        jobs::dead_letters,
+        photos::photo,
+        places::{nearby_places, place_details, viewport_places},
+        rating_photos::{
+            delete_rating_photo, rating_photo, rating_photo_thumbnail, upload_rating_photos,
+            MAX_UPLOAD_BYTES,
+        },
+        ratings::{
+            delete_place_rating, export_ratings, rate_place, search_cache_stats, search_for_place,
+        },
+        tiles::place_tile,
     },
+    shutdown::Shutdown,
     sms::{DynSMSVerify, SMSVerify},
 };
 use axum::{
+    extract::DefaultBodyLimit,
     middleware,
-    routing::{post, put},
+    routing::{get, post, put},
     Router,
 };
 use tokio::sync::Mutex;
 
-pub fn create_router<U, P, S, V>(
+/// Builds the app and starts its background jobs and place refreshes, which
+/// run until `shutdown`. Must be called from within a Tokio runtime.
+#[allow(clippy::too_many_arguments)]
+pub fn create_router<U, P, R, J, S, V>(
     user_repo: U,
     places_repo: P,
+    ratings_repo: R,
+    jobs_repo: J,
     sms_verify: V,
     places_search: S,
     oauth: OAuth,
+    photo_proxy: PhotoProxy,
+    blob_store: DynBlobStore,
+    job_options: JobOptions,
+    shutdown: Shutdown,
 ) -> Router
 where
     U: UserRepository,
     P: PlacesRepository,
+    R: RatingsRepository,
+    J: JobsRepository,
     S: Search,
     V: SMSVerify,
 {
     let user_repo = Arc::new(Mutex::new(user_repo)) as DynUserRepo;
     let places_repo = Arc::new(Mutex::new(places_repo)) as DynPlacesRepo;
+    let ratings_repo = Arc::new(Mutex::new(ratings_repo)) as DynRatingsRepo;
     let sms_verify = Arc::new(sms_verify) as DynSMSVerify;
-    let places_search = Arc::new(Mutex::new(places_search)) as DynPlacesSearch;
+    let places_search = Arc::new(places_search) as DynPlacesSearch;
+    let jobs = JobQueue::new(
+        Arc::new(Mutex::new(jobs_repo)) as DynJobsRepo,
+        places_repo.clone(),
+        places_search.clone(),
+        job_options,
+    );
+    let place_activity = PlaceActivity::default();
+    jobs.start(&shutdown);
+    RefreshScheduler::new(
+        places_repo.clone(),
+        jobs.clone(),
+        place_activity.clone(),
+        job_options,
+    )
+    .start(&shutdown);
     let app_state = AppState {
         user_repo,
         places_repo,
+        ratings_repo,
         sms_verify,
         places_search,
+        search_sessions: SearchSessions::new(),
+        tile_cache: TileCache::default(),
+        jobs,
+        place_activity,
+        photo_proxy,
+        blob_store,
         oauth,
     };
 
+    let admin = Router::new()
+        .route("/jobs/dead-letters", get(dead_letters))
+        .route("/search-places/cache-stats", get(search_cache_stats))
+        .layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            admin_auth,
+        ));
+
     Router::new()
         .route("/search-places", post(search_for_place))
+        .route("/places/nearby", get(nearby_places))
+        .route("/places/viewport", get(viewport_places))
+        .route("/places/:id", get(place_details))
+        .route(
+            "/places/:id/rating",
+            put(rate_place).delete(delete_place_rating),
+        )
+        .route(
+            "/places/:id/rating/photos",
+            post(upload_rating_photos).layer(DefaultBodyLimit::max(MAX_UPLOAD_BYTES)),
+        )
+        .route("/tiles/:z/:x/:y", get(place_tile))
+        .route("/ratings/export", get(export_ratings))
+        .route(
+            "/ratings/photos/:id",
+            get(rating_photo).delete(delete_rating_photo),
+        )
+        .route("/ratings/photos/:id/thumbnail", get(rating_photo_thumbnail))
         .layer(middleware::from_fn_with_state(app_state.clone(), auth))
+        .merge(admin)
+        // Photo tokens are signed, so the proxy is public and clients can put
+        // the variant URLs straight into image tags.
+        .route("/photos/:size/:token", get(photo))
         .route("/authenticate", put(authenticate))
         .route("/verify-phone", post(verify_phone))
         .route("/refresh-token", post(refresh_token))
@@ -42,21 +42,18 @@ pub async fn authenticate(
     State(app_state): State<AppState>,
     Json(payload): Json<AuthenticateRequest>,
 ) -> Result<(), (StatusCode, String)> {
-    let first_name: String;
-    match validate_name(&payload.first_name) {
-        Ok(name) => first_name = name,
+    let first_name: String = match validate_name(&payload.first_name) {
+        Ok(name) => name,
         Err(e) => return Err((StatusCode::BAD_REQUEST, e)),
     };
 
-    let last_name: String;
-    match validate_name(&payload.last_name) {
-        Ok(name) => last_name = name,
+    let last_name: String = match validate_name(&payload.last_name) {
+        Ok(name) => name,
         Err(e) => return Err((StatusCode::BAD_REQUEST, e)),
     };
 
-    let phone_number: u64;
-    match validate_phone_number(&payload.phone_number) {
-        Ok(number) => phone_number = number,
+    let phone_number: u64 = match validate_phone_number(&payload.phone_number) {
+        Ok(number) => number,
         Err(e) => return Err((StatusCode::BAD_REQUEST, e)),
     };
 
@@ -98,16 +95,15 @@ pub async fn verify_phone(
     State(app_state): State<AppState>,
     Json(payload): Json<VerifyRequest>,
 ) -> Result<Json<TokenResponse>, (StatusCode, String)> {
-    let phone_number: u64;
-    match validate_phone_number(&payload.phone_number) {
-        Ok(number) => phone_number = number,
+    let phone_number: u64 = match validate_phone_number(&payload.phone_number) {
+        Ok(number) => number,
         Err(e) => return Err((StatusCode::BAD_REQUEST, e)),
     };
 
     let mut user: User;
     match app_state.user_repo.lock().await.read(phone_number).await {
         Ok(u) => {
-            if u.len() == 0 {
+            if u.is_empty() {
                 return Err((StatusCode::BAD_REQUEST, "No user saved".to_string()));
             }
             if u.len() > 1 {
@@ -147,14 +143,13 @@ pub async fn verify_phone(
         }
     }
 
-    let access_token: String;
-    match app_state.oauth.generate_jwt(
+    let access_token: String = match app_state.oauth.generate_jwt(
         &user.first_name,
         &user.last_name,
         user.phone_number,
         user.is_verified,
     ) {
-        Ok(token) => access_token = token,
+        Ok(token) => token,
         Err(_) => {
             return Err((
                 StatusCode::INTERNAL_SERVER_ERROR,
@@ -163,21 +158,20 @@ pub async fn verify_phone(
         }
     };
 
-    let refresh_token: String;
-    match app_state.oauth.generate_refresh_token(
+    let refresh_token: String = match app_state.oauth.generate_refresh_token(
         &user.first_name,
         &user.last_name,
         user.phone_number,
         user.is_verified,
     ) {
-        Ok(token) => refresh_token = token,
+        Ok(token) => token,
         Err(_) => {
             return Err((
                 StatusCode::INTERNAL_SERVER_ERROR,
                 "Internal Server Error".to_string(),
             ));
         }
-    }
+    };
 
     Ok(Json(TokenResponse {
         access_token,
@@ -190,11 +184,11 @@ pub async fn refresh_token(
     Json(payload): Json<RefreshTokenRequest>,
 ) -> Result<Json<TokenResponse>, (StatusCode, String)> {
     let refresh_token = payload.refresh_token;
-    let access_token: String;
-    match app_state.oauth.refresh_token(&refresh_token) {
-        Ok(token) => access_token = token,
+
+    let access_token: String = match app_state.oauth.refresh_token(&refresh_token) {
+        Ok(token) => token,
         Err(_) => return Err((StatusCode::BAD_REQUEST, "Bad refresh token".to_string())),
-    }
+    };
 
     Ok(Json(TokenResponse {
         access_token,
@@ -279,7 +273,7 @@ fn validate_phone_number(phone_number: &str) -> Result<u64, String> {
         };
     }
 
-    if let Some(_) = c.next() {
+    if c.next().is_some() {
         return Err("Phone number too long".to_string());
     }
 
@@ -311,6 +305,24 @@ pub async fn auth<B>(
     }
 }
 
+/// Lets through requests carrying the admin token in place of an access
+/// token, for routes meant for operators rather than app users.
+pub async fn admin_auth<B>(
+    State(app_state): State<AppState>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Result<Response, StatusCode> {
+    let auth_header = req
+        .headers()
+        .get(http::header::AUTHORIZATION)
+        .and_then(|header| header.to_str().ok());
+
+    match auth_header {
+        Some(token) if app_state.oauth.verify_admin_token(token) => Ok(next.run(req).await),
+        _ => Err(StatusCode::UNAUTHORIZED),
+    }
+}
+
 async fn authorize_current_user(auth_header: &str, oauth: OAuth) -> Result<User, StatusCode> {
     match oauth.verify_jwt(auth_header) {
         Ok(u) => Ok(u),
@@ -0,0 +1,14 @@
+use axum::{extract::State, http::StatusCode, Json};
+
+use crate::{app_state::AppState, repository::jobs::Job};
+
+/// Jobs that ran out of attempts, most recently failed first.
+#[axum_macros::debug_handler]
+pub async fn dead_letters(
+    State(app_state): State<AppState>,
+) -> Result<Json<Vec<Job>>, (StatusCode, String)> {
+    match app_state.jobs.dead_letters().await {
+        Ok(jobs) => Ok(Json(jobs)),
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+    }
+}
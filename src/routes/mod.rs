@@ -1,2 +1,7 @@
 pub mod auth;
+pub mod jobs;
+pub mod photos;
+pub mod places;
+pub mod rating_photos;
 pub mod ratings;
+pub mod tiles;
@@ -0,0 +1,50 @@
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderName, StatusCode},
+    response::IntoResponse,
+};
+
+use crate::{
+    app_state::AppState,
+    places::photo::{Photo, PhotoSize, PHOTO_ATTRIBUTION},
+};
+
+/// Cached photos don't change, so clients may keep them for a week.
+const PHOTO_CACHE_CONTROL: &str = "public, max-age=604800, immutable";
+
+/// A place photo at one size, fetched through the server so clients never
+/// need Foursquare's CDN. The variant URLs on places link here. No access
+/// token is needed, so they work as an image's `src`: the token in the URL is
+/// signed by the API, so only photos it has listed can be fetched.
+#[axum_macros::debug_handler]
+pub async fn photo(
+    State(app_state): State<AppState>,
+    Path((size, token)): Path<(String, String)>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let size = match PhotoSize::parse(&size) {
+        Ok(s) => s,
+        Err(e) => return Err((StatusCode::BAD_REQUEST, e)),
+    };
+    let photo = match Photo::from_token(&token) {
+        Ok(p) => p,
+        Err(e) => return Err((StatusCode::FORBIDDEN, e)),
+    };
+    if !app_state.photo_proxy.allows(&photo) {
+        return Err((StatusCode::FORBIDDEN, "Photo not allowed".to_string()));
+    }
+
+    match app_state.photo_proxy.get(&photo, size).await {
+        Ok(bytes) => Ok((
+            [
+                (header::CONTENT_TYPE, photo.content_type()),
+                (header::CACHE_CONTROL, PHOTO_CACHE_CONTROL),
+                (
+                    HeaderName::from_static("x-photo-attribution"),
+                    PHOTO_ATTRIBUTION,
+                ),
+            ],
+            bytes,
+        )),
+        Err(e) => Err((StatusCode::BAD_GATEWAY, e)),
+    }
+}
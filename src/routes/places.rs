@@ -0,0 +1,485 @@
+use std::collections::{HashMap, HashSet};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    geo::{
+        cluster::{grid_clusters, MAX_ZOOM},
+        BoundingBox, Coordinates,
+    },
+    places::{
+        search::{update_place, MAX_SEARCH_RADIUS_METERS},
+        Place,
+    },
+    repository::{
+        places::{DynPlacesRepo, NearbyPlaceOptions, ReadPlaceOptions, SearchPlaceOptions},
+        ratings::{PlaceScore, Rating, ReadRatingOptions, ReadRatingPhotoOptions},
+        user::User,
+    },
+    routes::rating_photos::RatingPhotoResponse,
+};
+
+pub const DEFAULT_NEARBY_RADIUS_METERS: f64 = 1_000.0;
+pub const DEFAULT_NEARBY_LIMIT: usize = 20;
+pub const MAX_NEARBY_LIMIT: usize = 50;
+
+#[derive(Deserialize)]
+pub struct NearbyParams {
+    lat: f64,
+    lng: f64,
+    /// In meters.
+    radius: Option<f64>,
+    limit: Option<usize>,
+}
+
+impl NearbyParams {
+    fn into_options(self) -> Result<NearbyPlaceOptions, String> {
+        if !(-90.0..=90.0).contains(&self.lat) || !(-180.0..=180.0).contains(&self.lng) {
+            return Err("Location not valid".to_string());
+        }
+        let radius_meters = self.radius.unwrap_or(DEFAULT_NEARBY_RADIUS_METERS);
+        if !(radius_meters > 0.0 && radius_meters <= MAX_SEARCH_RADIUS_METERS) {
+            return Err(format!(
+                "Radius must be between 0 and {} meters",
+                MAX_SEARCH_RADIUS_METERS
+            ));
+        }
+        let limit = self.limit.unwrap_or(DEFAULT_NEARBY_LIMIT);
+        if limit == 0 || limit > MAX_NEARBY_LIMIT {
+            return Err(format!("Limit must be between 1 and {}", MAX_NEARBY_LIMIT));
+        }
+        Ok(NearbyPlaceOptions {
+            center: Coordinates {
+                latitude: self.lat,
+                longitude: self.lng,
+            },
+            radius_meters,
+            limit,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NearbyPlaceResponse {
+    #[serde(flatten)]
+    place: Place,
+    distance_meters: f64,
+    /// Compass bearing from the requested location, clockwise from north.
+    bearing_degrees: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NearbyResponse {
+    places: Vec<NearbyPlaceResponse>,
+}
+
+#[axum_macros::debug_handler]
+pub async fn nearby_places(
+    State(app_state): State<AppState>,
+    Query(params): Query<NearbyParams>,
+) -> Result<Json<NearbyResponse>, (StatusCode, String)> {
+    let options = match params.into_options() {
+        Ok(o) => o,
+        Err(e) => return Err((StatusCode::BAD_REQUEST, e)),
+    };
+    let center = options.center;
+
+    let nearby = match app_state.places_repo.lock().await.nearby(options).await {
+        Ok(n) => n,
+        Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+    };
+
+    Ok(Json(NearbyResponse {
+        places: nearby
+            .into_iter()
+            .map(|n| NearbyPlaceResponse {
+                bearing_degrees: n
+                    .place
+                    .coordinates
+                    .map(|c| center.bearing_degrees(&c))
+                    .unwrap_or_default(),
+                distance_meters: n.distance_meters,
+                place: n.place,
+            })
+            .collect(),
+    }))
+}
+
+/// The most places a viewport query looks at. Zoomed out over a busy area
+/// the rest are left out, and the response says so.
+pub const MAX_VIEWPORT_PLACES: usize = 5_000;
+
+/// Cells per side of the grid a crowded viewport is sampled over, so the
+/// places left out are spread across the map rather than whichever sort last
+/// by name.
+const VIEWPORT_SAMPLE_GRID: usize = 4;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ViewportParams {
+    min_longitude: f64,
+    min_latitude: f64,
+    max_longitude: f64,
+    max_latitude: f64,
+    zoom: u8,
+}
+
+impl ViewportParams {
+    fn bounding_box(&self) -> Result<BoundingBox, String> {
+        let latitudes = (-90.0..=90.0).contains(&self.min_latitude)
+            && (-90.0..=90.0).contains(&self.max_latitude)
+            && self.min_latitude <= self.max_latitude;
+        let longitudes = (-180.0..=180.0).contains(&self.min_longitude)
+            && (-180.0..=180.0).contains(&self.max_longitude)
+            && self.min_longitude <= self.max_longitude;
+        if !latitudes || !longitudes {
+            return Err("Bounding box not valid".to_string());
+        }
+        if self.zoom > MAX_ZOOM {
+            return Err(format!("Zoom must be between 0 and {}", MAX_ZOOM));
+        }
+        Ok(BoundingBox {
+            min_longitude: self.min_longitude,
+            min_latitude: self.min_latitude,
+            max_longitude: self.max_longitude,
+            max_latitude: self.max_latitude,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ViewportPlace {
+    #[serde(flatten)]
+    place: Place,
+    rating_count: u64,
+    average_score: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ViewportCluster {
+    count: usize,
+    centroid: Coordinates,
+    bounding_box: BoundingBox,
+    /// Ratings of every place in the cluster, which `average_score` averages.
+    rating_count: u64,
+    average_score: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ViewportResponse {
+    places: Vec<ViewportPlace>,
+    clusters: Vec<ViewportCluster>,
+    /// Whether there were more than `MAX_VIEWPORT_PLACES` places to show.
+    truncated: bool,
+}
+
+fn cluster(
+    places: Vec<(Coordinates, Place)>,
+    scores: &HashMap<u64, PlaceScore>,
+) -> ViewportCluster {
+    let count = places.len();
+    let mut bounding_box = BoundingBox {
+        min_longitude: f64::MAX,
+        min_latitude: f64::MAX,
+        max_longitude: f64::MIN,
+        max_latitude: f64::MIN,
+    };
+    let (mut latitude_sum, mut longitude_sum) = (0.0, 0.0);
+    let (mut rating_count, mut score_sum) = (0, 0.0);
+    for (coordinates, place) in &places {
+        latitude_sum += coordinates.latitude;
+        longitude_sum += coordinates.longitude;
+        bounding_box.min_longitude = bounding_box.min_longitude.min(coordinates.longitude);
+        bounding_box.min_latitude = bounding_box.min_latitude.min(coordinates.latitude);
+        bounding_box.max_longitude = bounding_box.max_longitude.max(coordinates.longitude);
+        bounding_box.max_latitude = bounding_box.max_latitude.max(coordinates.latitude);
+        if let Some(score) = scores.get(&place.id) {
+            rating_count += score.rating_count;
+            score_sum += score.average_score * score.rating_count as f64;
+        }
+    }
+
+    ViewportCluster {
+        count,
+        centroid: Coordinates {
+            latitude: latitude_sum / count as f64,
+            longitude: longitude_sum / count as f64,
+        },
+        bounding_box,
+        rating_count,
+        average_score: (rating_count > 0).then(|| score_sum / rating_count as f64),
+    }
+}
+
+/// The located places inside `bounding_box`, and whether some were left out
+/// to keep to `MAX_VIEWPORT_PLACES`. When there are too many, each cell of a
+/// grid over the box gets an equal share of the places shown.
+async fn search_viewport(
+    places_repo: &DynPlacesRepo,
+    bounding_box: BoundingBox,
+) -> Result<(Vec<Place>, bool), String> {
+    let places_repo = places_repo.lock().await;
+    // Empty text matches every place.
+    let search = |bounding_box, limit| SearchPlaceOptions {
+        text: String::new(),
+        bounding_box: Some(bounding_box),
+        include_unlocated: false,
+        closest_to: None,
+        limit,
+    };
+
+    // One more than fits tells whether any were left out.
+    let places = places_repo
+        .search(search(bounding_box, MAX_VIEWPORT_PLACES + 1))
+        .await?;
+    if places.len() <= MAX_VIEWPORT_PLACES {
+        return Ok((places, false));
+    }
+
+    let grid = bounding_box.grid(VIEWPORT_SAMPLE_GRID);
+    let cell_limit = MAX_VIEWPORT_PLACES / grid.len();
+    let mut sampled = Vec::with_capacity(MAX_VIEWPORT_PLACES);
+    let mut seen = HashSet::new();
+    for cell in grid {
+        // Cells share their edges, so a place on one can be found twice.
+        for place in places_repo.search(search(cell, cell_limit)).await? {
+            if seen.insert(place.id) {
+                sampled.push(place);
+            }
+        }
+    }
+    Ok((sampled, true))
+}
+
+/// Stored places inside the visible map, with dense areas collapsed into
+/// clusters for the zoom level.
+#[axum_macros::debug_handler]
+pub async fn viewport_places(
+    State(app_state): State<AppState>,
+    Query(params): Query<ViewportParams>,
+) -> Result<Json<ViewportResponse>, (StatusCode, String)> {
+    let bounding_box = match params.bounding_box() {
+        Ok(b) => b,
+        Err(e) => return Err((StatusCode::BAD_REQUEST, e)),
+    };
+
+    let (places, truncated) = match search_viewport(&app_state.places_repo, bounding_box).await {
+        Ok(p) => p,
+        Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+    };
+
+    let place_ids: Vec<u64> = places.iter().map(|p| p.id).collect();
+    let scores = match app_state.ratings_repo.lock().await.scores(&place_ids).await {
+        Ok(s) => s,
+        Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+    };
+
+    let located: Vec<(Coordinates, Place)> = places
+        .into_iter()
+        .filter_map(|p| Some((p.coordinates?, p)))
+        .collect();
+    let mut response = ViewportResponse {
+        places: Vec::new(),
+        clusters: Vec::new(),
+        truncated,
+    };
+    for mut group in grid_clusters(located, params.zoom) {
+        if group.len() > 1 {
+            response.clusters.push(cluster(group, &scores));
+            continue;
+        }
+        if let Some((_, place)) = group.pop() {
+            let score = scores.get(&place.id);
+            response.places.push(ViewportPlace {
+                rating_count: score.map(|s| s.rating_count).unwrap_or_default(),
+                average_score: score.map(|s| s.average_score),
+                place,
+            });
+        }
+    }
+    Ok(Json(response))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaceDetailsParams {
+    /// The `sessionId` of the search the place was picked from.
+    session_id: Option<String>,
+}
+
+/// Retrieves the coordinates of a place that was found without them, such as
+/// a Mapbox suggestion, now that `user` picked it. The retrieve ends the
+/// user's search session it was picked from; without one of theirs, it is
+/// billed as a session of its own. The place is still worth showing without
+/// coordinates, so failures are only logged.
+pub(crate) async fn locate_place(
+    app_state: &AppState,
+    user: &User,
+    place: Place,
+    session_id: Option<&str>,
+) -> Place {
+    if place.coordinates.is_some() || place.mapbox_id.is_none() {
+        return place;
+    }
+    let session_id = session_id.and_then(|id| {
+        app_state
+            .search_sessions
+            .retrieve_token(user.phone_number, id)
+    });
+    let session_token = match &session_id {
+        Some(id) => id.clone(),
+        None => uuid::Uuid::new_v4().to_string(),
+    };
+    let located = app_state
+        .places_search
+        .get_coordinates(place.id, &session_token)
+        .await;
+    if let Some(id) = session_id {
+        app_state.search_sessions.end(&id);
+    }
+    let coordinates = match located {
+        Ok(located) => located.coordinates,
+        Err(e) => {
+            eprintln!("Error getting coordinates of place {}: {}", place.id, e);
+            return place;
+        }
+    };
+    match update_place(&app_state.places_repo, place.id, |stored| {
+        stored.coordinates = coordinates
+    })
+    .await
+    {
+        Ok(located) => located,
+        Err(e) => {
+            eprintln!("Error storing coordinates of place {}: {}", place.id, e);
+            Place {
+                coordinates,
+                ..place
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaceDetailsResponse {
+    #[serde(flatten)]
+    place: Place,
+    rating_count: u64,
+    average_score: Option<f64>,
+    /// The signed in user's rating of the place, if they rated it.
+    user_rating: Option<Rating>,
+    /// Photos everyone attached to their ratings, oldest first.
+    rating_photos: Vec<RatingPhotoResponse>,
+}
+
+/// A stored place with its scores and the caller's rating. Places without
+/// coordinates, photos or fresh details are enriched by the search provider
+/// first, and what it finds is stored for next time.
+#[axum_macros::debug_handler]
+pub async fn place_details(
+    State(app_state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(place_id): Path<u64>,
+    Query(params): Query<PlaceDetailsParams>,
+) -> Result<Json<PlaceDetailsResponse>, (StatusCode, String)> {
+    let place = match app_state
+        .places_repo
+        .lock()
+        .await
+        .read(ReadPlaceOptions {
+            id: Some(place_id),
+            name: None,
+            address: None,
+            postcode: None,
+        })
+        .await
+    {
+        Ok(mut places) => match places.pop() {
+            Some(place) => place,
+            None => return Err((StatusCode::NOT_FOUND, "Place not found".to_string())),
+        },
+        Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+    };
+
+    app_state.place_activity.record([place_id], Utc::now());
+    let mut place = locate_place(&app_state, &user, place, params.session_id.as_deref()).await;
+
+    // A place is still worth showing without photos or details, so
+    // enrichment failures are only logged.
+    if place.details_stale(Utc::now()) {
+        // Only what a details fetch fills in, keeping the coordinates
+        // located above.
+        match app_state.places_search.get_details(place_id).await {
+            Ok(enriched) => {
+                place.details = enriched.details;
+                place.website = enriched.website;
+                place.categories = enriched.categories;
+            }
+            Err(e) => eprintln!("Error getting details of place {}: {}", place_id, e),
+        }
+    }
+
+    if place.photos.as_ref().is_none_or(|p| p.is_empty()) {
+        match app_state.places_search.get_photos(place_id).await {
+            Ok(enriched) if enriched.photos.as_ref().is_some_and(|p| !p.is_empty()) => {
+                place.photos = enriched.photos.clone();
+                let stored = update_place(&app_state.places_repo, place_id, |stored| {
+                    stored.photos = enriched.photos
+                })
+                .await;
+                if let Err(e) = stored {
+                    eprintln!("Error storing photos of place {}: {}", place_id, e);
+                }
+            }
+            Ok(_) => {}
+            Err(e) => eprintln!("Error getting photos of place {}: {}", place_id, e),
+        }
+    }
+
+    let ratings_repo = app_state.ratings_repo.lock().await;
+    let score = match ratings_repo.scores(&[place_id]).await {
+        Ok(mut scores) => scores.remove(&place_id),
+        Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+    };
+    let user_rating = match ratings_repo
+        .read(ReadRatingOptions {
+            id: None,
+            place_id: Some(place_id),
+            phone_number: Some(user.phone_number),
+        })
+        .await
+    {
+        Ok(mut ratings) => ratings.pop(),
+        Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+    };
+    let rating_photos = match ratings_repo
+        .read_photos(ReadRatingPhotoOptions {
+            id: None,
+            rating_id: None,
+            place_id: Some(place_id),
+        })
+        .await
+    {
+        Ok(photos) => photos.into_iter().map(RatingPhotoResponse::from).collect(),
+        Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+    };
+
+    Ok(Json(PlaceDetailsResponse {
+        place,
+        rating_count: score.map(|s| s.rating_count).unwrap_or_default(),
+        average_score: score.map(|s| s.average_score),
+        user_rating,
+        rating_photos,
+    }))
+}
@@ -0,0 +1,417 @@
+use axum::{
+    extract::{Multipart, Path, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    blob::{Blob, DynBlobStore},
+    places::upload::{
+        process_photo, PhotoFormat, ProcessedPhoto, MAX_PHOTO_BYTES, MAX_RATING_PHOTOS,
+    },
+    repository::{
+        ratings::{
+            Rating, RatingPhoto, RatingsRepository, ReadRatingOptions, ReadRatingPhotoOptions,
+        },
+        user::User,
+    },
+};
+
+/// The most photos one upload request can carry. Clients with more send
+/// several requests.
+pub const MAX_UPLOAD_PHOTOS: usize = 4;
+
+/// The most an upload request can carry: `MAX_UPLOAD_PHOTOS` photos and room
+/// for the multipart framing.
+pub const MAX_UPLOAD_BYTES: usize = MAX_UPLOAD_PHOTOS * MAX_PHOTO_BYTES + 1024 * 1024;
+
+/// The multipart field photos are uploaded in. There may be several.
+const PHOTO_FIELD: &str = "photo";
+
+/// Stored photos never change, so clients may keep them for a week.
+const RATING_PHOTO_CACHE_CONTROL: &str = "public, max-age=604800, immutable";
+
+/// A photo attached to a rating, with where to download it. Who took it
+/// isn't shared.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RatingPhotoResponse {
+    pub id: u64,
+    pub rating_id: u64,
+    pub place_id: u64,
+    pub url: String,
+    pub thumbnail_url: String,
+    pub width: u32,
+    pub height: u32,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<RatingPhoto> for RatingPhotoResponse {
+    fn from(photo: RatingPhoto) -> Self {
+        RatingPhotoResponse {
+            id: photo.id,
+            rating_id: photo.rating_id,
+            place_id: photo.place_id,
+            url: format!("/ratings/photos/{}", photo.id),
+            thumbnail_url: format!("/ratings/photos/{}/thumbnail", photo.id),
+            width: photo.width,
+            height: photo.height,
+            created_at: photo.created_at,
+        }
+    }
+}
+
+/// Reads the next photo in the upload, giving up as soon as it's too big
+/// rather than reading the rest of it.
+async fn read_photo(
+    field: &mut axum::extract::multipart::Field<'_>,
+) -> Result<Vec<u8>, (StatusCode, String)> {
+    let mut bytes = Vec::new();
+    loop {
+        match field.chunk().await {
+            Ok(Some(chunk)) => {
+                if bytes.len() + chunk.len() > MAX_PHOTO_BYTES {
+                    return Err((
+                        StatusCode::PAYLOAD_TOO_LARGE,
+                        format!("Photos must be at most {} bytes", MAX_PHOTO_BYTES),
+                    ));
+                }
+                bytes.extend_from_slice(&chunk);
+            }
+            Ok(None) => return Ok(bytes),
+            Err(e) => return Err((e.status(), e.body_text())),
+        }
+    }
+}
+
+/// A photo and thumbnail in the blob store, not yet added to a rating.
+struct StoredPhoto {
+    key: String,
+    thumbnail_key: String,
+    width: u32,
+    height: u32,
+}
+
+/// Stores the photo and its thumbnail under the rating. Returns their keys.
+async fn store_photo(
+    blob_store: &DynBlobStore,
+    rating_id: u64,
+    photo: ProcessedPhoto,
+) -> Result<(String, String), String> {
+    let name = uuid::Uuid::new_v4();
+    let extension = photo.format.extension();
+    let key = format!("ratings/{}/{}.{}", rating_id, name, extension);
+    let thumbnail_key = format!("ratings/{}/{}-thumbnail.{}", rating_id, name, extension);
+
+    blob_store
+        .put(
+            &key,
+            Blob {
+                bytes: photo.image,
+                content_type: photo.format.content_type().to_string(),
+            },
+        )
+        .await?;
+    if let Err(e) = blob_store
+        .put(
+            &thumbnail_key,
+            Blob {
+                bytes: photo.thumbnail,
+                content_type: photo.format.content_type().to_string(),
+            },
+        )
+        .await
+    {
+        delete_blobs(blob_store, &[&key]).await;
+        return Err(e);
+    }
+    Ok((key, thumbnail_key))
+}
+
+/// Deletes blobs that are no longer linked to anything. Failures only leave
+/// unreachable files behind, so they are logged.
+async fn delete_blobs(blob_store: &DynBlobStore, keys: &[&str]) {
+    for key in keys {
+        if let Err(e) = blob_store.delete(key).await {
+            eprintln!("Error deleting blob {}: {}", key, e);
+        }
+    }
+}
+
+async fn count_photos(
+    ratings_repo: &dyn RatingsRepository,
+    rating_id: u64,
+) -> Result<usize, String> {
+    ratings_repo
+        .read_photos(ReadRatingPhotoOptions {
+            id: None,
+            rating_id: Some(rating_id),
+            place_id: None,
+        })
+        .await
+        .map(|photos| photos.len())
+}
+
+fn too_many_photos() -> (StatusCode, String) {
+    (
+        StatusCode::BAD_REQUEST,
+        format!("A rating can have at most {} photos", MAX_RATING_PHOTOS),
+    )
+}
+
+/// Attaches up to `MAX_UPLOAD_PHOTOS` photos to the signed in user's rating
+/// of a place. Each `photo` field must be a JPEG or PNG; they're stored
+/// upright, without metadata such as where they were taken, and with a
+/// thumbnail.
+#[axum_macros::debug_handler]
+pub async fn upload_rating_photos(
+    State(app_state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(place_id): Path<u64>,
+    mut multipart: Multipart,
+) -> Result<Json<Vec<RatingPhotoResponse>>, (StatusCode, String)> {
+    let (rating, photo_count) = {
+        let ratings_repo = app_state.ratings_repo.lock().await;
+        let rating = match ratings_repo
+            .read(ReadRatingOptions {
+                id: None,
+                place_id: Some(place_id),
+                phone_number: Some(user.phone_number),
+            })
+            .await
+        {
+            Ok(mut ratings) => match ratings.pop() {
+                Some(r) => r,
+                None => {
+                    return Err((
+                        StatusCode::NOT_FOUND,
+                        "Rate the place before adding photos".to_string(),
+                    ))
+                }
+            },
+            Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+        };
+        match count_photos(&*ratings_repo, rating.id).await {
+            Ok(count) => (rating, count),
+            Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+        }
+    };
+
+    // Each photo is stored before the next is read, so only one is held in
+    // memory at a time. If any is rejected, those already stored are removed,
+    // so a bad upload stores nothing.
+    let mut blobs = Vec::new();
+    if let Err(e) = store_photos(&app_state, &rating, photo_count, &mut multipart, &mut blobs).await
+    {
+        delete_photo_blobs(&app_state.blob_store, &blobs).await;
+        return Err(e);
+    }
+    if blobs.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "No photos uploaded".to_string()));
+    }
+
+    // Another upload to the rating may have finished since the photos were
+    // counted, so the repository counts them again as it adds them.
+    let added = {
+        let mut ratings_repo = app_state.ratings_repo.lock().await;
+        add_photos(&mut *ratings_repo, &rating, &user, &blobs).await
+    };
+    match added {
+        Ok(stored) => Ok(Json(stored.into_iter().map(|p| p.into()).collect())),
+        Err(e) => {
+            delete_photo_blobs(&app_state.blob_store, &blobs).await;
+            Err(e)
+        }
+    }
+}
+
+/// Reads, processes and stores the upload's photos one after another,
+/// adding each to `blobs` once it's stored.
+async fn store_photos(
+    app_state: &AppState,
+    rating: &Rating,
+    photo_count: usize,
+    multipart: &mut Multipart,
+    blobs: &mut Vec<StoredPhoto>,
+) -> Result<(), (StatusCode, String)> {
+    loop {
+        let mut field = match multipart.next_field().await {
+            Ok(Some(f)) => f,
+            Ok(None) => return Ok(()),
+            Err(e) => return Err((e.status(), e.body_text())),
+        };
+        if field.name() != Some(PHOTO_FIELD) {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("Photos must be sent in {} fields", PHOTO_FIELD),
+            ));
+        }
+        if photo_count + blobs.len() >= MAX_RATING_PHOTOS {
+            return Err(too_many_photos());
+        }
+        if blobs.len() >= MAX_UPLOAD_PHOTOS {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("Upload at most {} photos at a time", MAX_UPLOAD_PHOTOS),
+            ));
+        }
+        let bytes = read_photo(&mut field).await?;
+        let format = match PhotoFormat::detect(&bytes) {
+            Some(f) => f,
+            None => {
+                return Err((
+                    StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                    "Photos must be JPEG or PNG images".to_string(),
+                ))
+            }
+        };
+        // Decoding and encoding images is slow enough to hold up other
+        // requests.
+        let photo = match tokio::task::spawn_blocking(move || process_photo(&bytes, format)).await {
+            Ok(Ok(photo)) => photo,
+            Ok(Err(e)) => return Err((StatusCode::BAD_REQUEST, e)),
+            Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+        };
+        let (width, height) = (photo.width, photo.height);
+        match store_photo(&app_state.blob_store, rating.id, photo).await {
+            Ok((key, thumbnail_key)) => blobs.push(StoredPhoto {
+                key,
+                thumbnail_key,
+                width,
+                height,
+            }),
+            Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+        }
+    }
+}
+
+/// Links the stored blobs to the rating, unless that would give it more than
+/// `MAX_RATING_PHOTOS`. On failure, none of them are linked.
+async fn add_photos(
+    ratings_repo: &mut dyn RatingsRepository,
+    rating: &Rating,
+    user: &User,
+    blobs: &[StoredPhoto],
+) -> Result<Vec<RatingPhoto>, (StatusCode, String)> {
+    let now = Utc::now();
+    let photos: Vec<RatingPhoto> = blobs
+        .iter()
+        .map(|blob| RatingPhoto {
+            id: 0,
+            rating_id: rating.id,
+            place_id: rating.place_id,
+            phone_number: user.phone_number,
+            key: blob.key.clone(),
+            thumbnail_key: blob.thumbnail_key.clone(),
+            width: blob.width,
+            height: blob.height,
+            created_at: now,
+        })
+        .collect();
+    match ratings_repo.add_photos(&photos, MAX_RATING_PHOTOS).await {
+        Ok(Some(stored)) => Ok(stored),
+        Ok(None) => Err(too_many_photos()),
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+    }
+}
+
+async fn delete_photo_blobs(blob_store: &DynBlobStore, blobs: &[StoredPhoto]) {
+    for blob in blobs {
+        delete_blobs(blob_store, &[&blob.key, &blob.thumbnail_key]).await;
+    }
+}
+
+/// Removes the images of photos already deleted from the repository, such as
+/// those that went with a deleted rating.
+pub async fn delete_rating_photo_blobs(blob_store: &DynBlobStore, photos: &[RatingPhoto]) {
+    for photo in photos {
+        delete_blobs(blob_store, &[&photo.key, &photo.thumbnail_key]).await;
+    }
+}
+
+async fn read_rating_photo(
+    app_state: &AppState,
+    id: u64,
+) -> Result<RatingPhoto, (StatusCode, String)> {
+    match app_state
+        .ratings_repo
+        .lock()
+        .await
+        .read_photos(ReadRatingPhotoOptions {
+            id: Some(id),
+            rating_id: None,
+            place_id: None,
+        })
+        .await
+    {
+        Ok(mut photos) => match photos.pop() {
+            Some(p) => Ok(p),
+            None => Err((StatusCode::NOT_FOUND, "Photo not found".to_string())),
+        },
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+    }
+}
+
+async fn serve_blob(
+    app_state: &AppState,
+    key: &str,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    match app_state.blob_store.get(key).await {
+        Ok(Some(blob)) => Ok((
+            [
+                (header::CONTENT_TYPE, blob.content_type),
+                (
+                    header::CACHE_CONTROL,
+                    RATING_PHOTO_CACHE_CONTROL.to_string(),
+                ),
+            ],
+            blob.bytes,
+        )),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Photo not found".to_string())),
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+    }
+}
+
+#[axum_macros::debug_handler]
+pub async fn rating_photo(
+    State(app_state): State<AppState>,
+    Path(id): Path<u64>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let photo = read_rating_photo(&app_state, id).await?;
+    serve_blob(&app_state, &photo.key).await
+}
+
+#[axum_macros::debug_handler]
+pub async fn rating_photo_thumbnail(
+    State(app_state): State<AppState>,
+    Path(id): Path<u64>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let photo = read_rating_photo(&app_state, id).await?;
+    serve_blob(&app_state, &photo.thumbnail_key).await
+}
+
+/// Removes one of the signed in user's photos.
+#[axum_macros::debug_handler]
+pub async fn delete_rating_photo(
+    State(app_state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(id): Path<u64>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let photo = read_rating_photo(&app_state, id).await?;
+    if photo.phone_number != user.phone_number {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Only the photo's owner can delete it".to_string(),
+        ));
+    }
+
+    if let Err(e) = app_state.ratings_repo.lock().await.delete_photo(id).await {
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, e));
+    }
+    delete_blobs(&app_state.blob_store, &[&photo.key, &photo.thumbnail_key]).await;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -1,71 +1,471 @@
-use axum::{extract::State, http::StatusCode, Json};
-use futures::future;
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use futures::{stream, StreamExt};
 use serde::{Deserialize, Serialize};
 
-use crate::{app_state::AppState, geo::Coordinates, places::Place};
+use crate::{
+    app_state::AppState,
+    geo::Coordinates,
+    places::{
+        cache::CacheStats,
+        export::{to_geojson, to_kml, RatedPlace, GEOJSON_CONTENT_TYPE, KML_CONTENT_TYPE},
+        filter::{PlaceFilters, MAX_PRICE, MIN_PRICE},
+        search::{
+            DynPlacesSearch, PlaceCategory, SearchQuery, MAX_SEARCH_LIMIT, MAX_SEARCH_RADIUS_METERS,
+        },
+        Place,
+    },
+    repository::{
+        jobs::JobKind,
+        places::ReadPlaceOptions,
+        ratings::{Rating, ReadRatingOptions, MAX_SCORE, MIN_SCORE},
+        user::User,
+    },
+    routes::{places::locate_place, rating_photos::delete_rating_photo_blobs},
+};
 
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SearchRequest {
     place_name: String,
     location: Coordinates,
+    /// The `sessionId` from a previous response while the user keeps typing.
+    session_id: Option<String>,
+    /// Defaults to food when unset.
+    categories: Option<Vec<PlaceCategory>>,
+    /// How far from `location` to look, in meters.
+    radius: Option<f64>,
+    limit: Option<u8>,
+    /// An ISO 639-1 language code, `en` when unset.
+    language: Option<String>,
+    /// Only places open right now.
+    open_now: Option<bool>,
+    /// Only places open at this time, in each place's own time zone.
+    open_at: Option<DateTime<Utc>>,
+    /// Price tiers from 1 (cheapest) to 4.
+    min_price: Option<u8>,
+    max_price: Option<u8>,
+}
+
+impl SearchRequest {
+    /// Validates the opening hours and price filters.
+    fn filters(&self) -> Result<PlaceFilters, String> {
+        let open_at = match (self.open_now, self.open_at) {
+            (Some(true), Some(_)) => {
+                return Err("Only one of openNow and openAt can be set".to_string())
+            }
+            (Some(true), None) => Some(Utc::now()),
+            (_, open_at) => open_at,
+        };
+        for price in [self.min_price, self.max_price].into_iter().flatten() {
+            if !(MIN_PRICE..=MAX_PRICE).contains(&price) {
+                return Err(format!(
+                    "Price must be between {} and {}",
+                    MIN_PRICE, MAX_PRICE
+                ));
+            }
+        }
+        if let (Some(min), Some(max)) = (self.min_price, self.max_price) {
+            if min > max {
+                return Err("minPrice can't be more than maxPrice".to_string());
+            }
+        }
+        Ok(PlaceFilters {
+            open_at,
+            min_price: self.min_price,
+            max_price: self.max_price,
+        })
+    }
+
+    /// Validates the request. The session token is filled in by the caller.
+    fn into_query(self) -> Result<SearchQuery, String> {
+        let mut query = SearchQuery::new(self.location, &self.place_name, "");
+        if let Some(categories) = self.categories {
+            query.categories = categories;
+        }
+        if let Some(radius) = self.radius {
+            if !(radius > 0.0 && radius <= MAX_SEARCH_RADIUS_METERS) {
+                return Err(format!(
+                    "Radius must be between 0 and {} meters",
+                    MAX_SEARCH_RADIUS_METERS
+                ));
+            }
+            query.radius_meters = radius;
+        }
+        if let Some(limit) = self.limit {
+            if limit == 0 || limit > MAX_SEARCH_LIMIT {
+                return Err(format!("Limit must be between 1 and {}", MAX_SEARCH_LIMIT));
+            }
+            query.limit = Some(limit);
+        }
+        if let Some(language) = self.language {
+            if language.len() != 2 || !language.chars().all(|c| c.is_ascii_lowercase()) {
+                return Err("Language not valid".to_string());
+            }
+            query.language = language;
+        }
+        Ok(query)
+    }
 }
 
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SearchResponse {
     places: Vec<Place>,
+    session_id: String,
 }
 
 #[axum_macros::debug_handler]
 pub async fn search_for_place(
     State(app_state): State<AppState>,
+    Extension(user): Extension<User>,
     Json(payload): Json<SearchRequest>,
 ) -> Result<Json<SearchResponse>, (StatusCode, String)> {
-    let places_repo = &app_state.places_repo;
     let places_search = &app_state.places_search;
+    let session_id = payload.session_id.clone();
+    let filters = match payload.filters() {
+        Ok(f) => f,
+        Err(e) => return Err((StatusCode::BAD_REQUEST, e)),
+    };
+    let mut query = match payload.into_query() {
+        Ok(q) => q,
+        Err(e) => return Err((StatusCode::BAD_REQUEST, e)),
+    };
+    let session_id = app_state
+        .search_sessions
+        .resolve(user.phone_number, session_id.as_deref());
+    query.session_token = session_id.clone();
 
-    let places: Vec<Place>;
-    match places_search
-        .lock()
-        .await
-        .search_for_place(payload.location, payload.place_name)
-        .await
-    {
-        Ok(p) => places = p,
+    let mut places: Vec<Place> = match places_search.search_for_place(query).await {
+        Ok(p) => p,
         Err(e) => {
             return Err((StatusCode::INTERNAL_SERVER_ERROR, e));
         }
     };
-    let return_places = places.clone();
+    if !filters.is_empty() {
+        places = filter_places(places_search, &filters, places).await;
+    }
+    app_state
+        .place_activity
+        .record(places.iter().map(|p| p.id), Utc::now());
+    places = with_stored_photos(&app_state, places).await;
+    for place in places.iter().filter(|p| p.photos_missing()) {
+        if let Err(e) = app_state.jobs.enqueue(JobKind::FetchPhotos, place.id).await {
+            eprintln!("Error queueing photos of place {}: {}", place.id, e);
+        }
+    }
+
+    Ok(axum::Json(SearchResponse { places, session_id }))
+}
+
+/// Swaps in the stored copy of places found without photos that have been
+/// fetched since, such as cached results from before the photos job ran, so
+/// they aren't queued again.
+async fn with_stored_photos(app_state: &AppState, mut places: Vec<Place>) -> Vec<Place> {
+    let missing: Vec<u64> = places
+        .iter()
+        .filter(|p| p.photos.is_none())
+        .map(|p| p.id)
+        .collect();
+    if missing.is_empty() {
+        return places;
+    }
+    let stored = match app_state.places_repo.lock().await.read_many(&missing).await {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("Error reading stored photos of places: {}", e);
+            return places;
+        }
+    };
+    for place in places.iter_mut() {
+        if let Some(s) = stored
+            .iter()
+            .find(|s| s.id == place.id && s.photos.is_some())
+        {
+            *place = s.clone();
+        }
+    }
+    places
+}
 
-    let places_repo = places_repo.clone();
-    let places_search = places_search.clone();
-    tokio::spawn(async move {
-        let places_repo = &places_repo;
-        let places_search = &places_search;
+/// The most details requests one filtered search makes at a time, since
+/// searches run on every keystroke and each result may need its details.
+const MAX_DETAILS_REQUESTS: usize = 4;
 
-        let results = future::try_join_all(places.iter().map(|place| async move {
-            if place.photos == None {
-                return places_search.lock().await.get_photos(place.id).await;
+/// Keeps the places matching `filters`, fetching hours and prices first for
+/// places that don't have them yet. A place whose details can't be fetched
+/// is judged on what we already know about it.
+async fn filter_places(
+    places_search: &DynPlacesSearch,
+    filters: &PlaceFilters,
+    places: Vec<Place>,
+) -> Vec<Place> {
+    let now = Utc::now();
+    let places: Vec<Place> = stream::iter(places)
+        .map(|place| async move {
+            if !place.details_stale(now) {
+                return place;
             }
-            return Ok(place.clone());
-        }))
+            match places_search.get_details(place.id).await {
+                Ok(p) => p,
+                Err(e) => {
+                    eprintln!("Error getting details for place {}: {}", place.id, e);
+                    place
+                }
+            }
+        })
+        .buffered(MAX_DETAILS_REQUESTS)
+        .collect()
         .await;
+    places
+        .into_iter()
+        .filter(|place| filters.matches(place))
+        .collect()
+}
 
-        match results {
-            Ok(p) => {
-                return future::try_join_all(
-                    p.iter()
-                        .map(|place| async move { places_repo.lock().await.create(place).await }),
-                )
-                .await;
+#[axum_macros::debug_handler]
+pub async fn search_cache_stats(
+    State(app_state): State<AppState>,
+) -> Result<Json<CacheStats>, (StatusCode, String)> {
+    match app_state.places_search.cache_stats().await {
+        Some(stats) => Ok(Json(stats)),
+        None => Err((
+            StatusCode::NOT_FOUND,
+            "Search cache not enabled".to_string(),
+        )),
+    }
+}
+
+/// The longest review we store, in characters.
+pub const MAX_REVIEW_LENGTH: usize = 2000;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RateRequest {
+    score: u8,
+    review: Option<String>,
+    /// The `sessionId` of the search the place was picked from.
+    session_id: Option<String>,
+}
+
+/// Rates a place for the signed in user, replacing their earlier rating.
+#[axum_macros::debug_handler]
+pub async fn rate_place(
+    State(app_state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(place_id): Path<u64>,
+    Json(payload): Json<RateRequest>,
+) -> Result<Json<Rating>, (StatusCode, String)> {
+    if !(MIN_SCORE..=MAX_SCORE).contains(&payload.score) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Score must be between {} and {}", MIN_SCORE, MAX_SCORE),
+        ));
+    }
+    let review = payload
+        .review
+        .map(|r| r.trim().to_string())
+        .filter(|r| !r.is_empty());
+    if review
+        .as_ref()
+        .is_some_and(|r| r.chars().count() > MAX_REVIEW_LENGTH)
+    {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Review must be at most {} characters", MAX_REVIEW_LENGTH),
+        ));
+    }
+
+    let place = match app_state
+        .places_repo
+        .lock()
+        .await
+        .read(ReadPlaceOptions {
+            id: Some(place_id),
+            name: None,
+            address: None,
+            postcode: None,
+        })
+        .await
+    {
+        Ok(mut places) => match places.pop() {
+            Some(place) => place,
+            None => return Err((StatusCode::NOT_FOUND, "Place not found".to_string())),
+        },
+        Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+    };
+    // Rated places are shown on the map, so they need a location.
+    let place = locate_place(&app_state, &user, place, payload.session_id.as_deref()).await;
+
+    let now = Utc::now();
+    match app_state
+        .ratings_repo
+        .lock()
+        .await
+        .upsert(&Rating {
+            id: 0,
+            place_id,
+            phone_number: user.phone_number,
+            score: payload.score,
+            review,
+            created_at: now,
+            updated_at: now,
+        })
+        .await
+    {
+        Ok(rating) => {
+            app_state.place_activity.record([place_id], now);
+            if let Some(coordinates) = place.coordinates {
+                app_state.tile_cache.invalidate(&coordinates);
             }
-            Err(e) => Err(e),
+            Ok(Json(rating))
         }
-    });
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+    }
+}
 
-    return Ok(axum::Json(SearchResponse {
-        places: return_places,
-    }));
+/// Removes the signed in user's rating of a place, along with its photos.
+#[axum_macros::debug_handler]
+pub async fn delete_place_rating(
+    State(app_state): State<AppState>,
+    Extension(user): Extension<User>,
+    Path(place_id): Path<u64>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let (_, photos) = {
+        let mut ratings_repo = app_state.ratings_repo.lock().await;
+        let rating = match ratings_repo
+            .read(ReadRatingOptions {
+                id: None,
+                place_id: Some(place_id),
+                phone_number: Some(user.phone_number),
+            })
+            .await
+        {
+            Ok(mut ratings) => match ratings.pop() {
+                Some(r) => r,
+                None => return Err((StatusCode::NOT_FOUND, "Rating not found".to_string())),
+            },
+            Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+        };
+        match ratings_repo.delete(rating.id).await {
+            Ok(Some(deleted)) => deleted,
+            Ok(None) => return Err((StatusCode::NOT_FOUND, "Rating not found".to_string())),
+            Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+        }
+    };
+    delete_rating_photo_blobs(&app_state.blob_store, &photos).await;
+
+    // The place's score on the map has changed.
+    if let Ok(places) = app_state
+        .places_repo
+        .lock()
+        .await
+        .read(ReadPlaceOptions {
+            id: Some(place_id),
+            name: None,
+            address: None,
+            postcode: None,
+        })
+        .await
+    {
+        if let Some(coordinates) = places.first().and_then(|p| p.coordinates) {
+            app_state.tile_cache.invalidate(&coordinates);
+        }
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Geojson,
+    Kml,
+}
+
+#[derive(Deserialize)]
+pub struct ExportParams {
+    format: Option<ExportFormat>,
+}
+
+/// Downloads the signed in user's rated places as GeoJSON or, for Google My
+/// Maps, KML. There are no lists to export yet, so only ratings are.
+#[axum_macros::debug_handler]
+pub async fn export_ratings(
+    State(app_state): State<AppState>,
+    Extension(user): Extension<User>,
+    Query(params): Query<ExportParams>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let ratings = match app_state
+        .ratings_repo
+        .lock()
+        .await
+        .read(ReadRatingOptions {
+            id: None,
+            place_id: None,
+            phone_number: Some(user.phone_number),
+        })
+        .await
+    {
+        Ok(r) => r,
+        Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+    };
+
+    let place_ids: Vec<u64> = ratings.iter().map(|r| r.place_id).collect();
+    let mut rated: HashMap<u64, Place> = match app_state
+        .places_repo
+        .lock()
+        .await
+        .read_many(&place_ids)
+        .await
+    {
+        Ok(p) => p.into_iter().map(|p| (p.id, p)).collect(),
+        Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+    };
+    let mut places = Vec::with_capacity(ratings.len());
+    for rating in ratings {
+        match rated.remove(&rating.place_id) {
+            Some(place) => places.push(RatedPlace { place, rating }),
+            None => {
+                return Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Error getting place".to_string(),
+                ))
+            }
+        }
+    }
+    places.sort_by(|a, b| a.place.name.cmp(&b.place.name));
+
+    let (content_type, extension, body) = match params.format.unwrap_or_default() {
+        ExportFormat::Geojson => (
+            GEOJSON_CONTENT_TYPE,
+            "geojson",
+            to_geojson(&places).to_string(),
+        ),
+        ExportFormat::Kml => (
+            KML_CONTENT_TYPE,
+            "kml",
+            to_kml(
+                &format!("{} {}'s ratings", user.first_name, user.last_name),
+                &places,
+            ),
+        ),
+    };
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"critiq-ratings.{}\"", extension),
+            ),
+        ],
+        body,
+    ))
 }
@@ -0,0 +1,96 @@
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+};
+
+use crate::{
+    app_state::AppState,
+    geo::tile::{encode_tile, TileFeature, TileId, TileValue, MVT_CONTENT_TYPE},
+    repository::places::SearchPlaceOptions,
+};
+
+pub const PLACES_LAYER: &str = "places";
+
+/// The most places looked at for one tile.
+pub const MAX_TILE_PLACES: usize = 5_000;
+
+/// A Mapbox Vector Tile with a point for every rated place in the tile,
+/// carrying its name, average score, rating count and categories.
+#[axum_macros::debug_handler]
+pub async fn place_tile(
+    State(app_state): State<AppState>,
+    Path((z, x, y)): Path<(u8, u32, String)>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    // Clients ask for `{y}.mvt`, which axum can't split off by itself.
+    let tile = match y
+        .strip_suffix(".mvt")
+        .unwrap_or(&y)
+        .parse()
+        .map_err(|_| "Tile not valid".to_string())
+        .and_then(|y| TileId::new(z, x, y))
+    {
+        Ok(t) => t,
+        Err(e) => return Err((StatusCode::BAD_REQUEST, e)),
+    };
+
+    if let Some(bytes) = app_state.tile_cache.get(&tile) {
+        return Ok(([(header::CONTENT_TYPE, MVT_CONTENT_TYPE)], bytes));
+    }
+
+    let places = match app_state
+        .places_repo
+        .lock()
+        .await
+        .search(SearchPlaceOptions {
+            text: String::new(),
+            bounding_box: Some(tile.bounding_box()),
+            include_unlocated: false,
+            closest_to: None,
+            limit: MAX_TILE_PLACES,
+        })
+        .await
+    {
+        Ok(p) => p,
+        Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+    };
+    let place_ids: Vec<u64> = places.iter().map(|p| p.id).collect();
+    let scores = match app_state.ratings_repo.lock().await.scores(&place_ids).await {
+        Ok(s) => s,
+        Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+    };
+
+    let features: Vec<TileFeature> = places
+        .into_iter()
+        .filter_map(|place| {
+            let score = scores.get(&place.id)?;
+            let mut properties = vec![
+                ("name".to_string(), TileValue::String(place.name)),
+                ("score".to_string(), TileValue::Double(score.average_score)),
+                (
+                    "ratingCount".to_string(),
+                    TileValue::Uint(score.rating_count),
+                ),
+            ];
+            if let Some(categories) = place.categories.filter(|c| !c.is_empty()) {
+                properties.push((
+                    "category".to_string(),
+                    TileValue::String(categories[0].clone()),
+                ));
+                properties.push((
+                    "categories".to_string(),
+                    TileValue::String(categories.join(",")),
+                ));
+            }
+            Some(TileFeature {
+                id: place.id,
+                coordinates: place.coordinates?,
+                properties,
+            })
+        })
+        .collect();
+
+    let bytes = encode_tile(&tile, PLACES_LAYER, &features);
+    app_state.tile_cache.insert(tile, bytes.clone());
+    Ok(([(header::CONTENT_TYPE, MVT_CONTENT_TYPE)], bytes))
+}
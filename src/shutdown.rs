@@ -0,0 +1,91 @@
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+use tokio::{sync::watch, task::JoinHandle};
+
+/// Background tasks spawned through this stop when `shutdown` is called,
+/// which then waits for them to finish what they were doing.
+#[derive(Clone)]
+pub struct Shutdown {
+    sender: Arc<watch::Sender<bool>>,
+    tasks: Arc<Mutex<Vec<JoinHandle<()>>>>,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        let (sender, _) = watch::channel(false);
+        Shutdown {
+            sender: Arc::new(sender),
+            tasks: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Spawns a task that should watch `triggered` and return soon after.
+    pub fn spawn<F>(&self, task: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let handle = tokio::spawn(task);
+        self.tasks.lock().unwrap().push(handle);
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        *self.sender.borrow()
+    }
+
+    /// Completes once `shutdown` has been called.
+    pub async fn triggered(&self) {
+        let mut receiver = self.sender.subscribe();
+        while !*receiver.borrow_and_update() {
+            if receiver.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+
+    pub async fn shutdown(&self) {
+        self.sender.send_replace(true);
+        let tasks: Vec<JoinHandle<()>> = self.tasks.lock().unwrap().drain(..).collect();
+        for task in tasks {
+            if let Err(e) = task.await {
+                eprintln!("Error finishing background task: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_shutdown_waits_for_tasks() {
+        let shutdown = Shutdown::new();
+        let finished = Arc::new(AtomicBool::new(false));
+
+        let task_shutdown = shutdown.clone();
+        let task_finished = finished.clone();
+        shutdown.spawn(async move {
+            task_shutdown.triggered().await;
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            task_finished.store(true, Ordering::SeqCst);
+        });
+        assert!(!shutdown.is_triggered());
+
+        shutdown.shutdown().await;
+        assert!(shutdown.is_triggered());
+        assert!(finished.load(Ordering::SeqCst));
+        // Already triggered.
+        shutdown.triggered().await;
+    }
+}
@@ -4,20 +4,30 @@ use reqwest::{StatusCode, Url};
 
 use super::SMSVerify;
 
+pub const TWILIO_VERIFY_BASE_URL: &str = "https://verify.twilio.com";
+
 #[derive(Clone)]
 pub struct TwilioSMS {
     account_sid: String,
     service_sid: String,
     auth_token: String,
+    verify_url: String,
 }
 
 impl TwilioSMS {
     pub fn new(account_sid: &str, service_sid: &str, auth_token: &str) -> TwilioSMS {
-        return TwilioSMS {
-            account_sid: account_sid.clone().to_owned(),
-            service_sid: service_sid.clone().to_owned(),
-            auth_token: auth_token.clone().to_owned(),
-        };
+        TwilioSMS {
+            account_sid: account_sid.to_owned(),
+            service_sid: service_sid.to_owned(),
+            auth_token: auth_token.to_owned(),
+            verify_url: TWILIO_VERIFY_BASE_URL.to_owned(),
+        }
+    }
+
+    /// Points the client at another Twilio Verify host, such as a local mock server.
+    pub fn with_base_url(mut self, verify_url: &str) -> TwilioSMS {
+        self.verify_url = verify_url.trim_end_matches('/').to_owned();
+        self
     }
 }
 
@@ -29,9 +39,10 @@ struct VerificationCheckResponse {
 #[async_trait]
 impl SMSVerify for TwilioSMS {
     async fn send_verification_code(&self, phone_number: u64) -> Result<(), String> {
-        let mut url = "https://verify.twilio.com/v2/Services"
-            .parse::<Url>()
-            .unwrap();
+        let mut url = match format!("{}/v2/Services", self.verify_url).parse::<Url>() {
+            Ok(u) => u,
+            Err(_) => return Err("Invalid Twilio url".to_string()),
+        };
         url.path_segments_mut()
             .map_err(|_| "cannot be base")
             .unwrap()
@@ -62,9 +73,10 @@ impl SMSVerify for TwilioSMS {
         }
     }
     async fn verify_code(&self, phone_number: u64, verification_code: u32) -> Result<(), String> {
-        let mut url = "https://verify.twilio.com/v2/Services"
-            .parse::<Url>()
-            .unwrap();
+        let mut url = match format!("{}/v2/Services", self.verify_url).parse::<Url>() {
+            Ok(u) => u,
+            Err(_) => return Err("Invalid Twilio url".to_string()),
+        };
         url.path_segments_mut()
             .map_err(|_| "cannot be base")
             .unwrap()
@@ -96,7 +108,7 @@ impl SMSVerify for TwilioSMS {
                             serde_json::from_str(&t);
                         match body {
                             Ok(body) => {
-                                if body.status != "approved".to_string() {
+                                if body.status != "approved" {
                                     return Err("Status not accepted".to_string());
                                 }
                                 Ok(())
@@ -129,7 +141,7 @@ trait VerificationCode {
 impl VerificationCode for u32 {
     fn format_verification_code(self) -> String {
         if self < 10_000 {
-            return format!("0{}", self.to_string());
+            return format!("0{}", self);
         }
 
         self.to_string()
@@ -0,0 +1,34 @@
+CREATE EXTENSION IF NOT EXISTS postgis;
+
+CREATE INDEX IF NOT EXISTS places_location_idx ON places
+    USING GIST ((geography(ST_SetSRID(ST_MakePoint(longitude, latitude), 4326))))
+    WHERE latitude IS NOT NULL AND longitude IS NOT NULL;
+
+-- Stored places within radius_meters of (lat, lng), closest first. Each row
+-- is the place as JSON alongside its distance in meters.
+CREATE OR REPLACE FUNCTION nearby_places(
+    lat DOUBLE PRECISION,
+    lng DOUBLE PRECISION,
+    radius_meters DOUBLE PRECISION,
+    max_results INTEGER
+)
+RETURNS TABLE (place JSONB, distance_meters DOUBLE PRECISION)
+LANGUAGE sql STABLE
+AS $$
+    SELECT
+        to_jsonb(places) AS place,
+        ST_Distance(
+            geography(ST_SetSRID(ST_MakePoint(places.longitude, places.latitude), 4326)),
+            geography(ST_SetSRID(ST_MakePoint(lng, lat), 4326))
+        ) AS distance_meters
+    FROM places
+    WHERE places.latitude IS NOT NULL
+        AND places.longitude IS NOT NULL
+        AND ST_DWithin(
+            geography(ST_SetSRID(ST_MakePoint(places.longitude, places.latitude), 4326)),
+            geography(ST_SetSRID(ST_MakePoint(lng, lat), 4326)),
+            radius_meters
+        )
+    ORDER BY distance_meters, places.id
+    LIMIT max_results;
+$$;
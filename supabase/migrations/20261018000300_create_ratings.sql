@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS ratings (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    place_id BIGINT NOT NULL REFERENCES places (id) ON DELETE CASCADE,
+    phone_number BIGINT NOT NULL REFERENCES users (phone_number) ON DELETE CASCADE,
+    score SMALLINT NOT NULL CHECK (score BETWEEN 1 AND 10),
+    review TEXT,
+    created_at TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL,
+    UNIQUE (place_id, phone_number)
+);
+
+CREATE INDEX IF NOT EXISTS ratings_phone_number_idx ON ratings (phone_number);
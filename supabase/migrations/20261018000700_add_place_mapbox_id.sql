@@ -0,0 +1,3 @@
+-- The Mapbox id of places found through suggestions, kept so their
+-- coordinates can be retrieved once someone picks one
+ALTER TABLE places ADD COLUMN IF NOT EXISTS "mapboxId" TEXT;
@@ -0,0 +1,74 @@
+CREATE TABLE IF NOT EXISTS jobs (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    kind TEXT NOT NULL,
+    place_id BIGINT NOT NULL REFERENCES places (id) ON DELETE CASCADE,
+    -- pending, running or dead
+    status TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    run_at TIMESTAMPTZ NOT NULL,
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL
+);
+
+-- At most one live job of each kind per place
+CREATE UNIQUE INDEX IF NOT EXISTS jobs_kind_place_id_idx ON jobs (kind, place_id)
+    WHERE status <> 'dead';
+CREATE INDEX IF NOT EXISTS jobs_status_run_at_idx ON jobs (status, run_at);
+
+-- Queues a job unless the same kind of job for the place is already pending
+-- or running, and returns the live job either way.
+CREATE OR REPLACE FUNCTION enqueue_job(
+    job_kind TEXT,
+    job_place_id BIGINT,
+    queued_at TIMESTAMPTZ
+)
+RETURNS SETOF jobs
+LANGUAGE sql VOLATILE
+AS $$
+    INSERT INTO jobs (kind, place_id, status, run_at, created_at, updated_at)
+    VALUES (job_kind, job_place_id, 'pending', queued_at, queued_at, queued_at)
+    ON CONFLICT (kind, place_id) WHERE status <> 'dead' DO NOTHING;
+
+    SELECT * FROM jobs
+    WHERE kind = job_kind AND place_id = job_place_id AND status <> 'dead';
+$$;
+
+-- Marks at most max_jobs pending jobs due by claimed_at as running. Rows
+-- locked by other workers are skipped, so several instances can share the
+-- queue.
+CREATE OR REPLACE FUNCTION claim_jobs(claimed_at TIMESTAMPTZ, max_jobs INTEGER)
+RETURNS SETOF jobs
+LANGUAGE sql VOLATILE
+AS $$
+    UPDATE jobs SET status = 'running', updated_at = claimed_at
+    WHERE id IN (
+        SELECT id FROM jobs
+        WHERE status = 'pending' AND run_at <= claimed_at
+        ORDER BY run_at, id
+        LIMIT max_jobs
+        FOR UPDATE SKIP LOCKED
+    )
+    RETURNING *;
+$$;
+
+-- Records a failed attempt. The job runs again at retry_at, or is moved to
+-- the dead letters when retry_at is null.
+CREATE OR REPLACE FUNCTION fail_job(
+    job_id BIGINT,
+    job_error TEXT,
+    retry_at TIMESTAMPTZ,
+    failed_at TIMESTAMPTZ
+)
+RETURNS SETOF jobs
+LANGUAGE sql VOLATILE
+AS $$
+    UPDATE jobs SET
+        status = CASE WHEN retry_at IS NULL THEN 'dead' ELSE 'pending' END,
+        attempts = attempts + 1,
+        run_at = COALESCE(retry_at, run_at),
+        last_error = job_error,
+        updated_at = failed_at
+    WHERE id = job_id
+    RETURNING *;
+$$;
@@ -0,0 +1,42 @@
+-- Stored places whose name matches name_pattern (an ILIKE pattern), for
+-- searches PostgREST filters can't express. With a bounding box, places not
+-- located yet only match in the locality and region of a place inside it.
+-- With a center, the closest come first and places not located last;
+-- otherwise places are ordered by name.
+CREATE OR REPLACE FUNCTION search_places(
+    name_pattern TEXT,
+    min_lat DOUBLE PRECISION,
+    max_lat DOUBLE PRECISION,
+    min_lng DOUBLE PRECISION,
+    max_lng DOUBLE PRECISION,
+    include_unlocated BOOLEAN,
+    center_lat DOUBLE PRECISION,
+    center_lng DOUBLE PRECISION,
+    max_results INTEGER
+)
+RETURNS SETOF places
+LANGUAGE sql STABLE
+AS $$
+    SELECT * FROM places
+    WHERE places.name ILIKE name_pattern
+        AND (
+            min_lat IS NULL
+            OR (places.latitude BETWEEN min_lat AND max_lat
+                AND places.longitude BETWEEN min_lng AND max_lng)
+            OR (include_unlocated AND places.latitude IS NULL
+                AND (places.place, places.region) IN (
+                    SELECT located.place, located.region FROM places located
+                    WHERE located.latitude BETWEEN min_lat AND max_lat
+                        AND located.longitude BETWEEN min_lng AND max_lng
+                ))
+        )
+    ORDER BY
+        CASE WHEN center_lat IS NULL THEN places.name END,
+        places.latitude IS NULL,
+        ST_Distance(
+            geography(ST_SetSRID(ST_MakePoint(places.longitude, places.latitude), 4326)),
+            geography(ST_SetSRID(ST_MakePoint(center_lng, center_lat), 4326))
+        ),
+        places.id
+    LIMIT max_results;
+$$;
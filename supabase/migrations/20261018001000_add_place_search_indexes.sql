@@ -0,0 +1,63 @@
+-- Trigram indexes over the text place searches match, so ILIKE on part of a
+-- name, street or town doesn't scan every place
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX IF NOT EXISTS places_name_trgm_idx ON places USING GIN (name gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS places_address_trgm_idx ON places USING GIN (address gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS places_street_trgm_idx ON places USING GIN (street gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS places_place_trgm_idx ON places USING GIN (place gin_trgm_ops);
+
+-- Matches the street address, street and town as well as the name. The
+-- parameter is renamed, so the function is replaced rather than updated.
+DROP FUNCTION IF EXISTS search_places(
+    TEXT,
+    DOUBLE PRECISION,
+    DOUBLE PRECISION,
+    DOUBLE PRECISION,
+    DOUBLE PRECISION,
+    BOOLEAN,
+    DOUBLE PRECISION,
+    DOUBLE PRECISION,
+    INTEGER
+);
+
+CREATE OR REPLACE FUNCTION search_places(
+    text_pattern TEXT,
+    min_lat DOUBLE PRECISION,
+    max_lat DOUBLE PRECISION,
+    min_lng DOUBLE PRECISION,
+    max_lng DOUBLE PRECISION,
+    include_unlocated BOOLEAN,
+    center_lat DOUBLE PRECISION,
+    center_lng DOUBLE PRECISION,
+    max_results INTEGER
+)
+RETURNS SETOF places
+LANGUAGE sql STABLE
+AS $$
+    SELECT * FROM places
+    WHERE (places.name ILIKE text_pattern
+            OR places.address ILIKE text_pattern
+            OR places.street ILIKE text_pattern
+            OR places.place ILIKE text_pattern)
+        AND (
+            min_lat IS NULL
+            OR (places.latitude BETWEEN min_lat AND max_lat
+                AND places.longitude BETWEEN min_lng AND max_lng)
+            OR (include_unlocated AND places.latitude IS NULL
+                AND (places.place, places.region) IN (
+                    SELECT located.place, located.region FROM places located
+                    WHERE located.latitude BETWEEN min_lat AND max_lat
+                        AND located.longitude BETWEEN min_lng AND max_lng
+                ))
+        )
+    ORDER BY
+        CASE WHEN center_lat IS NULL THEN places.name END,
+        places.latitude IS NULL,
+        ST_Distance(
+            geography(ST_SetSRID(ST_MakePoint(places.longitude, places.latitude), 4326)),
+            geography(ST_SetSRID(ST_MakePoint(center_lng, center_lat), 4326))
+        ),
+        places.id
+    LIMIT max_results;
+$$;
@@ -0,0 +1,29 @@
+-- Adds photos, all to the same rating, unless the rating would then have
+-- more than max_photos, in which case nothing is added. Locking the rating
+-- makes concurrent uploads to it count one after another.
+CREATE OR REPLACE FUNCTION add_rating_photos(photos JSONB, max_photos INTEGER)
+RETURNS SETOF rating_photos
+LANGUAGE sql VOLATILE
+AS $$
+    SELECT id FROM ratings WHERE id = (photos -> 0 ->> 'rating_id')::BIGINT FOR UPDATE;
+
+    INSERT INTO rating_photos
+    (rating_id, place_id, phone_number, key, thumbnail_key, width, height, created_at)
+    SELECT p.rating_id, p.place_id, p.phone_number, p.key, p.thumbnail_key,
+        p.width, p.height, p.created_at
+    FROM jsonb_to_recordset(photos) AS p(
+        rating_id BIGINT,
+        place_id BIGINT,
+        phone_number BIGINT,
+        key TEXT,
+        thumbnail_key TEXT,
+        width INTEGER,
+        height INTEGER,
+        created_at TIMESTAMPTZ
+    )
+    WHERE (
+        SELECT COUNT(*) FROM rating_photos
+        WHERE rating_id = (photos -> 0 ->> 'rating_id')::BIGINT
+    ) + jsonb_array_length(photos) <= max_photos
+    RETURNING *;
+$$;
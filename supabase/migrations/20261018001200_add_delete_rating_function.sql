@@ -0,0 +1,26 @@
+-- Deletes a rating and its photos, returning both as
+-- {"rating": ..., "photos": [...]} so the photos' images can be removed from
+-- the blob store too. rating is null when there was no such rating. Locking
+-- the rating first waits for any photos being added to it.
+CREATE OR REPLACE FUNCTION delete_rating(deleted_rating_id BIGINT)
+RETURNS JSONB
+LANGUAGE plpgsql VOLATILE
+AS $$
+DECLARE
+    deleted_photos JSONB;
+    deleted_rating JSONB;
+BEGIN
+    PERFORM id FROM ratings WHERE id = deleted_rating_id FOR UPDATE;
+
+    WITH photos AS (
+        DELETE FROM rating_photos WHERE rating_id = deleted_rating_id RETURNING *
+    )
+    SELECT COALESCE(jsonb_agg(to_jsonb(photos) ORDER BY photos.id), '[]'::JSONB)
+    INTO deleted_photos FROM photos;
+
+    DELETE FROM ratings WHERE id = deleted_rating_id
+    RETURNING to_jsonb(ratings) INTO deleted_rating;
+
+    RETURN jsonb_build_object('rating', deleted_rating, 'photos', deleted_photos);
+END;
+$$;
@@ -0,0 +1,1468 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use chrono::Utc;
+use critiq_backend::{
+    geo::{tile::TileId, Coordinates},
+    places::{
+        photo::{Photo, PHOTO_ATTRIBUTION},
+        search::PlaceCategory,
+        OpeningHours, OpeningPeriod, Place, PlaceDetails,
+    },
+};
+use serde_json::{json, Value};
+
+use common::{
+    default_places,
+    mock::{MockRoute, MockServer},
+    place, TestApp, ADMIN_TOKEN, PHOTO_PREFIX, RETRIEVED_COORDINATES, VERIFICATION_CODE,
+};
+
+const PHONE_NUMBER: &str = "(202)809-8681";
+
+async fn sign_in(app: &TestApp) -> (String, String) {
+    let (status, _) = app
+        .request(
+            Method::PUT,
+            "/authenticate",
+            Some(json!({
+                "firstName": "hunter",
+                "lastName": "simmons",
+                "phoneNumber": PHONE_NUMBER,
+            })),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, tokens) = app
+        .request(
+            Method::POST,
+            "/verify-phone",
+            Some(json!({ "phoneNumber": PHONE_NUMBER, "code": VERIFICATION_CODE })),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    (
+        tokens["accessToken"].as_str().unwrap().to_string(),
+        tokens["refreshToken"].as_str().unwrap().to_string(),
+    )
+}
+
+fn search_body(place_name: &str) -> Value {
+    json!({
+        "placeName": place_name,
+        "location": { "latitude": 40.7608, "longitude": -111.891 },
+    })
+}
+
+fn place_names(body: &Value) -> Vec<&str> {
+    body["places"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| p["name"].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_authenticate_verify_search_and_refresh() {
+    let app = TestApp::new();
+
+    let (access_token, refresh_token) = sign_in(&app).await;
+    assert_eq!(app.sms_verify.sent_to(), vec![2028098681]);
+
+    let (status, body) = app
+        .request(
+            Method::POST,
+            "/search-places",
+            Some(search_body("arlo")),
+            Some(&access_token),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(place_names(&body), vec!["Arlo", "Arlo's Bakery"]);
+    assert_eq!(body["places"][0]["address"]["address"], "271 N Center St");
+    assert_eq!(
+        body["places"][0]["coordinates"],
+        json!({ "latitude": 40.775563, "longitude": -111.893962 })
+    );
+    assert_eq!(body["places"][1]["coordinates"], Value::Null);
+
+    let (status, tokens) = app
+        .request(
+            Method::POST,
+            "/refresh-token",
+            Some(json!({ "refreshToken": refresh_token })),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(tokens["refreshToken"], refresh_token.as_str());
+    let refreshed_access_token = tokens["accessToken"].as_str().unwrap();
+
+    let (status, body) = app
+        .request(
+            Method::POST,
+            "/search-places",
+            Some(search_body("iguana")),
+            Some(refreshed_access_token),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(place_names(&body), vec!["Red Iguana"]);
+}
+
+#[tokio::test]
+async fn test_search_places_keeps_session_across_keystrokes() {
+    let app = TestApp::new();
+    let (access_token, _) = sign_in(&app).await;
+
+    let (status, body) = app
+        .request(
+            Method::POST,
+            "/search-places",
+            Some(search_body("ar")),
+            Some(&access_token),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let session_id = body["sessionId"].as_str().unwrap().to_string();
+
+    let mut next = search_body("arl");
+    next["sessionId"] = json!(session_id);
+    let (_, body) = app
+        .request(
+            Method::POST,
+            "/search-places",
+            Some(next),
+            Some(&access_token),
+        )
+        .await;
+    assert_eq!(body["sessionId"], session_id.as_str());
+
+    let mut unknown = search_body("arlo");
+    unknown["sessionId"] = json!("not-a-session");
+    let (_, body) = app
+        .request(
+            Method::POST,
+            "/search-places",
+            Some(unknown),
+            Some(&access_token),
+        )
+        .await;
+    let new_session_id = body["sessionId"].as_str().unwrap().to_string();
+    assert_ne!(new_session_id, session_id);
+
+    assert_eq!(
+        app.search.session_tokens(),
+        vec![session_id.clone(), session_id, new_session_id]
+    );
+}
+
+#[tokio::test]
+async fn test_search_places_passes_query_options() {
+    let app = TestApp::new();
+    let (access_token, _) = sign_in(&app).await;
+
+    let mut body = search_body("arlo");
+    body["categories"] = json!(["coffee", "dessert"]);
+    body["radius"] = json!(2500.0);
+    body["limit"] = json!(3);
+    body["language"] = json!("fr");
+    let (status, _) = app
+        .request(
+            Method::POST,
+            "/search-places",
+            Some(body),
+            Some(&access_token),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let query = &app.search.queries()[0];
+    assert_eq!(
+        query.categories,
+        vec![PlaceCategory::Coffee, PlaceCategory::Dessert]
+    );
+    assert_eq!(query.radius_meters, 2500.0);
+    assert_eq!(query.limit, Some(3));
+    assert_eq!(query.language, "fr");
+}
+
+#[tokio::test]
+async fn test_search_places_rejects_invalid_query_options() {
+    let app = TestApp::new();
+    let (access_token, _) = sign_in(&app).await;
+
+    for (field, value) in [
+        ("radius", json!(-1.0)),
+        ("radius", json!(1_000_000.0)),
+        ("limit", json!(0)),
+        ("limit", json!(11)),
+        ("language", json!("english")),
+        ("categories", json!(["nightclub"])),
+    ] {
+        let mut body = search_body("arlo");
+        body[field] = value;
+        let (status, _) = app
+            .request(
+                Method::POST,
+                "/search-places",
+                Some(body),
+                Some(&access_token),
+            )
+            .await;
+        assert!(status.is_client_error(), "{} was accepted", field);
+    }
+    assert!(app.search.queries().is_empty());
+}
+
+/// Arlo has a Foursquare id but no details yet, Arlo's Bakery already has
+/// its hours and price, and Red Iguana has neither.
+fn places_with_details() -> Vec<Place> {
+    let mut places = default_places();
+    places[0].foursquare_id = Some("5c2aab5bb9a389002cf7b4a3".to_string());
+    places[1].details = Some(PlaceDetails {
+        hours: Some(OpeningHours {
+            display: Some("Mon-Sun 7:00 AM-3:00 PM".to_string()),
+            periods: (1..=7)
+                .map(|day| OpeningPeriod {
+                    day,
+                    open: "0700".to_string(),
+                    close: "1500".to_string(),
+                })
+                .collect(),
+        }),
+        price: Some(1),
+        phone: None,
+        social: None,
+        timezone: Some("America/Denver".to_string()),
+        updated_at: Utc::now(),
+    });
+    places
+}
+
+#[tokio::test]
+async fn test_search_places_filters_by_price_and_opening_hours() {
+    let app = TestApp::with_places(places_with_details());
+    let (access_token, _) = sign_in(&app).await;
+
+    for (filters, expected) in [
+        (json!({ "maxPrice": 1 }), vec!["Arlo's Bakery"]),
+        // Arlo's price is fetched with its details before filtering.
+        (json!({ "minPrice": 2, "maxPrice": 4 }), vec!["Arlo"]),
+        // Monday 10am and 9pm in Salt Lake City.
+        (
+            json!({ "openAt": "2026-10-19T16:00:00Z" }),
+            vec!["Arlo's Bakery"],
+        ),
+        (json!({ "openAt": "2026-10-20T03:00:00Z" }), vec![]),
+        (
+            json!({ "openAt": "2026-10-19T16:00:00Z", "minPrice": 2 }),
+            vec![],
+        ),
+    ] {
+        let mut body = search_body("");
+        for (field, value) in filters.as_object().unwrap() {
+            body[field] = value.clone();
+        }
+        let (status, body) = app
+            .request(
+                Method::POST,
+                "/search-places",
+                Some(body),
+                Some(&access_token),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(place_names(&body), expected, "{}", filters);
+    }
+
+    let (_, body) = app
+        .request(
+            Method::POST,
+            "/search-places",
+            Some(search_body("")),
+            Some(&access_token),
+        )
+        .await;
+    assert_eq!(place_names(&body).len(), 3);
+}
+
+#[tokio::test]
+async fn test_search_places_rejects_invalid_filters() {
+    let app = TestApp::new();
+    let (access_token, _) = sign_in(&app).await;
+
+    for filters in [
+        json!({ "minPrice": 0 }),
+        json!({ "maxPrice": 5 }),
+        json!({ "minPrice": 3, "maxPrice": 2 }),
+        json!({ "openNow": true, "openAt": "2026-10-19T16:00:00Z" }),
+        json!({ "openAt": "monday" }),
+    ] {
+        let mut body = search_body("arlo");
+        for (field, value) in filters.as_object().unwrap() {
+            body[field] = value.clone();
+        }
+        let (status, _) = app
+            .request(
+                Method::POST,
+                "/search-places",
+                Some(body),
+                Some(&access_token),
+            )
+            .await;
+        assert!(status.is_client_error(), "{} was accepted", filters);
+    }
+    assert!(app.search.queries().is_empty());
+}
+
+#[tokio::test]
+async fn test_search_places_cache_serves_repeated_searches() {
+    let app = TestApp::with_search_cache().await;
+    let (access_token, _) = sign_in(&app).await;
+
+    for place_name in ["Arlo", "arlo "] {
+        let (status, body) = app
+            .request(
+                Method::POST,
+                "/search-places",
+                Some(search_body(place_name)),
+                Some(&access_token),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(place_names(&body), vec!["Arlo", "Arlo's Bakery"]);
+    }
+    assert_eq!(app.search.queries().len(), 1);
+
+    let (status, stats) = app
+        .request(
+            Method::GET,
+            "/search-places/cache-stats",
+            None,
+            Some(ADMIN_TOKEN),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        stats,
+        json!({ "hits": 1, "misses": 1, "hitRate": 0.5, "entries": 1 })
+    );
+
+    // Cache stats are for operators, not app users.
+    let (status, _) = app
+        .request(
+            Method::GET,
+            "/search-places/cache-stats",
+            None,
+            Some(&access_token),
+        )
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_cached_searches_show_photos_fetched_since() {
+    let app = TestApp::with_search_cache().await;
+    let (access_token, _) = sign_in(&app).await;
+
+    // The first search queues the photos, and the cached result it leaves
+    // behind has none. Later hits show the photos once they are stored.
+    let mut arlo = Value::Null;
+    for _ in 0..100 {
+        let (status, body) = app
+            .request(
+                Method::POST,
+                "/search-places",
+                Some(search_body("arlo")),
+                Some(&access_token),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        arlo = body["places"][0].clone();
+        if !arlo["photos"].is_null() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    assert_eq!(arlo["name"], "Arlo");
+    assert_eq!(arlo["photos"], photos_json(PHOTO_PREFIX, 1));
+    assert_eq!(app.search.queries().len(), 1);
+}
+
+#[tokio::test]
+async fn test_search_cache_stats_without_cache() {
+    let app = TestApp::new();
+
+    let (status, _) = app
+        .request(
+            Method::GET,
+            "/search-places/cache-stats",
+            None,
+            Some(ADMIN_TOKEN),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_search_places_fetches_photos_in_the_background() {
+    // Photos only come from Foursquare, so Communal's are never fetched and
+    // its failures never seen. Details are fresh, so none are refreshed.
+    let mut places = located_places();
+    for place in places.iter_mut().filter(|p| p.name != "Communal") {
+        place.foursquare_id = Some(format!("fsq-{}", place.id));
+        place.details = Some(PlaceDetails {
+            hours: None,
+            price: None,
+            phone: None,
+            social: None,
+            timezone: None,
+            updated_at: Utc::now(),
+        });
+    }
+    let app = TestApp::with_stored_places(places).await;
+    app.search.fail_photos(3);
+    app.search.fail_photos(4);
+    let (access_token, _) = sign_in(&app).await;
+
+    let (status, _) = app
+        .request(
+            Method::POST,
+            "/search-places",
+            Some(search_body("")),
+            Some(&access_token),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let mut dead_letters = Value::Null;
+    for _ in 0..100 {
+        let (status, body) = app
+            .request(Method::GET, "/jobs/dead-letters", None, Some(ADMIN_TOKEN))
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        if !body.as_array().unwrap().is_empty() {
+            dead_letters = body;
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    assert_eq!(
+        dead_letters,
+        json!([{
+            "id": dead_letters[0]["id"],
+            "kind": "fetchPhotos",
+            "placeId": 3,
+            "status": "dead",
+            "attempts": 2,
+            "runAt": dead_letters[0]["runAt"],
+            "lastError": "Error getting pictures",
+            "createdAt": dead_letters[0]["createdAt"],
+            "updatedAt": dead_letters[0]["updatedAt"],
+        }])
+    );
+
+    // The other places got their photos.
+    let (_, body) = app
+        .request(
+            Method::GET,
+            &format!("{}&zoom=18", VIEWPORT),
+            None,
+            Some(&access_token),
+        )
+        .await;
+    let arlo = body["places"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|p| p["name"] == "Arlo")
+        .unwrap();
+    assert_eq!(arlo["photos"], photos_json(PHOTO_PREFIX, 1));
+
+    // Dead letters are for operators, not app users.
+    for token in [None, Some(access_token.as_str())] {
+        let (status, _) = app
+            .request(Method::GET, "/jobs/dead-letters", None, token)
+            .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+}
+
+/// The photo `FakeSearch` finds for a place, as clients see it.
+fn photos_json(prefix: &str, place_id: u64) -> Value {
+    let photo = Photo {
+        prefix: prefix.to_string(),
+        suffix: format!("/{}.jpg", place_id),
+    };
+    json!([{
+        "prefix": photo.prefix,
+        "suffix": photo.suffix,
+        "variants": photo.variants(),
+        "attribution": PHOTO_ATTRIBUTION,
+    }])
+}
+
+/// Arlo and Red Iguana in Salt Lake City, Communal in Provo, and Arlo's
+/// Bakery without coordinates.
+fn located_places() -> Vec<Place> {
+    let mut places = default_places();
+    let mut red_iguana = places.pop().unwrap();
+    red_iguana.coordinates = Some(Coordinates {
+        latitude: 40.7718,
+        longitude: -111.912,
+    });
+    let mut provo = place(4, "Communal", "102 N University Ave");
+    provo.coordinates = Some(Coordinates {
+        latitude: 40.2338,
+        longitude: -111.6585,
+    });
+    places.extend([red_iguana, provo]);
+    places
+}
+
+#[tokio::test]
+async fn test_nearby_places_sorted_by_distance() {
+    let app = TestApp::with_stored_places(located_places()).await;
+    let (access_token, _) = sign_in(&app).await;
+
+    let (status, body) = app
+        .request(
+            Method::GET,
+            "/places/nearby?lat=40.7608&lng=-111.891&radius=5000",
+            None,
+            Some(&access_token),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(place_names(&body), vec!["Arlo", "Red Iguana"]);
+
+    let arlo = &body["places"][0];
+    let distance = arlo["distanceMeters"].as_f64().unwrap();
+    assert!((1_600.0..1_700.0).contains(&distance), "{}", distance);
+    let bearing = arlo["bearingDegrees"].as_f64().unwrap();
+    assert!((340.0..360.0).contains(&bearing), "{}", bearing);
+    assert_eq!(arlo["address"]["address"], "271 N Center St");
+    assert!(body["places"][1]["distanceMeters"].as_f64().unwrap() > distance);
+
+    let (_, body) = app
+        .request(
+            Method::GET,
+            "/places/nearby?lat=40.7608&lng=-111.891&radius=100000&limit=1",
+            None,
+            Some(&access_token),
+        )
+        .await;
+    assert_eq!(place_names(&body), vec!["Arlo"]);
+}
+
+#[tokio::test]
+async fn test_nearby_places_rejects_invalid_params() {
+    let app = TestApp::new();
+    let (access_token, _) = sign_in(&app).await;
+
+    for uri in [
+        "/places/nearby?lat=40.7608&lng=-111.891&radius=0",
+        "/places/nearby?lat=40.7608&lng=-111.891&radius=200000",
+        "/places/nearby?lat=91&lng=-111.891",
+        "/places/nearby?lat=40.7608&lng=-111.891&limit=51",
+        "/places/nearby?lng=-111.891",
+    ] {
+        let (status, _) = app
+            .request(Method::GET, uri, None, Some(&access_token))
+            .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", uri);
+    }
+
+    let (status, _) = app
+        .request(
+            Method::GET,
+            "/places/nearby?lat=40.7608&lng=-111.891",
+            None,
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+const VIEWPORT: &str =
+    "/places/viewport?minLongitude=-112.2&minLatitude=40.0&maxLongitude=-111.4&maxLatitude=41.0";
+
+fn place_id(body: &Value, name: &str) -> u64 {
+    body["places"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|p| p["name"] == name)
+        .unwrap()["id"]
+        .as_u64()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_viewport_clusters_places_with_scores() {
+    let app = TestApp::with_stored_places(located_places()).await;
+    let (access_token, _) = sign_in(&app).await;
+
+    let (status, body) = app
+        .request(
+            Method::GET,
+            &format!("{}&zoom=18", VIEWPORT),
+            None,
+            Some(&access_token),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let mut names = place_names(&body);
+    names.sort();
+    assert_eq!(names, vec!["Arlo", "Communal", "Red Iguana"]);
+    assert_eq!(body["clusters"], json!([]));
+    assert_eq!(body["truncated"], false);
+
+    for (name, score) in [("Arlo", 8), ("Red Iguana", 5)] {
+        let (status, rating) = app
+            .request(
+                Method::PUT,
+                &format!("/places/{}/rating", place_id(&body, name)),
+                Some(json!({ "score": score, "review": "  " })),
+                Some(&access_token),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(rating["score"], score);
+        assert_eq!(rating["review"], Value::Null);
+    }
+
+    let (status, body) = app
+        .request(
+            Method::GET,
+            &format!("{}&zoom=10", VIEWPORT),
+            None,
+            Some(&access_token),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(place_names(&body), vec!["Communal"]);
+    assert_eq!(body["places"][0]["ratingCount"], 0);
+    assert_eq!(body["places"][0]["averageScore"], Value::Null);
+
+    let cluster = &body["clusters"][0];
+    assert_eq!(body["clusters"].as_array().unwrap().len(), 1);
+    assert_eq!(cluster["count"], 2);
+    assert_eq!(cluster["ratingCount"], 2);
+    assert_eq!(cluster["averageScore"], 6.5);
+    let latitude = cluster["centroid"]["latitude"].as_f64().unwrap();
+    assert!((40.7718..40.7756).contains(&latitude), "{}", latitude);
+    assert_eq!(cluster["boundingBox"]["minLongitude"], -111.912);
+}
+
+/// `MAX_VIEWPORT_PLACES` in `routes::places`.
+const MAX_VIEWPORT_PLACES: usize = 5_000;
+
+/// `count` places crowded into the west of `VIEWPORT`, named so they sort
+/// before a lone place in the east.
+fn crowded_viewport(count: usize) -> Vec<Place> {
+    let mut places: Vec<Place> = (0..count)
+        .map(|i| {
+            let mut crowded = place(0, &format!("A {}", i), &format!("{} W Main St", i));
+            crowded.coordinates = Some(Coordinates {
+                latitude: 40.1 + (i % 100) as f64 * 0.001,
+                longitude: -112.1 + (i / 100) as f64 * 0.001,
+            });
+            crowded
+        })
+        .collect();
+    let mut lone = place(0, "Zest", "275 S 200 W");
+    lone.coordinates = Some(Coordinates {
+        latitude: 40.5,
+        longitude: -111.5,
+    });
+    places.push(lone);
+    places
+}
+
+#[tokio::test]
+async fn test_viewport_samples_crowded_maps_across_the_viewport() {
+    let app = TestApp::with_stored_places(crowded_viewport(MAX_VIEWPORT_PLACES - 1)).await;
+    let (access_token, _) = sign_in(&app).await;
+    let uri = format!("{}&zoom=22", VIEWPORT);
+
+    let (status, body) = app
+        .request(Method::GET, &uri, None, Some(&access_token))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body["places"].as_array().unwrap().len(),
+        MAX_VIEWPORT_PLACES
+    );
+    assert_eq!(body["truncated"], false);
+
+    let app = TestApp::with_stored_places(crowded_viewport(MAX_VIEWPORT_PLACES)).await;
+    let (access_token, _) = sign_in(&app).await;
+    let (status, body) = app
+        .request(Method::GET, &uri, None, Some(&access_token))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["truncated"], true);
+    let places = body["places"].as_array().unwrap();
+    assert!(places.len() < MAX_VIEWPORT_PLACES, "{}", places.len());
+    assert!(places.iter().any(|p| p["name"] == "Zest"));
+}
+
+#[tokio::test]
+async fn test_viewport_rejects_invalid_params() {
+    let app = TestApp::new();
+    let (access_token, _) = sign_in(&app).await;
+
+    for uri in [
+        format!("{}&zoom=23", VIEWPORT),
+        "/places/viewport?minLongitude=-111&minLatitude=40&maxLongitude=-112&maxLatitude=41&zoom=10"
+            .to_string(),
+        "/places/viewport?minLongitude=-112&minLatitude=40&maxLongitude=-111&maxLatitude=91&zoom=10"
+            .to_string(),
+        VIEWPORT.to_string(),
+    ] {
+        let (status, _) = app
+            .request(Method::GET, &uri, None, Some(&access_token))
+            .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", uri);
+    }
+}
+
+#[tokio::test]
+async fn test_rate_place_replaces_earlier_rating() {
+    let app = TestApp::with_stored_places(located_places()).await;
+    let (access_token, _) = sign_in(&app).await;
+
+    let (status, first) = app
+        .request(
+            Method::PUT,
+            "/places/1/rating",
+            Some(json!({ "score": 7, "review": "Great pasta" })),
+            Some(&access_token),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(first["placeId"], 1);
+    assert_eq!(first["phoneNumber"], 12028098681u64);
+    assert_eq!(first["review"], "Great pasta");
+
+    let (status, second) = app
+        .request(
+            Method::PUT,
+            "/places/1/rating",
+            Some(json!({ "score": 9 })),
+            Some(&access_token),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(second["id"], first["id"]);
+    assert_eq!(second["score"], 9);
+    assert_eq!(second["createdAt"], first["createdAt"]);
+
+    for (uri, score, expected) in [
+        ("/places/1/rating", 0, StatusCode::BAD_REQUEST),
+        ("/places/1/rating", 11, StatusCode::BAD_REQUEST),
+        ("/places/99/rating", 5, StatusCode::NOT_FOUND),
+    ] {
+        let (status, _) = app
+            .request(
+                Method::PUT,
+                uri,
+                Some(json!({ "score": score })),
+                Some(&access_token),
+            )
+            .await;
+        assert_eq!(status, expected, "{} {}", uri, score);
+    }
+}
+
+async fn rate(app: &TestApp, access_token: &str, place_id: u64, score: u8) -> (StatusCode, Value) {
+    app.request(
+        Method::PUT,
+        &format!("/places/{}/rating", place_id),
+        Some(json!({ "score": score })),
+        Some(access_token),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn test_place_tiles_show_rated_places_and_refresh_on_rating() {
+    let app = TestApp::with_stored_places(located_places()).await;
+    let (access_token, _) = sign_in(&app).await;
+    let arlo = Coordinates {
+        latitude: 40.775563,
+        longitude: -111.893962,
+    };
+    let tile = TileId::containing(&arlo, 10);
+    let uri = format!("/tiles/{}/{}/{}.mvt", tile.z, tile.x, tile.y);
+
+    let (status, _) = rate(&app, &access_token, 1, 8).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = app
+        .request(Method::GET, &uri, None, Some(&access_token))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let tile_text = body.as_str().unwrap();
+    assert!(tile_text.contains("places"));
+    assert!(tile_text.contains("Arlo"));
+    assert!(!tile_text.contains("Red Iguana"));
+
+    let (status, _) = rate(&app, &access_token, 3, 6).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, body) = app
+        .request(Method::GET, &uri, None, Some(&access_token))
+        .await;
+    assert!(body.as_str().unwrap().contains("Red Iguana"));
+
+    for uri in [
+        "/tiles/2/4/0.mvt",
+        "/tiles/23/0/0.mvt",
+        "/tiles/2/0/abc.mvt",
+    ] {
+        let (status, _) = app
+            .request(Method::GET, uri, None, Some(&access_token))
+            .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", uri);
+    }
+}
+
+#[tokio::test]
+async fn test_export_ratings_as_geojson_and_kml() {
+    let app = TestApp::with_stored_places(located_places()).await;
+    let (access_token, _) = sign_in(&app).await;
+
+    let (status, body) = app
+        .request(Method::GET, "/ratings/export", None, Some(&access_token))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["features"], json!([]));
+
+    let (status, _) = app
+        .request(
+            Method::PUT,
+            "/places/1/rating",
+            Some(json!({ "score": 9, "review": "Get the focaccia" })),
+            Some(&access_token),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = rate(&app, &access_token, 2, 6).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = app
+        .request(
+            Method::GET,
+            "/ratings/export?format=geojson",
+            None,
+            Some(&access_token),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["type"], "FeatureCollection");
+    let arlo = &body["features"][0];
+    assert_eq!(arlo["properties"]["name"], "Arlo");
+    assert_eq!(arlo["properties"]["score"], 9);
+    assert_eq!(arlo["properties"]["notes"], "Get the focaccia");
+    assert_eq!(arlo["properties"]["address"], "271 N Center St");
+    assert_eq!(arlo["geometry"]["type"], "Point");
+    assert_eq!(body["features"][1]["properties"]["name"], "Arlo's Bakery");
+    assert_eq!(body["features"][1]["geometry"], Value::Null);
+
+    let (status, body) = app
+        .request(
+            Method::GET,
+            "/ratings/export?format=kml",
+            None,
+            Some(&access_token),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let kml = body.as_str().unwrap();
+    assert!(kml.contains("<name>Hunter Simmons&apos;s ratings</name>"));
+    assert!(kml.contains("<name>Arlo&apos;s Bakery</name>"));
+    assert!(kml.contains("Score: 9/10\nGet the focaccia"));
+
+    let (status, _) = app
+        .request(
+            Method::GET,
+            "/ratings/export?format=csv",
+            None,
+            Some(&access_token),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_place_details_with_scores_and_enriched_photos() {
+    let app = TestApp::with_stored_places(located_places()).await;
+    let (access_token, _) = sign_in(&app).await;
+
+    let (status, body) = app
+        .request(Method::GET, "/places/1", None, Some(&access_token))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["name"], "Arlo");
+    assert_eq!(body["ratingCount"], 0);
+    assert_eq!(body["averageScore"], Value::Null);
+    assert_eq!(body["userRating"], Value::Null);
+    assert_eq!(body["photos"], photos_json(PHOTO_PREFIX, 1));
+
+    // The photos were stored, so other endpoints see them too.
+    let (_, body) = app
+        .request(
+            Method::GET,
+            &format!("{}&zoom=18", VIEWPORT),
+            None,
+            Some(&access_token),
+        )
+        .await;
+    let arlo = body["places"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|p| p["name"] == "Arlo")
+        .unwrap();
+    assert_eq!(arlo["photos"].as_array().unwrap().len(), 1);
+
+    let (status, _) = rate(&app, &access_token, 1, 7).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, body) = app
+        .request(Method::GET, "/places/1", None, Some(&access_token))
+        .await;
+    assert_eq!(body["ratingCount"], 1);
+    assert_eq!(body["averageScore"], 7.0);
+    assert_eq!(body["userRating"]["score"], 7);
+
+    let (status, _) = app
+        .request(Method::GET, "/places/99", None, Some(&access_token))
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = app.request(Method::GET, "/places/1", None, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_place_details_fetches_missing_details() {
+    let mut places = located_places();
+    places[0].foursquare_id = Some("5c2aab5bb9a389002cf7b4a3".to_string());
+    let app = TestApp::with_stored_places(places).await;
+    let (access_token, _) = sign_in(&app).await;
+
+    let (status, body) = app
+        .request(Method::GET, "/places/1", None, Some(&access_token))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["details"]["price"], 2);
+    assert_eq!(body["details"]["timezone"], "America/Denver");
+
+    // Without a Foursquare id there are no details to fetch.
+    let (_, body) = app
+        .request(Method::GET, "/places/3", None, Some(&access_token))
+        .await;
+    assert_eq!(body["details"]["price"], Value::Null);
+}
+
+#[tokio::test]
+async fn test_picked_suggestions_are_located() {
+    let mut places = default_places();
+    places[1].mapbox_id = Some("mapbox-arlos-bakery".to_string());
+    places[2].mapbox_id = Some("mapbox-red-iguana".to_string());
+    let app = TestApp::with_stored_places(places).await;
+    let (access_token, _) = sign_in(&app).await;
+
+    let (_, body) = app
+        .request(
+            Method::POST,
+            "/search-places",
+            Some(search_body("arlo")),
+            Some(&access_token),
+        )
+        .await;
+    let session_id = body["sessionId"].as_str().unwrap().to_string();
+
+    let (status, body) = app
+        .request(
+            Method::GET,
+            &format!("/places/2?sessionId={}", session_id),
+            None,
+            Some(&access_token),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body["coordinates"],
+        json!({
+            "latitude": RETRIEVED_COORDINATES.latitude,
+            "longitude": RETRIEVED_COORDINATES.longitude,
+        })
+    );
+
+    // The retrieve ended the session, so the next suggest starts another.
+    let mut next = search_body("red");
+    next["sessionId"] = json!(session_id);
+    let (_, body) = app
+        .request(
+            Method::POST,
+            "/search-places",
+            Some(next),
+            Some(&access_token),
+        )
+        .await;
+    assert_ne!(body["sessionId"], session_id.as_str());
+
+    // A session id that isn't one of the user's is never billed.
+    let (status, _) = app
+        .request(
+            Method::PUT,
+            "/places/3/rating",
+            Some(json!({ "score": 8, "sessionId": "another-session" })),
+            Some(&access_token),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Places that are already located, or that Mapbox didn't suggest, are
+    // never looked up.
+    for path in ["/places/2", "/places/1"] {
+        app.request(Method::GET, path, None, Some(&access_token))
+            .await;
+    }
+    let retrievals = app.search.retrievals();
+    assert_eq!(retrievals.len(), 2);
+    assert_eq!(retrievals[0], (2, session_id));
+    assert_eq!(retrievals[1].0, 3);
+    assert!(!app.search.session_tokens().contains(&retrievals[1].1));
+    assert_ne!(retrievals[1].1, "another-session");
+}
+
+#[tokio::test]
+async fn test_place_photos_are_proxied_and_cached() {
+    let cdn = MockServer::start(vec![MockRoute::new(
+        Method::GET,
+        "/img/general/200x200/1.jpg",
+        StatusCode::OK,
+        "foursquare/photo.jpg",
+    )])
+    .await;
+    let app = TestApp::with_photo_cdn(located_places(), &cdn.url).await;
+    let (access_token, _) = sign_in(&app).await;
+
+    let (_, body) = app
+        .request(Method::GET, "/places/1", None, Some(&access_token))
+        .await;
+    let prefix = format!("{}/img/general/", cdn.url);
+    assert_eq!(body["photos"], photos_json(&prefix, 1));
+
+    // Photos load without an access token, as image tags fetch them.
+    let thumbnail = body["photos"][0]["variants"]["thumbnail"].as_str().unwrap();
+    for _ in 0..2 {
+        let (status, body) = app.request(Method::GET, thumbnail, None, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "not really a jpeg\n");
+    }
+    // The second request was served from the cache.
+    assert_eq!(cdn.requests().len(), 1);
+
+    // Sizes the CDN doesn't have fail.
+    let original = body["photos"][0]["variants"]["original"].as_str().unwrap();
+    let (status, _) = app
+        .request(Method::GET, original, None, Some(&access_token))
+        .await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+
+    // Only photos on the CDN are proxied.
+    let elsewhere = Photo {
+        prefix: "https://example.com/".to_string(),
+        suffix: "/1.jpg".to_string(),
+    };
+    let (status, _) = app
+        .request(
+            Method::GET,
+            &elsewhere.variants().thumbnail,
+            None,
+            Some(&access_token),
+        )
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    // Nor are photos the API never listed.
+    let unsigned = thumbnail.rsplit_once('.').unwrap().0;
+    let (status, _) = app.request(Method::GET, unsigned, None, None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(cdn.requests().len(), 2);
+    let (status, _) = app
+        .request(Method::GET, "/photos/huge/abc", None, Some(&access_token))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_stale_places_are_refreshed_in_the_background() {
+    let mut places = located_places();
+    places[0].foursquare_id = Some("5c2aab5bb9a389002cf7b4a3".to_string());
+    let app = TestApp::with_stored_places(places).await;
+    let (access_token, _) = sign_in(&app).await;
+
+    let mut details = Value::Null;
+    for _ in 0..100 {
+        let (_, body) = app
+            .request(
+                Method::GET,
+                &format!("{}&zoom=18", VIEWPORT),
+                None,
+                Some(&access_token),
+            )
+            .await;
+        let arlo = body["places"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|p| p["name"] == "Arlo")
+            .unwrap()
+            .clone();
+        if !arlo["details"].is_null() {
+            details = arlo["details"].clone();
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    assert_eq!(details["price"], 2);
+    assert_eq!(details["timezone"], "America/Denver");
+}
+
+const BOUNDARY: &str = "critiq-test-boundary";
+
+/// A multipart body with each of `files` in a field of its own.
+fn multipart_body(files: &[(&str, Vec<u8>)]) -> (String, Vec<u8>) {
+    let mut body = Vec::new();
+    for (field, bytes) in files {
+        body.extend(
+            format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"{}\"; filename=\"upload\"\r\n\
+                Content-Type: application/octet-stream\r\n\r\n",
+                BOUNDARY, field
+            )
+            .as_bytes(),
+        );
+        body.extend(bytes);
+        body.extend(b"\r\n");
+    }
+    body.extend(format!("--{}--\r\n", BOUNDARY).as_bytes());
+    (format!("multipart/form-data; boundary={}", BOUNDARY), body)
+}
+
+fn jpeg(width: u32, height: u32) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    image::DynamicImage::ImageRgb8(image::RgbImage::new(width, height))
+        .write_to(
+            &mut std::io::Cursor::new(&mut bytes),
+            image::ImageOutputFormat::Jpeg(90),
+        )
+        .unwrap();
+    bytes
+}
+
+async fn upload_photos(
+    app: &TestApp,
+    access_token: &str,
+    place_id: u64,
+    files: &[(&str, Vec<u8>)],
+) -> (StatusCode, Value) {
+    let (content_type, body) = multipart_body(files);
+    let (status, _, body) = app
+        .request_bytes(
+            Method::POST,
+            &format!("/places/{}/rating/photos", place_id),
+            Some((&content_type, body)),
+            Some(access_token),
+        )
+        .await;
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+#[tokio::test]
+async fn test_rating_photos_upload_serve_and_delete() {
+    let app = TestApp::with_stored_places(located_places()).await;
+    let (access_token, _) = sign_in(&app).await;
+
+    // Photos belong to a rating.
+    let (status, _) = upload_photos(&app, &access_token, 1, &[("photo", jpeg(800, 600))]).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    rate(&app, &access_token, 1, 8).await;
+
+    let (status, photos) = upload_photos(
+        &app,
+        &access_token,
+        1,
+        &[("photo", jpeg(800, 600)), ("photo", jpeg(300, 500))],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(photos.as_array().unwrap().len(), 2);
+    assert_eq!(photos[0]["placeId"], 1);
+    assert_eq!(
+        (photos[0]["width"].clone(), photos[0]["height"].clone()),
+        (json!(800), json!(600))
+    );
+    let id = photos[0]["id"].as_u64().unwrap();
+    assert_eq!(photos[0]["url"], format!("/ratings/photos/{}", id));
+
+    let (status, headers, bytes) = app
+        .request_bytes(
+            Method::GET,
+            &format!("/ratings/photos/{}", id),
+            None,
+            Some(&access_token),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers["content-type"], "image/jpeg");
+    assert_eq!(image::load_from_memory(&bytes).unwrap().width(), 800);
+    let thumbnail_url = photos[0]["thumbnailUrl"].as_str().unwrap();
+    let (status, _, bytes) = app
+        .request_bytes(Method::GET, thumbnail_url, None, Some(&access_token))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(image::load_from_memory(&bytes).unwrap().width(), 400);
+
+    let (_, details) = app
+        .request(Method::GET, "/places/1", None, Some(&access_token))
+        .await;
+    assert_eq!(details["ratingPhotos"], photos);
+
+    for (files, expected) in [
+        (
+            vec![("photo", b"GIF89a not a photo".to_vec())],
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        ),
+        (
+            vec![("photo", b"\xff\xd8\xff broken".to_vec())],
+            StatusCode::BAD_REQUEST,
+        ),
+        (vec![("image", jpeg(10, 10))], StatusCode::BAD_REQUEST),
+        (vec![], StatusCode::BAD_REQUEST),
+        (
+            vec![("photo", vec![0xff; 10 * 1024 * 1024 + 1])],
+            StatusCode::PAYLOAD_TOO_LARGE,
+        ),
+        // More than one request may carry.
+        (vec![("photo", jpeg(10, 10)); 5], StatusCode::BAD_REQUEST),
+    ] {
+        let (status, _) = upload_photos(&app, &access_token, 1, &files).await;
+        assert_eq!(status, expected);
+    }
+
+    let (status, _) = app
+        .request(
+            Method::DELETE,
+            &format!("/ratings/photos/{}", id),
+            None,
+            Some(&access_token),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = app
+        .request(
+            Method::GET,
+            &format!("/ratings/photos/{}", id),
+            None,
+            Some(&access_token),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (_, details) = app
+        .request(Method::GET, "/places/1", None, Some(&access_token))
+        .await;
+    assert_eq!(details["ratingPhotos"].as_array().unwrap().len(), 1);
+    // Only the other photo and its thumbnail are left.
+    let stored = std::fs::read_dir(app.blob_dir.join("ratings/1"))
+        .unwrap()
+        .count();
+    assert_eq!(stored, 2);
+
+    // Deleting the rating deletes what's left of its photos.
+    let (status, _) = app
+        .request(
+            Method::DELETE,
+            "/places/1/rating",
+            None,
+            Some(&access_token),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let stored = std::fs::read_dir(app.blob_dir.join("ratings/1"))
+        .unwrap()
+        .count();
+    assert_eq!(stored, 0);
+    let (_, details) = app
+        .request(Method::GET, "/places/1", None, Some(&access_token))
+        .await;
+    assert_eq!(details["ratingPhotos"], json!([]));
+    let (status, _) = app
+        .request(
+            Method::DELETE,
+            "/places/1/rating",
+            None,
+            Some(&access_token),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_concurrent_rating_photo_uploads_keep_to_the_limit() {
+    let app = TestApp::with_stored_places(located_places()).await;
+    let (access_token, _) = sign_in(&app).await;
+    rate(&app, &access_token, 1, 8).await;
+    for _ in 0..2 {
+        let (status, _) =
+            upload_photos(&app, &access_token, 1, &vec![("photo", jpeg(10, 10)); 4]).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    // Each fits on its own, but not both.
+    let files = vec![("photo", jpeg(10, 10)); 2];
+    let ((first, _), (second, _)) = tokio::join!(
+        upload_photos(&app, &access_token, 1, &files),
+        upload_photos(&app, &access_token, 1, &files),
+    );
+    let mut statuses = vec![first, second];
+    statuses.sort();
+    assert_eq!(statuses, vec![StatusCode::OK, StatusCode::BAD_REQUEST]);
+
+    let (_, details) = app
+        .request(Method::GET, "/places/1", None, Some(&access_token))
+        .await;
+    assert_eq!(details["ratingPhotos"].as_array().unwrap().len(), 10);
+    // The rejected upload's photos and thumbnails were removed.
+    let stored = std::fs::read_dir(app.blob_dir.join("ratings/1"))
+        .unwrap()
+        .count();
+    assert_eq!(stored, 20);
+}
+
+#[tokio::test]
+async fn test_authenticate_twice_keeps_one_user() {
+    let app = TestApp::new();
+
+    sign_in(&app).await;
+    sign_in(&app).await;
+
+    assert_eq!(app.sms_verify.sent_to(), vec![2028098681, 2028098681]);
+}
+
+#[tokio::test]
+async fn test_authenticate_rejects_malformed_phone_number() {
+    let app = TestApp::new();
+
+    let (status, body) = app
+        .request(
+            Method::PUT,
+            "/authenticate",
+            Some(json!({
+                "firstName": "Hunter",
+                "lastName": "Simmons",
+                "phoneNumber": "202-809-8681",
+            })),
+            None,
+        )
+        .await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body, "Phone number not valid");
+    assert!(app.sms_verify.sent_to().is_empty());
+}
+
+#[tokio::test]
+async fn test_verify_phone_rejects_wrong_code() {
+    let app = TestApp::new();
+    app.request(
+        Method::PUT,
+        "/authenticate",
+        Some(json!({
+            "firstName": "Hunter",
+            "lastName": "Simmons",
+            "phoneNumber": PHONE_NUMBER,
+        })),
+        None,
+    )
+    .await;
+
+    let (status, _) = app
+        .request(
+            Method::POST,
+            "/verify-phone",
+            Some(json!({ "phoneNumber": PHONE_NUMBER, "code": 111111 })),
+            None,
+        )
+        .await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_verify_phone_without_user() {
+    let app = TestApp::new();
+
+    let (status, body) = app
+        .request(
+            Method::POST,
+            "/verify-phone",
+            Some(json!({ "phoneNumber": PHONE_NUMBER, "code": VERIFICATION_CODE })),
+            None,
+        )
+        .await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body, "No user saved");
+}
+
+#[tokio::test]
+async fn test_search_places_requires_valid_token() {
+    let app = TestApp::new();
+
+    let (status, _) = app
+        .request(
+            Method::POST,
+            "/search-places",
+            Some(search_body("arlo")),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _) = app
+        .request(
+            Method::POST,
+            "/search-places",
+            Some(search_body("arlo")),
+            Some("not-a-token"),
+        )
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_refresh_token_rejects_bad_token() {
+    let app = TestApp::new();
+
+    let (status, body) = app
+        .request(
+            Method::POST,
+            "/refresh-token",
+            Some(json!({ "refreshToken": "not-a-token" })),
+            None,
+        )
+        .await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body, "Bad refresh token");
+}
@@ -0,0 +1,209 @@
+//! Local stand-ins for the Mapbox, Foursquare and Twilio HTTP APIs. Each
+//! server answers configured method/path pairs with a recorded fixture from
+//! `tests/fixtures` and keeps every request it receives for assertions.
+
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    extract::State,
+    http::{HeaderMap, Method, StatusCode, Uri},
+    response::IntoResponse,
+    Router,
+};
+use tokio::task::JoinHandle;
+
+pub const TWILIO_SERVICE_SID: &str = "VA1234567890abcdef1234567890abcdef";
+
+pub fn fixture(path: &str) -> String {
+    let path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), path);
+    std::fs::read_to_string(&path).unwrap_or_else(|_| panic!("fixture {} to exist", path))
+}
+
+#[derive(Clone)]
+pub struct MockRoute {
+    method: Method,
+    path: String,
+    status: StatusCode,
+    body: String,
+}
+
+impl MockRoute {
+    pub fn new(method: Method, path: &str, status: StatusCode, fixture_path: &str) -> Self {
+        MockRoute {
+            method,
+            path: path.to_string(),
+            status,
+            body: fixture(fixture_path),
+        }
+    }
+
+    pub fn mapbox_suggest(status: StatusCode, fixture_path: &str) -> Self {
+        Self::new(
+            Method::GET,
+            "/search/searchbox/v1/suggest",
+            status,
+            fixture_path,
+        )
+    }
+
+    pub fn mapbox_retrieve(mapbox_id: &str, status: StatusCode, fixture_path: &str) -> Self {
+        Self::new(
+            Method::GET,
+            &format!("/search/searchbox/v1/retrieve/{}", mapbox_id),
+            status,
+            fixture_path,
+        )
+    }
+
+    pub fn foursquare_search(status: StatusCode, fixture_path: &str) -> Self {
+        Self::new(Method::GET, "/v3/places/search", status, fixture_path)
+    }
+
+    pub fn foursquare_photos(foursquare_id: &str, status: StatusCode, fixture_path: &str) -> Self {
+        Self::new(
+            Method::GET,
+            &format!("/v3/places/{}/photos", foursquare_id),
+            status,
+            fixture_path,
+        )
+    }
+
+    pub fn foursquare_details(foursquare_id: &str, status: StatusCode, fixture_path: &str) -> Self {
+        Self::new(
+            Method::GET,
+            &format!("/v3/places/{}", foursquare_id),
+            status,
+            fixture_path,
+        )
+    }
+
+    pub fn nominatim_search(status: StatusCode, fixture_path: &str) -> Self {
+        Self::new(Method::GET, "/search", status, fixture_path)
+    }
+
+    pub fn twilio_verifications(status: StatusCode, fixture_path: &str) -> Self {
+        Self::new(
+            Method::POST,
+            &format!("/v2/Services/{}/Verifications", TWILIO_SERVICE_SID),
+            status,
+            fixture_path,
+        )
+    }
+
+    pub fn twilio_verification_check(status: StatusCode, fixture_path: &str) -> Self {
+        Self::new(
+            Method::POST,
+            &format!("/v2/Services/{}/VerificationCheck", TWILIO_SERVICE_SID),
+            status,
+            fixture_path,
+        )
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RecordedRequest {
+    pub method: Method,
+    pub path: String,
+    pub query: Option<String>,
+    pub headers: HeaderMap,
+    pub body: String,
+}
+
+impl RecordedRequest {
+    pub fn query_param(&self, name: &str) -> Option<String> {
+        urlencoded_param(self.query.as_deref().unwrap_or_default(), name)
+    }
+
+    pub fn form_param(&self, name: &str) -> Option<String> {
+        urlencoded_param(&self.body, name)
+    }
+}
+
+fn urlencoded_param(encoded: &str, name: &str) -> Option<String> {
+    let url = reqwest::Url::parse(&format!("http://mock/?{}", encoded)).ok()?;
+    url.query_pairs()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.into_owned())
+}
+
+#[derive(Clone)]
+struct MockState {
+    routes: Arc<Vec<MockRoute>>,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+}
+
+pub struct MockServer {
+    pub url: String,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+    handle: JoinHandle<()>,
+}
+
+impl MockServer {
+    pub async fn start(routes: Vec<MockRoute>) -> MockServer {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let state = MockState {
+            routes: Arc::new(routes),
+            requests: requests.clone(),
+        };
+        let app = Router::new().fallback(respond).with_state(state);
+
+        let server = axum::Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0)))
+            .serve(app.into_make_service());
+        let url = format!("http://{}", server.local_addr());
+        let handle = tokio::spawn(async move {
+            server.await.unwrap();
+        });
+
+        MockServer {
+            url,
+            requests,
+            handle,
+        }
+    }
+
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+async fn respond(
+    State(state): State<MockState>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: String,
+) -> impl IntoResponse {
+    state.requests.lock().unwrap().push(RecordedRequest {
+        method: method.clone(),
+        path: uri.path().to_string(),
+        query: uri.query().map(|q| q.to_string()),
+        headers,
+        body,
+    });
+
+    match state
+        .routes
+        .iter()
+        .find(|r| r.method == method && r.path == uri.path())
+    {
+        Some(route) => (
+            route.status,
+            [("content-type", "application/json")],
+            route.body.clone(),
+        ),
+        None => (
+            StatusCode::NOT_FOUND,
+            [("content-type", "text/plain")],
+            format!("no fixture for {} {}", method, uri.path()),
+        ),
+    }
+}
@@ -0,0 +1,391 @@
+//! In-process harness for driving the HTTP API without any external services.
+//! The router is built with the in-memory repositories and the fakes below,
+//! and requests are dispatched straight into it with `tower::ServiceExt`.
+
+#![allow(dead_code)]
+
+pub mod mock;
+
+use std::{
+    collections::HashSet,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use axum::{
+    async_trait,
+    body::Body,
+    http::{header, HeaderMap, Method, Request, StatusCode},
+    Router,
+};
+use chrono::Utc;
+use critiq_backend::{
+    blob::local::LocalBlobStore,
+    create_router,
+    geo::Coordinates,
+    jobs::JobOptions,
+    oauth::OAuth,
+    places::{
+        cache::CachedSearch,
+        photo::{Photo, PhotoProxy},
+        search::{Search, SearchQuery},
+        Address, Place, PlaceDetails,
+    },
+    repository::{
+        local::{
+            jobs::LocalJobsRepository, places::LocalPlacesRepository,
+            ratings::LocalRatingsRepository, user::LocalUserRepository,
+        },
+        places::PlacesRepository,
+    },
+    shutdown::Shutdown,
+    sms::SMSVerify,
+};
+use serde_json::Value;
+use tower::ServiceExt;
+
+pub const JWT_KEY: &str =
+    "5atKdFrP3CcuCocV42qJvnCTQ7zsuHfuFkMHmHiZrZxK16K4vfa2NabpRjaMKn5M91fKnk5xVGhxNV";
+pub const VERIFICATION_CODE: u32 = 123456;
+/// Sent in place of an access token to reach the operator routes.
+pub const ADMIN_TOKEN: &str = "test-admin-token";
+/// Where `FakeSearch` photos are, unless a test serves them itself.
+pub const PHOTO_PREFIX: &str = "https://fastly.4sqi.net/img/general/";
+
+/// Accepts `VERIFICATION_CODE` for any number a code was sent to.
+#[derive(Clone, Default)]
+pub struct FakeSMSVerify {
+    sent_to: Arc<Mutex<Vec<u64>>>,
+}
+
+impl FakeSMSVerify {
+    pub fn sent_to(&self) -> Vec<u64> {
+        self.sent_to.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl SMSVerify for FakeSMSVerify {
+    async fn send_verification_code(&self, phone_number: u64) -> Result<(), String> {
+        self.sent_to.lock().unwrap().push(phone_number);
+        Ok(())
+    }
+
+    async fn verify_code(&self, phone_number: u64, verification_code: u32) -> Result<(), String> {
+        if !self.sent_to.lock().unwrap().contains(&phone_number) {
+            return Err("No verification code sent".to_string());
+        }
+        if verification_code != VERIFICATION_CODE {
+            return Err("Status not accepted".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Where `FakeSearch` locates places picked without coordinates.
+pub const RETRIEVED_COORDINATES: Coordinates = Coordinates {
+    latitude: 40.7608,
+    longitude: -111.891,
+};
+
+/// Serves a fixed catalogue of places, matching on a case-insensitive
+/// substring of the name, and records every query it is sent.
+#[derive(Clone)]
+pub struct FakeSearch {
+    places: Vec<Place>,
+    queries: Arc<Mutex<Vec<SearchQuery>>>,
+    retrievals: Arc<Mutex<Vec<(u64, String)>>>,
+    failing_photos: Arc<Mutex<HashSet<u64>>>,
+    photo_prefix: String,
+}
+
+impl FakeSearch {
+    pub fn new(places: Vec<Place>) -> Self {
+        FakeSearch {
+            places,
+            queries: Arc::new(Mutex::new(Vec::new())),
+            retrievals: Arc::new(Mutex::new(Vec::new())),
+            failing_photos: Arc::new(Mutex::new(HashSet::new())),
+            photo_prefix: PHOTO_PREFIX.to_string(),
+        }
+    }
+
+    /// Makes every request for the photos of the place fail.
+    pub fn fail_photos(&self, place_id: u64) {
+        self.failing_photos.lock().unwrap().insert(place_id);
+    }
+
+    pub fn queries(&self) -> Vec<SearchQuery> {
+        self.queries.lock().unwrap().clone()
+    }
+
+    /// The places coordinates were looked up for, with the session token.
+    pub fn retrievals(&self) -> Vec<(u64, String)> {
+        self.retrievals.lock().unwrap().clone()
+    }
+
+    pub fn session_tokens(&self) -> Vec<String> {
+        self.queries()
+            .into_iter()
+            .map(|q| q.session_token)
+            .collect()
+    }
+}
+
+#[async_trait]
+impl Search for FakeSearch {
+    async fn search_for_place(&self, query: SearchQuery) -> Result<Vec<Place>, String> {
+        self.queries.lock().unwrap().push(query.clone());
+        let search_string = query.search_string.to_lowercase();
+        Ok(self
+            .places
+            .iter()
+            .filter(|p| p.name.to_lowercase().contains(&search_string))
+            .cloned()
+            .collect())
+    }
+
+    async fn get_photos(&self, place_id: u64) -> Result<Place, String> {
+        if self.failing_photos.lock().unwrap().contains(&place_id) {
+            return Err("Error getting pictures".to_string());
+        }
+        match self.places.iter().find(|p| p.id == place_id) {
+            Some(place) => {
+                let mut place = place.clone();
+                place.photos = Some(vec![Photo {
+                    prefix: self.photo_prefix.clone(),
+                    suffix: format!("/{}.jpg", place.id),
+                }]);
+                Ok(place)
+            }
+            None => Err("Error getting place".to_string()),
+        }
+    }
+
+    /// Like Foursquare, only knows the details of places with its id.
+    async fn get_details(&self, place_id: u64) -> Result<Place, String> {
+        match self.places.iter().find(|p| p.id == place_id) {
+            Some(place) if place.foursquare_id.is_none() => Ok(place.clone()),
+            Some(place) => {
+                let mut place = place.clone();
+                place.details = Some(PlaceDetails {
+                    hours: None,
+                    price: Some(2),
+                    phone: Some("(385) 266-8845".to_string()),
+                    social: None,
+                    timezone: Some("America/Denver".to_string()),
+                    updated_at: Utc::now(),
+                });
+                Ok(place)
+            }
+            None => Err("Error getting place".to_string()),
+        }
+    }
+
+    async fn get_coordinates(&self, place_id: u64, session_token: &str) -> Result<Place, String> {
+        self.retrievals
+            .lock()
+            .unwrap()
+            .push((place_id, session_token.to_string()));
+        match self.places.iter().find(|p| p.id == place_id) {
+            Some(place) => Ok(Place {
+                coordinates: Some(RETRIEVED_COORDINATES),
+                ..place.clone()
+            }),
+            None => Err("Error getting place".to_string()),
+        }
+    }
+}
+
+pub fn place(id: u64, name: &str, address: &str) -> Place {
+    Place {
+        id,
+        name: name.to_string(),
+        address: Address {
+            address: address.to_string(),
+            full_address: Some(format!("{}, Salt Lake City, Utah 84106", address)),
+            country: Some("United States".to_string()),
+            region: Some("Utah".to_string()),
+            postcode: Some("84106".to_string()),
+            place: Some("Salt Lake City".to_string()),
+            ..Address::default()
+        },
+        ..Place::default()
+    }
+}
+
+pub fn default_places() -> Vec<Place> {
+    let mut arlo = place(1, "Arlo", "271 N Center St");
+    arlo.foursquare_id = Some("5c2aab5bb9a389002cf7b4a3".to_string());
+    arlo.coordinates = Some(Coordinates {
+        latitude: 40.775563,
+        longitude: -111.893962,
+    });
+    vec![
+        arlo,
+        place(2, "Arlo's Bakery", "1 S Main St"),
+        place(3, "Red Iguana", "736 W North Temple"),
+    ]
+}
+
+/// Caches photos in a directory of its own, so tests don't share photos.
+fn photo_proxy() -> PhotoProxy {
+    let cache_dir = std::env::temp_dir().join(format!("critiq-photos-{}", uuid::Uuid::new_v4()));
+    PhotoProxy::new(cache_dir)
+}
+
+pub struct TestApp {
+    router: Router,
+    pub sms_verify: FakeSMSVerify,
+    pub search: FakeSearch,
+    /// Where uploaded photos are stored, a directory of its own per app.
+    pub blob_dir: PathBuf,
+}
+
+impl TestApp {
+    pub fn new() -> Self {
+        Self::with_places(default_places())
+    }
+
+    pub fn with_places(places: Vec<Place>) -> Self {
+        let search = FakeSearch::new(places);
+        Self::with_search(LocalPlacesRepository::new(), search.clone(), search)
+    }
+
+    /// Puts a `CachedSearch` in front of the fake provider, with its places
+    /// stored as a real provider would.
+    pub async fn with_search_cache() -> Self {
+        let mut places_repo = LocalPlacesRepository::new();
+        for place in &default_places() {
+            places_repo.create(place).await.unwrap();
+        }
+        let search = FakeSearch::new(default_places());
+        Self::with_search(places_repo, search.clone(), CachedSearch::new(search))
+    }
+
+    /// Starts with `places` already in the places repository.
+    pub async fn with_stored_places(places: Vec<Place>) -> Self {
+        let mut places_repo = LocalPlacesRepository::new();
+        for place in &places {
+            places_repo.create(place).await.unwrap();
+        }
+        let search = FakeSearch::new(places);
+        Self::with_search(places_repo, search.clone(), search)
+    }
+
+    /// Starts with `places` stored, with photos found for them served from
+    /// `cdn_url` and proxied from there.
+    pub async fn with_photo_cdn(places: Vec<Place>, cdn_url: &str) -> Self {
+        let mut places_repo = LocalPlacesRepository::new();
+        for place in &places {
+            places_repo.create(place).await.unwrap();
+        }
+        let mut search = FakeSearch::new(places);
+        search.photo_prefix = format!("{}/img/general/", cdn_url);
+        let photo_proxy = photo_proxy().with_origins(vec![cdn_url.to_string()]);
+        Self::with_search_and_proxy(places_repo, search.clone(), search, photo_proxy)
+    }
+
+    fn with_search<S: Search>(
+        places_repo: LocalPlacesRepository,
+        search: FakeSearch,
+        places_search: S,
+    ) -> Self {
+        Self::with_search_and_proxy(places_repo, search, places_search, photo_proxy())
+    }
+
+    fn with_search_and_proxy<S: Search>(
+        places_repo: LocalPlacesRepository,
+        search: FakeSearch,
+        places_search: S,
+        photo_proxy: PhotoProxy,
+    ) -> Self {
+        let sms_verify = FakeSMSVerify::default();
+        let blob_dir = std::env::temp_dir().join(format!("critiq-blobs-{}", uuid::Uuid::new_v4()));
+        let router = create_router(
+            LocalUserRepository::new(),
+            places_repo,
+            LocalRatingsRepository::new(),
+            LocalJobsRepository::new(),
+            sms_verify.clone(),
+            places_search,
+            OAuth::new(JWT_KEY).with_admin_token(ADMIN_TOKEN),
+            photo_proxy,
+            Arc::new(LocalBlobStore::new(&blob_dir)),
+            JobOptions {
+                concurrency: 2,
+                max_attempts: 2,
+                backoff: Duration::from_millis(10),
+                poll_interval: Duration::from_millis(10),
+                ..JobOptions::default()
+            },
+            Shutdown::new(),
+        );
+        TestApp {
+            router,
+            sms_verify,
+            search,
+            blob_dir,
+        }
+    }
+
+    /// Sends a request with an optional JSON body and access token, returning the
+    /// status and the body as JSON (`Value::Null` when empty, a string when not JSON).
+    pub async fn request(
+        &self,
+        method: Method,
+        uri: &str,
+        body: Option<Value>,
+        access_token: Option<&str>,
+    ) -> (StatusCode, Value) {
+        let mut request = Request::builder().method(method).uri(uri);
+        if let Some(token) = access_token {
+            request = request.header(header::AUTHORIZATION, token);
+        }
+        let request = match body {
+            Some(body) => request
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+            None => request.body(Body::empty()).unwrap(),
+        };
+
+        let response = self.router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        if bytes.is_empty() {
+            return (status, Value::Null);
+        }
+        let body = serde_json::from_slice(&bytes)
+            .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&bytes).to_string()));
+        (status, body)
+    }
+
+    /// Sends a request with a body of any type, returning the response as is.
+    pub async fn request_bytes(
+        &self,
+        method: Method,
+        uri: &str,
+        body: Option<(&str, Vec<u8>)>,
+        access_token: Option<&str>,
+    ) -> (StatusCode, HeaderMap, Vec<u8>) {
+        let mut request = Request::builder().method(method).uri(uri);
+        if let Some(token) = access_token {
+            request = request.header(header::AUTHORIZATION, token);
+        }
+        let request = match body {
+            Some((content_type, body)) => request
+                .header(header::CONTENT_TYPE, content_type)
+                .body(Body::from(body))
+                .unwrap(),
+            None => request.body(Body::empty()).unwrap(),
+        };
+
+        let response = self.router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let headers = response.headers().clone();
+        let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (status, headers, bytes.to_vec())
+    }
+}
@@ -0,0 +1,905 @@
+mod common;
+
+use std::sync::Arc;
+
+use axum::http::StatusCode;
+use critiq_backend::{
+    geo::Coordinates,
+    places::{
+        composite::{CompositeSearch, DEFAULT_PROVIDER_TIMEOUT},
+        database::DatabaseSearch,
+        foursquare::search::FoursquareSearchApi,
+        local_first::LocalFirstSearch,
+        mapbox::search::MapboxSearchApi,
+        osm::search::OsmSearchApi,
+        photo::{Photo, PhotoSize},
+        search::{PlaceCategory, Search, SearchQuery},
+    },
+    repository::{
+        local::places::LocalPlacesRepository,
+        places::{DynPlacesRepo, ReadPlaceOptions},
+    },
+    sms::{twilio::TwilioSMS, SMSVerify},
+};
+use tokio::sync::Mutex;
+
+use common::mock::{MockRoute, MockServer, TWILIO_SERVICE_SID};
+
+const FOURSQUARE_ID: &str = "5c2aab5bb9a389002cf7b4a3";
+const SESSION_TOKEN: &str = "0e3a9d6c-5b1f-4f8e-9c2d-7a6b5e4d3c2b";
+const ARLO_MAPBOX_ID: &str = "dXJuOm1ieHBvaTo0ZTg2ZWFkNS1jOWMwLTQ3OWEtOTA5Mi1kMDVlNDQ3NDdlODk";
+const RED_IGUANA_MAPBOX_ID: &str =
+    "dXJuOm1ieHBvaTpmNjNjNzZmMi0wNjUwLTQ5NDktYTFmZS1iYTZmZWE1ZDZiODQ";
+
+fn salt_lake_city() -> Coordinates {
+    Coordinates {
+        latitude: 40.7608,
+        longitude: -111.891,
+    }
+}
+
+fn places_repo() -> DynPlacesRepo {
+    Arc::new(Mutex::new(LocalPlacesRepository::new()))
+}
+
+fn mapbox(server: &MockServer, places_repo: DynPlacesRepo) -> MapboxSearchApi {
+    MapboxSearchApi::new("mapbox-token", "foursquare-token", places_repo)
+        .with_base_urls(&server.url, &server.url)
+}
+
+fn foursquare(server: &MockServer, places_repo: DynPlacesRepo) -> FoursquareSearchApi {
+    FoursquareSearchApi::new("foursquare-token", places_repo).with_base_url(&server.url)
+}
+
+fn osm(server: &MockServer, places_repo: DynPlacesRepo) -> OsmSearchApi {
+    OsmSearchApi::new(&server.url, places_repo).with_user_agent("critiq-tests")
+}
+
+fn twilio(server: &MockServer) -> TwilioSMS {
+    TwilioSMS::new("AC1234567890abcdef", TWILIO_SERVICE_SID, "auth-token")
+        .with_base_url(&server.url)
+}
+
+#[tokio::test]
+async fn test_mapbox_search_parses_and_stores_suggestions() {
+    let server = MockServer::start(vec![MockRoute::mapbox_suggest(
+        StatusCode::OK,
+        "mapbox/suggest.json",
+    )])
+    .await;
+    let places_repo = places_repo();
+
+    let places = mapbox(&server, places_repo.clone())
+        .search_for_place(SearchQuery::new(salt_lake_city(), "arlo", SESSION_TOKEN))
+        .await
+        .unwrap();
+
+    assert_eq!(places.len(), 2);
+    assert_eq!(places[0].name, "Arlo");
+    assert_eq!(places[0].address.address, "271 N Center St");
+    assert_eq!(places[0].address.postcode, Some("84103".to_string()));
+    assert_eq!(places[0].address.street, Some("n center st".to_string()));
+    assert_eq!(places[0].foursquare_id, Some(FOURSQUARE_ID.to_string()));
+    assert_eq!(places[0].mapbox_id, Some(ARLO_MAPBOX_ID.to_string()));
+    assert_eq!(places[1].name, "Red Iguana");
+    assert_eq!(places[1].address.street, None);
+    assert_eq!(places[1].foursquare_id, None);
+    assert_ne!(places[0].id, places[1].id);
+    // Coordinates wait until someone picks the place.
+    assert!(places.iter().all(|p| p.coordinates.is_none()));
+
+    let stored = places_repo
+        .lock()
+        .await
+        .read(ReadPlaceOptions {
+            id: Some(places[0].id),
+            name: None,
+            address: None,
+            postcode: None,
+        })
+        .await
+        .unwrap();
+    assert_eq!(stored[0].mapbox_id, places[0].mapbox_id);
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(
+        requests[0].query_param("session_token"),
+        Some(SESSION_TOKEN.to_string())
+    );
+    assert_eq!(requests[0].query_param("q"), Some("arlo".to_string()));
+    assert_eq!(
+        requests[0].query_param("access_token"),
+        Some("mapbox-token".to_string())
+    );
+    assert_eq!(
+        requests[0].query_param("proximity"),
+        Some("-111.891,40.7608".to_string())
+    );
+}
+
+#[tokio::test]
+async fn test_mapbox_search_sends_query_options() {
+    let server = MockServer::start(vec![MockRoute::mapbox_suggest(
+        StatusCode::OK,
+        "mapbox/suggest.json",
+    )])
+    .await;
+    let mut query = SearchQuery::new(salt_lake_city(), "arlo", SESSION_TOKEN);
+    query.categories = vec![PlaceCategory::Coffee, PlaceCategory::Bakery];
+    query.radius_meters = 1_000.0;
+    query.limit = Some(5);
+    query.language = "es".to_string();
+
+    mapbox(&server, places_repo())
+        .search_for_place(query.clone())
+        .await
+        .unwrap();
+
+    let suggest = &server.requests()[0];
+    assert_eq!(
+        suggest.query_param("poi_category"),
+        Some("coffee,bakery".to_string())
+    );
+    assert_eq!(suggest.query_param("limit"), Some("5".to_string()));
+    assert_eq!(suggest.query_param("language"), Some("es".to_string()));
+    let bbox = query.bounding_box();
+    assert_eq!(
+        suggest.query_param("bbox"),
+        Some(format!(
+            "{},{},{},{}",
+            bbox.min_longitude, bbox.min_latitude, bbox.max_longitude, bbox.max_latitude
+        ))
+    );
+    assert!(bbox.max_latitude - bbox.min_latitude < 0.02);
+}
+
+#[tokio::test]
+async fn test_mapbox_get_coordinates_retrieves_picked_suggestions() {
+    let server = MockServer::start(vec![
+        MockRoute::mapbox_suggest(StatusCode::OK, "mapbox/suggest.json"),
+        MockRoute::mapbox_retrieve(ARLO_MAPBOX_ID, StatusCode::OK, "mapbox/retrieve_arlo.json"),
+        MockRoute::mapbox_retrieve(
+            RED_IGUANA_MAPBOX_ID,
+            StatusCode::OK,
+            "mapbox/retrieve_empty.json",
+        ),
+    ])
+    .await;
+    let places_repo = places_repo();
+    let mapbox = mapbox(&server, places_repo.clone());
+    let places = mapbox
+        .search_for_place(SearchQuery::new(salt_lake_city(), "arlo", SESSION_TOKEN))
+        .await
+        .unwrap();
+
+    let arlo = mapbox
+        .get_coordinates(places[0].id, SESSION_TOKEN)
+        .await
+        .unwrap();
+    assert_eq!(
+        arlo.coordinates,
+        Some(Coordinates {
+            latitude: 40.775563,
+            longitude: -111.893962,
+        })
+    );
+    assert!(mapbox
+        .get_coordinates(places[1].id, SESSION_TOKEN)
+        .await
+        .is_err());
+
+    // Stored coordinates are used from then on, even by later searches.
+    mapbox
+        .get_coordinates(places[0].id, SESSION_TOKEN)
+        .await
+        .unwrap();
+    let places = mapbox
+        .search_for_place(SearchQuery::new(salt_lake_city(), "arlo", SESSION_TOKEN))
+        .await
+        .unwrap();
+    assert_eq!(places[0].coordinates, arlo.coordinates);
+
+    let retrieved: Vec<String> = server
+        .requests()
+        .iter()
+        .filter(|r| r.path.starts_with("/search/searchbox/v1/retrieve"))
+        .map(|r| {
+            assert_eq!(
+                r.query_param("session_token"),
+                Some(SESSION_TOKEN.to_string())
+            );
+            r.path.clone()
+        })
+        .collect();
+    assert_eq!(
+        retrieved,
+        vec![
+            format!("/search/searchbox/v1/retrieve/{}", ARLO_MAPBOX_ID),
+            format!("/search/searchbox/v1/retrieve/{}", RED_IGUANA_MAPBOX_ID),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_unlocated_mapbox_suggestions_are_found_locally_in_their_town() {
+    let server = MockServer::start(vec![MockRoute::mapbox_suggest(
+        StatusCode::OK,
+        "mapbox/suggest.json",
+    )])
+    .await;
+    let places_repo = places_repo();
+    let mut communal = common::place(0, "Communal", "102 N University Ave");
+    communal.coordinates = Some(salt_lake_city());
+    places_repo.lock().await.create(&communal).await.unwrap();
+    let search = LocalFirstSearch::new(places_repo.clone(), mapbox(&server, places_repo.clone()))
+        .with_min_results(1);
+    let local = DatabaseSearch::new(places_repo);
+
+    let first = search
+        .search_for_place(SearchQuery::new(salt_lake_city(), "arlo", SESSION_TOKEN))
+        .await
+        .unwrap();
+    let found = local
+        .search_for_place(SearchQuery::new(salt_lake_city(), "arlo", SESSION_TOKEN))
+        .await
+        .unwrap();
+    assert!(found.iter().all(|p| p.coordinates.is_none()));
+    assert_eq!(found[0].id, first[0].id);
+
+    let new_york = Coordinates {
+        latitude: 40.7128,
+        longitude: -74.006,
+    };
+    assert!(local
+        .search_for_place(SearchQuery::new(new_york, "arlo", SESSION_TOKEN))
+        .await
+        .unwrap()
+        .is_empty());
+
+    // Suggestions nobody picked yet aren't enough to skip Mapbox.
+    search
+        .search_for_place(SearchQuery::new(salt_lake_city(), "arlo", SESSION_TOKEN))
+        .await
+        .unwrap();
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn test_mapbox_search_error_status() {
+    let server = MockServer::start(vec![MockRoute::mapbox_suggest(
+        StatusCode::UNAUTHORIZED,
+        "mapbox/unauthorized.json",
+    )])
+    .await;
+
+    let result = mapbox(&server, places_repo())
+        .search_for_place(SearchQuery::new(salt_lake_city(), "arlo", SESSION_TOKEN))
+        .await;
+
+    assert_eq!(
+        result.unwrap_err(),
+        "Error requesting data from Mapbox".to_string()
+    );
+}
+
+#[tokio::test]
+async fn test_mapbox_search_malformed_json() {
+    let server = MockServer::start(vec![MockRoute::mapbox_suggest(
+        StatusCode::OK,
+        "mapbox/suggest_truncated.json",
+    )])
+    .await;
+    let places_repo = places_repo();
+
+    let result = mapbox(&server, places_repo.clone())
+        .search_for_place(SearchQuery::new(salt_lake_city(), "arlo", SESSION_TOKEN))
+        .await;
+
+    assert!(result.is_err());
+}
+
+async fn stored_place(places_repo: &DynPlacesRepo) -> u64 {
+    let mut place = common::place(0, "Arlo", "271 N Center St");
+    place.foursquare_id = Some(FOURSQUARE_ID.to_string());
+    places_repo.lock().await.create(&place).await.unwrap().id
+}
+
+#[tokio::test]
+async fn test_foursquare_photos_are_stored() {
+    let server = MockServer::start(vec![MockRoute::foursquare_photos(
+        FOURSQUARE_ID,
+        StatusCode::OK,
+        "foursquare/photos.json",
+    )])
+    .await;
+    let places_repo = places_repo();
+    let place_id = stored_place(&places_repo).await;
+
+    let place = mapbox(&server, places_repo.clone())
+        .get_photos(place_id)
+        .await
+        .unwrap();
+
+    let photos = place.photos.unwrap();
+    assert_eq!(
+        photos[0],
+        Photo {
+            prefix: "https://fastly.4sqi.net/img/general/".to_string(),
+            suffix: "/36253392_Jp8dFqZ5aJ8gzSm3nmgpTC7SYpbxYvXhkPqxUhWC_ko.jpg".to_string(),
+        }
+    );
+    let urls: Vec<String> = photos.iter().map(|p| p.url(PhotoSize::Original)).collect();
+    assert_eq!(
+        urls,
+        vec![
+            "https://fastly.4sqi.net/img/general/original/36253392_Jp8dFqZ5aJ8gzSm3nmgpTC7SYpbxYvXhkPqxUhWC_ko.jpg",
+            "https://fastly.4sqi.net/img/general/original/5493178_pWm6c1uZ_d7Qf9yE9a6h5bN0NUvn1x3t4l1W1Q3b3Xk.jpg",
+        ]
+    );
+
+    let requests = server.requests();
+    assert_eq!(
+        requests[0].headers.get("authorization").unwrap(),
+        "foursquare-token"
+    );
+}
+
+#[tokio::test]
+async fn test_foursquare_photos_error_status() {
+    let server = MockServer::start(vec![MockRoute::foursquare_photos(
+        FOURSQUARE_ID,
+        StatusCode::UNAUTHORIZED,
+        "foursquare/unauthorized.json",
+    )])
+    .await;
+    let places_repo = places_repo();
+    let place_id = stored_place(&places_repo).await;
+
+    let result = mapbox(&server, places_repo).get_photos(place_id).await;
+
+    assert_eq!(result.unwrap_err(), "Error getting pictures".to_string());
+}
+
+#[tokio::test]
+async fn test_foursquare_photos_malformed_json() {
+    let server = MockServer::start(vec![MockRoute::foursquare_photos(
+        FOURSQUARE_ID,
+        StatusCode::OK,
+        "foursquare/photos_malformed.json",
+    )])
+    .await;
+    let places_repo = places_repo();
+    let place_id = stored_place(&places_repo).await;
+
+    let result = mapbox(&server, places_repo).get_photos(place_id).await;
+
+    assert_eq!(result.unwrap_err(), "Error parsing JSON".to_string());
+}
+
+#[tokio::test]
+async fn test_foursquare_search_parses_and_stores_results() {
+    let server = MockServer::start(vec![MockRoute::foursquare_search(
+        StatusCode::OK,
+        "foursquare/search.json",
+    )])
+    .await;
+    let places_repo = places_repo();
+    let mut query = SearchQuery::new(salt_lake_city(), "arlo", SESSION_TOKEN);
+    query.categories = vec![PlaceCategory::Restaurant, PlaceCategory::Bar];
+    query.limit = Some(5);
+    query.language = "es".to_string();
+
+    let places = foursquare(&server, places_repo.clone())
+        .search_for_place(query)
+        .await
+        .unwrap();
+
+    assert_eq!(places.len(), 2);
+    assert_eq!(places[0].name, "Arlo");
+    assert_eq!(places[0].address.address, "271 N Center St");
+    assert_eq!(places[0].address.postcode, Some("84103".to_string()));
+    assert_eq!(places[0].address.place, Some("Salt Lake City".to_string()));
+    assert_eq!(
+        places[0].website,
+        Some("https://www.arlorestaurant.com".to_string())
+    );
+    assert_eq!(places[0].foursquare_id, Some(FOURSQUARE_ID.to_string()));
+    assert_eq!(
+        places[0].coordinates,
+        Some(Coordinates {
+            latitude: 40.775563,
+            longitude: -111.893962,
+        })
+    );
+    assert_eq!(places[1].name, "Red Iguana");
+    assert_ne!(places[0].id, places[1].id);
+
+    let request = &server.requests()[0];
+    assert_eq!(request.query_param("query"), Some("arlo".to_string()));
+    assert_eq!(
+        request.query_param("ll"),
+        Some("40.7608,-111.891".to_string())
+    );
+    assert_eq!(request.query_param("radius"), Some("50000".to_string()));
+    assert_eq!(
+        request.query_param("categories"),
+        Some("13065,13003".to_string())
+    );
+    assert_eq!(request.query_param("limit"), Some("5".to_string()));
+    assert_eq!(
+        request.headers.get("authorization").unwrap(),
+        "foursquare-token"
+    );
+    assert_eq!(request.headers.get("accept-language").unwrap(), "es");
+}
+
+#[tokio::test]
+async fn test_foursquare_search_error_status() {
+    let server = MockServer::start(vec![MockRoute::foursquare_search(
+        StatusCode::UNAUTHORIZED,
+        "foursquare/unauthorized.json",
+    )])
+    .await;
+
+    let result = foursquare(&server, places_repo())
+        .search_for_place(SearchQuery::new(salt_lake_city(), "arlo", SESSION_TOKEN))
+        .await;
+
+    assert_eq!(
+        result.unwrap_err(),
+        "Error requesting data from Foursquare".to_string()
+    );
+}
+
+#[tokio::test]
+async fn test_foursquare_search_malformed_json() {
+    let server = MockServer::start(vec![MockRoute::foursquare_search(
+        StatusCode::OK,
+        "foursquare/search_malformed.json",
+    )])
+    .await;
+
+    let result = foursquare(&server, places_repo())
+        .search_for_place(SearchQuery::new(salt_lake_city(), "arlo", SESSION_TOKEN))
+        .await;
+
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn test_foursquare_search_get_photos() {
+    let server = MockServer::start(vec![MockRoute::foursquare_photos(
+        FOURSQUARE_ID,
+        StatusCode::OK,
+        "foursquare/photos.json",
+    )])
+    .await;
+    let places_repo = places_repo();
+    let place_id = stored_place(&places_repo).await;
+
+    let place = foursquare(&server, places_repo)
+        .get_photos(place_id)
+        .await
+        .unwrap();
+
+    assert_eq!(place.photos.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_foursquare_details_are_stored() {
+    let server = MockServer::start(vec![MockRoute::foursquare_details(
+        FOURSQUARE_ID,
+        StatusCode::OK,
+        "foursquare/details.json",
+    )])
+    .await;
+    let places_repo = places_repo();
+    let place_id = stored_place(&places_repo).await;
+
+    let place = foursquare(&server, places_repo.clone())
+        .get_details(place_id)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        place.website.as_deref(),
+        Some("https://www.arlorestaurant.com")
+    );
+    assert_eq!(
+        place.categories,
+        Some(vec!["italian".to_string(), "restaurant".to_string()])
+    );
+    let details = place.details.clone().unwrap();
+    assert_eq!(details.price, Some(3));
+    assert_eq!(details.phone.as_deref(), Some("(385) 266-8845"));
+    assert_eq!(details.timezone.as_deref(), Some("America/Denver"));
+    assert_eq!(
+        details.social.as_ref().unwrap().instagram.as_deref(),
+        Some("arlorestaurant")
+    );
+    let hours = details.hours.as_ref().unwrap();
+    assert_eq!(hours.periods.len(), 6);
+    assert_eq!(
+        (hours.periods[5].day, hours.periods[5].open.as_str()),
+        (7, "1000")
+    );
+    assert!(!place.details_stale(details.updated_at));
+
+    let stored = places_repo
+        .lock()
+        .await
+        .read(ReadPlaceOptions {
+            id: Some(place_id),
+            name: None,
+            address: None,
+            postcode: None,
+        })
+        .await
+        .unwrap();
+    assert_eq!(stored[0].details.as_ref().unwrap().price, Some(3));
+
+    let requests = server.requests();
+    assert_eq!(
+        requests[0].query.as_deref(),
+        Some("fields=hours%2Cprice%2Ccategories%2Ctel%2Cwebsite%2Csocial_media%2Ctimezone")
+    );
+}
+
+#[tokio::test]
+async fn test_foursquare_details_error_status() {
+    let server = MockServer::start(vec![MockRoute::foursquare_details(
+        FOURSQUARE_ID,
+        StatusCode::UNAUTHORIZED,
+        "foursquare/unauthorized.json",
+    )])
+    .await;
+    let places_repo = places_repo();
+    let place_id = stored_place(&places_repo).await;
+
+    let result = mapbox(&server, places_repo).get_details(place_id).await;
+
+    assert_eq!(result.unwrap_err(), "Error getting place details");
+}
+
+#[tokio::test]
+async fn test_osm_search_maps_tags_to_places() {
+    let server = MockServer::start(vec![MockRoute::nominatim_search(
+        StatusCode::OK,
+        "osm/search.json",
+    )])
+    .await;
+
+    let places = osm(&server, places_repo())
+        .search_for_place(SearchQuery::new(salt_lake_city(), "arlo", SESSION_TOKEN))
+        .await
+        .unwrap();
+
+    assert_eq!(places.len(), 1);
+    assert_eq!(places[0].name, "Arlo");
+    assert_eq!(places[0].address.address, "271 North Center Street");
+    assert_eq!(
+        places[0].address.street,
+        Some("North Center Street".to_string())
+    );
+    assert_eq!(places[0].address.place, Some("Salt Lake City".to_string()));
+    assert_eq!(places[0].address.region, Some("Utah".to_string()));
+    assert_eq!(places[0].address.postcode, Some("84103".to_string()));
+    assert_eq!(places[0].address.country, Some("United States".to_string()));
+    assert_eq!(
+        places[0].categories,
+        Some(vec!["american".to_string(), "regional".to_string()])
+    );
+    assert_eq!(
+        places[0].website,
+        Some("https://www.arlorestaurant.com".to_string())
+    );
+    assert_eq!(
+        places[0].coordinates,
+        Some(Coordinates {
+            latitude: 40.775563,
+            longitude: -111.893962,
+        })
+    );
+    let request = &server.requests()[0];
+    assert_eq!(request.query_param("q"), Some("arlo".to_string()));
+    assert_eq!(request.query_param("format"), Some("jsonv2".to_string()));
+    assert_eq!(request.query_param("bounded"), Some("1".to_string()));
+    assert_eq!(request.headers.get("user-agent").unwrap(), "critiq-tests");
+}
+
+#[tokio::test]
+async fn test_osm_search_skips_results_without_house_number() {
+    let server = MockServer::start(vec![MockRoute::nominatim_search(
+        StatusCode::OK,
+        "osm/search_road_only.json",
+    )])
+    .await;
+    let places_repo = places_repo();
+
+    let places = osm(&server, places_repo.clone())
+        .search_for_place(SearchQuery::new(salt_lake_city(), "cafe", SESSION_TOKEN))
+        .await
+        .unwrap();
+
+    // Both cafes on the road alone would share its name as their address.
+    assert_eq!(places.len(), 1);
+    assert_eq!(places[0].name, "Publik Coffee");
+    assert_eq!(places[0].address.address, "502 West 200 South");
+    assert_eq!(
+        places[0].website,
+        Some("https://publikcoffee.example".to_string())
+    );
+    let stored = places_repo
+        .lock()
+        .await
+        .read(ReadPlaceOptions {
+            id: None,
+            name: None,
+            address: None,
+            postcode: None,
+        })
+        .await
+        .unwrap();
+    assert_eq!(stored.len(), 1);
+}
+
+#[tokio::test]
+async fn test_osm_search_filters_categories_and_limit() {
+    let server = MockServer::start(vec![MockRoute::nominatim_search(
+        StatusCode::OK,
+        "osm/search.json",
+    )])
+    .await;
+    let mut query = SearchQuery::new(salt_lake_city(), "arlo", SESSION_TOKEN);
+    query.categories = vec![PlaceCategory::Bakery, PlaceCategory::Restaurant];
+    query.limit = Some(1);
+
+    let places = osm(&server, places_repo())
+        .search_for_place(query)
+        .await
+        .unwrap();
+
+    assert_eq!(places.len(), 1);
+    assert_eq!(places[0].name, "Arlo");
+}
+
+#[tokio::test]
+async fn test_osm_details_are_looked_up_again() {
+    let server = MockServer::start(vec![MockRoute::nominatim_search(
+        StatusCode::OK,
+        "osm/search.json",
+    )])
+    .await;
+    let places_repo = places_repo();
+    let search = osm(&server, places_repo.clone());
+    let found = search
+        .search_for_place(SearchQuery::new(salt_lake_city(), "arlo", SESSION_TOKEN))
+        .await
+        .unwrap();
+    let mut closed = common::place(0, "Closed", "1 Closed St");
+    closed.address.full_address = None;
+    let closed = places_repo.lock().await.create(&closed).await.unwrap();
+    let foursquare_id = stored_place(&places_repo).await;
+
+    let arlo = search.get_details(found[0].id).await.unwrap();
+    let details = arlo.details.unwrap();
+    assert_eq!(
+        details.hours.unwrap().display,
+        Some("We-Su 17:00-21:00".to_string())
+    );
+    assert_eq!(
+        arlo.website,
+        Some("https://www.arlorestaurant.com".to_string())
+    );
+    let request = &server.requests()[1];
+    assert_eq!(request.query_param("q"), found[0].address.full_address);
+
+    // Places OpenStreetMap no longer has are only marked as looked up.
+    let closed = search.get_details(closed.id).await.unwrap();
+    assert_eq!(closed.details.unwrap().hours, None);
+    assert_eq!(
+        server.requests()[2].query_param("q"),
+        Some("Closed, 1 Closed St".to_string())
+    );
+
+    // Places from Foursquare are left to it.
+    let place = search.get_details(foursquare_id).await.unwrap();
+    assert!(place.details.is_none());
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn test_osm_search_error_status() {
+    let server = MockServer::start(vec![MockRoute::nominatim_search(
+        StatusCode::TOO_MANY_REQUESTS,
+        "osm/rate_limited.html",
+    )])
+    .await;
+
+    let result = osm(&server, places_repo())
+        .search_for_place(SearchQuery::new(salt_lake_city(), "arlo", SESSION_TOKEN))
+        .await;
+
+    assert_eq!(
+        result.unwrap_err(),
+        "Error requesting data from OpenStreetMap".to_string()
+    );
+}
+
+#[tokio::test]
+async fn test_osm_search_malformed_json() {
+    let server = MockServer::start(vec![MockRoute::nominatim_search(
+        StatusCode::OK,
+        "osm/search_malformed.json",
+    )])
+    .await;
+
+    let result = osm(&server, places_repo())
+        .search_for_place(SearchQuery::new(salt_lake_city(), "arlo", SESSION_TOKEN))
+        .await;
+
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn test_composite_search_falls_back_when_provider_fails() {
+    let server = MockServer::start(vec![
+        MockRoute::mapbox_suggest(StatusCode::UNAUTHORIZED, "mapbox/unauthorized.json"),
+        MockRoute::foursquare_search(StatusCode::OK, "foursquare/search.json"),
+    ])
+    .await;
+    let places_repo = places_repo();
+    let search = CompositeSearch::new(places_repo.clone())
+        .with_provider(
+            "mapbox",
+            mapbox(&server, places_repo.clone()),
+            DEFAULT_PROVIDER_TIMEOUT,
+        )
+        .with_provider(
+            "foursquare",
+            foursquare(&server, places_repo.clone()),
+            DEFAULT_PROVIDER_TIMEOUT,
+        );
+
+    let places = search
+        .search_for_place(SearchQuery::new(salt_lake_city(), "arlo", SESSION_TOKEN))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        places.iter().map(|p| p.name.as_str()).collect::<Vec<_>>(),
+        vec!["Arlo", "Red Iguana"]
+    );
+}
+
+#[tokio::test]
+async fn test_composite_search_merges_database_and_provider_results() {
+    let server = MockServer::start(vec![MockRoute::foursquare_search(
+        StatusCode::OK,
+        "foursquare/search.json",
+    )])
+    .await;
+    let places_repo = places_repo();
+    let mut stored = common::place(0, "Arlo Restaurant", "271 North Center Street");
+    stored.coordinates = Some(salt_lake_city());
+    places_repo.lock().await.create(&stored).await.unwrap();
+    let search = CompositeSearch::new(places_repo.clone())
+        .with_provider(
+            "foursquare",
+            foursquare(&server, places_repo.clone()),
+            DEFAULT_PROVIDER_TIMEOUT,
+        )
+        .with_provider(
+            "database",
+            DatabaseSearch::new(places_repo.clone()),
+            DEFAULT_PROVIDER_TIMEOUT,
+        );
+
+    let places = search
+        .search_for_place(SearchQuery::new(salt_lake_city(), "arlo", SESSION_TOKEN))
+        .await
+        .unwrap();
+
+    let arlos: Vec<_> = places
+        .iter()
+        .filter(|p| p.name.starts_with("Arlo"))
+        .collect();
+    assert_eq!(arlos.len(), 1);
+    assert_eq!(arlos[0].address.address, "271 N Center St");
+    assert_eq!(arlos[0].foursquare_id, Some(FOURSQUARE_ID.to_string()));
+}
+
+#[tokio::test]
+async fn test_twilio_send_verification_code() {
+    let server = MockServer::start(vec![MockRoute::twilio_verifications(
+        StatusCode::CREATED,
+        "twilio/verification.json",
+    )])
+    .await;
+
+    twilio(&server)
+        .send_verification_code(2028098681)
+        .await
+        .unwrap();
+
+    let requests = server.requests();
+    assert_eq!(
+        requests[0].form_param("To"),
+        Some("+12028098681".to_string())
+    );
+    assert_eq!(requests[0].form_param("Channel"), Some("sms".to_string()));
+    assert!(requests[0]
+        .headers
+        .get("authorization")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .starts_with("Basic "));
+}
+
+#[tokio::test]
+async fn test_twilio_send_verification_code_error_status() {
+    let server = MockServer::start(vec![MockRoute::twilio_verifications(
+        StatusCode::TOO_MANY_REQUESTS,
+        "twilio/too_many_requests.json",
+    )])
+    .await;
+
+    let result = twilio(&server).send_verification_code(2028098681).await;
+
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn test_twilio_verify_code_approved() {
+    let server = MockServer::start(vec![MockRoute::twilio_verification_check(
+        StatusCode::OK,
+        "twilio/verification_check_approved.json",
+    )])
+    .await;
+
+    twilio(&server).verify_code(2028098681, 4321).await.unwrap();
+
+    let requests = server.requests();
+    assert_eq!(requests[0].form_param("Code"), Some("04321".to_string()));
+}
+
+#[tokio::test]
+async fn test_twilio_verify_code_pending() {
+    let server = MockServer::start(vec![MockRoute::twilio_verification_check(
+        StatusCode::OK,
+        "twilio/verification_check_pending.json",
+    )])
+    .await;
+
+    let result = twilio(&server).verify_code(2028098681, 123456).await;
+
+    assert_eq!(result.unwrap_err(), "Status not accepted".to_string());
+}
+
+#[tokio::test]
+async fn test_twilio_verify_code_error_status() {
+    let server = MockServer::start(vec![MockRoute::twilio_verification_check(
+        StatusCode::NOT_FOUND,
+        "twilio/not_found.json",
+    )])
+    .await;
+
+    let result = twilio(&server).verify_code(2028098681, 123456).await;
+
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn test_twilio_verify_code_malformed_json() {
+    let server = MockServer::start(vec![MockRoute::twilio_verification_check(
+        StatusCode::OK,
+        "twilio/verification_check_malformed.json",
+    )])
+    .await;
+
+    let result = twilio(&server).verify_code(2028098681, 123456).await;
+
+    assert_eq!(result.unwrap_err(), "Error parsing JSON".to_string());
+}
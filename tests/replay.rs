@@ -0,0 +1,132 @@
+//! Regression tests against captured Mapbox and Foursquare traffic.
+//!
+//! By default responses are replayed from `tests/fixtures/replay`. To refresh
+//! the captures against the live APIs run:
+//!
+//! ```sh
+//! PROVIDER_REPLAY_MODE=record MAPBOX_API_KEY=... FOURSQUARE_API_KEY=... \
+//!     cargo test --test replay
+//! ```
+
+use std::{path::PathBuf, sync::Arc};
+
+use critiq_backend::{
+    geo::Coordinates,
+    places::{
+        foursquare::FOURSQUARE_BASE_URL,
+        mapbox::search::{MapboxSearchApi, MAPBOX_BASE_URL},
+        photo::PhotoSize,
+        search::{Search, SearchQuery},
+    },
+    replay::{ReplayMode, ReplayServer},
+    repository::{local::places::LocalPlacesRepository, places::DynPlacesRepo},
+};
+use tokio::sync::Mutex;
+
+struct Providers {
+    search: MapboxSearchApi,
+    _mapbox: ReplayServer,
+    _foursquare: ReplayServer,
+}
+
+fn fixtures_dir(provider: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/replay")
+        .join(provider)
+}
+
+fn api_key(name: &str, mode: &ReplayMode) -> String {
+    match mode {
+        ReplayMode::Record { .. } => {
+            std::env::var(name).unwrap_or_else(|_| panic!("{} must be set when recording.", name))
+        }
+        ReplayMode::Replay => "replayed".to_string(),
+    }
+}
+
+async fn providers(places_repo: DynPlacesRepo) -> Providers {
+    let mapbox_mode = ReplayMode::from_env(MAPBOX_BASE_URL);
+    let foursquare_mode = ReplayMode::from_env(FOURSQUARE_BASE_URL);
+    let mapbox_key = api_key("MAPBOX_API_KEY", &mapbox_mode);
+    let foursquare_key = api_key("FOURSQUARE_API_KEY", &foursquare_mode);
+
+    let mapbox = ReplayServer::start(mapbox_mode, &fixtures_dir("mapbox"))
+        .await
+        .unwrap();
+    let foursquare = ReplayServer::start(foursquare_mode, &fixtures_dir("foursquare"))
+        .await
+        .unwrap();
+    let search = MapboxSearchApi::new(&mapbox_key, &foursquare_key, places_repo)
+        .with_base_urls(&mapbox.url, &foursquare.url);
+
+    Providers {
+        search,
+        _mapbox: mapbox,
+        _foursquare: foursquare,
+    }
+}
+
+fn salt_lake_city() -> Coordinates {
+    Coordinates {
+        latitude: 40.7608,
+        longitude: -111.891,
+    }
+}
+
+#[tokio::test]
+async fn test_search_for_place_against_captured_traffic() {
+    let places_repo: DynPlacesRepo = Arc::new(Mutex::new(LocalPlacesRepository::new()));
+    let providers = providers(places_repo).await;
+
+    let places = providers
+        .search
+        .search_for_place(SearchQuery::new(
+            salt_lake_city(),
+            "arlo",
+            &uuid::Uuid::new_v4().to_string(),
+        ))
+        .await
+        .unwrap();
+
+    assert!(!places.is_empty());
+    assert!(places.iter().all(|p| !p.name.is_empty()));
+    assert!(places.iter().any(|p| p.name == "Arlo"));
+    assert!(places.iter().any(|p| p.foursquare_id.is_some()));
+
+    let arlo = places.iter().find(|p| p.name == "Arlo").unwrap();
+    let arlo = providers
+        .search
+        .get_coordinates(arlo.id, &uuid::Uuid::new_v4().to_string())
+        .await
+        .unwrap();
+    assert!(arlo.coordinates.is_some());
+}
+
+#[tokio::test]
+async fn test_get_photos_against_captured_traffic() {
+    let places_repo: DynPlacesRepo = Arc::new(Mutex::new(LocalPlacesRepository::new()));
+    let providers = providers(places_repo).await;
+
+    let places = providers
+        .search
+        .search_for_place(SearchQuery::new(
+            salt_lake_city(),
+            "arlo",
+            &uuid::Uuid::new_v4().to_string(),
+        ))
+        .await
+        .unwrap();
+    let place = places
+        .iter()
+        .find(|p| p.foursquare_id.is_some())
+        .expect("a place with a Foursquare id");
+
+    let place = providers.search.get_photos(place.id).await.unwrap();
+
+    let photos = place.photos.unwrap();
+    assert!(!photos.is_empty());
+    assert!(photos
+        .iter()
+        .map(|p| p.url(PhotoSize::Original))
+        .all(|url| url.starts_with("https://") && url.contains("/original/")));
+}